@@ -11,7 +11,10 @@ use bitflags::bitflags;
 use knuffel::errors::DecodeError;
 use knuffel::Decode as _;
 use miette::{miette, Context, IntoDiagnostic, NarratableReportHandler};
-use niri_ipc::{ConfiguredMode, LayoutSwitchTarget, SizeChange, Transform, WorkspaceReferenceArg};
+use niri_ipc::{
+    BroadcastRgb, Colorimetry, ConfiguredMode, Eotf, FramebufferFormat, HSyncPolarity,
+    LayoutSwitchTarget, MirrorScaling, SizeChange, Transform, VSyncPolarity, WorkspaceReferenceArg,
+};
 use regex::Regex;
 use smithay::backend::renderer::Color32F;
 use smithay::input::keyboard::keysyms::KEY_NoSymbol;
@@ -27,6 +30,8 @@ pub struct Config {
     pub input: Input,
     #[knuffel(children(name = "output"))]
     pub outputs: Outputs,
+    #[knuffel(children(name = "output-profile"))]
+    pub output_profiles: Vec<OutputProfile>,
     #[knuffel(children(name = "spawn-at-startup"))]
     pub spawn_at_startup: Vec<SpawnAtStartup>,
     #[knuffel(child, default)]
@@ -44,8 +49,14 @@ pub struct Config {
     ]
     pub screenshot_path: Option<String>,
     #[knuffel(child, default)]
+    pub screenshot_format: ScreenshotFormat,
+    #[knuffel(child, default)]
     pub hotkey_overlay: HotkeyOverlay,
     #[knuffel(child, default)]
+    pub workspace_switch_osd: WorkspaceSwitchOsd,
+    #[knuffel(child, default)]
+    pub capture_indicator: CaptureIndicator,
+    #[knuffel(child, default)]
     pub animations: Animations,
     #[knuffel(child, default)]
     pub environment: Environment,
@@ -56,6 +67,8 @@ pub struct Config {
     #[knuffel(child, default)]
     pub switch_events: SwitchBinds,
     #[knuffel(child, default)]
+    pub clamshell: Clamshell,
+    #[knuffel(child, default)]
     pub debug: DebugConfig,
     #[knuffel(children(name = "workspace"))]
     pub workspaces: Vec<Workspace>,
@@ -355,13 +368,35 @@ pub struct Output {
     #[knuffel(child, unwrap(argument, str), default = Transform::Normal)]
     pub transform: Transform,
     #[knuffel(child)]
+    pub auto_rotate: bool,
+    #[knuffel(child)]
     pub position: Option<Position>,
-    #[knuffel(child, unwrap(argument, str))]
-    pub mode: Option<ConfiguredMode>,
+    #[knuffel(child)]
+    pub mode: Option<Mode>,
+    #[knuffel(child)]
+    pub modeline: Option<Modeline>,
     #[knuffel(child)]
     pub variable_refresh_rate: Option<Vrr>,
+    #[knuffel(child)]
+    pub render_time_prediction: Option<RenderTimePrediction>,
+    #[knuffel(child, unwrap(argument))]
+    pub max_bpc: Option<u8>,
+    #[knuffel(child, unwrap(argument, str), default)]
+    pub framebuffer_format: FramebufferFormat,
+    #[knuffel(child, unwrap(argument, str), default)]
+    pub broadcast_rgb: BroadcastRgb,
+    #[knuffel(child, unwrap(argument, str), default)]
+    pub colorimetry: Colorimetry,
+    #[knuffel(child)]
+    pub hdr_metadata: Option<HdrMetadata>,
+    #[knuffel(child, unwrap(argument))]
+    pub icc_profile: Option<String>,
     #[knuffel(child, default = DEFAULT_BACKGROUND_COLOR)]
     pub background_color: Color,
+    #[knuffel(child)]
+    pub background_image: Option<BackgroundImage>,
+    #[knuffel(child)]
+    pub mirror: Option<Mirror>,
 }
 
 impl Output {
@@ -385,15 +420,37 @@ impl Default for Output {
             name: String::new(),
             scale: None,
             transform: Transform::Normal,
+            auto_rotate: false,
             position: None,
             mode: None,
+            modeline: None,
             variable_refresh_rate: None,
+            render_time_prediction: None,
+            max_bpc: None,
+            framebuffer_format: FramebufferFormat::default(),
+            broadcast_rgb: BroadcastRgb::default(),
+            colorimetry: Colorimetry::default(),
+            hdr_metadata: None,
+            icc_profile: None,
             background_color: DEFAULT_BACKGROUND_COLOR,
+            background_image: None,
+            mirror: None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(knuffel::Decode, Debug, Clone, PartialEq)]
+pub struct OutputProfile {
+    #[knuffel(argument)]
+    pub name: String,
+    #[knuffel(children(name = "output"))]
+    pub outputs: Outputs,
+    /// Commands to spawn when the profile is activated.
+    #[knuffel(children(name = "exec"))]
+    pub exec: Vec<SpawnAtStartup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct OutputName {
     pub connector: String,
     pub make: Option<String>,
@@ -415,6 +472,96 @@ pub struct Vrr {
     pub on_demand: bool,
 }
 
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq)]
+pub struct RenderTimePrediction {
+    #[knuffel(property(name = "safety-margin-ms"), default = 2)]
+    pub safety_margin_ms: u16,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq)]
+pub struct Mode {
+    /// Whether to compute the mode with CVT instead of picking one of the advertised modes.
+    #[knuffel(property, default)]
+    pub custom: bool,
+    #[knuffel(argument, str)]
+    pub mode: ConfiguredMode,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq)]
+pub struct Modeline {
+    /// Pixel clock in MHz.
+    #[knuffel(argument)]
+    pub clock: FloatOrInt<0, 100000>,
+    #[knuffel(argument)]
+    pub hdisplay: u16,
+    #[knuffel(argument)]
+    pub hsync_start: u16,
+    #[knuffel(argument)]
+    pub hsync_end: u16,
+    #[knuffel(argument)]
+    pub htotal: u16,
+    #[knuffel(argument)]
+    pub vdisplay: u16,
+    #[knuffel(argument)]
+    pub vsync_start: u16,
+    #[knuffel(argument)]
+    pub vsync_end: u16,
+    #[knuffel(argument)]
+    pub vtotal: u16,
+    #[knuffel(argument, str)]
+    pub hsync_polarity: HSyncPolarity,
+    #[knuffel(argument, str)]
+    pub vsync_polarity: VSyncPolarity,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq)]
+pub struct HdrMetadata {
+    #[knuffel(property, str, default)]
+    pub eotf: Eotf,
+    /// Maximum mastering display luminance in cd/m².
+    #[knuffel(property, default = 1000)]
+    pub max_luminance: u16,
+    /// Minimum mastering display luminance in cd/m².
+    #[knuffel(property, default = FloatOrInt(0.005))]
+    pub min_luminance: FloatOrInt<0, 6>,
+    /// Maximum content light level in cd/m².
+    #[knuffel(property, default)]
+    pub max_cll: u16,
+    /// Maximum frame-average light level in cd/m².
+    #[knuffel(property, default)]
+    pub max_fall: u16,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    /// Name of the output to mirror.
+    #[knuffel(argument)]
+    pub output: String,
+    #[knuffel(property, str, default)]
+    pub scaling: MirrorScaling,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, PartialEq, Eq)]
+pub struct BackgroundImage {
+    #[knuffel(argument)]
+    pub path: String,
+    #[knuffel(property, default)]
+    pub fit: BackgroundImageFit,
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackgroundImageFit {
+    /// Scale the image to cover the whole output, cropping what doesn't fit.
+    #[default]
+    Fill,
+    /// Scale the image to fit inside the output, showing the background color around it.
+    Fit,
+    /// Draw the image unscaled in the center of the output.
+    Center,
+    /// Repeat the image unscaled, starting from the top-left corner of the output.
+    Tile,
+}
+
 // MIN and MAX generics are only used during parsing to check the value.
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct FloatOrInt<const MIN: i32, const MAX: i32>(pub f64);
@@ -710,12 +857,114 @@ pub struct Struts {
     pub bottom: FloatOrInt<-65535, 65535>,
 }
 
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenshotFormat {
+    #[knuffel(argument)]
+    pub format: ImageFormat,
+    /// PNG compression level.
+    #[knuffel(property, default)]
+    pub compression: PngCompression,
+    /// JPEG quality from 1 to 100.
+    #[knuffel(property, default = Self::default().quality)]
+    pub quality: u8,
+}
+
+impl Default for ScreenshotFormat {
+    fn default() -> Self {
+        Self {
+            format: ImageFormat::Png,
+            compression: PngCompression::Default,
+            quality: 90,
+        }
+    }
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    /// JPEG, without the alpha channel.
+    Jpeg,
+    /// Lossless WebP.
+    Webp,
+    Qoi,
+}
+
+impl ImageFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Qoi => "image/qoi",
+        }
+    }
+
+    /// Usual file extension for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Qoi => "qoi",
+        }
+    }
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
 #[derive(knuffel::Decode, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct HotkeyOverlay {
     #[knuffel(child)]
     pub skip_at_startup: bool,
 }
 
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspaceSwitchOsd {
+    #[knuffel(child)]
+    pub on: bool,
+    #[knuffel(child, unwrap(argument), default = Self::default().duration_ms)]
+    pub duration_ms: u32,
+}
+
+impl Default for WorkspaceSwitchOsd {
+    fn default() -> Self {
+        Self {
+            on: false,
+            duration_ms: 1000,
+        }
+    }
+}
+
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq)]
+pub struct CaptureIndicator {
+    #[knuffel(child)]
+    pub off: bool,
+    #[knuffel(child, unwrap(argument), default = Self::default().border_width)]
+    pub border_width: FloatOrInt<0, 65535>,
+    #[knuffel(child, default = Self::default().color)]
+    pub color: Color,
+    #[knuffel(child)]
+    pub hide_badge: bool,
+}
+
+impl Default for CaptureIndicator {
+    fn default() -> Self {
+        Self {
+            off: false,
+            border_width: FloatOrInt(4.),
+            color: Color::from_rgba8_unpremul(224, 27, 36, 255),
+            hide_badge: false,
+        }
+    }
+}
+
 #[derive(knuffel::Decode, Debug, Clone, PartialEq)]
 pub struct Animations {
     #[knuffel(child)]
@@ -738,6 +987,8 @@ pub struct Animations {
     pub config_notification_open_close: ConfigNotificationOpenCloseAnim,
     #[knuffel(child, default)]
     pub screenshot_ui_open: ScreenshotUiOpenAnim,
+    #[knuffel(child, default)]
+    pub workspace_switch_osd_open_close: WorkspaceSwitchOsdOpenCloseAnim,
 }
 
 impl Default for Animations {
@@ -753,6 +1004,7 @@ impl Default for Animations {
             window_resize: Default::default(),
             config_notification_open_close: Default::default(),
             screenshot_ui_open: Default::default(),
+            workspace_switch_osd_open_close: Default::default(),
         }
     }
 }
@@ -900,6 +1152,21 @@ impl Default for ScreenshotUiOpenAnim {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkspaceSwitchOsdOpenCloseAnim(pub Animation);
+
+impl Default for WorkspaceSwitchOsdOpenCloseAnim {
+    fn default() -> Self {
+        Self(Animation {
+            off: false,
+            kind: AnimationKind::Easing(EasingParams {
+                duration_ms: 150,
+                curve: AnimationCurve::EaseOutQuad,
+            }),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Animation {
     pub off: bool,
@@ -950,6 +1217,8 @@ pub struct Workspace {
     pub name: WorkspaceName,
     #[knuffel(child, unwrap(argument))]
     pub open_on_output: Option<String>,
+    #[knuffel(child)]
+    pub background_image: Option<BackgroundImage>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1000,6 +1269,8 @@ pub struct WindowRule {
     pub block_out_from: Option<BlockOutFrom>,
     #[knuffel(child, unwrap(argument))]
     pub variable_refresh_rate: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub allow_tearing: Option<bool>,
 }
 
 // Remember to update the PartialEq impl when adding fields!
@@ -1083,6 +1354,10 @@ pub struct Bind {
     pub repeat: bool,
     pub cooldown: Option<Duration>,
     pub allow_when_locked: bool,
+    /// Title in the hotkey overlay.
+    ///
+    /// `None` means the default title, `Some(None)` means the bind is hidden from the overlay.
+    pub hotkey_overlay_title: Option<Option<String>>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -1129,10 +1404,19 @@ pub struct SwitchBinds {
     pub tablet_mode_off: Option<SwitchAction>,
 }
 
-#[derive(knuffel::Decode, Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SwitchAction {
-    #[knuffel(child, unwrap(arguments))]
-    pub spawn: Vec<String>,
+    pub action: Action,
+}
+
+#[derive(knuffel::Decode, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Clamshell {
+    /// Turn the laptop panel off when the lid closes with external monitors connected.
+    #[knuffel(child)]
+    pub on: bool,
+    /// Suspend when the lid closes without external monitors connected.
+    #[knuffel(child)]
+    pub suspend_when_undocked: bool,
 }
 
 // Remember to add new actions to the CLI enum too.
@@ -1144,6 +1428,9 @@ pub enum Action {
     Suspend,
     PowerOffMonitors,
     PowerOnMonitors,
+    PowerOffMonitor(#[knuffel(argument)] String),
+    PowerOnMonitor(#[knuffel(argument)] String),
+    ToggleRotationLock,
     ToggleDebugTint,
     DebugToggleOpaqueRegions,
     DebugToggleDamage,
@@ -1155,11 +1442,31 @@ pub enum Action {
     CancelScreenshot,
     #[knuffel(skip)]
     ScreenshotTogglePointer,
-    Screenshot,
-    ScreenshotScreen,
-    ScreenshotWindow,
     #[knuffel(skip)]
-    ScreenshotWindowById(u64),
+    ScreenshotToggleWindowPicking,
+    // The screenshot actions take write-to-disk, delay-ms and path.
+    Screenshot(
+        #[knuffel(property(name = "write-to-disk"), default = true)] bool,
+        #[knuffel(property(name = "delay-ms"))] Option<u16>,
+        #[knuffel(property(name = "path"))] Option<String>,
+    ),
+    ScreenshotScreen(
+        #[knuffel(property(name = "write-to-disk"), default = true)] bool,
+        #[knuffel(property(name = "delay-ms"))] Option<u16>,
+        #[knuffel(property(name = "path"))] Option<String>,
+    ),
+    ScreenshotWindow(
+        #[knuffel(property(name = "write-to-disk"), default = true)] bool,
+        #[knuffel(property(name = "delay-ms"))] Option<u16>,
+        #[knuffel(property(name = "path"))] Option<String>,
+    ),
+    #[knuffel(skip)]
+    ScreenshotWindowById {
+        id: u64,
+        write_to_disk: bool,
+        delay_ms: Option<u16>,
+        path: Option<String>,
+    },
     CloseWindow,
     #[knuffel(skip)]
     CloseWindowById(u64),
@@ -1255,6 +1562,11 @@ pub enum Action {
     MoveWorkspaceToMonitorRight,
     MoveWorkspaceToMonitorDown,
     MoveWorkspaceToMonitorUp,
+    SetDynamicCastWindow,
+    #[knuffel(skip)]
+    SetDynamicCastWindowById(u64),
+    SetDynamicCastMonitor(#[knuffel(argument)] Option<String>),
+    ClearDynamicCastTarget,
 }
 
 impl From<niri_ipc::Action> for Action {
@@ -1263,12 +1575,38 @@ impl From<niri_ipc::Action> for Action {
             niri_ipc::Action::Quit { skip_confirmation } => Self::Quit(skip_confirmation),
             niri_ipc::Action::PowerOffMonitors {} => Self::PowerOffMonitors,
             niri_ipc::Action::PowerOnMonitors {} => Self::PowerOnMonitors,
+            niri_ipc::Action::PowerOffMonitor { output } => Self::PowerOffMonitor(output),
+            niri_ipc::Action::PowerOnMonitor { output } => Self::PowerOnMonitor(output),
+            niri_ipc::Action::ToggleRotationLock {} => Self::ToggleRotationLock,
             niri_ipc::Action::Spawn { command } => Self::Spawn(command),
             niri_ipc::Action::DoScreenTransition { delay_ms } => Self::DoScreenTransition(delay_ms),
-            niri_ipc::Action::Screenshot {} => Self::Screenshot,
-            niri_ipc::Action::ScreenshotScreen {} => Self::ScreenshotScreen,
-            niri_ipc::Action::ScreenshotWindow { id: None } => Self::ScreenshotWindow,
-            niri_ipc::Action::ScreenshotWindow { id: Some(id) } => Self::ScreenshotWindowById(id),
+            niri_ipc::Action::Screenshot {
+                write_to_disk,
+                delay_ms,
+                path,
+            } => Self::Screenshot(write_to_disk, delay_ms, path),
+            niri_ipc::Action::ScreenshotScreen {
+                write_to_disk,
+                delay_ms,
+                path,
+            } => Self::ScreenshotScreen(write_to_disk, delay_ms, path),
+            niri_ipc::Action::ScreenshotWindow {
+                id: None,
+                write_to_disk,
+                delay_ms,
+                path,
+            } => Self::ScreenshotWindow(write_to_disk, delay_ms, path),
+            niri_ipc::Action::ScreenshotWindow {
+                id: Some(id),
+                write_to_disk,
+                delay_ms,
+                path,
+            } => Self::ScreenshotWindowById {
+                id,
+                write_to_disk,
+                delay_ms,
+                path,
+            },
             niri_ipc::Action::CloseWindow { id: None } => Self::CloseWindow,
             niri_ipc::Action::CloseWindow { id: Some(id) } => Self::CloseWindowById(id),
             niri_ipc::Action::FullscreenWindow { id: None } => Self::FullscreenWindow,
@@ -1383,6 +1721,14 @@ impl From<niri_ipc::Action> for Action {
             niri_ipc::Action::MoveWorkspaceToMonitorRight {} => Self::MoveWorkspaceToMonitorRight,
             niri_ipc::Action::MoveWorkspaceToMonitorDown {} => Self::MoveWorkspaceToMonitorDown,
             niri_ipc::Action::MoveWorkspaceToMonitorUp {} => Self::MoveWorkspaceToMonitorUp,
+            niri_ipc::Action::SetDynamicCastWindow { id: None } => Self::SetDynamicCastWindow,
+            niri_ipc::Action::SetDynamicCastWindow { id: Some(id) } => {
+                Self::SetDynamicCastWindowById(id)
+            }
+            niri_ipc::Action::SetDynamicCastMonitor { output } => {
+                Self::SetDynamicCastMonitor(output)
+            }
+            niri_ipc::Action::ClearDynamicCastTarget {} => Self::ClearDynamicCastTarget,
             niri_ipc::Action::ToggleDebugTint {} => Self::ToggleDebugTint,
             niri_ipc::Action::DebugToggleOpaqueRegions {} => Self::DebugToggleOpaqueRegions,
             niri_ipc::Action::DebugToggleDamage {} => Self::DebugToggleDamage,
@@ -1533,6 +1879,8 @@ pub struct DebugConfig {
     pub disable_resize_throttling: bool,
     #[knuffel(child)]
     pub disable_transactions: bool,
+    #[knuffel(child)]
+    pub disable_drm_syncobj: bool,
 }
 
 #[derive(knuffel::DecodeScalar, Debug, Clone, Copy, PartialEq, Eq)]
@@ -1888,6 +2236,50 @@ impl Outputs {
     }
 }
 
+impl OutputProfile {
+    /// Returns whether the connected outputs are exactly the outputs of this profile.
+    ///
+    /// Every connected output must match a different output of the profile, and every output of
+    /// the profile must be connected.
+    pub fn matches(&self, connected: &[OutputName]) -> bool {
+        fn assign(outputs: &[Output], connected: &[OutputName], used: &mut [bool]) -> bool {
+            let Some((output, rest)) = outputs.split_first() else {
+                return true;
+            };
+
+            for (idx, name) in connected.iter().enumerate() {
+                if used[idx] || !name.matches(&output.name) {
+                    continue;
+                }
+
+                used[idx] = true;
+                if assign(rest, connected, used) {
+                    return true;
+                }
+                used[idx] = false;
+            }
+
+            false
+        }
+
+        if self.outputs.0.len() != connected.len() {
+            return false;
+        }
+
+        assign(
+            &self.outputs.0,
+            connected,
+            &mut vec![false; connected.len()],
+        )
+    }
+
+    /// Returns the output config with this profile applied on top of `base`.
+    pub fn apply(&self, base: &Outputs) -> Outputs {
+        // Outputs::find() returns the first match, so the profile takes priority.
+        self.outputs.0.iter().chain(&base.0).cloned().collect()
+    }
+}
+
 impl OutputName {
     pub fn from_ipc_output(output: &niri_ipc::Output) -> Self {
         Self {
@@ -2259,6 +2651,21 @@ where
     }
 }
 
+impl<S> knuffel::Decode<S> for WorkspaceSwitchOsdOpenCloseAnim
+where
+    S: knuffel::traits::ErrorSpan,
+{
+    fn decode_node(
+        node: &knuffel::ast::SpannedNode<S>,
+        ctx: &mut knuffel::decode::Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        let default = Self::default().0;
+        Ok(Self(Animation::decode_node(node, ctx, default, |_, _| {
+            Ok(false)
+        })?))
+    }
+}
+
 impl Animation {
     pub fn new_off() -> Self {
         Self {
@@ -2698,6 +3105,7 @@ where
         let mut cooldown = None;
         let mut allow_when_locked = false;
         let mut allow_when_locked_node = None;
+        let mut hotkey_overlay_title = None;
         for (name, val) in &node.properties {
             match &***name {
                 "repeat" => {
@@ -2712,6 +3120,9 @@ where
                     allow_when_locked = knuffel::traits::DecodeScalar::decode(val, ctx)?;
                     allow_when_locked_node = Some(name);
                 }
+                "hotkey-overlay-title" => {
+                    hotkey_overlay_title = Some(knuffel::traits::DecodeScalar::decode(val, ctx)?);
+                }
                 name_str => {
                     ctx.emit_error(DecodeError::unexpected(
                         name,
@@ -2733,6 +3144,7 @@ where
             repeat: true,
             cooldown: None,
             allow_when_locked: false,
+            hotkey_overlay_title: None,
         };
 
         if let Some(child) = children.next() {
@@ -2761,6 +3173,7 @@ where
                         repeat,
                         cooldown,
                         allow_when_locked,
+                        hotkey_overlay_title,
                     })
                 }
                 Err(e) => {
@@ -2778,6 +3191,37 @@ where
     }
 }
 
+impl<S> knuffel::Decode<S> for SwitchAction
+where
+    S: knuffel::traits::ErrorSpan,
+{
+    fn decode_node(
+        node: &knuffel::ast::SpannedNode<S>,
+        ctx: &mut knuffel::decode::Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        expect_only_children(node, ctx);
+
+        let mut children = node.children();
+        let Some(child) = children.next() else {
+            return Err(DecodeError::missing(
+                node,
+                "expected an action for this switch event",
+            ));
+        };
+
+        for unwanted_child in children {
+            ctx.emit_error(DecodeError::unexpected(
+                unwanted_child,
+                "node",
+                "only one action is allowed per switch event",
+            ));
+        }
+
+        let action = Action::decode_node(child, ctx)?;
+        Ok(Self { action })
+    }
+}
+
 impl FromStr for Key {
     type Err = miette::Error;
 
@@ -3013,10 +3457,41 @@ mod tests {
             output "eDP-1" {
                 scale 2
                 transform "flipped-90"
+                auto-rotate
                 position x=10 y=20
                 mode "1920x1080@144"
                 variable-refresh-rate on-demand=true
+                render-time-prediction safety-margin-ms=3
+                icc-profile "~/.local/share/icc/monitor.icc"
                 background-color "rgba(25, 25, 102, 1.0)"
+                background-image "~/Pictures/wallpaper.png" fit="tile"
+            }
+
+            output "HDMI-A-1" {
+                mirror "eDP-1" scaling="fill"
+            }
+
+            output "DP-1" {
+                mode custom=true "2560x1440@75"
+            }
+
+            output "DP-2" {
+                modeline 173.00 1920 2048 2248 2576 1080 1083 1088 1120 "-hsync" "+vsync"
+                max-bpc 10
+                framebuffer-format "xrgb2101010"
+                broadcast-rgb "full"
+                colorimetry "bt2020-rgb"
+                hdr-metadata eotf="pq" max-luminance=600 max-cll=600 max-fall=400
+            }
+
+            output-profile "docked" {
+                output "eDP-1" {
+                    off
+                }
+                output "Dell Inc. U2720Q ABC123" {
+                    scale 1.5
+                }
+                exec "notify-send" "Docked"
             }
 
             layout {
@@ -3076,11 +3551,23 @@ mod tests {
             }
 
             screenshot-path "~/Screenshots/screenshot.png"
+            screenshot-format "jpeg" quality=80
 
             hotkey-overlay {
                 skip-at-startup
             }
 
+            workspace-switch-osd {
+                on
+                duration-ms 1500
+            }
+
+            capture-indicator {
+                border-width 2
+                color "#ff8000"
+                hide-badge
+            }
+
             animations {
                 slowdown 2.0
 
@@ -3109,6 +3596,7 @@ mod tests {
                 open-on-output "eDP-1"
                 open-maximized true
                 open-fullscreen false
+                allow-tearing true
 
                 focus-ring {
                     off
@@ -3122,30 +3610,40 @@ mod tests {
             }
 
             binds {
-                Mod+T allow-when-locked=true { spawn "alacritty"; }
-                Mod+Q { close-window; }
+                Mod+T allow-when-locked=true hotkey-overlay-title="Open a Terminal" { spawn "alacritty"; }
+                Mod+Q hotkey-overlay-title=null { close-window; }
                 Mod+Shift+H { focus-monitor-left; }
                 Mod+Ctrl+Shift+L { move-window-to-monitor-right; }
                 Mod+Comma { consume-window-into-column; }
                 Mod+1 { focus-workspace 1; }
                 Mod+Shift+1 { focus-workspace "workspace-1"; }
                 Mod+Shift+E { quit skip-confirmation=true; }
+                Print { screenshot-screen write-to-disk=false delay-ms=500; }
                 Mod+WheelScrollDown cooldown-ms=150 { focus-workspace-down; }
             }
 
             switch-events {
                 tablet-mode-on { spawn "bash" "-c" "gsettings set org.gnome.desktop.a11y.applications screen-keyboard-enabled true"; }
                 tablet-mode-off { spawn "bash" "-c" "gsettings set org.gnome.desktop.a11y.applications screen-keyboard-enabled false"; }
+                lid-open { power-on-monitors; }
+            }
+
+            clamshell {
+                on
+                suspend-when-undocked
             }
 
             debug {
                 render-drm-device "/dev/dri/renderD129"
+                disable-drm-syncobj
             }
 
             workspace "workspace-1" {
                 open-on-output "eDP-1"
             }
-            workspace "workspace-2"
+            workspace "workspace-2" {
+                background-image "/usr/share/backgrounds/default.jpg"
+            }
             workspace "workspace-3"
             "##,
             Config {
@@ -3227,15 +3725,97 @@ mod tests {
                     name: "eDP-1".to_owned(),
                     scale: Some(FloatOrInt(2.)),
                     transform: Transform::Flipped90,
+                    auto_rotate: true,
                     position: Some(Position { x: 10, y: 20 }),
-                    mode: Some(ConfiguredMode {
-                        width: 1920,
-                        height: 1080,
-                        refresh: Some(144.),
+                    mode: Some(Mode {
+                        custom: false,
+                        mode: ConfiguredMode {
+                            width: 1920,
+                            height: 1080,
+                            refresh: Some(144.),
+                        },
                     }),
+                    modeline: None,
                     variable_refresh_rate: Some(Vrr { on_demand: true }),
+                    render_time_prediction: Some(RenderTimePrediction {
+                        safety_margin_ms: 3,
+                    }),
+                    max_bpc: None,
+                    framebuffer_format: FramebufferFormat::Xrgb8888,
+                    broadcast_rgb: BroadcastRgb::Automatic,
+                    colorimetry: Colorimetry::Default,
+                    hdr_metadata: None,
+                    icc_profile: Some("~/.local/share/icc/monitor.icc".to_owned()),
                     background_color: Color::from_rgba8_unpremul(25, 25, 102, 255),
+                    background_image: Some(BackgroundImage {
+                        path: String::from("~/Pictures/wallpaper.png"),
+                        fit: BackgroundImageFit::Tile,
+                    }),
+                    mirror: None,
+                }, Output {
+                    name: "HDMI-A-1".to_owned(),
+                    mirror: Some(Mirror {
+                        output: "eDP-1".to_owned(),
+                        scaling: MirrorScaling::Fill,
+                    }),
+                    ..Default::default()
+                }, Output {
+                    name: "DP-1".to_owned(),
+                    mode: Some(Mode {
+                        custom: true,
+                        mode: ConfiguredMode {
+                            width: 2560,
+                            height: 1440,
+                            refresh: Some(75.),
+                        },
+                    }),
+                    ..Default::default()
+                }, Output {
+                    name: "DP-2".to_owned(),
+                    modeline: Some(Modeline {
+                        clock: FloatOrInt(173.),
+                        hdisplay: 1920,
+                        hsync_start: 2048,
+                        hsync_end: 2248,
+                        htotal: 2576,
+                        vdisplay: 1080,
+                        vsync_start: 1083,
+                        vsync_end: 1088,
+                        vtotal: 1120,
+                        hsync_polarity: HSyncPolarity::NHSync,
+                        vsync_polarity: VSyncPolarity::PVSync,
+                    }),
+                    max_bpc: Some(10),
+                    framebuffer_format: FramebufferFormat::Xrgb2101010,
+                    broadcast_rgb: BroadcastRgb::Full,
+                    colorimetry: Colorimetry::Bt2020Rgb,
+                    hdr_metadata: Some(HdrMetadata {
+                        eotf: Eotf::Pq,
+                        max_luminance: 600,
+                        min_luminance: FloatOrInt(0.005),
+                        max_cll: 600,
+                        max_fall: 400,
+                    }),
+                    ..Default::default()
                 }]),
+                output_profiles: vec![OutputProfile {
+                    name: "docked".to_owned(),
+                    outputs: Outputs(vec![
+                        Output {
+                            off: true,
+                            name: "eDP-1".to_owned(),
+                            ..Default::default()
+                        },
+                        Output {
+                            name: "Dell Inc. U2720Q ABC123".to_owned(),
+                            scale: Some(FloatOrInt(1.5)),
+                            ..Default::default()
+                        },
+                    ]),
+                    exec: vec![SpawnAtStartup {
+                        command: vec!["notify-send".to_owned(), "Docked".to_owned()],
+                    }],
+                }],
                 layout: Layout {
                     focus_ring: FocusRing {
                         off: false,
@@ -3312,9 +3892,24 @@ mod tests {
                     hide_after_inactive_ms: Some(3000),
                 },
                 screenshot_path: Some(String::from("~/Screenshots/screenshot.png")),
+                screenshot_format: ScreenshotFormat {
+                    format: ImageFormat::Jpeg,
+                    compression: PngCompression::Default,
+                    quality: 80,
+                },
                 hotkey_overlay: HotkeyOverlay {
                     skip_at_startup: true,
                 },
+                workspace_switch_osd: WorkspaceSwitchOsd {
+                    on: true,
+                    duration_ms: 1500,
+                },
+                capture_indicator: CaptureIndicator {
+                    off: false,
+                    border_width: FloatOrInt(2.),
+                    color: Color::from_rgba8_unpremul(255, 128, 0, 255),
+                    hide_badge: true,
+                },
                 animations: Animations {
                     slowdown: 2.,
                     workspace_switch: WorkspaceSwitchAnim(Animation {
@@ -3381,6 +3976,7 @@ mod tests {
                     open_on_output: Some("eDP-1".to_owned()),
                     open_maximized: Some(true),
                     open_fullscreen: Some(false),
+                    allow_tearing: Some(true),
                     focus_ring: BorderRule {
                         off: true,
                         width: Some(FloatOrInt(3.)),
@@ -3397,14 +3993,20 @@ mod tests {
                     Workspace {
                         name: WorkspaceName("workspace-1".to_string()),
                         open_on_output: Some("eDP-1".to_string()),
+                        background_image: None,
                     },
                     Workspace {
                         name: WorkspaceName("workspace-2".to_string()),
                         open_on_output: None,
+                        background_image: Some(BackgroundImage {
+                            path: String::from("/usr/share/backgrounds/default.jpg"),
+                            fit: BackgroundImageFit::Fill,
+                        }),
                     },
                     Workspace {
                         name: WorkspaceName("workspace-3".to_string()),
                         open_on_output: None,
+                        background_image: None,
                     },
                 ],
                 binds: Binds(vec![
@@ -3417,6 +4019,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: true,
+                        hotkey_overlay_title: Some(Some(String::from("Open a Terminal"))),
                     },
                     Bind {
                         key: Key {
@@ -3427,6 +4030,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: Some(None),
                     },
                     Bind {
                         key: Key {
@@ -3437,6 +4041,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                     Bind {
                         key: Key {
@@ -3447,6 +4052,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                     Bind {
                         key: Key {
@@ -3457,6 +4063,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                     Bind {
                         key: Key {
@@ -3467,6 +4074,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                     Bind {
                         key: Key {
@@ -3479,6 +4087,7 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                     Bind {
                         key: Key {
@@ -3489,6 +4098,18 @@ mod tests {
                         repeat: true,
                         cooldown: None,
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
+                    },
+                    Bind {
+                        key: Key {
+                            trigger: Trigger::Keysym(Keysym::Print),
+                            modifiers: Modifiers::empty(),
+                        },
+                        action: Action::ScreenshotScreen(false, Some(500), None),
+                        repeat: true,
+                        cooldown: None,
+                        allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                     Bind {
                         key: Key {
@@ -3499,28 +4120,36 @@ mod tests {
                         repeat: true,
                         cooldown: Some(Duration::from_millis(150)),
                         allow_when_locked: false,
+                        hotkey_overlay_title: None,
                     },
                 ]),
                 switch_events: SwitchBinds {
-                    lid_open: None,
+                    lid_open: Some(SwitchAction {
+                        action: Action::PowerOnMonitors,
+                    }),
                     lid_close: None,
                     tablet_mode_on: Some(SwitchAction {
-                        spawn: vec![
+                        action: Action::Spawn(vec![
                             "bash".to_owned(),
                             "-c".to_owned(),
                             "gsettings set org.gnome.desktop.a11y.applications screen-keyboard-enabled true".to_owned(),
-                        ],
+                        ]),
                     }),
                     tablet_mode_off: Some(SwitchAction {
-                        spawn: vec![
+                        action: Action::Spawn(vec![
                             "bash".to_owned(),
                             "-c".to_owned(),
                             "gsettings set org.gnome.desktop.a11y.applications screen-keyboard-enabled false".to_owned(),
-                        ],
+                        ]),
                     }),
                 },
+                clamshell: Clamshell {
+                    on: true,
+                    suspend_when_undocked: true,
+                },
                 debug: DebugConfig {
                     render_drm_device: Some(PathBuf::from("/dev/dri/renderD129")),
+                    disable_drm_syncobj: true,
                     ..Default::default()
                 },
             },
@@ -3532,6 +4161,14 @@ mod tests {
         let _ = Config::default();
     }
 
+    #[test]
+    fn explicit_sync_enabled_by_default() {
+        assert!(!Config::default().debug.disable_drm_syncobj);
+
+        let config = Config::parse("test.kdl", "debug {}").unwrap();
+        assert!(!config.debug.disable_drm_syncobj);
+    }
+
     #[test]
     fn parse_mode() {
         assert_eq!(
@@ -3779,6 +4416,46 @@ mod tests {
         assert!(!check("unknown unknown unknown", "DP-2", None, None, None));
     }
 
+    #[test]
+    fn test_output_profile_match() {
+        let profile = |names: &[&str]| OutputProfile {
+            name: String::new(),
+            outputs: names
+                .iter()
+                .map(|name| Output {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            exec: vec![],
+        };
+
+        let laptop = make_output_name("eDP-1", Some("BOE"), Some("0x0BCA"), None);
+        let dell = make_output_name("DP-3", Some("Dell"), Some("U2720Q"), Some("ABC"));
+        let lg = make_output_name("DP-4", Some("LG"), Some("27GL850"), None);
+
+        let docked = profile(&["eDP-1", "Dell U2720Q ABC"]);
+        assert!(docked.matches(&[laptop.clone(), dell.clone()]));
+        assert!(docked.matches(&[dell.clone(), laptop.clone()]));
+        assert!(!docked.matches(&[laptop.clone()]));
+        assert!(!docked.matches(&[laptop.clone(), lg.clone()]));
+        assert!(!docked.matches(&[laptop.clone(), dell.clone(), lg.clone()]));
+
+        // Both profile outputs match the Dell monitor, so the LG one is left over.
+        let ambiguous = profile(&["DP-3", "Dell U2720Q ABC"]);
+        assert!(!ambiguous.matches(&[dell.clone(), lg.clone()]));
+
+        // Two identical monitors without a serial both match by make and model, so the first
+        // profile output must leave DP-4 to the second one.
+        let lg2 = make_output_name("DP-5", Some("LG"), Some("27GL850"), None);
+        let twins = profile(&["LG 27GL850 Unknown", "DP-4"]);
+        assert!(twins.matches(&[lg.clone(), lg2.clone()]));
+        assert!(!twins.matches(&[lg.clone(), dell.clone()]));
+
+        assert!(profile(&[]).matches(&[]));
+        assert!(!profile(&[]).matches(&[laptop]));
+    }
+
     #[test]
     fn test_output_name_sorting() {
         let mut names = vec![
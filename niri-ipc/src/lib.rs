@@ -60,6 +60,26 @@ pub enum Request {
         /// Configuration to apply.
         action: OutputAction,
     },
+    /// Create a virtual output.
+    ///
+    /// Virtual outputs take part in the layout like real monitors and can be screencast, for
+    /// example to stream them to another device. The reply contains the name of the new output.
+    CreateVirtualOutput {
+        /// Mode of the virtual output.
+        mode: ConfiguredMode,
+    },
+    /// Destroy a virtual output.
+    DestroyVirtualOutput {
+        /// Name of the virtual output.
+        output: String,
+    },
+    /// Request render time prediction statistics of the outputs.
+    RenderTimes,
+    /// Request frame timing statistics of the outputs.
+    FrameStats {
+        /// Output name, or `None` for all outputs.
+        output: Option<String>,
+    },
     /// Start continuously receiving events from the compositor.
     ///
     /// The compositor should reply with `Reply::Ok(Response::Handled)`, then continuously send
@@ -113,6 +133,23 @@ pub enum Response {
     FocusedWindow(Option<Window>),
     /// Output configuration change result.
     OutputConfigChanged(OutputConfigChanged),
+    /// A virtual output was created.
+    ///
+    /// Contains the name of the new output.
+    VirtualOutputCreated(String),
+    /// A screenshot was saved.
+    ///
+    /// Contains the path where the screenshot was written, or `None` if it was only copied to the
+    /// clipboard. An error is returned instead if the screenshot couldn't be written to disk.
+    ScreenshotSaved(Option<String>),
+    /// Render time prediction statistics.
+    ///
+    /// Map from output name to statistics.
+    RenderTimes(HashMap<String, RenderTimeStats>),
+    /// Frame timing statistics.
+    ///
+    /// Map from output name to statistics.
+    FrameStats(HashMap<String, FrameStats>),
 }
 
 /// Actions that niri can perform.
@@ -134,6 +171,20 @@ pub enum Action {
     PowerOffMonitors {},
     /// Power on all monitors via DPMS.
     PowerOnMonitors {},
+    /// Power off a single monitor via DPMS.
+    PowerOffMonitor {
+        /// Name of the output to power off.
+        #[cfg_attr(feature = "clap", arg())]
+        output: String,
+    },
+    /// Power on a single monitor via DPMS.
+    PowerOnMonitor {
+        /// Name of the output to power on.
+        #[cfg_attr(feature = "clap", arg())]
+        output: String,
+    },
+    /// Lock or unlock automatic screen rotation.
+    ToggleRotationLock {},
     /// Spawn a command.
     Spawn {
         /// Command to spawn.
@@ -147,9 +198,47 @@ pub enum Action {
         delay_ms: Option<u16>,
     },
     /// Open the screenshot UI.
-    Screenshot {},
+    Screenshot {
+        /// Whether to write the screenshot to disk in addition to the clipboard.
+        ///
+        /// The screenshot is written to disk only if a path is set, either with `path` or with
+        /// the `screenshot-path` config option.
+        #[cfg_attr(
+            feature = "clap",
+            arg(short = 'd', long, action = clap::ArgAction::Set, default_value_t = true)
+        )]
+        #[serde(default = "default_true")]
+        write_to_disk: bool,
+        /// Delay in milliseconds before taking the screenshot.
+        #[cfg_attr(feature = "clap", arg(long))]
+        delay_ms: Option<u16>,
+        /// Path to save the screenshot to, overriding the `screenshot-path` config option.
+        ///
+        /// The path is formatted with `strftime(3)` the same way as `screenshot-path`.
+        #[cfg_attr(feature = "clap", arg(short, long))]
+        path: Option<String>,
+    },
     /// Screenshot the focused screen.
-    ScreenshotScreen {},
+    ScreenshotScreen {
+        /// Whether to write the screenshot to disk in addition to the clipboard.
+        ///
+        /// The screenshot is written to disk only if a path is set, either with `path` or with
+        /// the `screenshot-path` config option.
+        #[cfg_attr(
+            feature = "clap",
+            arg(short = 'd', long, action = clap::ArgAction::Set, default_value_t = true)
+        )]
+        #[serde(default = "default_true")]
+        write_to_disk: bool,
+        /// Delay in milliseconds before taking the screenshot.
+        #[cfg_attr(feature = "clap", arg(long))]
+        delay_ms: Option<u16>,
+        /// Path to save the screenshot to, overriding the `screenshot-path` config option.
+        ///
+        /// The path is formatted with `strftime(3)` the same way as `screenshot-path`.
+        #[cfg_attr(feature = "clap", arg(short, long))]
+        path: Option<String>,
+    },
     /// Screenshot a window.
     #[cfg_attr(feature = "clap", clap(about = "Screenshot the focused window"))]
     ScreenshotWindow {
@@ -158,6 +247,24 @@ pub enum Action {
         /// If `None`, uses the focused window.
         #[cfg_attr(feature = "clap", arg(long))]
         id: Option<u64>,
+        /// Whether to write the screenshot to disk in addition to the clipboard.
+        ///
+        /// The screenshot is written to disk only if a path is set, either with `path` or with
+        /// the `screenshot-path` config option.
+        #[cfg_attr(
+            feature = "clap",
+            arg(short = 'd', long, action = clap::ArgAction::Set, default_value_t = true)
+        )]
+        #[serde(default = "default_true")]
+        write_to_disk: bool,
+        /// Delay in milliseconds before taking the screenshot.
+        #[cfg_attr(feature = "clap", arg(long))]
+        delay_ms: Option<u16>,
+        /// Path to save the screenshot to, overriding the `screenshot-path` config option.
+        ///
+        /// The path is formatted with `strftime(3)` the same way as `screenshot-path`.
+        #[cfg_attr(feature = "clap", arg(short, long))]
+        path: Option<String>,
     },
     /// Close a window.
     #[cfg_attr(feature = "clap", clap(about = "Close the focused window"))]
@@ -404,6 +511,32 @@ pub enum Action {
     MoveWorkspaceToMonitorDown {},
     /// Move the focused workspace to the monitor above.
     MoveWorkspaceToMonitorUp {},
+    /// Set the dynamic screencast target to a window.
+    #[cfg_attr(
+        feature = "clap",
+        clap(about = "Set the dynamic screencast target to the focused window")
+    )]
+    SetDynamicCastWindow {
+        /// Id of the window to target.
+        ///
+        /// If `None`, uses the focused window.
+        #[cfg_attr(feature = "clap", arg(long))]
+        id: Option<u64>,
+    },
+    /// Set the dynamic screencast target to a monitor.
+    #[cfg_attr(
+        feature = "clap",
+        clap(about = "Set the dynamic screencast target to the focused monitor")
+    )]
+    SetDynamicCastMonitor {
+        /// Name of the output to target.
+        ///
+        /// If `None`, uses the focused output.
+        #[cfg_attr(feature = "clap", arg())]
+        output: Option<String>,
+    },
+    /// Clear the dynamic screencast target, making it show nothing.
+    ClearDynamicCastTarget {},
     /// Toggle a debug tint on windows.
     ToggleDebugTint {},
     /// Toggle visualization of render element opaque regions.
@@ -469,6 +602,56 @@ pub enum OutputAction {
         #[cfg_attr(feature = "clap", arg())]
         mode: ModeToSet,
     },
+    /// Set a custom output mode.
+    ///
+    /// Unlike `mode`, this does not need to be one of the modes advertised by the output. The
+    /// timings are computed with CVT reduced blanking.
+    CustomMode {
+        /// Custom mode to set, for example 2560x1440@75.
+        ///
+        /// The refresh rate defaults to 60 Hz.
+        #[cfg_attr(feature = "clap", arg())]
+        mode: ConfiguredMode,
+    },
+    /// Set a custom output modeline.
+    ///
+    /// This uses the same format as the X11 modelines and the output of the `cvt` and `gtf`
+    /// utilities.
+    Modeline {
+        /// Pixel clock in MHz.
+        #[cfg_attr(feature = "clap", arg())]
+        clock: f64,
+        /// Horizontal active pixels.
+        #[cfg_attr(feature = "clap", arg())]
+        hdisplay: u16,
+        /// Horizontal sync pulse start in pixels.
+        #[cfg_attr(feature = "clap", arg())]
+        hsync_start: u16,
+        /// Horizontal sync pulse end in pixels.
+        #[cfg_attr(feature = "clap", arg())]
+        hsync_end: u16,
+        /// Total horizontal pixels including blanking.
+        #[cfg_attr(feature = "clap", arg())]
+        htotal: u16,
+        /// Vertical active lines.
+        #[cfg_attr(feature = "clap", arg())]
+        vdisplay: u16,
+        /// Vertical sync pulse start in lines.
+        #[cfg_attr(feature = "clap", arg())]
+        vsync_start: u16,
+        /// Vertical sync pulse end in lines.
+        #[cfg_attr(feature = "clap", arg())]
+        vsync_end: u16,
+        /// Total vertical lines including blanking.
+        #[cfg_attr(feature = "clap", arg())]
+        vtotal: u16,
+        /// Horizontal sync polarity: "+hsync" or "-hsync".
+        #[cfg_attr(feature = "clap", arg(allow_hyphen_values = true))]
+        hsync_polarity: HSyncPolarity,
+        /// Vertical sync polarity: "+vsync" or "-vsync".
+        #[cfg_attr(feature = "clap", arg(allow_hyphen_values = true))]
+        vsync_polarity: VSyncPolarity,
+    },
     /// Set the output scale.
     Scale {
         /// Scale factor to set, or "auto" for automatic selection.
@@ -493,6 +676,33 @@ pub enum OutputAction {
         #[cfg_attr(feature = "clap", command(flatten))]
         vrr: VrrToSet,
     },
+    /// Mirror another output onto this output.
+    ///
+    /// The output is removed from the layout while it mirrors.
+    Mirror {
+        /// Name of the output to mirror.
+        #[cfg_attr(feature = "clap", arg())]
+        output: String,
+        /// How to scale the mirrored output to this output.
+        #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t))]
+        scaling: MirrorScaling,
+    },
+    /// Stop mirroring and return the output to the layout.
+    Unmirror,
+}
+
+/// How a mirrored output is scaled to the output that mirrors it.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum MirrorScaling {
+    /// Scale to fit inside the output, keeping the aspect ratio, with bars around.
+    #[default]
+    Fit,
+    /// Scale to cover the whole output, keeping the aspect ratio, cropping what doesn't fit.
+    Fill,
+    /// Stretch to the output size, ignoring the aspect ratio.
+    Stretch,
 }
 
 /// Output mode to set.
@@ -517,6 +727,26 @@ pub struct ConfiguredMode {
     pub refresh: Option<f64>,
 }
 
+/// Horizontal sync polarity of a modeline.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum HSyncPolarity {
+    /// Positive horizontal sync (`+hsync`).
+    PHSync,
+    /// Negative horizontal sync (`-hsync`).
+    NHSync,
+}
+
+/// Vertical sync polarity of a modeline.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum VSyncPolarity {
+    /// Positive vertical sync (`+vsync`).
+    PVSync,
+    /// Negative vertical sync (`-vsync`).
+    NVSync,
+}
+
 /// Output scale to set.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
@@ -598,12 +828,113 @@ pub struct Output {
     pub vrr_supported: bool,
     /// Whether variable refresh rate is enabled on the output.
     pub vrr_enabled: bool,
+    /// Whether the output is powered on.
+    ///
+    /// Outputs are powered off with the power-off actions or through the
+    /// wlr-output-power-management protocol.
+    pub powered: bool,
+    /// Color settings of the output.
+    ///
+    /// `None` if the output is disabled or isn't a physical monitor.
+    pub color: Option<OutputColor>,
     /// Logical output information.
     ///
     /// `None` if the output is not mapped to any logical output (for example, if it is disabled).
     pub logical: Option<LogicalOutput>,
 }
 
+/// Color settings of an output.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct OutputColor {
+    /// Maximum bits per color channel on the link, if the connector supports setting it.
+    pub max_bpc: Option<u8>,
+    /// Format of the framebuffers that are scanned out.
+    pub framebuffer_format: FramebufferFormat,
+    /// RGB quantization range, if the connector supports setting it.
+    pub broadcast_rgb: Option<BroadcastRgb>,
+    /// Colorimetry signaled to the monitor, if the connector supports setting it.
+    pub colorimetry: Option<Colorimetry>,
+    /// Whether the connector supports sending HDR metadata.
+    pub hdr_supported: bool,
+    /// HDR metadata sent to the monitor, if any.
+    pub hdr_metadata: Option<HdrMetadata>,
+}
+
+/// Framebuffer format of an output.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum FramebufferFormat {
+    /// 8 bits per color channel.
+    #[default]
+    Xrgb8888,
+    /// 10 bits per color channel.
+    Xrgb2101010,
+}
+
+/// RGB quantization range sent to the monitor.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum BroadcastRgb {
+    /// Let the driver pick the range based on the mode.
+    #[default]
+    Automatic,
+    /// Full range (0-255).
+    Full,
+    /// Limited range (16-235).
+    Limited,
+}
+
+/// Colorimetry signaled to the monitor.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum Colorimetry {
+    /// Default colorimetry of the monitor, usually sRGB.
+    #[default]
+    Default,
+    /// ITU-R BT.709 YCbCr.
+    Bt709Ycc,
+    /// ITU-R BT.2020 RGB.
+    Bt2020Rgb,
+    /// ITU-R BT.2020 YCbCr.
+    Bt2020Ycc,
+    /// DCI-P3 RGB with a D65 white point.
+    DciP3RgbD65,
+    /// opRGB (IEC 61966-2-5).
+    OpRgb,
+}
+
+/// HDR static metadata sent to the monitor.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct HdrMetadata {
+    /// Transfer function of the signal.
+    pub eotf: Eotf,
+    /// Maximum mastering display luminance in cd/m².
+    pub max_luminance: u16,
+    /// Minimum mastering display luminance in cd/m².
+    pub min_luminance: f64,
+    /// Maximum content light level in cd/m², or 0 if unknown.
+    pub max_cll: u16,
+    /// Maximum frame-average light level in cd/m², or 0 if unknown.
+    pub max_fall: u16,
+}
+
+/// Transfer function signaled in the HDR metadata.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum Eotf {
+    /// Traditional gamma, SDR luminance range.
+    TraditionalSdr,
+    /// Traditional gamma, HDR luminance range.
+    TraditionalHdr,
+    /// SMPTE ST 2084 (PQ).
+    #[default]
+    Pq,
+    /// Hybrid log-gamma.
+    Hlg,
+}
+
 /// Output mode.
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
@@ -689,6 +1020,65 @@ pub struct Window {
     pub is_focused: bool,
 }
 
+/// Ongoing screen capture.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Cast {
+    /// Unique id of this capture.
+    ///
+    /// This id remains constant while the capture is ongoing.
+    pub id: u64,
+    /// How the contents are being captured.
+    pub kind: CastKind,
+    /// What is being captured.
+    pub target: CastTarget,
+    /// Process ID of the capturing client, if known.
+    ///
+    /// Screencasts go through the portal, so their client is not known.
+    pub pid: Option<i32>,
+}
+
+/// Method of a screen capture.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum CastKind {
+    /// PipeWire screencast started through xdg-desktop-portal-gnome.
+    PipeWire,
+    /// Capture through the wlr-screencopy protocol.
+    WlrScreencopy,
+    /// Capture through the ext-image-copy-capture protocol.
+    ImageCopyCapture,
+}
+
+/// Target of a screen capture.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum CastTarget {
+    /// An output.
+    Output {
+        /// Name of the output.
+        name: String,
+    },
+    /// A toplevel window.
+    Window {
+        /// Id of the window.
+        id: u64,
+    },
+    /// A rectangle in the global logical coordinate space.
+    Area {
+        /// Logical X position.
+        x: i32,
+        /// Logical Y position.
+        y: i32,
+        /// Width in logical pixels.
+        width: i32,
+        /// Height in logical pixels.
+        height: i32,
+    },
+    /// A dynamic screencast target that currently shows nothing.
+    Nothing,
+}
+
 /// Output configuration change result.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
@@ -699,6 +1089,67 @@ pub enum OutputConfigChanged {
     OutputWasMissing,
 }
 
+/// Render time prediction statistics of an output.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct RenderTimeStats {
+    /// Configured safety margin in milliseconds, or `None` if render time prediction is disabled.
+    pub safety_margin_ms: Option<u16>,
+    /// Render time that the next frame is scheduled for, in microseconds.
+    ///
+    /// This is the longest render time among the recent frames, or `None` if nothing was rendered
+    /// yet.
+    pub predicted_render_time_us: Option<u64>,
+    /// Number of redraws that were delayed until right before the deadline.
+    pub delayed_frames: u64,
+    /// Number of frames that were scheduled with prediction but missed their deadline.
+    pub missed_deadlines: u64,
+    /// Whether prediction is suspended after a missed deadline, rendering frames right away.
+    pub falling_back: bool,
+}
+
+/// Frame timing statistics of an output since it was connected.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct FrameStats {
+    /// Number of frames submitted for presentation.
+    pub frames: u64,
+    /// Number of frames where a client buffer was directly scanned out on the primary plane.
+    pub direct_scanout_frames: u64,
+    /// Wall time it took to render the frames, in microseconds.
+    pub render_time_us: Histogram,
+    /// Time from submitting the frames to their presentation, in microseconds.
+    pub time_to_present_us: Histogram,
+    /// Number of vblanks by which the frames were presented later than their target.
+    pub missed_vblanks: Histogram,
+    /// Damaged area of the frames, in percent of the output area.
+    pub damage_percent: Histogram,
+}
+
+/// A histogram of recorded values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Histogram {
+    /// Buckets in increasing order.
+    pub buckets: Vec<HistogramBucket>,
+    /// Number of recorded values.
+    pub count: u64,
+    /// Sum of the recorded values.
+    pub sum: u64,
+    /// Largest recorded value.
+    pub max: u64,
+}
+
+/// A histogram bucket.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct HistogramBucket {
+    /// Inclusive upper bound of the values in this bucket, or `None` for the last bucket.
+    pub le: Option<u64>,
+    /// Number of values in this bucket.
+    pub count: u64,
+}
+
 /// A workspace.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
@@ -817,6 +1268,26 @@ pub enum Event {
         /// Index of the newly active layout.
         idx: u8,
     },
+    /// A screen capture started, or the target of an ongoing capture changed.
+    CastStarted {
+        /// The new or updated capture.
+        cast: Cast,
+    },
+    /// A screen capture stopped.
+    CastStopped {
+        /// Id of the stopped capture.
+        id: u64,
+    },
+    /// The active output profile changed.
+    OutputProfileChanged {
+        /// Name of the newly active output profile, or `None` if no profile matches the connected
+        /// outputs.
+        name: Option<String>,
+    },
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl FromStr for WorkspaceReferenceArg {
@@ -910,6 +1381,100 @@ impl FromStr for Transform {
     }
 }
 
+impl FromStr for MirrorScaling {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fit" => Ok(Self::Fit),
+            "fill" => Ok(Self::Fill),
+            "stretch" => Ok(Self::Stretch),
+            _ => Err(r#"invalid mirror scaling, can be "fit", "fill" or "stretch""#),
+        }
+    }
+}
+
+impl FromStr for FramebufferFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xrgb8888" => Ok(Self::Xrgb8888),
+            "xrgb2101010" => Ok(Self::Xrgb2101010),
+            _ => Err(r#"invalid framebuffer format, can be "xrgb8888" or "xrgb2101010""#),
+        }
+    }
+}
+
+impl FromStr for BroadcastRgb {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "automatic" => Ok(Self::Automatic),
+            "full" => Ok(Self::Full),
+            "limited" => Ok(Self::Limited),
+            _ => Err(r#"invalid broadcast RGB, can be "automatic", "full" or "limited""#),
+        }
+    }
+}
+
+impl FromStr for Colorimetry {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "bt709-ycc" => Ok(Self::Bt709Ycc),
+            "bt2020-rgb" => Ok(Self::Bt2020Rgb),
+            "bt2020-ycc" => Ok(Self::Bt2020Ycc),
+            "dci-p3-rgb-d65" => Ok(Self::DciP3RgbD65),
+            "oprgb" => Ok(Self::OpRgb),
+            _ => Err(
+                r#"invalid colorimetry, can be "default", "bt709-ycc", "bt2020-rgb", "bt2020-ycc", "dci-p3-rgb-d65" or "oprgb""#,
+            ),
+        }
+    }
+}
+
+impl FromStr for Eotf {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "traditional-sdr" => Ok(Self::TraditionalSdr),
+            "traditional-hdr" => Ok(Self::TraditionalHdr),
+            "pq" => Ok(Self::Pq),
+            "hlg" => Ok(Self::Hlg),
+            _ => Err(r#"invalid EOTF, can be "traditional-sdr", "traditional-hdr", "pq" or "hlg""#),
+        }
+    }
+}
+
+impl FromStr for HSyncPolarity {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "+hsync" => Ok(Self::PHSync),
+            "-hsync" => Ok(Self::NHSync),
+            _ => Err(r#"invalid horizontal sync polarity, can be "+hsync" or "-hsync""#),
+        }
+    }
+}
+
+impl FromStr for VSyncPolarity {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "+vsync" => Ok(Self::PVSync),
+            "-vsync" => Ok(Self::NVSync),
+            _ => Err(r#"invalid vertical sync polarity, can be "+vsync" or "-vsync""#),
+        }
+    }
+}
+
 impl FromStr for ModeToSet {
     type Err = &'static str;
 
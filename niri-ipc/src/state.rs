@@ -9,7 +9,7 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
-use crate::{Event, KeyboardLayouts, Window, Workspace};
+use crate::{Cast, Event, KeyboardLayouts, Window, Workspace};
 
 /// Part of the state communicated via the event stream.
 pub trait EventStreamStatePart {
@@ -40,6 +40,12 @@ pub struct EventStreamState {
 
     /// State of the keyboard layouts.
     pub keyboard_layouts: KeyboardLayoutsState,
+
+    /// State of the screen captures.
+    pub casts: CastsState,
+
+    /// State of the output profiles.
+    pub output_profile: OutputProfileState,
 }
 
 /// The workspaces state communicated over the event stream.
@@ -56,6 +62,20 @@ pub struct WindowsState {
     pub windows: HashMap<u64, Window>,
 }
 
+/// The screen capture state communicated over the event stream.
+#[derive(Debug, Default)]
+pub struct CastsState {
+    /// Map from a capture id to the capture.
+    pub casts: HashMap<u64, Cast>,
+}
+
+/// The output profile state communicated over the event stream.
+#[derive(Debug, Default)]
+pub struct OutputProfileState {
+    /// Name of the active output profile, if any.
+    pub name: Option<String>,
+}
+
 /// The keyboard layout state communicated over the event stream.
 #[derive(Debug, Default)]
 pub struct KeyboardLayoutsState {
@@ -69,6 +89,8 @@ impl EventStreamStatePart for EventStreamState {
         events.extend(self.workspaces.replicate());
         events.extend(self.windows.replicate());
         events.extend(self.keyboard_layouts.replicate());
+        events.extend(self.casts.replicate());
+        events.extend(self.output_profile.replicate());
         events
     }
 
@@ -76,6 +98,8 @@ impl EventStreamStatePart for EventStreamState {
         let event = self.workspaces.apply(event)?;
         let event = self.windows.apply(event)?;
         let event = self.keyboard_layouts.apply(event)?;
+        let event = self.casts.apply(event)?;
+        let event = self.output_profile.apply(event)?;
         Some(event)
     }
 }
@@ -192,3 +216,44 @@ impl EventStreamStatePart for KeyboardLayoutsState {
         None
     }
 }
+
+impl EventStreamStatePart for CastsState {
+    fn replicate(&self) -> Vec<Event> {
+        self.casts
+            .values()
+            .cloned()
+            .map(|cast| Event::CastStarted { cast })
+            .collect()
+    }
+
+    fn apply(&mut self, event: Event) -> Option<Event> {
+        match event {
+            Event::CastStarted { cast } => {
+                self.casts.insert(cast.id, cast);
+            }
+            Event::CastStopped { id } => {
+                let cast = self.casts.remove(&id);
+                cast.expect("stopped cast was missing from the map");
+            }
+            event => return Some(event),
+        }
+        None
+    }
+}
+
+impl EventStreamStatePart for OutputProfileState {
+    fn replicate(&self) -> Vec<Event> {
+        let name = self.name.clone();
+        vec![Event::OutputProfileChanged { name }]
+    }
+
+    fn apply(&mut self, event: Event) -> Option<Event> {
+        match event {
+            Event::OutputProfileChanged { name } => {
+                self.name = name;
+            }
+            event => return Some(event),
+        }
+        None
+    }
+}
@@ -0,0 +1,257 @@
+//! Headless backend with virtual outputs.
+//!
+//! Used for running niri without any display or GPU, for example in CI or for remote sessions
+//! that are fed through screencasts.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use niri_config::{Config, OutputName};
+use niri_ipc::ConfiguredMode;
+use smithay::backend::allocator::dmabuf::Dmabuf;
+use smithay::backend::allocator::gbm::GbmDevice;
+use smithay::backend::drm::DrmDeviceFd;
+use smithay::backend::egl::{EGLContext, EGLDevice, EGLDisplay};
+use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::backend::renderer::{DebugFlags, ImportDma, ImportEgl, Renderer};
+use smithay::output::Output;
+use smithay::reexports::calloop::LoopHandle;
+use smithay::utils::DeviceFd;
+
+use super::virtual_output::VirtualOutputs;
+use super::{IpcOutputMap, RenderResult};
+use crate::niri::{Niri, State};
+use crate::render_helpers::{resources, shaders};
+
+/// Options of the headless backend.
+#[derive(Debug, Clone, Copy)]
+pub struct HeadlessOptions {
+    /// Number of virtual outputs.
+    pub outputs: u8,
+    /// Mode of the virtual outputs that don't have one set in the config.
+    pub mode: ConfiguredMode,
+}
+
+pub struct Headless {
+    config: Rc<RefCell<Config>>,
+    renderer: GlesRenderer,
+    /// GBM device of the render node, used for screencasting.
+    gbm: Option<GbmDevice<DrmDeviceFd>>,
+    virtual_outputs: VirtualOutputs,
+    ipc_outputs: Arc<Mutex<IpcOutputMap>>,
+}
+
+impl Headless {
+    pub fn new(
+        config: Rc<RefCell<Config>>,
+        _event_loop: LoopHandle<State>,
+        options: HeadlessOptions,
+    ) -> anyhow::Result<Self> {
+        let (renderer, gbm) = create_surfaceless_renderer()?;
+
+        let mut virtual_outputs = VirtualOutputs::new(config.clone());
+        for idx in 1..=options.outputs {
+            let name = OutputName {
+                connector: format!("HEADLESS-{idx}"),
+                make: Some("niri".to_owned()),
+                model: Some("Headless".to_owned()),
+                serial: None,
+            };
+            virtual_outputs.add(name, options.mode);
+        }
+
+        Ok(Self {
+            config,
+            renderer,
+            gbm,
+            virtual_outputs,
+            ipc_outputs: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub fn init(&mut self, niri: &mut Niri) {
+        let renderer = &mut self.renderer;
+        if let Err(err) = renderer.bind_wl_display(&niri.display_handle) {
+            warn!("error binding renderer wl_display: {err}");
+        }
+
+        resources::init(renderer);
+        shaders::init(renderer);
+
+        let config = self.config.borrow();
+        if let Some(src) = config.animations.window_resize.custom_shader.as_deref() {
+            shaders::set_custom_resize_program(renderer, Some(src));
+        }
+        if let Some(src) = config.animations.window_close.custom_shader.as_deref() {
+            shaders::set_custom_close_program(renderer, Some(src));
+        }
+        if let Some(src) = config.animations.window_open.custom_shader.as_deref() {
+            shaders::set_custom_open_program(renderer, Some(src));
+        }
+        drop(config);
+
+        niri.layout.update_shaders();
+
+        // Textures from the old renderer are no longer valid.
+        niri.background_images.clear();
+        niri.update_background_images();
+
+        self.on_output_config_changed(niri);
+    }
+
+    pub fn seat_name(&self) -> String {
+        "headless".to_owned()
+    }
+
+    pub fn with_primary_renderer<T>(
+        &mut self,
+        f: impl FnOnce(&mut GlesRenderer) -> T,
+    ) -> Option<T> {
+        Some(f(&mut self.renderer))
+    }
+
+    pub fn render(
+        &mut self,
+        niri: &mut Niri,
+        output: &Output,
+        target_presentation_time: Duration,
+    ) -> RenderResult {
+        let _span = tracy_client::span!("Headless::render");
+
+        self.virtual_outputs
+            .render(&mut self.renderer, niri, output, target_presentation_time)
+    }
+
+    pub fn toggle_debug_tint(&mut self) {
+        let renderer = &mut self.renderer;
+        renderer.set_debug_flags(renderer.debug_flags() ^ DebugFlags::TINT);
+    }
+
+    pub fn import_dmabuf(&mut self, dmabuf: &Dmabuf) -> bool {
+        match self.renderer.import_dmabuf(dmabuf, None) {
+            Ok(_texture) => true,
+            Err(err) => {
+                debug!("error importing dmabuf: {err:?}");
+                false
+            }
+        }
+    }
+
+    pub fn gbm_device(&self) -> Option<GbmDevice<DrmDeviceFd>> {
+        self.gbm.clone()
+    }
+
+    pub fn ipc_outputs(&self) -> Arc<Mutex<IpcOutputMap>> {
+        self.ipc_outputs.clone()
+    }
+
+    pub fn profile_output_names(&self) -> Vec<OutputName> {
+        self.ipc_outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| !self.virtual_outputs.is_created_at_runtime(**id))
+            .map(|(_, output)| OutputName::from_ipc_output(output))
+            .collect()
+    }
+
+    pub fn create_virtual_output(&mut self, niri: &mut Niri, mode: ConfiguredMode) -> String {
+        let name = self.virtual_outputs.create(niri, mode);
+        self.refresh_ipc_outputs(niri);
+        name
+    }
+
+    pub fn destroy_virtual_output(&mut self, niri: &mut Niri, name: &str) -> bool {
+        let destroyed = self.virtual_outputs.destroy(niri, name);
+        self.refresh_ipc_outputs(niri);
+        destroyed
+    }
+
+    pub fn set_virtual_output_mode(
+        &mut self,
+        niri: &mut Niri,
+        name: &str,
+        mode: ConfiguredMode,
+    ) -> bool {
+        let changed = self.virtual_outputs.set_mode(niri, name, mode);
+        self.refresh_ipc_outputs(niri);
+        changed
+    }
+
+    pub fn on_output_config_changed(&mut self, niri: &mut Niri) {
+        self.virtual_outputs.on_output_config_changed(niri);
+        self.refresh_ipc_outputs(niri);
+    }
+
+    fn refresh_ipc_outputs(&self, niri: &mut Niri) {
+        let _span = tracy_client::span!("Headless::refresh_ipc_outputs");
+
+        let ipc_outputs = self.virtual_outputs.ipc_outputs(niri).collect();
+
+        let mut guard = self.ipc_outputs.lock().unwrap();
+        *guard = ipc_outputs;
+        niri.ipc_outputs_changed = true;
+    }
+}
+
+/// Creates a GLES renderer on a surfaceless EGL display.
+///
+/// Hardware devices are preferred, falling back to software rendering (llvmpipe). For hardware
+/// devices, also opens a GBM device on the render node for allocating screencast buffers.
+fn create_surfaceless_renderer() -> anyhow::Result<(GlesRenderer, Option<GbmDevice<DrmDeviceFd>>)> {
+    let mut devices: Vec<_> = EGLDevice::enumerate()
+        .context("error enumerating EGL devices")?
+        .collect();
+    devices.sort_by_key(|device| {
+        device
+            .extensions()
+            .iter()
+            .any(|ext| ext == "EGL_MESA_device_software")
+    });
+
+    for device in devices {
+        let render_node = device.render_device_path().ok();
+
+        let res = (|| {
+            let display = unsafe { EGLDisplay::new(device) }.context("error creating display")?;
+            let context = EGLContext::new(&display).context("error creating context")?;
+            let renderer =
+                unsafe { GlesRenderer::new(context) }.context("error creating renderer")?;
+            anyhow::Ok(renderer)
+        })();
+
+        let renderer = match res {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                debug!("skipping EGL device: {err:?}");
+                continue;
+            }
+        };
+
+        let gbm = render_node.and_then(|path| match open_gbm_device(&path) {
+            Ok(gbm) => Some(gbm),
+            Err(err) => {
+                warn!("error opening GBM device {path:?}: {err:?}");
+                None
+            }
+        });
+
+        return Ok((renderer, gbm));
+    }
+
+    Err(anyhow!("no usable EGL device found"))
+}
+
+fn open_gbm_device(path: &Path) -> anyhow::Result<GbmDevice<DrmDeviceFd>> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let fd = DrmDeviceFd::new(DeviceFd::from(OwnedFd::from(file)));
+    let gbm = GbmDevice::new(fd)?;
+    Ok(gbm)
+}
@@ -2,7 +2,11 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use anyhow::{bail, ensure};
+use niri_config::OutputName;
+use niri_ipc::ConfiguredMode;
 use smithay::backend::allocator::dmabuf::Dmabuf;
+use smithay::backend::drm::DrmNode;
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::output::Output;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
@@ -11,15 +15,21 @@ use crate::input::CompositorMod;
 use crate::niri::Niri;
 use crate::utils::id::IdCounter;
 
+pub mod headless;
+pub use headless::Headless;
+
 pub mod tty;
 pub use tty::Tty;
 
 pub mod winit;
 pub use winit::Winit;
 
+pub mod virtual_output;
+
 pub enum Backend {
     Tty(Tty),
     Winit(Winit),
+    Headless(Headless),
 }
 
 #[derive(PartialEq, Eq)]
@@ -54,6 +64,7 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.init(niri),
             Backend::Winit(winit) => winit.init(niri),
+            Backend::Headless(headless) => headless.init(niri),
         }
     }
 
@@ -61,6 +72,7 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.seat_name(),
             Backend::Winit(winit) => winit.seat_name(),
+            Backend::Headless(headless) => headless.seat_name(),
         }
     }
 
@@ -71,6 +83,14 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.with_primary_renderer(f),
             Backend::Winit(winit) => winit.with_primary_renderer(f),
+            Backend::Headless(headless) => headless.with_primary_renderer(f),
+        }
+    }
+
+    pub fn primary_render_node(&self) -> Option<DrmNode> {
+        match self {
+            Backend::Tty(tty) => Some(tty.primary_render_node()),
+            Backend::Winit(_) | Backend::Headless(_) => None,
         }
     }
 
@@ -83,6 +103,7 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.render(niri, output, target_presentation_time),
             Backend::Winit(winit) => winit.render(niri, output),
+            Backend::Headless(headless) => headless.render(niri, output, target_presentation_time),
         }
     }
 
@@ -90,20 +111,21 @@ impl Backend {
         match self {
             Backend::Tty(_) => CompositorMod::Super,
             Backend::Winit(_) => CompositorMod::Alt,
+            Backend::Headless(_) => CompositorMod::Super,
         }
     }
 
     pub fn change_vt(&mut self, vt: i32) {
         match self {
             Backend::Tty(tty) => tty.change_vt(vt),
-            Backend::Winit(_) => (),
+            Backend::Winit(_) | Backend::Headless(_) => (),
         }
     }
 
     pub fn suspend(&mut self) {
         match self {
             Backend::Tty(tty) => tty.suspend(),
-            Backend::Winit(_) => (),
+            Backend::Winit(_) | Backend::Headless(_) => (),
         }
     }
 
@@ -111,6 +133,7 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.toggle_debug_tint(),
             Backend::Winit(winit) => winit.toggle_debug_tint(),
+            Backend::Headless(headless) => headless.toggle_debug_tint(),
         }
     }
 
@@ -118,13 +141,14 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.import_dmabuf(dmabuf),
             Backend::Winit(winit) => winit.import_dmabuf(dmabuf),
+            Backend::Headless(headless) => headless.import_dmabuf(dmabuf),
         }
     }
 
     pub fn early_import(&mut self, surface: &WlSurface) {
         match self {
             Backend::Tty(tty) => tty.early_import(surface),
-            Backend::Winit(_) => (),
+            Backend::Winit(_) | Backend::Headless(_) => (),
         }
     }
 
@@ -132,6 +156,24 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.ipc_outputs(),
             Backend::Winit(winit) => winit.ipc_outputs(),
+            Backend::Headless(headless) => headless.ipc_outputs(),
+        }
+    }
+
+    /// Returns the names of the connected outputs that output profiles match against.
+    ///
+    /// Virtual outputs created at runtime don't count, since they come and go with screencasts.
+    pub fn profile_output_names(&self) -> Vec<OutputName> {
+        match self {
+            Backend::Tty(tty) => tty.profile_output_names(),
+            Backend::Winit(winit) => winit
+                .ipc_outputs()
+                .lock()
+                .unwrap()
+                .values()
+                .map(OutputName::from_ipc_output)
+                .collect(),
+            Backend::Headless(headless) => headless.profile_output_names(),
         }
     }
 
@@ -143,20 +185,35 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.primary_gbm_device(),
             Backend::Winit(_) => None,
+            Backend::Headless(headless) => headless.gbm_device(),
         }
     }
 
     pub fn set_monitors_active(&mut self, active: bool) {
         match self {
             Backend::Tty(tty) => tty.set_monitors_active(active),
-            Backend::Winit(_) => (),
+            Backend::Winit(_) | Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn has_external_monitor(&self) -> bool {
+        match self {
+            Backend::Tty(tty) => tty.has_external_monitor(),
+            Backend::Winit(_) | Backend::Headless(_) => false,
+        }
+    }
+
+    pub fn set_output_powered(&mut self, output: &Output, powered: bool) {
+        match self {
+            Backend::Tty(tty) => tty.set_output_powered(output, powered),
+            Backend::Winit(_) | Backend::Headless(_) => (),
         }
     }
 
     pub fn set_output_on_demand_vrr(&mut self, niri: &mut Niri, output: &Output, enable_vrr: bool) {
         match self {
             Backend::Tty(tty) => tty.set_output_on_demand_vrr(niri, output, enable_vrr),
-            Backend::Winit(_) => (),
+            Backend::Winit(_) | Backend::Headless(_) => (),
         }
     }
 
@@ -164,13 +221,52 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.on_output_config_changed(niri),
             Backend::Winit(_) => (),
+            Backend::Headless(headless) => headless.on_output_config_changed(niri),
+        }
+    }
+
+    /// Creates a virtual output at runtime, returning its name.
+    pub fn create_virtual_output(
+        &mut self,
+        niri: &mut Niri,
+        mode: ConfiguredMode,
+    ) -> anyhow::Result<String> {
+        match self {
+            Backend::Tty(tty) => Ok(tty.create_virtual_output(niri, mode)),
+            Backend::Winit(_) => bail!("virtual outputs are not supported on the winit backend"),
+            Backend::Headless(headless) => Ok(headless.create_virtual_output(niri, mode)),
         }
     }
 
+    pub fn destroy_virtual_output(&mut self, niri: &mut Niri, name: &str) -> anyhow::Result<()> {
+        let destroyed = match self {
+            Backend::Tty(tty) => tty.destroy_virtual_output(niri, name),
+            Backend::Winit(_) => false,
+            Backend::Headless(headless) => headless.destroy_virtual_output(niri, name),
+        };
+        ensure!(destroyed, "there's no virtual output named {name}");
+        Ok(())
+    }
+
+    pub fn set_virtual_output_mode(
+        &mut self,
+        niri: &mut Niri,
+        name: &str,
+        mode: ConfiguredMode,
+    ) -> anyhow::Result<()> {
+        let changed = match self {
+            Backend::Tty(tty) => tty.set_virtual_output_mode(niri, name, mode),
+            Backend::Winit(_) => false,
+            Backend::Headless(headless) => headless.set_virtual_output_mode(niri, name, mode),
+        };
+        ensure!(changed, "there's no virtual output named {name}");
+        Ok(())
+    }
+
     pub fn on_debug_config_changed(&mut self) {
         match self {
             Backend::Tty(tty) => tty.on_debug_config_changed(),
-            Backend::Winit(_) => (),
+            Backend::Winit(_) | Backend::Headless(_) => (),
         }
     }
 
@@ -197,4 +293,12 @@ impl Backend {
             panic!("backend is not Winit")
         }
     }
+
+    pub fn headless(&mut self) -> &mut Headless {
+        if let Self::Headless(v) = self {
+            v
+        } else {
+            panic!("backend is not Headless")
+        }
+    }
 }
@@ -4,7 +4,7 @@ use std::fmt::Write;
 use std::iter::zip;
 use std::num::NonZeroU64;
 use std::os::fd::AsFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -14,20 +14,27 @@ use anyhow::{anyhow, bail, ensure, Context};
 use bytemuck::cast_slice_mut;
 use libc::dev_t;
 use niri_config::{Config, OutputName};
+use niri_ipc::{
+    BroadcastRgb, Colorimetry, ConfiguredMode, Eotf, FramebufferFormat, HSyncPolarity,
+    VSyncPolarity,
+};
 use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::allocator::format::FormatSet;
 use smithay::backend::allocator::gbm::{GbmAllocator, GbmBufferFlags, GbmDevice};
 use smithay::backend::allocator::Fourcc;
 use smithay::backend::drm::compositor::{DrmCompositor, PrimaryPlaneElement};
+use smithay::backend::drm::gbm::{framebuffer_from_wayland_buffer, GbmFramebuffer};
 use smithay::backend::drm::{
     DrmDevice, DrmDeviceFd, DrmEvent, DrmEventMetadata, DrmEventTime, DrmNode, NodeType,
 };
 use smithay::backend::egl::context::ContextPriority;
 use smithay::backend::egl::{EGLDevice, EGLDisplay};
 use smithay::backend::libinput::{LibinputInputBackend, LibinputSessionInterface};
+use smithay::backend::renderer::element::{Element, Id};
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::backend::renderer::multigpu::gbm::GbmGlesBackend;
 use smithay::backend::renderer::multigpu::{GpuManager, MultiFrame, MultiRenderer};
+use smithay::backend::renderer::utils::with_renderer_surface_state;
 use smithay::backend::renderer::{DebugFlags, ImportDma, ImportEgl, Renderer};
 use smithay::backend::session::libseat::LibSeatSession;
 use smithay::backend::session::{Event as SessionEvent, Session};
@@ -38,7 +45,7 @@ use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
 use smithay::reexports::calloop::{Dispatcher, LoopHandle, RegistrationToken};
 use smithay::reexports::drm::control::{
     self, connector, crtc, property, Device, Mode as DrmMode, ModeFlags, ModeTypeFlags,
-    ResourceHandle,
+    PageFlipFlags, ResourceHandle,
 };
 use smithay::reexports::gbm::Modifier;
 use smithay::reexports::input::Libinput;
@@ -50,10 +57,12 @@ use smithay::wayland::dmabuf::{DmabufFeedback, DmabufFeedbackBuilder, DmabufGlob
 use smithay::wayland::drm_lease::{
     DrmLease, DrmLeaseBuilder, DrmLeaseRequest, DrmLeaseState, LeaseRejected,
 };
+use smithay::wayland::drm_syncobj::{supports_syncobj_eventfd, DrmSyncobjState};
 use smithay_drm_extras::drm_scanner::{DrmScanEvent, DrmScanner};
 use wayland_protocols::wp::linux_dmabuf::zv1::server::zwp_linux_dmabuf_feedback_v1::TrancheFlags;
 use wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
 
+use super::virtual_output::VirtualOutputs;
 use super::{IpcOutputMap, RenderResult};
 use crate::backend::OutputId;
 use crate::frame_clock::FrameClock;
@@ -61,9 +70,31 @@ use crate::niri::{Niri, RedrawState, State};
 use crate::render_helpers::debug::draw_damage;
 use crate::render_helpers::renderer::AsGlesRenderer;
 use crate::render_helpers::{resources, shaders, RenderTarget};
-use crate::utils::{get_monotonic_time, logical_output};
+use crate::utils::icc::{gamma_ramp, Vcgt};
+use crate::utils::{expand_home, get_monotonic_time, is_laptop_panel, logical_output};
 
 const SUPPORTED_COLOR_FORMATS: &[Fourcc] = &[Fourcc::Argb8888, Fourcc::Abgr8888];
+const SUPPORTED_COLOR_FORMATS_10_BIT: &[Fourcc] = &[
+    Fourcc::Argb2101010,
+    Fourcc::Abgr2101010,
+    Fourcc::Argb8888,
+    Fourcc::Abgr8888,
+];
+
+const BROADCAST_RGB_NAMES: &[(BroadcastRgb, &str)] = &[
+    (BroadcastRgb::Automatic, "Automatic"),
+    (BroadcastRgb::Full, "Full"),
+    (BroadcastRgb::Limited, "Limited 16:235"),
+];
+
+const COLORIMETRY_NAMES: &[(Colorimetry, &str)] = &[
+    (Colorimetry::Default, "Default"),
+    (Colorimetry::Bt709Ycc, "BT709_YCC"),
+    (Colorimetry::Bt2020Rgb, "BT2020_RGB"),
+    (Colorimetry::Bt2020Ycc, "BT2020_YCC"),
+    (Colorimetry::DciP3RgbD65, "DCI-P3_RGB_D65"),
+    (Colorimetry::OpRgb, "opRGB"),
+];
 
 pub struct Tty {
     config: Rc<RefCell<Config>>,
@@ -85,6 +116,8 @@ pub struct Tty {
     update_output_config_on_resume: bool,
     // Whether the debug tinting is enabled.
     debug_tint: bool,
+    // Virtual outputs created at runtime.
+    virtual_outputs: VirtualOutputs,
     ipc_outputs: Arc<Mutex<IpcOutputMap>>,
 }
 
@@ -181,9 +214,23 @@ struct Surface {
     connector: connector::Handle,
     dmabuf_feedback: Option<SurfaceDmabufFeedback>,
     gamma_props: Option<GammaProps>,
-    /// Gamma change to apply upon session resume.
-    pending_gamma_change: Option<Option<Vec<u16>>>,
+    /// Gamma ramp set by a gamma control client.
+    client_gamma: Option<Vec<u16>>,
+    /// ICC profile path from the output config.
+    icc_profile: Option<String>,
+    /// Calibration curves from the ICC profile, applied on top of the client gamma.
+    vcgt: Option<Vcgt>,
+    /// Whether the gamma changed and needs to be applied upon session resume.
+    pending_gamma_change: bool,
     vrr_enabled: bool,
+    /// Whether the last frame qualified for a tearing page flip.
+    tearing: bool,
+    /// Async page flip waiting for its page flip event.
+    pending_async_flip: Option<AsyncFlip>,
+    /// Framebuffer of the last async page flip, kept alive while it is on screen.
+    async_flip_fb: Option<GbmFramebuffer>,
+    /// Color settings that the connector was set up with.
+    color: ColorSettings,
     /// Tracy frame that goes from vblank to vblank.
     vblank_frame: Option<tracy_client::Frame>,
     /// Frame name for the VBlank frame.
@@ -195,6 +242,22 @@ struct Surface {
     sequence_delta_plot_name: tracy_client::PlotName,
 }
 
+/// Page flip submitted directly to the DRM device, bypassing the DRM compositor.
+struct AsyncFlip {
+    fb: GbmFramebuffer,
+    data: (OutputPresentationFeedback, Duration),
+}
+
+/// Color-related connector settings from the output config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ColorSettings {
+    max_bpc: u8,
+    framebuffer_format: FramebufferFormat,
+    broadcast_rgb: BroadcastRgb,
+    colorimetry: Colorimetry,
+    hdr_metadata: Option<niri_config::HdrMetadata>,
+}
+
 pub struct SurfaceDmabufFeedback {
     pub render: DmabufFeedback,
     pub scanout: DmabufFeedback,
@@ -223,6 +286,8 @@ impl Tty {
             UdevBackend::new(session.seat()).context("error creating a udev backend")?;
         let udev_dispatcher = Dispatcher::new(udev_backend, move |event, _, state: &mut State| {
             state.backend.tty().on_udev_event(&mut state.niri, event);
+            // Monitors may have been connected or disconnected.
+            state.refresh_output_profile();
         });
         event_loop
             .register_dispatcher(udev_dispatcher.clone())
@@ -244,6 +309,8 @@ impl Tty {
         event_loop
             .insert_source(notifier, move |event, _, state| {
                 state.backend.tty().on_session_event(&mut state.niri, event);
+                // Monitors may have changed while the session was paused.
+                state.refresh_output_profile();
             })
             .unwrap();
 
@@ -279,6 +346,8 @@ impl Tty {
         }
         info!("using as the render node: {}", node_path);
 
+        let virtual_outputs = VirtualOutputs::new(config.clone());
+
         Ok(Self {
             config,
             session,
@@ -291,6 +360,7 @@ impl Tty {
             dmabuf_global: None,
             update_output_config_on_resume: false,
             debug_tint: false,
+            virtual_outputs,
             ipc_outputs: Arc::new(Mutex::new(HashMap::new())),
         })
     }
@@ -408,13 +478,14 @@ impl Tty {
                     // Also, restore our VRR.
                     let device = self.devices.get_mut(&node).unwrap();
                     for (crtc, surface) in device.surfaces.iter_mut() {
-                        if let Some(ramp) = surface.pending_gamma_change.take() {
-                            let ramp = ramp.as_deref();
-                            let res = if let Some(gamma_props) = &mut surface.gamma_props {
-                                gamma_props.set_gamma(&device.drm, ramp)
-                            } else {
-                                set_gamma_for_crtc(&device.drm, *crtc, ramp)
-                            };
+                        if mem::take(&mut surface.pending_gamma_change) {
+                            let res = set_crtc_gamma(
+                                &device.drm,
+                                *crtc,
+                                surface.gamma_props.as_mut(),
+                                surface.client_gamma.as_deref(),
+                                surface.vcgt.as_ref(),
+                            );
                             if let Err(err) = res {
                                 warn!("error applying pending gamma change: {err:?}");
                             }
@@ -429,7 +500,10 @@ impl Tty {
                             .global_space
                             .outputs()
                             .find(|output| {
-                                let tty_state: &TtyOutputState = output.user_data().get().unwrap();
+                                let Some(tty_state) = output.user_data().get::<TtyOutputState>()
+                                else {
+                                    return false;
+                                };
                                 tty_state.node == node && tty_state.crtc == *crtc
                             })
                             .cloned();
@@ -489,14 +563,26 @@ impl Tty {
         let device_fd = DrmDeviceFd::new(DeviceFd::from(fd));
 
         let (drm, drm_notifier) = DrmDevice::new(device_fd.clone(), true)?;
-        let gbm = GbmDevice::new(device_fd)?;
 
-        let display = unsafe { EGLDisplay::new(gbm.clone())? };
+        // With a broken or missing GPU driver, this fails unless the user forced Mesa's software
+        // rasterizer through its environment variables.
+        let (gbm, display) = create_gbm_egl(device_fd.clone()).context(
+            "error initializing EGL; if the GPU driver is broken, try starting niri with \
+             LIBGL_ALWAYS_SOFTWARE=1 GBM_ALWAYS_SOFTWARE=1",
+        )?;
         let egl_device = EGLDevice::device_for_display(&display)?;
 
-        let render_node = egl_device
-            .try_get_render_node()?
-            .context("no render node")?;
+        let render_node = match egl_device.try_get_render_node()? {
+            Some(render_node) => render_node,
+            None => {
+                // Software rendering devices have no render node. Use the same node that
+                // Tty::new() picked for this device so that the primary GPU checks still work.
+                info!("EGL device has no render node, using software rendering");
+                node.node_with_type(NodeType::Render)
+                    .and_then(Result::ok)
+                    .unwrap_or(node)
+            }
+        };
         self.gpu_manager
             .as_mut()
             .add_node(render_node, gbm.clone())
@@ -537,6 +623,10 @@ impl Tty {
 
             niri.layout.update_shaders();
 
+            // Textures from the old renderer are no longer valid.
+            niri.background_images.clear();
+            niri.update_background_images();
+
             // Create the dmabuf global.
             let primary_formats = renderer.dmabuf_formats();
             let default_feedback =
@@ -569,6 +659,21 @@ impl Tty {
                     }
                 }
             }
+
+            // Create the syncobj global for explicit sync. Syncobjs are imported into the primary
+            // render device. The global isn't removed along with the device, so only create it
+            // once.
+            if render_node == self.primary_render_node && niri.drm_syncobj_state.is_none() {
+                if self.config.borrow().debug.disable_drm_syncobj {
+                    debug!("explicit sync is disabled in the config");
+                } else if supports_syncobj_eventfd(&device_fd) {
+                    let state = DrmSyncobjState::new::<State>(&niri.display_handle, device_fd);
+                    niri.drm_syncobj_state = Some(state);
+                    debug!("enabled explicit sync");
+                } else {
+                    debug!("primary render device doesn't support syncobj eventfd");
+                }
+            }
         }
 
         let token = niri
@@ -663,6 +768,13 @@ impl Tty {
             }
         }
 
+        // With the lid closed, external monitors coming and going turn the laptop panel off and
+        // on.
+        if niri.lid_closed {
+            self.on_output_config_changed(niri);
+            return;
+        }
+
         self.refresh_ipc_outputs(niri);
     }
 
@@ -747,6 +859,8 @@ impl Tty {
         let connector_name = format_connector_name(&connector);
         debug!("connecting connector: {connector_name}");
 
+        let is_clamshell = self.is_clamshell_active(niri);
+
         let device = self.devices.get_mut(&node).context("missing device")?;
 
         let output_name = make_output_name(&device.drm, connector.handle(), connector_name.clone());
@@ -784,14 +898,20 @@ impl Tty {
             return Ok(());
         }
 
+        if is_clamshell && is_laptop_panel(&output_name.connector) {
+            debug!("laptop panel is disabled while the lid is closed");
+            return Ok(());
+        }
+
         for m in connector.modes() {
             trace!("{m:?}");
         }
 
-        let (mode, fallback) =
-            pick_mode(&connector, config.mode).ok_or_else(|| anyhow!("no mode"))?;
+        let target_mode = config.mode.filter(|m| !m.custom).map(|m| m.mode);
+        let (mut mode, fallback) =
+            pick_mode(&connector, target_mode).ok_or_else(|| anyhow!("no mode"))?;
         if fallback {
-            let target = config.mode.unwrap();
+            let target = target_mode.unwrap();
             warn!(
                 "configured mode {}x{}{} could not be found, falling back to preferred",
                 target.width,
@@ -805,11 +925,13 @@ impl Tty {
         }
         debug!("picking mode: {mode:?}");
 
-        // We only use 8888 RGB formats, so set max bpc to 8 to allow more types of links to run.
-        match set_max_bpc(&device.drm, connector.handle(), 8) {
-            Ok(bpc) => debug!("set max bpc to {bpc}"),
-            Err(err) => debug!("error setting max bpc: {err:?}"),
-        }
+        let custom_mode = configured_custom_mode(&config).and_then(|res| {
+            res.map_err(|err| warn!("error computing custom mode: {err:?}"))
+                .ok()
+        });
+
+        let color = ColorSettings::from_config(&config);
+        color.apply(&device.drm, connector.handle());
 
         // Try to enable VRR if requested.
         let mut vrr_enabled = false;
@@ -854,14 +976,16 @@ impl Tty {
             .map_err(|err| debug!("error getting gamma properties: {err:?}"))
             .ok();
 
-        // Reset gamma in case it was set before.
-        let res = if let Some(gamma_props) = &mut gamma_props {
-            gamma_props.set_gamma(&device.drm, None)
-        } else {
-            set_gamma_for_crtc(&device.drm, crtc, None)
-        };
+        // Reset gamma in case it was set before, applying the calibration curves if any.
+        let icc_profile = config.icc_profile.clone();
+        let vcgt = load_vcgt(&config);
+        let res = set_crtc_gamma(&device.drm, crtc, gamma_props.as_mut(), None, vcgt.as_ref());
         if let Err(err) = res {
-            debug!("error resetting gamma: {err:?}");
+            if vcgt.is_some() {
+                warn!("error applying calibration curves: {err:?}");
+            } else {
+                debug!("error resetting gamma: {err:?}");
+            }
         }
 
         let surface = device
@@ -952,7 +1076,7 @@ impl Tty {
             Some(planes),
             allocator.clone(),
             device.gbm.clone(),
-            SUPPORTED_COLOR_FORMATS,
+            color.color_formats(),
             // This is only used to pick a good internal format, so it can use the surface's render
             // formats, even though we only ever render on the primary GPU.
             render_formats.clone(),
@@ -986,7 +1110,7 @@ impl Tty {
                     Some(planes),
                     allocator,
                     device.gbm.clone(),
-                    SUPPORTED_COLOR_FORMATS,
+                    color.color_formats(),
                     render_formats,
                     device.drm.cursor_size(),
                     cursor_plane_gbm,
@@ -1000,6 +1124,25 @@ impl Tty {
         }
         compositor.use_direct_scanout(!config.debug.disable_direct_scanout);
 
+        // The custom mode is not advertised by the connector, so the compositor is created with
+        // an advertised one first. use_mode() test-commits the custom mode, so if the output
+        // rejects it, we stay on the advertised mode.
+        if let Some(custom_mode) = custom_mode {
+            match compositor.use_mode(custom_mode) {
+                Ok(()) => {
+                    debug!("using custom mode: {custom_mode:?}");
+                    mode = custom_mode;
+
+                    let wl_mode = Mode::from(mode);
+                    output.change_current_state(Some(wl_mode), None, None, None);
+                    output.set_preferred(wl_mode);
+                }
+                Err(err) => {
+                    warn!("error setting custom mode, falling back to {mode:?}: {err:?}");
+                }
+            }
+        }
+
         let mut dmabuf_feedback = None;
         if let Ok(primary_renderer) = self.gpu_manager.single_renderer(&self.primary_render_node) {
             let primary_formats = primary_renderer.dmabuf_formats();
@@ -1021,7 +1164,7 @@ impl Tty {
 
         // Some buggy monitors replug upon powering off, so powering on here would prevent such
         // monitors from powering off. Therefore, we avoid unconditionally powering on.
-        if !niri.monitors_active {
+        if !niri.monitors_active || niri.powered_off_outputs.contains(&output_name) {
             if let Err(err) = compositor.clear() {
                 warn!("error clearing drm surface: {err:?}");
             }
@@ -1045,7 +1188,14 @@ impl Tty {
             dmabuf_feedback,
             gamma_props,
             vrr_enabled,
-            pending_gamma_change: None,
+            tearing: false,
+            pending_async_flip: None,
+            async_flip_fb: None,
+            color,
+            client_gamma: None,
+            icc_profile,
+            vcgt,
+            pending_gamma_change: false,
             vblank_frame: None,
             vblank_frame_name,
             time_since_presentation_plot_name,
@@ -1107,7 +1257,9 @@ impl Tty {
             .global_space
             .outputs()
             .find(|output| {
-                let tty_state: &TtyOutputState = output.user_data().get().unwrap();
+                let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+                    return false;
+                };
                 tty_state.node == node && tty_state.crtc == crtc
             })
             .cloned();
@@ -1187,7 +1339,9 @@ impl Tty {
             .global_space
             .outputs()
             .find(|output| {
-                let tty_state: &TtyOutputState = output.user_data().get().unwrap();
+                let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+                    return false;
+                };
                 tty_state.node == node && tty_state.crtc == crtc
             })
             .cloned()
@@ -1206,7 +1360,8 @@ impl Tty {
             state @ (RedrawState::Idle
             | RedrawState::Queued
             | RedrawState::WaitingForEstimatedVBlank(_)
-            | RedrawState::WaitingForEstimatedVBlankAndQueued(_)) => {
+            | RedrawState::WaitingForEstimatedVBlankAndQueued(_)
+            | RedrawState::WaitingForRenderStart(_)) => {
                 // This is an error!() because it shouldn't happen, but on some systems it somehow
                 // does. Kernel sending rogue vblank events?
                 //
@@ -1216,12 +1371,30 @@ impl Tty {
                     "unexpected redraw state for output {name} (should be WaitingForVBlank); \
                      can happen when resuming from sleep or powering on monitors: {state:?}"
                 );
+
+                // The redraw that the timer would start is replaced by the one below.
+                if let RedrawState::WaitingForRenderStart(token) = state {
+                    niri.event_loop.remove(token);
+                }
+
                 true
             }
         };
 
         // Mark the last frame as submitted.
-        match surface.compositor.frame_submitted() {
+        let is_async_flip = surface.pending_async_flip.is_some();
+        let submitted = if let Some(flip) = surface.pending_async_flip.take() {
+            surface.async_flip_fb = Some(flip.fb);
+            Ok(Some(flip.data))
+        } else {
+            let submitted = surface.compositor.frame_submitted();
+            if matches!(submitted, Ok(Some(_))) {
+                // The DRM compositor's frame replaced the async flip framebuffer on screen.
+                surface.async_flip_fb = None;
+            }
+            submitted
+        };
+        match submitted {
             Ok(Some((mut feedback, target_presentation_time))) => {
                 let refresh = output_state
                     .frame_clock
@@ -1229,8 +1402,10 @@ impl Tty {
                     .unwrap_or(Duration::ZERO);
                 // FIXME: ideally should be monotonically increasing for a surface.
                 let seq = meta.sequence as u64;
-                let mut flags = wp_presentation_feedback::Kind::Vsync
-                    | wp_presentation_feedback::Kind::HwCompletion;
+                let mut flags = wp_presentation_feedback::Kind::HwCompletion;
+                if !is_async_flip {
+                    flags.insert(wp_presentation_feedback::Kind::Vsync);
+                }
 
                 let time = if presentation_time.is_zero() {
                     now
@@ -1241,6 +1416,10 @@ impl Tty {
 
                 feedback.presented::<_, smithay::utils::Monotonic>(time, refresh, seq, flags);
 
+                output_state
+                    .frame_stats
+                    .frame_presented(time, target_presentation_time, refresh);
+
                 if !presentation_time.is_zero() {
                     let misprediction_s =
                         presentation_time.as_secs_f64() - target_presentation_time.as_secs_f64();
@@ -1296,6 +1475,7 @@ impl Tty {
             RedrawState::Idle => unreachable!(),
             RedrawState::Queued => unreachable!(),
             RedrawState::WaitingForVBlank { .. } => unreachable!(),
+            RedrawState::WaitingForRenderStart(_) => unreachable!(),
             RedrawState::WaitingForEstimatedVBlank(_) => (),
             // The timer fired just in front of a redraw.
             RedrawState::WaitingForEstimatedVBlankAndQueued(_) => {
@@ -1334,6 +1514,25 @@ impl Tty {
     ) -> RenderResult {
         let span = tracy_client::span!("Tty::render");
 
+        if self.virtual_outputs.contains(output) {
+            span.emit_text(&output.name());
+
+            let mut renderer = match self.gpu_manager.single_renderer(&self.primary_render_node) {
+                Ok(renderer) => renderer,
+                Err(err) => {
+                    warn!("error creating renderer for primary GPU: {err:?}");
+                    return RenderResult::Skipped;
+                }
+            };
+
+            return self.virtual_outputs.render(
+                renderer.as_gles_renderer(),
+                niri,
+                output,
+                target_presentation_time,
+            );
+        }
+
         let mut rv = RenderResult::Skipped;
 
         let tty_state: &TtyOutputState = output.user_data().get().unwrap();
@@ -1376,10 +1575,37 @@ impl Tty {
             draw_damage(&mut output_state.debug_damage_tracker, &mut elements);
         }
 
+        let tearing_surface = niri.tearing_surface(output);
+
         // Hand them over to the DRM.
         let drm_compositor = &mut surface.compositor;
         match drm_compositor.render_frame::<_, _>(&mut renderer, &elements, [0.; 4]) {
             Ok(res) => {
+                // Tear only when the window is directly scanned out, otherwise we'd also tear
+                // everything composited together with it.
+                let tearing = tearing_surface.as_ref().map_or(false, |wl_surface| {
+                    let id = Id::from_wayland_resource(wl_surface);
+                    matches!(
+                        &res.primary_element,
+                        PrimaryPlaneElement::Element(element) if element.id() == &id
+                    )
+                });
+                let direct_scanout = matches!(res.primary_element, PrimaryPlaneElement::Element(_));
+                let damage = res.damage.as_ref().map(|damage| damage.to_vec());
+
+                if surface.tearing != tearing {
+                    surface.tearing = tearing;
+                    debug!(
+                        "{}: {}",
+                        surface.name.connector,
+                        if tearing {
+                            "tearing allowed for the scanned out window"
+                        } else {
+                            "tearing not allowed, using vsync"
+                        }
+                    );
+                }
+
                 let needs_sync = res.needs_sync()
                     || self
                         .config
@@ -1405,7 +1631,38 @@ impl Tty {
                         niri.take_presentation_feedbacks(output, &res.states);
                     let data = (presentation_feedbacks, target_presentation_time);
 
-                    match drm_compositor.queue_frame(data) {
+                    let queued = if let Some(wl_surface) = tearing_surface.filter(|_| tearing) {
+                        match queue_async_flip(
+                            &device.drm,
+                            &device.gbm,
+                            tty_state.crtc,
+                            &wl_surface,
+                        ) {
+                            Ok(fb) => {
+                                trace!("{}: queued async page flip", surface.name.connector);
+                                surface.pending_async_flip = Some(AsyncFlip { fb, data });
+
+                                // The DRM compositor didn't commit this frame, so make it commit
+                                // all planes when it takes over again.
+                                if let Err(err) = drm_compositor.reset_state() {
+                                    warn!("error resetting DRM compositor state: {err:?}");
+                                }
+
+                                Ok(())
+                            }
+                            Err(err) => {
+                                debug!(
+                                    "{}: async page flip failed, using vsync: {err:?}",
+                                    surface.name.connector
+                                );
+                                drm_compositor.queue_frame(data)
+                            }
+                        }
+                    } else {
+                        drm_compositor.queue_frame(data)
+                    };
+
+                    match queued {
                         Ok(()) => {
                             let output_state = niri.output_state.get_mut(output).unwrap();
                             let new_state = RedrawState::WaitingForVBlank {
@@ -1415,6 +1672,7 @@ impl Tty {
                                 RedrawState::Idle => unreachable!(),
                                 RedrawState::Queued => (),
                                 RedrawState::WaitingForVBlank { .. } => unreachable!(),
+                                RedrawState::WaitingForRenderStart(_) => unreachable!(),
                                 RedrawState::WaitingForEstimatedVBlank(_) => unreachable!(),
                                 RedrawState::WaitingForEstimatedVBlankAndQueued(token) => {
                                     niri.event_loop.remove(token);
@@ -1427,6 +1685,13 @@ impl Tty {
                             output_state.frame_callback_sequence =
                                 output_state.frame_callback_sequence.wrapping_add(1);
 
+                            let output_size = output.current_mode().unwrap().size;
+                            output_state.frame_stats.frame_queued(
+                                direct_scanout,
+                                output_size,
+                                damage.as_deref(),
+                            );
+
                             return RenderResult::Submitted;
                         }
                         Err(err) => {
@@ -1511,7 +1776,10 @@ impl Tty {
     }
 
     pub fn get_gamma_size(&self, output: &Output) -> anyhow::Result<u32> {
-        let tty_state = output.user_data().get::<TtyOutputState>().unwrap();
+        let tty_state = output
+            .user_data()
+            .get::<TtyOutputState>()
+            .context("virtual outputs don't support gamma")?;
         let crtc = tty_state.crtc;
 
         let device = self
@@ -1532,7 +1800,10 @@ impl Tty {
     }
 
     pub fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> anyhow::Result<()> {
-        let tty_state = output.user_data().get::<TtyOutputState>().unwrap();
+        let tty_state = output
+            .user_data()
+            .get::<TtyOutputState>()
+            .context("virtual outputs don't support gamma")?;
         let crtc = tty_state.crtc;
 
         let device = self
@@ -1540,19 +1811,21 @@ impl Tty {
             .get_mut(&tty_state.node)
             .context("missing device")?;
         let surface = device.surfaces.get_mut(&crtc).context("missing surface")?;
+        surface.client_gamma = ramp;
 
         // Cannot change properties while the device is inactive.
         if !self.session.is_active() {
-            surface.pending_gamma_change = Some(ramp);
+            surface.pending_gamma_change = true;
             return Ok(());
         }
 
-        let ramp = ramp.as_deref();
-        if let Some(gamma_props) = &mut surface.gamma_props {
-            gamma_props.set_gamma(&device.drm, ramp)
-        } else {
-            set_gamma_for_crtc(&device.drm, crtc, ramp)
-        }
+        set_crtc_gamma(
+            &device.drm,
+            crtc,
+            surface.gamma_props.as_mut(),
+            surface.client_gamma.as_deref(),
+            surface.vcgt.as_ref(),
+        )
     }
 
     fn refresh_ipc_outputs(&self, niri: &mut Niri) {
@@ -1571,7 +1844,7 @@ impl Tty {
                 let current_crtc_mode = surface.map(|surface| surface.compositor.pending_mode());
                 let mut current_mode = None;
 
-                let modes = connector
+                let mut modes: Vec<_> = connector
                     .modes()
                     .iter()
                     .filter(|m| !m.flags().contains(ModeFlags::INTERLACE))
@@ -1592,7 +1865,17 @@ impl Tty {
 
                 if let Some(crtc_mode) = current_crtc_mode {
                     if current_mode.is_none() {
-                        if crtc_mode.flags().contains(ModeFlags::INTERLACE) {
+                        if crtc_mode.mode_type().contains(ModeTypeFlags::USERDEF) {
+                            // Custom modes are not in the connector mode list, so add them at the
+                            // end.
+                            current_mode = Some(modes.len());
+                            modes.push(niri_ipc::Mode {
+                                width: crtc_mode.size().0,
+                                height: crtc_mode.size().1,
+                                refresh_rate: Mode::from(crtc_mode).refresh as u32,
+                                is_preferred: false,
+                            });
+                        } else if crtc_mode.flags().contains(ModeFlags::INTERLACE) {
                             warn!("connector mode list missing current mode (interlaced)");
                         } else {
                             error!("connector mode list missing current mode");
@@ -1603,15 +1886,56 @@ impl Tty {
                 let vrr_supported = is_vrr_capable(&device.drm, connector.handle()) == Some(true);
                 let vrr_enabled = surface.map_or(false, |surface| surface.vrr_enabled);
 
+                let color = surface.map(|surface| {
+                    let framebuffer_format = match surface.compositor.format() {
+                        Fourcc::Argb2101010
+                        | Fourcc::Abgr2101010
+                        | Fourcc::Xrgb2101010
+                        | Fourcc::Xbgr2101010 => FramebufferFormat::Xrgb2101010,
+                        _ => FramebufferFormat::Xrgb8888,
+                    };
+                    let broadcast_rgb =
+                        get_drm_enum_property(&device.drm, connector.handle(), "Broadcast RGB")
+                            .and_then(|name| from_drm_name(BROADCAST_RGB_NAMES, &name));
+                    let colorimetry =
+                        get_drm_enum_property(&device.drm, connector.handle(), "Colorspace")
+                            .and_then(|name| from_drm_name(COLORIMETRY_NAMES, &name));
+                    let hdr_supported =
+                        find_drm_property(&device.drm, connector.handle(), "HDR_OUTPUT_METADATA")
+                            .is_some();
+
+                    niri_ipc::OutputColor {
+                        max_bpc: get_max_bpc(&device.drm, connector.handle()),
+                        framebuffer_format,
+                        broadcast_rgb,
+                        colorimetry,
+                        hdr_supported,
+                        hdr_metadata: get_hdr_metadata(&device.drm, connector.handle()),
+                    }
+                });
+
                 let logical = niri
                     .global_space
                     .outputs()
                     .find(|output| {
-                        let tty_state: &TtyOutputState = output.user_data().get().unwrap();
+                        let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+                            return false;
+                        };
                         tty_state.node == *node && tty_state.crtc == crtc
                     })
                     .map(logical_output);
 
+                let powered = niri
+                    .output_state
+                    .keys()
+                    .find(|output| {
+                        let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+                            return false;
+                        };
+                        tty_state.node == *node && tty_state.crtc == crtc
+                    })
+                    .is_some_and(|output| niri.is_output_powered(output));
+
                 let ipc_output = niri_ipc::Output {
                     name: connector_name,
                     make: output_name.make.unwrap_or_else(|| "Unknown".into()),
@@ -1622,6 +1946,8 @@ impl Tty {
                     current_mode,
                     vrr_supported,
                     vrr_enabled,
+                    powered,
+                    color,
                     logical,
                 };
 
@@ -1633,6 +1959,8 @@ impl Tty {
             }
         }
 
+        ipc_outputs.extend(self.virtual_outputs.ipc_outputs(niri));
+
         let mut guard = self.ipc_outputs.lock().unwrap();
         *guard = ipc_outputs;
         niri.ipc_outputs_changed = true;
@@ -1642,6 +1970,20 @@ impl Tty {
         self.ipc_outputs.clone()
     }
 
+    pub fn profile_output_names(&self) -> Vec<OutputName> {
+        self.ipc_outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| !self.virtual_outputs.is_created_at_runtime(**id))
+            .map(|(_, output)| OutputName::from_ipc_output(output))
+            .collect()
+    }
+
+    pub fn primary_render_node(&self) -> DrmNode {
+        self.primary_render_node
+    }
+
     #[cfg(feature = "xdp-gnome-screencast")]
     pub fn primary_gbm_device(&self) -> Option<GbmDevice<DrmDeviceFd>> {
         // Try to find a device corresponding to the primary render node.
@@ -1674,6 +2016,58 @@ impl Tty {
         }
     }
 
+    /// Returns whether a monitor other than a laptop panel is connected and not disabled in the
+    /// config.
+    pub fn has_external_monitor(&self) -> bool {
+        let config = self.config.borrow();
+        self.devices.values().any(|device| {
+            device.drm_scanner.crtcs().any(|(connector, crtc)| {
+                let connector_name = format_connector_name(connector);
+                if connector.state() != connector::State::Connected
+                    || is_laptop_panel(&connector_name)
+                    || device
+                        .non_desktop_connectors
+                        .contains(&(connector.handle(), crtc))
+                {
+                    return false;
+                }
+
+                let output_name = make_output_name(&device.drm, connector.handle(), connector_name);
+                !config
+                    .outputs
+                    .find(&output_name)
+                    .is_some_and(|config| config.off)
+            })
+        })
+    }
+
+    /// Returns whether laptop panels should be off because the lid is closed with an external
+    /// monitor connected.
+    fn is_clamshell_active(&self, niri: &Niri) -> bool {
+        niri.lid_closed && self.config.borrow().clamshell.on && self.has_external_monitor()
+    }
+
+    pub fn set_output_powered(&mut self, output: &Output, powered: bool) {
+        // Same as in set_monitors_active(), the CRTC is enabled again on the next frame.
+        if powered {
+            return;
+        }
+
+        // Virtual outputs have nothing to power off.
+        let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+            return;
+        };
+        let Some(device) = self.devices.get_mut(&tty_state.node) else {
+            return;
+        };
+        let Some(surface) = device.surfaces.get_mut(&tty_state.crtc) else {
+            return;
+        };
+        if let Err(err) = surface.compositor.clear() {
+            warn!("error clearing drm surface: {err:?}");
+        }
+    }
+
     pub fn set_output_on_demand_vrr(&mut self, niri: &mut Niri, output: &Output, enable_vrr: bool) {
         let _span = tracy_client::span!("Tty::set_output_on_demand_vrr");
 
@@ -1682,9 +2076,12 @@ impl Tty {
         if output_state.frame_clock.vrr() == enable_vrr {
             return;
         }
+        // Virtual outputs don't support VRR.
+        let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+            return;
+        };
         for (&node, device) in self.devices.iter_mut() {
             for (&crtc, surface) in device.surfaces.iter_mut() {
-                let tty_state: &TtyOutputState = output.user_data().get().unwrap();
                 if tty_state.node == node && tty_state.crtc == crtc {
                     try_to_change_vrr(
                         &device.drm,
@@ -1714,6 +2111,8 @@ impl Tty {
         let mut to_disconnect = vec![];
         let mut to_connect = vec![];
 
+        let is_clamshell = self.is_clamshell_active(niri);
+
         for (&node, device) in &mut self.devices {
             for (&crtc, surface) in device.surfaces.iter_mut() {
                 let config = self
@@ -1735,11 +2134,59 @@ impl Tty {
                     continue;
                 };
 
-                let Some((mode, fallback)) = pick_mode(connector, config.mode) else {
+                if is_clamshell && is_laptop_panel(&surface.name.connector) {
+                    to_disconnect.push((node, crtc));
+                    continue;
+                }
+
+                // Color settings need a modeset with the new connector properties, and possibly a
+                // swapchain with a different format, so recreate the output to apply them.
+                if surface.color != ColorSettings::from_config(&config) {
+                    debug!(
+                        "output {:?}: color settings changed, reconnecting",
+                        surface.name.connector
+                    );
+                    to_disconnect.push((node, crtc));
+                    to_connect.push((node, connector.clone(), crtc));
+                    continue;
+                }
+
+                if surface.icc_profile != config.icc_profile {
+                    surface.icc_profile = config.icc_profile.clone();
+                    surface.vcgt = load_vcgt(&config);
+
+                    let res = set_crtc_gamma(
+                        &device.drm,
+                        crtc,
+                        surface.gamma_props.as_mut(),
+                        surface.client_gamma.as_deref(),
+                        surface.vcgt.as_ref(),
+                    );
+                    if let Err(err) = res {
+                        warn!(
+                            "output {:?}: error applying calibration curves: {err:?}",
+                            surface.name.connector
+                        );
+                    }
+                }
+
+                let target_mode = config.mode.filter(|m| !m.custom).map(|m| m.mode);
+                let Some((picked_mode, fallback)) = pick_mode(connector, target_mode) else {
                     warn!("couldn't pick mode for enabled connector");
                     continue;
                 };
 
+                let custom_mode = configured_custom_mode(&config).and_then(|res| {
+                    res.map_err(|err| {
+                        warn!(
+                            "output {:?}: error computing custom mode: {err:?}",
+                            surface.name.connector
+                        )
+                    })
+                    .ok()
+                });
+                let mut mode = custom_mode.unwrap_or(picked_mode);
+
                 let change_mode = surface.compositor.pending_mode() != mode;
                 let change_always_vrr = surface.vrr_enabled != config.is_vrr_always_on();
                 let is_on_demand_vrr = config.is_vrr_on_demand();
@@ -1751,7 +2198,9 @@ impl Tty {
                     .global_space
                     .outputs()
                     .find(|output| {
-                        let tty_state: &TtyOutputState = output.user_data().get().unwrap();
+                        let Some(tty_state) = output.user_data().get::<TtyOutputState>() else {
+                            return false;
+                        };
                         tty_state.node == node && tty_state.crtc == crtc
                     })
                     .cloned();
@@ -1778,8 +2227,8 @@ impl Tty {
                 }
 
                 if change_mode {
-                    if fallback {
-                        let target = config.mode.unwrap();
+                    if fallback && custom_mode.is_none() {
+                        let target = target_mode.unwrap();
                         warn!(
                             "output {:?}: configured mode {}x{}{} could not be found, \
                              falling back to preferred",
@@ -1799,8 +2248,25 @@ impl Tty {
                         surface.name.connector
                     );
                     if let Err(err) = surface.compositor.use_mode(mode) {
-                        warn!("error changing mode: {err:?}");
-                        continue;
+                        if custom_mode.is_none() {
+                            warn!("error changing mode: {err:?}");
+                            continue;
+                        }
+
+                        // The output rejected the custom mode, go back to an advertised one.
+                        warn!(
+                            "output {:?}: error setting custom mode, \
+                             falling back to {picked_mode:?}: {err:?}",
+                            surface.name.connector
+                        );
+                        mode = picked_mode;
+                        if surface.compositor.pending_mode() == mode {
+                            continue;
+                        }
+                        if let Err(err) = surface.compositor.use_mode(mode) {
+                            warn!("error changing mode: {err:?}");
+                            continue;
+                        }
                     }
 
                     let wl_mode = Mode::from(mode);
@@ -1833,7 +2299,7 @@ impl Tty {
                     .cloned()
                     .unwrap_or_default();
 
-                if !config.off {
+                if !config.off && !(is_clamshell && is_laptop_panel(&output_name.connector)) {
                     to_connect.push((node, connector.clone(), crtc));
                 }
             }
@@ -1849,9 +2315,34 @@ impl Tty {
             }
         }
 
+        self.virtual_outputs.on_output_config_changed(niri);
+
         self.refresh_ipc_outputs(niri);
     }
 
+    pub fn create_virtual_output(&mut self, niri: &mut Niri, mode: ConfiguredMode) -> String {
+        let name = self.virtual_outputs.create(niri, mode);
+        self.refresh_ipc_outputs(niri);
+        name
+    }
+
+    pub fn destroy_virtual_output(&mut self, niri: &mut Niri, name: &str) -> bool {
+        let destroyed = self.virtual_outputs.destroy(niri, name);
+        self.refresh_ipc_outputs(niri);
+        destroyed
+    }
+
+    pub fn set_virtual_output_mode(
+        &mut self,
+        niri: &mut Niri,
+        name: &str,
+        mode: ConfiguredMode,
+    ) -> bool {
+        let changed = self.virtual_outputs.set_mode(niri, name, mode);
+        self.refresh_ipc_outputs(niri);
+        changed
+    }
+
     pub fn on_debug_config_changed(&mut self) {
         let config = self.config.borrow();
         let debug = &config.debug;
@@ -2210,6 +2701,7 @@ fn queue_estimated_vblank_timer(
         RedrawState::Idle => unreachable!(),
         RedrawState::Queued => (),
         RedrawState::WaitingForVBlank { .. } => unreachable!(),
+        RedrawState::WaitingForRenderStart(_) => unreachable!(),
         RedrawState::WaitingForEstimatedVBlank(token)
         | RedrawState::WaitingForEstimatedVBlankAndQueued(token) => {
             output_state.redraw_state = RedrawState::WaitingForEstimatedVBlank(token);
@@ -2246,6 +2738,40 @@ fn queue_estimated_vblank_timer(
     output_state.redraw_state = RedrawState::WaitingForEstimatedVBlank(token);
 }
 
+/// Flips the primary plane to the buffer of `wl_surface` without waiting for the vblank.
+///
+/// Fails if the driver doesn't support async page flips or if the flip would change more than the
+/// framebuffer address, for example the format or the modifier.
+fn queue_async_flip(
+    drm: &DrmDevice,
+    gbm: &GbmDevice<DrmDeviceFd>,
+    crtc: crtc::Handle,
+    wl_surface: &WlSurface,
+) -> anyhow::Result<GbmFramebuffer> {
+    let buffer = with_renderer_surface_state(wl_surface, |state| state.buffer().cloned())
+        .flatten()
+        .context("surface has no buffer")?;
+    let fb = framebuffer_from_wayland_buffer(drm.device_fd(), gbm, &buffer, true)
+        .context("error creating framebuffer")?
+        .context("buffer can't be scanned out")?;
+
+    drm.page_flip(
+        crtc,
+        *fb.as_ref(),
+        PageFlipFlags::EVENT | PageFlipFlags::ASYNC,
+        None,
+    )
+    .context("error queueing async page flip")?;
+
+    Ok(fb)
+}
+
+fn create_gbm_egl(device_fd: DrmDeviceFd) -> anyhow::Result<(GbmDevice<DrmDeviceFd>, EGLDisplay)> {
+    let gbm = GbmDevice::new(device_fd).context("error creating GBM device")?;
+    let display = unsafe { EGLDisplay::new(gbm.clone()) }.context("error creating EGL display")?;
+    Ok((gbm, display))
+}
+
 fn pick_mode(
     connector: &connector::Info,
     target: Option<niri_ipc::ConfiguredMode>,
@@ -2312,6 +2838,179 @@ fn pick_mode(
     mode.map(|m| (*m, fallback))
 }
 
+/// Returns the custom mode set in the output config, if any.
+///
+/// A modeline takes priority over a custom mode.
+fn configured_custom_mode(config: &niri_config::Output) -> Option<anyhow::Result<control::Mode>> {
+    if let Some(modeline) = &config.modeline {
+        return Some(calculate_drm_mode_from_modeline(modeline));
+    }
+
+    let mode = config.mode.filter(|m| m.custom)?;
+    Some(calculate_mode_cvt(mode.mode))
+}
+
+fn calculate_drm_mode_from_modeline(
+    modeline: &niri_config::Modeline,
+) -> anyhow::Result<control::Mode> {
+    let niri_config::Modeline {
+        clock,
+        hdisplay,
+        hsync_start,
+        hsync_end,
+        htotal,
+        vdisplay,
+        vsync_start,
+        vsync_end,
+        vtotal,
+        hsync_polarity,
+        vsync_polarity,
+    } = *modeline;
+
+    ensure!(clock.0 > 0., "pixel clock must be positive");
+    ensure!(
+        0 < hdisplay && hdisplay <= hsync_start && hsync_start < hsync_end && hsync_end <= htotal,
+        "horizontal timings must satisfy \
+         0 < hdisplay <= hsync_start < hsync_end <= htotal"
+    );
+    ensure!(
+        0 < vdisplay && vdisplay <= vsync_start && vsync_start < vsync_end && vsync_end <= vtotal,
+        "vertical timings must satisfy \
+         0 < vdisplay <= vsync_start < vsync_end <= vtotal"
+    );
+
+    let clock = (clock.0 * 1000.).round() as u32;
+    let vrefresh = (f64::from(clock) * 1000. / (f64::from(htotal) * f64::from(vtotal))).round();
+
+    let mut flags = ModeFlags::empty();
+    flags |= match hsync_polarity {
+        HSyncPolarity::PHSync => ModeFlags::PHSYNC,
+        HSyncPolarity::NHSync => ModeFlags::NHSYNC,
+    };
+    flags |= match vsync_polarity {
+        VSyncPolarity::PVSync => ModeFlags::PVSYNC,
+        VSyncPolarity::NVSync => ModeFlags::NVSYNC,
+    };
+
+    Ok(custom_drm_mode(
+        clock,
+        (hdisplay, hsync_start, hsync_end, htotal),
+        (vdisplay, vsync_start, vsync_end, vtotal),
+        vrefresh as u32,
+        flags,
+    ))
+}
+
+/// Computes a mode with the VESA CVT reduced blanking (v1) timings.
+///
+/// Matches the output of `cvt --reduced`, but also allows refresh rates other than multiples of
+/// 60 Hz.
+fn calculate_mode_cvt(mode: ConfiguredMode) -> anyhow::Result<control::Mode> {
+    // Character cell horizontal granularity in pixels.
+    const CELL_GRAN: u16 = 8;
+    // Minimum vertical blanking interval time in µs.
+    const RB_MIN_V_BLANK: f64 = 460.;
+    // Fixed vertical front porch in lines.
+    const RB_V_FPORCH: u16 = 3;
+    // Minimum vertical back porch in lines.
+    const MIN_V_BPORCH: u16 = 6;
+    // Fixed horizontal sync width and blanking in pixels.
+    const RB_H_SYNC: u16 = 32;
+    const RB_H_BLANK: u16 = 160;
+    // Pixel clock step in MHz.
+    const CLOCK_STEP: f64 = 0.25;
+
+    let ConfiguredMode {
+        width,
+        height,
+        refresh,
+    } = mode;
+    let refresh = refresh.unwrap_or(60.);
+
+    ensure!(width > 0 && height > 0, "mode size must be positive");
+    ensure!(refresh > 0., "refresh rate must be positive");
+
+    let h_pixels = width / CELL_GRAN * CELL_GRAN;
+    let v_lines = height;
+
+    // The vsync width encodes the aspect ratio.
+    let v_sync = if v_lines % 3 == 0 && u32::from(v_lines) * 4 / 3 == u32::from(h_pixels) {
+        4
+    } else if v_lines % 9 == 0 && u32::from(v_lines) * 16 / 9 == u32::from(h_pixels) {
+        5
+    } else if v_lines % 10 == 0 && u32::from(v_lines) * 16 / 10 == u32::from(h_pixels) {
+        6
+    } else if (v_lines % 4 == 0 && u32::from(v_lines) * 5 / 4 == u32::from(h_pixels))
+        || (v_lines % 9 == 0 && u32::from(v_lines) * 15 / 9 == u32::from(h_pixels))
+    {
+        7
+    } else {
+        10
+    };
+
+    let h_period_est = (1_000_000. / refresh - RB_MIN_V_BLANK) / f64::from(v_lines);
+    ensure!(h_period_est > 0., "refresh rate is too high");
+
+    let vbi_lines = (RB_MIN_V_BLANK / h_period_est).floor() as u32 + 1;
+    let min_vbi_lines = u32::from(RB_V_FPORCH + v_sync + MIN_V_BPORCH);
+    let total_v_lines = u32::from(v_lines) + vbi_lines.max(min_vbi_lines);
+    let total_pixels = u32::from(h_pixels) + u32::from(RB_H_BLANK);
+
+    let vtotal = u16::try_from(total_v_lines).context("mode is too tall")?;
+    let htotal = u16::try_from(total_pixels).context("mode is too wide")?;
+
+    let clock = f64::from(total_v_lines) * f64::from(total_pixels) * refresh / 1_000_000.;
+    let clock = (clock / CLOCK_STEP).floor() * CLOCK_STEP;
+    let clock = (clock * 1000.).round() as u32;
+
+    let hsync_end = h_pixels + RB_H_BLANK / 2;
+    let hsync_start = hsync_end - RB_H_SYNC;
+    let vsync_start = v_lines + RB_V_FPORCH;
+    let vsync_end = vsync_start + v_sync;
+
+    let vrefresh = (f64::from(clock) * 1000. / f64::from(total_v_lines * total_pixels)).round();
+
+    Ok(custom_drm_mode(
+        clock,
+        (h_pixels, hsync_start, hsync_end, htotal),
+        (v_lines, vsync_start, vsync_end, vtotal),
+        vrefresh as u32,
+        ModeFlags::PHSYNC | ModeFlags::NVSYNC,
+    ))
+}
+
+fn custom_drm_mode(
+    clock: u32,
+    (hdisplay, hsync_start, hsync_end, htotal): (u16, u16, u16, u16),
+    (vdisplay, vsync_start, vsync_end, vtotal): (u16, u16, u16, u16),
+    vrefresh: u32,
+    flags: ModeFlags,
+) -> control::Mode {
+    let mut name = [0; 32];
+    let name_str = format!("{hdisplay}x{vdisplay}");
+    for (dst, src) in zip(&mut name[..31], name_str.bytes()) {
+        *dst = src as _;
+    }
+
+    control::Mode::from(drm_ffi::drm_mode_modeinfo {
+        clock,
+        hdisplay,
+        hsync_start,
+        hsync_end,
+        htotal,
+        hskew: 0,
+        vdisplay,
+        vsync_start,
+        vsync_end,
+        vtotal,
+        vscan: 0,
+        vrefresh,
+        flags: flags.bits(),
+        type_: ModeTypeFlags::USERDEF.bits(),
+        name,
+    })
+}
+
 fn get_edid_info(
     device: &DrmDevice,
     connector: connector::Handle,
@@ -2364,6 +3063,242 @@ fn set_max_bpc(device: &DrmDevice, connector: connector::Handle, bpc: u64) -> an
     Err(anyhow!("couldn't find max bpc property"))
 }
 
+fn get_max_bpc(device: &DrmDevice, connector: connector::Handle) -> Option<u8> {
+    let (_, info, value) = find_drm_property(device, connector, "max bpc")?;
+    let property::Value::UnsignedRange(value) = info.value_type().convert_value(value) else {
+        return None;
+    };
+    u8::try_from(value).ok()
+}
+
+fn get_drm_enum_property(
+    device: &DrmDevice,
+    connector: connector::Handle,
+    name: &str,
+) -> Option<String> {
+    let (_, info, value) = find_drm_property(device, connector, name)?;
+    let value_type = info.value_type();
+    let property::Value::Enum(Some(value)) = value_type.convert_value(value) else {
+        return None;
+    };
+    value.name().to_str().ok().map(str::to_owned)
+}
+
+fn set_drm_enum_property(
+    device: &DrmDevice,
+    connector: connector::Handle,
+    name: &str,
+    value: &str,
+) -> anyhow::Result<()> {
+    let (prop, info, current) =
+        find_drm_property(device, connector, name).context("property missing")?;
+    let property::ValueType::Enum(values) = info.value_type() else {
+        bail!("wrong property type")
+    };
+
+    let (_, values) = values.values();
+    let value = values
+        .iter()
+        .find(|v| v.name().to_str() == Ok(value))
+        .with_context(|| format!("value {value:?} is not supported"))?;
+    if value.value() == current {
+        return Ok(());
+    }
+
+    device
+        .set_property(connector, prop, value.value())
+        .context("error setting property")?;
+
+    Ok(())
+}
+
+fn drm_name<T: PartialEq + Copy>(names: &[(T, &'static str)], value: T) -> &'static str {
+    names.iter().find(|(v, _)| *v == value).unwrap().1
+}
+
+fn from_drm_name<T: Copy>(names: &[(T, &'static str)], name: &str) -> Option<T> {
+    names.iter().find(|(_, n)| *n == name).map(|(v, _)| *v)
+}
+
+// Matches struct hdr_output_metadata from the kernel UAPI.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct hdr_output_metadata {
+    metadata_type: u32,
+    hdmi_metadata_type1: hdr_metadata_infoframe,
+    _padding: [u8; 2],
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct hdr_metadata_infoframe {
+    eotf: u8,
+    metadata_type: u8,
+    display_primaries: [[u16; 2]; 3],
+    white_point: [u16; 2],
+    max_display_mastering_luminance: u16,
+    min_display_mastering_luminance: u16,
+    max_cll: u16,
+    max_fall: u16,
+}
+
+fn set_hdr_metadata(
+    device: &DrmDevice,
+    connector: connector::Handle,
+    metadata: Option<niri_config::HdrMetadata>,
+) -> anyhow::Result<()> {
+    let (prop, _, current) = find_drm_property(device, connector, "HDR_OUTPUT_METADATA")
+        .context("HDR_OUTPUT_METADATA property missing")?;
+
+    let Some(metadata) = metadata else {
+        if current != 0 {
+            device
+                .set_property(connector, prop, property::Value::Blob(0).into())
+                .context("error clearing HDR_OUTPUT_METADATA")?;
+        }
+        return Ok(());
+    };
+
+    let eotf = match metadata.eotf {
+        Eotf::TraditionalSdr => 0,
+        Eotf::TraditionalHdr => 1,
+        Eotf::Pq => 2,
+        Eotf::Hlg => 3,
+    };
+
+    // Static metadata type 1 with the BT.2020 primaries in 0.00002 units, in the green, blue, red
+    // order from SMPTE ST 2086.
+    let mut data = hdr_output_metadata {
+        metadata_type: 0,
+        hdmi_metadata_type1: hdr_metadata_infoframe {
+            eotf,
+            metadata_type: 0,
+            display_primaries: [[8500, 39850], [6550, 2300], [35400, 14600]],
+            white_point: [15635, 16450],
+            max_display_mastering_luminance: metadata.max_luminance,
+            min_display_mastering_luminance: (metadata.min_luminance.0 * 10000.).round() as u16,
+            max_cll: metadata.max_cll,
+            max_fall: metadata.max_fall,
+        },
+        _padding: [0; 2],
+    };
+
+    let blob =
+        drm_ffi::mode::create_property_blob(device.as_fd(), bytemuck::bytes_of_mut(&mut data))
+            .context("error creating property blob")?;
+    let blob = u64::from(blob.blob_id);
+
+    let res = device
+        .set_property(connector, prop, property::Value::Blob(blob).into())
+        .context("error setting HDR_OUTPUT_METADATA");
+
+    // The connector state holds its own reference to the blob.
+    if let Err(err) = device.destroy_property_blob(blob) {
+        warn!("error destroying HDR_OUTPUT_METADATA property blob: {err:?}");
+    }
+
+    res
+}
+
+fn get_hdr_metadata(
+    device: &DrmDevice,
+    connector: connector::Handle,
+) -> Option<niri_ipc::HdrMetadata> {
+    let (_, info, value) = find_drm_property(device, connector, "HDR_OUTPUT_METADATA")?;
+    let blob = info.value_type().convert_value(value).as_blob()?;
+    if blob == 0 {
+        return None;
+    }
+
+    let data = device.get_property_blob(blob).ok()?;
+    let data: hdr_output_metadata = bytemuck::try_pod_read_unaligned(&data).ok()?;
+    let frame = data.hdmi_metadata_type1;
+
+    let eotf = match frame.eotf {
+        0 => Eotf::TraditionalSdr,
+        1 => Eotf::TraditionalHdr,
+        2 => Eotf::Pq,
+        3 => Eotf::Hlg,
+        _ => return None,
+    };
+
+    Some(niri_ipc::HdrMetadata {
+        eotf,
+        max_luminance: frame.max_display_mastering_luminance,
+        min_luminance: f64::from(frame.min_display_mastering_luminance) / 10000.,
+        max_cll: frame.max_cll,
+        max_fall: frame.max_fall,
+    })
+}
+
+impl ColorSettings {
+    fn from_config(config: &niri_config::Output) -> Self {
+        let framebuffer_format = config.framebuffer_format;
+
+        // By default, set max bpc to the framebuffer bit depth to allow more types of links to
+        // run.
+        let max_bpc = config.max_bpc.unwrap_or(match framebuffer_format {
+            FramebufferFormat::Xrgb8888 => 8,
+            FramebufferFormat::Xrgb2101010 => 10,
+        });
+
+        Self {
+            max_bpc,
+            framebuffer_format,
+            broadcast_rgb: config.broadcast_rgb,
+            colorimetry: config.colorimetry,
+            hdr_metadata: config.hdr_metadata,
+        }
+    }
+
+    /// Formats to try for the framebuffers, in order of preference.
+    fn color_formats(&self) -> &'static [Fourcc] {
+        match self.framebuffer_format {
+            FramebufferFormat::Xrgb8888 => SUPPORTED_COLOR_FORMATS,
+            FramebufferFormat::Xrgb2101010 => SUPPORTED_COLOR_FORMATS_10_BIT,
+        }
+    }
+
+    /// Sets the connector properties.
+    ///
+    /// Most connectors don't have all of the properties, so errors for settings left at their
+    /// defaults are only logged at debug level.
+    fn apply(&self, device: &DrmDevice, connector: connector::Handle) {
+        match set_max_bpc(device, connector, u64::from(self.max_bpc)) {
+            Ok(bpc) => debug!("set max bpc to {bpc}"),
+            Err(err) => debug!("error setting max bpc: {err:?}"),
+        }
+
+        let name = drm_name(BROADCAST_RGB_NAMES, self.broadcast_rgb);
+        match set_drm_enum_property(device, connector, "Broadcast RGB", name) {
+            Ok(()) => debug!("set broadcast RGB to {name:?}"),
+            Err(err) if self.broadcast_rgb == BroadcastRgb::Automatic => {
+                debug!("error setting broadcast RGB: {err:?}")
+            }
+            Err(err) => warn!("error setting broadcast RGB: {err:?}"),
+        }
+
+        let name = drm_name(COLORIMETRY_NAMES, self.colorimetry);
+        match set_drm_enum_property(device, connector, "Colorspace", name) {
+            Ok(()) => debug!("set colorimetry to {name:?}"),
+            Err(err) if self.colorimetry == Colorimetry::Default => {
+                debug!("error setting colorimetry: {err:?}")
+            }
+            Err(err) => warn!("error setting colorimetry: {err:?}"),
+        }
+
+        match set_hdr_metadata(device, connector, self.hdr_metadata) {
+            Ok(()) => (),
+            Err(err) if self.hdr_metadata.is_none() => {
+                debug!("error clearing HDR metadata: {err:?}")
+            }
+            Err(err) => warn!("error setting HDR metadata: {err:?}"),
+        }
+    }
+}
+
 fn is_vrr_capable(device: &DrmDevice, connector: connector::Handle) -> Option<bool> {
     let (_, info, value) = find_drm_property(device, connector, "vrr_capable")?;
     info.value_type().convert_value(value).as_boolean()
@@ -2387,6 +3322,53 @@ fn set_vrr_enabled(device: &DrmDevice, crtc: crtc::Handle, enabled: bool) -> any
     }
 }
 
+/// Sets the CRTC gamma to the client ramp with the calibration curves applied on top.
+fn set_crtc_gamma(
+    device: &DrmDevice,
+    crtc: crtc::Handle,
+    gamma_props: Option<&mut GammaProps>,
+    client: Option<&[u16]>,
+    vcgt: Option<&Vcgt>,
+) -> anyhow::Result<()> {
+    let ramp = if vcgt.is_some() {
+        let gamma_size = if let Some(gamma_props) = &gamma_props {
+            gamma_props.gamma_size(device)?
+        } else {
+            let info = device.get_crtc(crtc).context("error getting crtc info")?;
+            info.gamma_length()
+        };
+        gamma_ramp(client, vcgt, gamma_size as usize)
+    } else {
+        client.map(<[u16]>::to_vec)
+    };
+
+    let ramp = ramp.as_deref();
+    if let Some(gamma_props) = gamma_props {
+        gamma_props.set_gamma(device, ramp)
+    } else {
+        set_gamma_for_crtc(device, crtc, ramp)
+    }
+}
+
+fn load_vcgt(config: &niri_config::Output) -> Option<Vcgt> {
+    let path = config.icc_profile.as_deref()?;
+
+    let mut expanded = PathBuf::from(path);
+    match expand_home(&expanded) {
+        Ok(Some(x)) => expanded = x,
+        Ok(None) => (),
+        Err(err) => warn!("error expanding ~: {err:?}"),
+    }
+
+    match Vcgt::load(&expanded) {
+        Ok(vcgt) => Some(vcgt),
+        Err(err) => {
+            warn!("error loading ICC profile {path:?}: {err:?}");
+            None
+        }
+    }
+}
+
 pub fn set_gamma_for_crtc(
     device: &DrmDevice,
     crtc: crtc::Handle,
@@ -2492,3 +3474,108 @@ fn make_output_name(
         serial: info.as_ref().and_then(|info| info.serial()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timings(mode: control::Mode) -> (u32, [u16; 8], ModeFlags) {
+        let (hdisplay, vdisplay) = mode.size();
+        let (hsync_start, hsync_end, htotal) = mode.hsync();
+        let (vsync_start, vsync_end, vtotal) = mode.vsync();
+        (
+            mode.clock(),
+            [
+                hdisplay,
+                hsync_start,
+                hsync_end,
+                htotal,
+                vdisplay,
+                vsync_start,
+                vsync_end,
+                vtotal,
+            ],
+            mode.flags(),
+        )
+    }
+
+    #[test]
+    fn cvt_reduced_blanking() {
+        let mode = |width, height, refresh| {
+            let mode = calculate_mode_cvt(ConfiguredMode {
+                width,
+                height,
+                refresh,
+            });
+            timings(mode.unwrap())
+        };
+
+        // Reference values from `cvt --reduced`.
+        let flags = ModeFlags::PHSYNC | ModeFlags::NVSYNC;
+        assert_eq!(
+            mode(1920, 1080, None),
+            (
+                138500,
+                [1920, 1968, 2000, 2080, 1080, 1083, 1088, 1111],
+                flags
+            )
+        );
+        assert_eq!(
+            mode(2560, 1440, Some(120.)),
+            (
+                497750,
+                [2560, 2608, 2640, 2720, 1440, 1443, 1448, 1525],
+                flags
+            )
+        );
+        assert_eq!(
+            mode(1280, 1024, Some(60.)),
+            (
+                91000,
+                [1280, 1328, 1360, 1440, 1024, 1027, 1034, 1054],
+                flags
+            )
+        );
+
+        assert!(calculate_mode_cvt(ConfiguredMode {
+            width: 1920,
+            height: 1080,
+            refresh: Some(0.),
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn modeline() {
+        let modeline = niri_config::Modeline {
+            clock: niri_config::FloatOrInt(173.),
+            hdisplay: 1920,
+            hsync_start: 2048,
+            hsync_end: 2248,
+            htotal: 2576,
+            vdisplay: 1080,
+            vsync_start: 1083,
+            vsync_end: 1088,
+            vtotal: 1120,
+            hsync_polarity: HSyncPolarity::NHSync,
+            vsync_polarity: VSyncPolarity::PVSync,
+        };
+        let mode = calculate_drm_mode_from_modeline(&modeline).unwrap();
+        assert_eq!(
+            timings(mode),
+            (
+                173000,
+                [1920, 2048, 2248, 2576, 1080, 1083, 1088, 1120],
+                ModeFlags::NHSYNC | ModeFlags::PVSYNC
+            )
+        );
+        assert_eq!(mode.vrefresh(), 60);
+        assert!(mode.mode_type().contains(ModeTypeFlags::USERDEF));
+
+        let broken = niri_config::Modeline {
+            hsync_start: 1900,
+            ..modeline
+        };
+        assert!(calculate_drm_mode_from_modeline(&broken).is_err());
+    }
+}
@@ -0,0 +1,586 @@
+//! Virtual outputs that render into offscreen buffers.
+//!
+//! These make up all outputs of the headless backend, and can be created at runtime on the TTY
+//! backend, for example to stream them to another device as a second screen.
+
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use niri_config::{Config, OutputName};
+use niri_ipc::ConfiguredMode;
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::damage::{OutputDamageTracker, RenderOutputResult};
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::backend::renderer::{Bind, Offscreen};
+use smithay::desktop::utils::OutputPresentationFeedback;
+use smithay::output::{Mode, Output, PhysicalProperties, Subpixel};
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
+use smithay::utils::Transform;
+
+use super::{OutputId, RenderResult};
+use crate::frame_clock::FrameClock;
+use crate::niri::{Niri, OutputRenderElements, RedrawState};
+use crate::render_helpers::debug::draw_damage;
+use crate::render_helpers::RenderTarget;
+use crate::utils::{get_monotonic_time, logical_output};
+
+/// Set of virtual outputs.
+pub struct VirtualOutputs {
+    config: Rc<RefCell<Config>>,
+    outputs: Vec<VirtualOutput>,
+}
+
+/// Virtual output that may be enabled or disabled through the config.
+struct VirtualOutput {
+    id: OutputId,
+    name: OutputName,
+    /// Mode used when the config doesn't set one.
+    default_mode: ConfiguredMode,
+    /// Whether this output was created at runtime rather than being one of the backend's outputs.
+    created_at_runtime: bool,
+    /// Rendering state, if the output is enabled.
+    surface: Option<VirtualSurface>,
+}
+
+struct VirtualSurface {
+    output: Output,
+    mode: Mode,
+    damage_tracker: OutputDamageTracker,
+    /// Offscreen buffer that the output renders into.
+    buffer: Option<GlesTexture>,
+}
+
+impl VirtualOutputs {
+    pub fn new(config: Rc<RefCell<Config>>) -> Self {
+        Self {
+            config,
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Adds a virtual output.
+    ///
+    /// The output is connected upon the next call to [`Self::on_output_config_changed()`].
+    pub fn add(&mut self, name: OutputName, default_mode: ConfiguredMode) {
+        self.push(name, default_mode, false);
+    }
+
+    fn push(&mut self, name: OutputName, default_mode: ConfiguredMode, created_at_runtime: bool) {
+        self.outputs.push(VirtualOutput {
+            id: OutputId::next(),
+            name,
+            default_mode,
+            created_at_runtime,
+            surface: None,
+        });
+    }
+
+    /// Creates and connects a new virtual output, returning its name.
+    pub fn create(&mut self, niri: &mut Niri, mode: ConfiguredMode) -> String {
+        let connector = self.free_connector_name();
+        debug!("creating virtual output {connector}");
+        let name = OutputName {
+            connector: connector.clone(),
+            make: Some("niri".to_owned()),
+            model: Some("Virtual".to_owned()),
+            serial: None,
+        };
+        self.push(name, mode, true);
+        self.on_output_config_changed(niri);
+
+        connector
+    }
+
+    /// Returns the first `VIRTUAL-N` connector name that isn't taken.
+    fn free_connector_name(&self) -> String {
+        let mut idx = 1;
+        loop {
+            let connector = format!("VIRTUAL-{idx}");
+            if !self
+                .outputs
+                .iter()
+                .any(|virt| virt.name.connector == connector)
+            {
+                return connector;
+            }
+            idx += 1;
+        }
+    }
+
+    /// Disconnects and removes the virtual output with this name.
+    ///
+    /// Returns `false` if there was no such virtual output.
+    pub fn destroy(&mut self, niri: &mut Niri, connector: &str) -> bool {
+        let Some(idx) = self
+            .outputs
+            .iter()
+            .position(|virt| virt.name.connector.eq_ignore_ascii_case(connector))
+        else {
+            return false;
+        };
+
+        let virt = self.outputs.remove(idx);
+        debug!("destroying virtual output {}", virt.name.connector);
+        if let Some(surface) = virt.surface {
+            niri.remove_output(&surface.output);
+        }
+
+        true
+    }
+
+    /// Changes the mode of the virtual output with this name.
+    ///
+    /// A mode set in the config still takes precedence. Returns `false` if there was no such
+    /// virtual output.
+    pub fn set_mode(&mut self, niri: &mut Niri, connector: &str, mode: ConfiguredMode) -> bool {
+        let Some(virt) = self
+            .outputs
+            .iter_mut()
+            .find(|virt| virt.name.connector.eq_ignore_ascii_case(connector))
+        else {
+            return false;
+        };
+
+        virt.default_mode = mode;
+        self.on_output_config_changed(niri);
+
+        true
+    }
+
+    /// Returns whether the output with this ID is a virtual output created at runtime.
+    pub fn is_created_at_runtime(&self, id: OutputId) -> bool {
+        self.outputs
+            .iter()
+            .any(|virt| virt.id == id && virt.created_at_runtime)
+    }
+
+    /// Returns whether this output is one of the virtual outputs.
+    pub fn contains(&self, output: &Output) -> bool {
+        self.outputs
+            .iter()
+            .filter_map(|virt| virt.surface.as_ref())
+            .any(|surface| surface.output == *output)
+    }
+
+    pub fn render(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        niri: &mut Niri,
+        output: &Output,
+        target_presentation_time: Duration,
+    ) -> RenderResult {
+        let _span = tracy_client::span!("VirtualOutputs::render");
+
+        let Some(surface) = self
+            .outputs
+            .iter_mut()
+            .filter_map(|virt| virt.surface.as_mut())
+            .find(|surface| surface.output == *output)
+        else {
+            error!("missing virtual output for {}", output.name());
+            return RenderResult::Skipped;
+        };
+
+        // Render the elements.
+        let mut elements =
+            niri.render::<GlesRenderer>(renderer, output, true, RenderTarget::Output);
+
+        // Visualize the damage, if enabled.
+        if niri.debug_draw_damage {
+            let output_state = niri.output_state.get_mut(output).unwrap();
+            draw_damage(&mut output_state.debug_damage_tracker, &mut elements);
+        }
+
+        let mut rv = RenderResult::Skipped;
+        match surface.render(renderer, &elements) {
+            Ok(res) => {
+                niri.update_primary_scanout_output(output, &res.states);
+
+                if res.damage.is_some() {
+                    let feedbacks = niri.take_presentation_feedbacks(output, &res.states);
+                    queue_vblank_timer(niri, output.clone(), target_presentation_time, feedbacks);
+                    return RenderResult::Submitted;
+                }
+
+                rv = RenderResult::NoDamage;
+            }
+            Err(err) => {
+                warn!("error rendering virtual output: {err:?}");
+            }
+        }
+
+        // Queue a timer to fire at the predicted vblank time.
+        queue_estimated_vblank_timer(niri, output.clone(), target_presentation_time);
+
+        rv
+    }
+
+    /// Enables, disables and changes modes of the virtual outputs according to the config.
+    pub fn on_output_config_changed(&mut self, niri: &mut Niri) {
+        let _span = tracy_client::span!("VirtualOutputs::on_output_config_changed");
+
+        for virt in &mut self.outputs {
+            let (off, mode) = {
+                let config = self.config.borrow();
+                let config = config.outputs.find(&virt.name);
+                let off = config.map_or(false, |c| c.off);
+                let mode = config
+                    .and_then(|c| c.mode)
+                    .map(|m| m.mode)
+                    .unwrap_or(virt.default_mode);
+                (off, mode)
+            };
+            let (mode, refresh_interval) = mode_from_config(mode);
+
+            if off {
+                if let Some(surface) = virt.surface.take() {
+                    debug!("disabling virtual output {}", virt.name.connector);
+                    niri.remove_output(&surface.output);
+                }
+                continue;
+            }
+
+            if let Some(surface) = &mut virt.surface {
+                if surface.mode == mode {
+                    continue;
+                }
+
+                debug!(
+                    "virtual output {}: changing mode to {mode:?}",
+                    virt.name.connector
+                );
+                let output = &surface.output;
+                output.change_current_state(Some(mode), None, None, None);
+                output.set_preferred(mode);
+                surface.mode = mode;
+                surface.damage_tracker = OutputDamageTracker::from_output(output);
+                surface.buffer = None;
+
+                let output_state = niri.output_state.get_mut(output).unwrap();
+                output_state.frame_clock = FrameClock::new(Some(refresh_interval), false);
+                niri.output_resized(output);
+                continue;
+            }
+
+            debug!("enabling virtual output {}", virt.name.connector);
+            let output = Output::new(
+                virt.name.connector.clone(),
+                PhysicalProperties {
+                    size: (0, 0).into(),
+                    subpixel: Subpixel::Unknown,
+                    make: virt.name.make.clone().unwrap_or_default(),
+                    model: virt.name.model.clone().unwrap_or_default(),
+                },
+            );
+            output.change_current_state(Some(mode), None, None, None);
+            output.set_preferred(mode);
+            output.user_data().insert_if_missing(|| virt.name.clone());
+
+            niri.add_output(output.clone(), Some(refresh_interval), false);
+
+            // The damage tracker needs the scale and transform that add_output() set.
+            let damage_tracker = OutputDamageTracker::from_output(&output);
+            virt.surface = Some(VirtualSurface {
+                output,
+                mode,
+                damage_tracker,
+                buffer: None,
+            });
+        }
+    }
+
+    /// Returns the IPC information about the virtual outputs.
+    pub fn ipc_outputs<'a>(
+        &'a self,
+        niri: &'a Niri,
+    ) -> impl Iterator<Item = (OutputId, niri_ipc::Output)> + 'a {
+        self.outputs.iter().map(|virt| {
+            let surface = virt.surface.as_ref();
+            let modes = surface
+                .map(|surface| {
+                    vec![niri_ipc::Mode {
+                        width: surface.mode.size.w.clamp(0, u16::MAX as i32) as u16,
+                        height: surface.mode.size.h.clamp(0, u16::MAX as i32) as u16,
+                        refresh_rate: surface.mode.refresh as u32,
+                        is_preferred: true,
+                    }]
+                })
+                .unwrap_or_default();
+
+            let logical = surface
+                .filter(|surface| niri.global_space.outputs().any(|o| *o == surface.output))
+                .map(|surface| logical_output(&surface.output));
+            let powered = surface.is_some_and(|surface| niri.is_output_powered(&surface.output));
+
+            let ipc_output = niri_ipc::Output {
+                name: virt.name.connector.clone(),
+                make: virt.name.make.clone().unwrap_or_default(),
+                model: virt.name.model.clone().unwrap_or_default(),
+                serial: None,
+                physical_size: None,
+                current_mode: surface.map(|_| 0),
+                modes,
+                vrr_supported: false,
+                vrr_enabled: false,
+                powered,
+                color: None,
+                logical,
+            };
+            (virt.id, ipc_output)
+        })
+    }
+}
+
+impl VirtualSurface {
+    fn render(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        elements: &[OutputRenderElements<GlesRenderer>],
+    ) -> anyhow::Result<RenderOutputResult<'_>> {
+        // The buffer is kept between frames, so only the damaged parts need to be redrawn.
+        let age = if self.buffer.is_some() { 1 } else { 0 };
+
+        let buffer = match &self.buffer {
+            Some(buffer) => buffer.clone(),
+            None => {
+                let size = self.mode.size.to_logical(1).to_buffer(1, Transform::Normal);
+                let buffer: GlesTexture = renderer
+                    .create_buffer(Fourcc::Abgr8888, size)
+                    .context("error creating buffer")?;
+                self.buffer = Some(buffer.clone());
+                buffer
+            }
+        };
+
+        renderer.bind(buffer).context("error binding buffer")?;
+
+        let res = self
+            .damage_tracker
+            .render_output(renderer, age, elements, [0.; 4])
+            .map_err(|err| anyhow!("error rendering output: {err:?}"))?;
+        Ok(res)
+    }
+}
+
+/// Converts a mode from the config into an output mode and its refresh interval.
+fn mode_from_config(mode: ConfiguredMode) -> (Mode, Duration) {
+    // The frame clock needs a refresh interval shorter than a second.
+    let refresh = mode.refresh.unwrap_or(60.).clamp(1., 1000.);
+    let refresh_interval =
+        Duration::from_secs_f64(1. / refresh).min(Duration::from_nanos(999_999_999));
+
+    let mode = Mode {
+        size: (i32::from(mode.width), i32::from(mode.height)).into(),
+        refresh: (refresh * 1000.).round() as i32,
+    };
+    (mode, refresh_interval)
+}
+
+/// Queues a timer that presents the submitted frame at the predicted vblank time.
+fn queue_vblank_timer(
+    niri: &mut Niri,
+    output: Output,
+    target_presentation_time: Duration,
+    mut feedbacks: OutputPresentationFeedback,
+) {
+    let output_state = niri.output_state.get_mut(&output).unwrap();
+    let new_state = RedrawState::WaitingForVBlank {
+        redraw_needed: false,
+    };
+    match mem::replace(&mut output_state.redraw_state, new_state) {
+        RedrawState::Idle => unreachable!(),
+        RedrawState::Queued => (),
+        RedrawState::WaitingForVBlank { .. } => unreachable!(),
+        RedrawState::WaitingForRenderStart(_) => unreachable!(),
+        RedrawState::WaitingForEstimatedVBlank(_) => unreachable!(),
+        RedrawState::WaitingForEstimatedVBlankAndQueued(token) => {
+            niri.event_loop.remove(token);
+        }
+    }
+
+    // Nothing will overwrite this frame anymore, so we can send frame callbacks now.
+    output_state.frame_callback_sequence = output_state.frame_callback_sequence.wrapping_add(1);
+
+    let refresh = output_state
+        .frame_clock
+        .refresh_interval()
+        .unwrap_or(Duration::ZERO);
+    let duration = target_presentation_time.saturating_sub(get_monotonic_time());
+    let duration = if duration.is_zero() {
+        refresh
+    } else {
+        duration
+    };
+
+    let timer = Timer::from_duration(duration);
+    niri.event_loop
+        .insert_source(timer, move |_, _, state| {
+            let niri = &mut state.niri;
+            let Some(output_state) = niri.output_state.get_mut(&output) else {
+                return TimeoutAction::Drop;
+            };
+
+            let now = get_monotonic_time();
+            feedbacks.presented::<_, smithay::utils::Monotonic>(
+                now,
+                refresh,
+                0,
+                wp_presentation_feedback::Kind::Vsync,
+            );
+            output_state.frame_clock.presented(now);
+
+            let redraw_needed =
+                match mem::replace(&mut output_state.redraw_state, RedrawState::Idle) {
+                    RedrawState::WaitingForVBlank { redraw_needed } => redraw_needed,
+                    state => {
+                        error!("unexpected redraw state on virtual vblank: {state:?}");
+                        true
+                    }
+                };
+
+            if redraw_needed || output_state.unfinished_animations_remain {
+                niri.queue_redraw(&output);
+            } else {
+                niri.send_frame_callbacks(&output);
+            }
+
+            TimeoutAction::Drop
+        })
+        .unwrap();
+}
+
+/// Queues a timer to fire at the predicted vblank time after a frame without damage.
+fn queue_estimated_vblank_timer(
+    niri: &mut Niri,
+    output: Output,
+    target_presentation_time: Duration,
+) {
+    let output_state = niri.output_state.get_mut(&output).unwrap();
+    match mem::take(&mut output_state.redraw_state) {
+        RedrawState::Idle => unreachable!(),
+        RedrawState::Queued => (),
+        RedrawState::WaitingForVBlank { .. } => unreachable!(),
+        RedrawState::WaitingForRenderStart(_) => unreachable!(),
+        RedrawState::WaitingForEstimatedVBlank(token)
+        | RedrawState::WaitingForEstimatedVBlankAndQueued(token) => {
+            output_state.redraw_state = RedrawState::WaitingForEstimatedVBlank(token);
+            return;
+        }
+    }
+
+    let mut duration = target_presentation_time.saturating_sub(get_monotonic_time());
+    if duration.is_zero() {
+        duration += output_state
+            .frame_clock
+            .refresh_interval()
+            .unwrap_or(Duration::from_micros(16_667));
+    }
+
+    let timer = Timer::from_duration(duration);
+    let token = niri
+        .event_loop
+        .insert_source(timer, move |_, _, state| {
+            let niri = &mut state.niri;
+            let Some(output_state) = niri.output_state.get_mut(&output) else {
+                return TimeoutAction::Drop;
+            };
+
+            // We waited for the timer, now we can send frame callbacks again.
+            output_state.frame_callback_sequence =
+                output_state.frame_callback_sequence.wrapping_add(1);
+
+            match mem::replace(&mut output_state.redraw_state, RedrawState::Idle) {
+                RedrawState::WaitingForEstimatedVBlank(_) => (),
+                // The timer fired just in front of a redraw.
+                RedrawState::WaitingForEstimatedVBlankAndQueued(_) => {
+                    output_state.redraw_state = RedrawState::Queued;
+                    return TimeoutAction::Drop;
+                }
+                state => {
+                    error!("unexpected redraw state on estimated vblank: {state:?}");
+                }
+            }
+
+            if output_state.unfinished_animations_remain {
+                niri.queue_redraw(&output);
+            } else {
+                niri.send_frame_callbacks(&output);
+            }
+
+            TimeoutAction::Drop
+        })
+        .unwrap();
+    output_state.redraw_state = RedrawState::WaitingForEstimatedVBlank(token);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(refresh: Option<f64>) -> ConfiguredMode {
+        ConfiguredMode {
+            width: 1920,
+            height: 1080,
+            refresh,
+        }
+    }
+
+    fn name(connector: &str) -> OutputName {
+        OutputName {
+            connector: connector.to_owned(),
+            make: None,
+            model: None,
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn connector_names() {
+        let mut outputs = VirtualOutputs::new(Rc::new(RefCell::new(Config::default())));
+        outputs.add(name("HEADLESS-1"), mode(None));
+        assert_eq!(outputs.free_connector_name(), "VIRTUAL-1");
+
+        outputs.push(name("VIRTUAL-1"), mode(None), true);
+        outputs.push(name("VIRTUAL-2"), mode(None), true);
+        assert_eq!(outputs.free_connector_name(), "VIRTUAL-3");
+
+        // Names of destroyed outputs are reused.
+        outputs.outputs.remove(1);
+        assert_eq!(outputs.free_connector_name(), "VIRTUAL-1");
+    }
+
+    #[test]
+    fn created_at_runtime() {
+        let mut outputs = VirtualOutputs::new(Rc::new(RefCell::new(Config::default())));
+        outputs.add(name("HEADLESS-1"), mode(None));
+        outputs.push(name("VIRTUAL-1"), mode(None), true);
+
+        let headless = outputs.outputs[0].id;
+        let virt = outputs.outputs[1].id;
+        assert!(!outputs.is_created_at_runtime(headless));
+        assert!(outputs.is_created_at_runtime(virt));
+    }
+
+    #[test]
+    fn mode_refresh() {
+        let (m, interval) = mode_from_config(mode(None));
+        assert_eq!(m.size, (1920, 1080).into());
+        assert_eq!(m.refresh, 60_000);
+        assert_eq!(interval, Duration::from_secs_f64(1. / 60.));
+
+        let (m, _) = mode_from_config(mode(Some(143.856)));
+        assert_eq!(m.refresh, 143_856);
+
+        // The refresh interval must stay below a second for the frame clock.
+        let (m, interval) = mode_from_config(mode(Some(0.5)));
+        assert_eq!(m.refresh, 1000);
+        assert!(interval < Duration::from_secs(1));
+
+        let (m, _) = mode_from_config(mode(Some(5000.)));
+        assert_eq!(m.refresh, 1_000_000);
+    }
+}
@@ -84,6 +84,8 @@ impl Winit {
                 current_mode: Some(0),
                 vrr_supported: false,
                 vrr_enabled: false,
+                powered: true,
+                color: None,
                 logical: Some(logical_output(&output)),
             },
         )])));
@@ -158,6 +160,10 @@ impl Winit {
 
         niri.layout.update_shaders();
 
+        // Textures from the old renderer are no longer valid.
+        niri.background_images.clear();
+        niri.update_background_images();
+
         niri.add_output(self.output.clone(), None, false);
     }
 
@@ -235,6 +241,7 @@ impl Winit {
             RedrawState::Idle => unreachable!(),
             RedrawState::Queued => (),
             RedrawState::WaitingForVBlank { .. } => unreachable!(),
+            RedrawState::WaitingForRenderStart(_) => unreachable!(),
             RedrawState::WaitingForEstimatedVBlank(_) => unreachable!(),
             RedrawState::WaitingForEstimatedVBlankAndQueued(_) => unreachable!(),
         }
@@ -2,7 +2,7 @@ use std::ffi::OsString;
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
-use niri_ipc::{Action, OutputAction};
+use niri_ipc::{Action, ConfiguredMode, OutputAction};
 
 use crate::utils::version;
 
@@ -25,6 +25,21 @@ pub struct Cli {
     /// on a TTY as your non-main compositor instance, to avoid messing up the global environment.
     #[arg(long)]
     pub session: bool,
+    /// Run without a display or GPU access, with the given number of virtual outputs.
+    ///
+    /// The virtual outputs are named `HEADLESS-1`, `HEADLESS-2`, and so on, and can be
+    /// configured in the config file like any other output. Rendering uses a surfaceless EGL
+    /// display, falling back to software rendering when no GPU is available.
+    #[arg(long, value_name = "OUTPUTS")]
+    pub headless: Option<u8>,
+    /// Mode of the headless virtual outputs that don't have one set in the config.
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "1920x1080@60",
+        requires = "headless"
+    )]
+    pub headless_mode: ConfiguredMode,
     /// Command to run upon compositor startup.
     #[arg(last = true)]
     pub command: Vec<OsString>,
@@ -90,6 +105,31 @@ pub enum Msg {
         #[command(subcommand)]
         action: OutputAction,
     },
+    /// Create a virtual output.
+    ///
+    /// Virtual outputs take part in the layout like real monitors and can be screencast, for
+    /// example to use a tablet running a streaming app as a second screen.
+    CreateVirtualOutput {
+        /// Mode of the virtual output, for example `1920x1080@60`.
+        #[arg()]
+        mode: ConfiguredMode,
+    },
+    /// Destroy a virtual output.
+    DestroyVirtualOutput {
+        /// Name of the virtual output.
+        #[arg()]
+        output: String,
+    },
+    /// Print render time prediction statistics of the outputs.
+    RenderTimes,
+    /// Print frame timing statistics of the outputs.
+    FrameStats {
+        /// Output name, or all outputs if not set.
+        ///
+        /// Run `niri msg outputs` to see the output names.
+        #[arg()]
+        output: Option<String>,
+    },
     /// Start continuously receiving events from the compositor.
     EventStream,
     /// Print the version of the running niri instance.
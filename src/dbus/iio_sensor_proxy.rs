@@ -0,0 +1,198 @@
+use std::thread;
+
+use anyhow::Context;
+use zbus::blocking::Connection;
+use zbus::dbus_proxy;
+
+use crate::utils::orientation::Orientation;
+
+#[dbus_proxy(
+    interface = "net.hadess.SensorProxy",
+    default_service = "net.hadess.SensorProxy",
+    default_path = "/net/hadess/SensorProxy"
+)]
+trait SensorProxy {
+    fn claim_accelerometer(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn has_accelerometer(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn accelerometer_orientation(&self) -> zbus::Result<String>;
+}
+
+/// Starts watching the accelerometer orientation on `conn`, normally the system bus.
+///
+/// Orientation changes are sent to `to_niri` from a separate thread. Returns `Ok(false)` if there
+/// is no accelerometer.
+pub fn start(
+    conn: Connection,
+    to_niri: calloop::channel::Sender<Orientation>,
+) -> anyhow::Result<bool> {
+    let proxy = SensorProxyProxyBlocking::new(&conn).context("error creating a proxy")?;
+
+    let has_accelerometer = proxy
+        .has_accelerometer()
+        .context("error getting HasAccelerometer")?;
+    if !has_accelerometer {
+        return Ok(false);
+    }
+
+    let changes = proxy.receive_accelerometer_orientation_changed();
+    proxy
+        .claim_accelerometer()
+        .context("error calling ClaimAccelerometer")?;
+
+    let send = move |orientation: String| match orientation.parse() {
+        Ok(orientation) => to_niri.send(orientation).is_ok(),
+        Err(_) => {
+            // The orientation is "undefined" until the first reading.
+            trace!("ignoring accelerometer orientation: {orientation}");
+            true
+        }
+    };
+
+    let orientation = proxy
+        .accelerometer_orientation()
+        .context("error getting AccelerometerOrientation")?;
+    send(orientation);
+
+    thread::Builder::new()
+        .name("Accelerometer Watcher".to_owned())
+        .spawn(move || {
+            // Keep the connection and the proxy alive since the accelerometer is released when we
+            // disconnect.
+            let _conn = conn;
+            let _proxy = proxy;
+
+            for change in changes {
+                match change.get() {
+                    Ok(orientation) => {
+                        if !send(orientation) {
+                            break;
+                        }
+                    }
+                    Err(err) => warn!("error getting accelerometer orientation: {err:?}"),
+                }
+            }
+        })
+        .context("error spawning the accelerometer watcher thread")?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+    use std::time::{Duration, Instant};
+
+    use calloop::EventLoop;
+    use zbus::blocking::ConnectionBuilder;
+    use zbus::{dbus_interface, Guid};
+
+    use super::*;
+
+    const PATH: &str = "/net/hadess/SensorProxy";
+
+    struct FakeSensorProxy {
+        orientation: String,
+    }
+
+    #[dbus_interface(name = "net.hadess.SensorProxy")]
+    impl FakeSensorProxy {
+        fn claim_accelerometer(&self) {}
+
+        #[dbus_interface(property)]
+        fn has_accelerometer(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn accelerometer_orientation(&self) -> String {
+            self.orientation.clone()
+        }
+    }
+
+    fn set_orientation(server: &Connection, orientation: &str) {
+        let iface = server
+            .object_server()
+            .interface::<_, FakeSensorProxy>(PATH)
+            .unwrap();
+        async_io::block_on(async {
+            let mut proxy = iface.get_mut().await;
+            proxy.orientation = orientation.to_owned();
+            proxy
+                .accelerometer_orientation_changed(iface.signal_context())
+                .await
+        })
+        .unwrap();
+    }
+
+    fn next_orientation(
+        event_loop: &mut EventLoop<Vec<Orientation>>,
+        received: &mut Vec<Orientation>,
+    ) -> Orientation {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.is_empty() {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for an orientation"
+            );
+            event_loop
+                .dispatch(Duration::from_millis(100), received)
+                .unwrap();
+        }
+        received.remove(0)
+    }
+
+    #[test]
+    fn orientation_changes_reach_niri() {
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let server = thread::spawn(move || {
+            let fake = FakeSensorProxy {
+                orientation: String::from("undefined"),
+            };
+            ConnectionBuilder::unix_stream(server_stream)
+                .server(&guid)
+                .p2p()
+                .serve_at(PATH, fake)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = ConnectionBuilder::unix_stream(client_stream)
+            .p2p()
+            .build()
+            .unwrap();
+        let server = server.join().unwrap();
+
+        let mut event_loop = EventLoop::<Vec<Orientation>>::try_new().unwrap();
+        let (to_niri, from_sensor_proxy) = calloop::channel::channel();
+        event_loop
+            .handle()
+            .insert_source(from_sensor_proxy, |event, _, received| {
+                if let calloop::channel::Event::Msg(orientation) = event {
+                    received.push(orientation);
+                }
+            })
+            .unwrap();
+
+        assert!(start(client, to_niri).unwrap());
+
+        let mut received = vec![];
+        set_orientation(&server, "left-up");
+        assert_eq!(
+            next_orientation(&mut event_loop, &mut received),
+            Orientation::LeftUp
+        );
+
+        set_orientation(&server, "undefined");
+        set_orientation(&server, "bottom-up");
+        assert_eq!(
+            next_orientation(&mut event_loop, &mut received),
+            Orientation::BottomUp
+        );
+        assert!(received.is_empty());
+    }
+}
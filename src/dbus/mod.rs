@@ -1,11 +1,14 @@
+use anyhow::Context;
 use zbus::blocking::Connection;
 use zbus::Interface;
 
 use crate::niri::State;
+use crate::utils::orientation::Orientation;
 
 pub mod freedesktop_screensaver;
 pub mod gnome_shell_introspect;
 pub mod gnome_shell_screenshot;
+pub mod iio_sensor_proxy;
 pub mod mutter_display_config;
 pub mod mutter_service_channel;
 
@@ -32,6 +35,8 @@ pub struct DBusServers {
     pub conn_introspect: Option<Connection>,
     #[cfg(feature = "xdp-gnome-screencast")]
     pub conn_screen_cast: Option<Connection>,
+    /// Sender for the accelerometer watcher, until it is started.
+    accelerometer_to_niri: Option<calloop::channel::Sender<Orientation>>,
 }
 
 impl DBusServers {
@@ -47,6 +52,22 @@ impl DBusServers {
         if is_session_instance {
             let service_channel = ServiceChannel::new(niri.display_handle.clone());
             dbus.conn_service_channel = try_start(service_channel);
+
+            let (to_niri, from_sensor_proxy) = calloop::channel::channel();
+            niri.event_loop
+                .insert_source(from_sensor_proxy, move |event, _, state| match event {
+                    calloop::channel::Event::Msg(orientation) => {
+                        state.on_orientation_changed(orientation)
+                    }
+                    calloop::channel::Event::Closed => (),
+                })
+                .unwrap();
+            dbus.accelerometer_to_niri = Some(to_niri);
+
+            // Claiming the accelerometer keeps the sensor powered, so only do it when needed.
+            if config.outputs.0.iter().any(|o| o.auto_rotate) {
+                dbus.start_accelerometer_watcher();
+            }
         }
 
         if is_session_instance || config.debug.dbus_interfaces_in_non_session_instances {
@@ -102,6 +123,22 @@ impl DBusServers {
 
         niri.dbus = Some(dbus);
     }
+
+    /// Starts watching the accelerometer, unless it was already started.
+    pub fn start_accelerometer_watcher(&mut self) {
+        let Some(to_niri) = self.accelerometer_to_niri.take() else {
+            return;
+        };
+
+        let res = Connection::system()
+            .context("error connecting to the system bus")
+            .and_then(|conn| iio_sensor_proxy::start(conn, to_niri));
+        match res {
+            Ok(true) => (),
+            Ok(false) => debug!("no accelerometer found, disabling automatic rotation"),
+            Err(err) => debug!("error starting the accelerometer watcher: {err:?}"),
+        }
+    }
 }
 
 fn try_start<I: Start>(iface: I) -> Option<Connection> {
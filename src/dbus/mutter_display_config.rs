@@ -8,6 +8,7 @@ use zbus::{dbus_interface, fdo, SignalContext};
 
 use super::Start;
 use crate::backend::IpcOutputMap;
+use crate::utils::is_laptop_panel;
 
 pub struct DisplayConfig {
     ipc_outputs: Arc<Mutex<IpcOutputMap>>,
@@ -61,9 +62,7 @@ impl DisplayConfig {
             // Take only enabled outputs.
             .filter(|output| output.current_mode.is_some() && output.logical.is_some())
             .map(|output| {
-                // Loosely matches the check in Mutter.
-                let c = &output.name;
-                let is_laptop_panel = matches!(c.get(..4), Some("eDP-" | "LVDS" | "DSI-"));
+                let is_laptop_panel = is_laptop_panel(&output.name);
                 let display_name = make_display_name(output, is_laptop_panel);
 
                 let mut properties = HashMap::new();
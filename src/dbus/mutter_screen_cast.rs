@@ -11,6 +11,11 @@ use zbus::{dbus_interface, fdo, InterfaceRef, ObjectServer, SignalContext};
 use super::Start;
 use crate::backend::IpcOutputMap;
 
+/// Window id of the dynamic cast target entry in the window list.
+///
+/// Real window ids start from 1 and count up, so they never reach this value.
+pub const DYNAMIC_CAST_WINDOW_ID: u64 = u64::MAX;
+
 #[derive(Clone)]
 pub struct ScreenCast {
     ipc_outputs: Arc<Mutex<IpcOutputMap>>,
@@ -57,6 +62,24 @@ struct RecordWindowProperties {
     _is_recording: Option<bool>,
 }
 
+#[derive(Debug, DeserializeDict, Type)]
+#[zvariant(signature = "dict")]
+struct RecordAreaProperties {
+    #[zvariant(rename = "cursor-mode")]
+    cursor_mode: Option<CursorMode>,
+    #[zvariant(rename = "is-recording")]
+    _is_recording: Option<bool>,
+}
+
+#[derive(Debug, DeserializeDict, Type)]
+#[zvariant(signature = "dict")]
+struct RecordVirtualProperties {
+    #[zvariant(rename = "cursor-mode")]
+    cursor_mode: Option<CursorMode>,
+    #[zvariant(rename = "is-platform")]
+    _is_platform: Option<bool>,
+}
+
 static STREAM_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Clone)]
@@ -72,12 +95,32 @@ enum StreamTarget {
     // FIXME: update on scale changes and whatnot.
     Output(niri_ipc::Output),
     Window { id: u64 },
+    Area(AreaRect),
+    Dynamic,
+    Virtual,
 }
 
 #[derive(Debug, Clone)]
 pub enum StreamTargetId {
-    Output { name: String },
-    Window { id: u64 },
+    Output {
+        name: String,
+    },
+    Window {
+        id: u64,
+    },
+    Area(AreaRect),
+    Dynamic,
+    /// New virtual output created for the duration of the cast.
+    Virtual,
+}
+
+/// Rectangle in the global logical coordinate space.
+#[derive(Debug, Clone, Copy)]
+pub struct AreaRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
 }
 
 #[derive(Debug, SerializeDict, Type, Value)]
@@ -244,9 +287,107 @@ impl Session {
 
         let cursor_mode = properties.cursor_mode.unwrap_or_default();
 
-        let target = StreamTarget::Window {
-            id: properties.window_id,
+        let target = if properties.window_id == DYNAMIC_CAST_WINDOW_ID {
+            StreamTarget::Dynamic
+        } else {
+            StreamTarget::Window {
+                id: properties.window_id,
+            }
+        };
+        let stream = Stream::new(target, cursor_mode, self.to_niri.clone());
+        match server.at(&path, stream.clone()).await {
+            Ok(true) => {
+                let iface = server.interface(&path).await.unwrap();
+                self.streams.lock().unwrap().push((stream, iface));
+            }
+            Ok(false) => return Err(fdo::Error::Failed("stream path already exists".to_owned())),
+            Err(err) => {
+                return Err(fdo::Error::Failed(format!(
+                    "error creating stream object: {err:?}"
+                )))
+            }
+        }
+
+        Ok(path)
+    }
+
+    async fn record_area(
+        &mut self,
+        #[zbus(object_server)] server: &ObjectServer,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        properties: RecordAreaProperties,
+    ) -> fdo::Result<OwnedObjectPath> {
+        debug!(x, y, width, height, ?properties, "record_area");
+
+        if width <= 0 || height <= 0 {
+            return Err(fdo::Error::InvalidArgs("area must not be empty".to_owned()));
+        }
+
+        // Check that the area intersects at least one monitor.
+        let area = AreaRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let intersects = {
+            let ipc_outputs = self.ipc_outputs.lock().unwrap();
+            ipc_outputs
+                .values()
+                .filter_map(|o| o.logical.as_ref())
+                .any(|l| area.intersects(l))
         };
+        if !intersects {
+            return Err(fdo::Error::Failed(
+                "area is outside of all monitors".to_owned(),
+            ));
+        }
+
+        let path = format!(
+            "/org/gnome/Mutter/ScreenCast/Stream/u{}",
+            STREAM_ID.fetch_add(1, Ordering::SeqCst)
+        );
+        let path = OwnedObjectPath::try_from(path).unwrap();
+
+        let cursor_mode = properties.cursor_mode.unwrap_or_default();
+
+        let target = StreamTarget::Area(area);
+        let stream = Stream::new(target, cursor_mode, self.to_niri.clone());
+        match server.at(&path, stream.clone()).await {
+            Ok(true) => {
+                let iface = server.interface(&path).await.unwrap();
+                self.streams.lock().unwrap().push((stream, iface));
+            }
+            Ok(false) => return Err(fdo::Error::Failed("stream path already exists".to_owned())),
+            Err(err) => {
+                return Err(fdo::Error::Failed(format!(
+                    "error creating stream object: {err:?}"
+                )))
+            }
+        }
+
+        Ok(path)
+    }
+
+    async fn record_virtual(
+        &mut self,
+        #[zbus(object_server)] server: &ObjectServer,
+        properties: RecordVirtualProperties,
+    ) -> fdo::Result<OwnedObjectPath> {
+        debug!(?properties, "record_virtual");
+
+        let path = format!(
+            "/org/gnome/Mutter/ScreenCast/Stream/u{}",
+            STREAM_ID.fetch_add(1, Ordering::SeqCst)
+        );
+        let path = OwnedObjectPath::try_from(path).unwrap();
+
+        let cursor_mode = properties.cursor_mode.unwrap_or_default();
+
+        let target = StreamTarget::Virtual;
         let stream = Stream::new(target, cursor_mode, self.to_niri.clone());
         match server.at(&path, stream.clone()).await {
             Ok(true) => {
@@ -284,7 +425,11 @@ impl Stream {
                     size: (logical.width as i32, logical.height as i32),
                 }
             }
-            StreamTarget::Window { .. } => {
+            StreamTarget::Area(area) => StreamParameters {
+                position: (area.x, area.y),
+                size: (area.width, area.height),
+            },
+            StreamTarget::Window { .. } | StreamTarget::Dynamic | StreamTarget::Virtual => {
                 // Does any consumer need this?
                 StreamParameters {
                     position: (0, 0),
@@ -386,6 +531,18 @@ impl StreamTarget {
                 name: output.name.clone(),
             },
             StreamTarget::Window { id } => StreamTargetId::Window { id: *id },
+            StreamTarget::Area(area) => StreamTargetId::Area(*area),
+            StreamTarget::Dynamic => StreamTargetId::Dynamic,
+            StreamTarget::Virtual => StreamTargetId::Virtual,
         }
     }
 }
+
+impl AreaRect {
+    fn intersects(&self, logical: &niri_ipc::LogicalOutput) -> bool {
+        self.x < logical.x + logical.width as i32
+            && logical.x < self.x + self.width
+            && self.y < logical.y + logical.height as i32
+            && logical.y < self.y + self.height
+    }
+}
@@ -1,13 +1,28 @@
+use std::collections::VecDeque;
 use std::num::NonZeroU64;
 use std::time::Duration;
 
 use crate::utils::get_monotonic_time;
 
+/// Number of recent render times that the render time prediction is based on.
+const RENDER_TIME_SAMPLES: usize = 60;
+
+/// Number of frames to render right away after a frame predicted to make it missed its deadline.
+const MISSED_DEADLINE_FALLBACK_FRAMES: u32 = 60;
+
 #[derive(Debug)]
 pub struct FrameClock {
     last_presentation_time: Option<Duration>,
     refresh_interval_ns: Option<NonZeroU64>,
     vrr: bool,
+    /// Wall time it took to render recent submitted frames.
+    render_times: VecDeque<Duration>,
+    /// Target presentation time of the submitted frame, if it was scheduled with prediction.
+    predicted_target: Option<Duration>,
+    /// Frames left until render time prediction is used again after a missed deadline.
+    fallback_frames_left: u32,
+    delayed_frames: u64,
+    missed_deadlines: u64,
 }
 
 impl FrameClock {
@@ -23,6 +38,11 @@ impl FrameClock {
             last_presentation_time: None,
             refresh_interval_ns,
             vrr,
+            render_times: VecDeque::with_capacity(RENDER_TIME_SAMPLES),
+            predicted_target: None,
+            fallback_frames_left: 0,
+            delayed_frames: 0,
+            missed_deadlines: 0,
         }
     }
 
@@ -45,6 +65,10 @@ impl FrameClock {
     }
 
     pub fn presented(&mut self, presentation_time: Duration) {
+        if let Some(target) = self.predicted_target.take() {
+            self.check_deadline(target, presentation_time);
+        }
+
         if presentation_time.is_zero() {
             // Not interested in these.
             return;
@@ -95,4 +119,137 @@ impl FrameClock {
             last_presentation_time + Duration::from_nanos(to_next_ns)
         }
     }
+
+    /// Records the wall time it took to render a submitted frame.
+    ///
+    /// `predicted_target` is the target presentation time if the frame was scheduled with render
+    /// time prediction. It is checked against the actual presentation time.
+    pub fn frame_rendered(&mut self, render_time: Duration, predicted_target: Option<Duration>) {
+        if self.render_times.len() == RENDER_TIME_SAMPLES {
+            self.render_times.pop_front();
+        }
+        self.render_times.push_back(render_time);
+
+        self.predicted_target = predicted_target;
+        self.fallback_frames_left = self.fallback_frames_left.saturating_sub(1);
+    }
+
+    /// Returns the render time to plan for, the longest among the recent frames.
+    pub fn predicted_render_time(&self) -> Option<Duration> {
+        self.render_times.iter().max().copied()
+    }
+
+    /// Returns when to start rendering the frame for `target_presentation_time`.
+    ///
+    /// Returns `None` when the render time can't be predicted and the frame should be rendered
+    /// right away: with VRR, before the first presentation, or after a missed deadline.
+    pub fn render_start_time(
+        &self,
+        target_presentation_time: Duration,
+        safety_margin: Duration,
+    ) -> Option<Duration> {
+        if self.vrr || self.refresh_interval_ns.is_none() || self.last_presentation_time.is_none() {
+            return None;
+        }
+
+        if self.is_falling_back() {
+            return None;
+        }
+
+        let render_time = self.predicted_render_time()?;
+        Some(target_presentation_time.saturating_sub(render_time + safety_margin))
+    }
+
+    pub fn frame_delayed(&mut self) {
+        self.delayed_frames += 1;
+    }
+
+    pub fn delayed_frames(&self) -> u64 {
+        self.delayed_frames
+    }
+
+    pub fn missed_deadlines(&self) -> u64 {
+        self.missed_deadlines
+    }
+
+    /// Returns whether render time prediction is suspended after a missed deadline.
+    pub fn is_falling_back(&self) -> bool {
+        self.fallback_frames_left > 0
+    }
+
+    fn check_deadline(&mut self, target_presentation_time: Duration, presentation_time: Duration) {
+        let Some(refresh_interval) = self.refresh_interval() else {
+            return;
+        };
+
+        // Presentation times are not exact, so only count frames that landed a whole refresh
+        // cycle late.
+        if presentation_time <= target_presentation_time + refresh_interval / 2 {
+            return;
+        }
+
+        debug!(
+            "missed render deadline by {:?}, rendering right away for the next {} frames",
+            presentation_time - target_presentation_time,
+            MISSED_DEADLINE_FALLBACK_FRAMES
+        );
+        self.missed_deadlines += 1;
+        self.fallback_frames_left = MISSED_DEADLINE_FALLBACK_FRAMES;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: Duration = Duration::from_micros(16_667);
+
+    fn clock() -> FrameClock {
+        let mut clock = FrameClock::new(Some(REFRESH), false);
+        clock.presented(Duration::from_secs(1));
+        clock
+    }
+
+    #[test]
+    fn render_start_time() {
+        let mut clock = clock();
+        let target = Duration::from_secs(1) + REFRESH;
+        let margin = Duration::from_millis(2);
+        assert_eq!(clock.render_start_time(target, margin), None);
+
+        clock.frame_rendered(Duration::from_millis(3), None);
+        clock.frame_rendered(Duration::from_millis(5), None);
+        clock.frame_rendered(Duration::from_millis(4), None);
+        assert_eq!(
+            clock.render_start_time(target, margin),
+            Some(target - Duration::from_millis(7))
+        );
+
+        clock.set_vrr(true);
+        assert_eq!(clock.render_start_time(target, margin), None);
+    }
+
+    #[test]
+    fn fall_back_on_missed_deadline() {
+        let mut clock = clock();
+        let target = Duration::from_secs(1) + REFRESH;
+        let margin = Duration::from_millis(2);
+
+        clock.frame_rendered(Duration::from_millis(3), Some(target));
+        clock.presented(target);
+        assert_eq!(clock.missed_deadlines(), 0);
+        assert!(clock.render_start_time(target + REFRESH, margin).is_some());
+
+        let target = target + REFRESH;
+        clock.frame_rendered(Duration::from_millis(3), Some(target));
+        clock.presented(target + REFRESH);
+        assert_eq!(clock.missed_deadlines(), 1);
+        assert!(clock.is_falling_back());
+        assert_eq!(clock.render_start_time(target + REFRESH * 2, margin), None);
+
+        for _ in 0..MISSED_DEADLINE_FALLBACK_FRAMES {
+            clock.frame_rendered(Duration::from_millis(3), None);
+        }
+        assert!(!clock.is_falling_back());
+    }
 }
@@ -0,0 +1,260 @@
+//! Frame timing statistics of an output, to find the cause of stutter without a profiler.
+
+use std::time::Duration;
+
+use smithay::utils::{Physical, Rectangle, Size};
+
+/// Bucket bounds of the render time histogram, in microseconds.
+const RENDER_TIME_BOUNDS_US: &[u64] = &[500, 1_000, 2_000, 4_000, 8_000, 16_000, 33_000];
+
+/// Bucket bounds of the time to present histogram, in microseconds.
+const TIME_TO_PRESENT_BOUNDS_US: &[u64] = &[4_000, 8_000, 16_000, 33_000, 50_000, 100_000];
+
+/// Bucket bounds of the missed vblanks histogram.
+const MISSED_VBLANKS_BOUNDS: &[u64] = &[0, 1, 2, 3];
+
+/// Bucket bounds of the damage histogram, in percent of the output area.
+const DAMAGE_BOUNDS_PERCENT: &[u64] = &[1, 10, 25, 50, 75];
+
+#[derive(Debug)]
+pub struct FrameStats {
+    /// Number of frames submitted for presentation.
+    frames: u64,
+    /// Number of frames with a client buffer directly scanned out on the primary plane.
+    direct_scanout_frames: u64,
+    render_time_us: Histogram,
+    time_to_present_us: Histogram,
+    missed_vblanks: Histogram,
+    damage_percent: Histogram,
+    /// When the last frame was submitted, if it hasn't been presented yet.
+    submitted_at: Option<Duration>,
+}
+
+/// A histogram with fixed buckets.
+#[derive(Debug)]
+pub struct Histogram {
+    /// Inclusive upper bounds of the buckets, followed by an unbounded bucket.
+    bounds: &'static [u64],
+    counts: Vec<u64>,
+    sum: u64,
+    max: u64,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self {
+            frames: 0,
+            direct_scanout_frames: 0,
+            render_time_us: Histogram::new(RENDER_TIME_BOUNDS_US),
+            time_to_present_us: Histogram::new(TIME_TO_PRESENT_BOUNDS_US),
+            missed_vblanks: Histogram::new(MISSED_VBLANKS_BOUNDS),
+            damage_percent: Histogram::new(DAMAGE_BOUNDS_PERCENT),
+            submitted_at: None,
+        }
+    }
+
+    /// Records a frame submitted for presentation at `now`.
+    pub fn frame_rendered(&mut self, render_time: Duration, now: Duration) {
+        self.frames += 1;
+        self.render_time_us.record(render_time.as_micros() as u64);
+        self.submitted_at = Some(now);
+    }
+
+    /// Records a frame queued to the display with the damage reported by the DRM compositor.
+    pub fn frame_queued(
+        &mut self,
+        direct_scanout: bool,
+        output_size: Size<i32, Physical>,
+        damage: Option<&[Rectangle<i32, Physical>]>,
+    ) {
+        if direct_scanout {
+            self.direct_scanout_frames += 1;
+        }
+
+        if let Some(percent) = damage.and_then(|damage| damage_percent(output_size, damage)) {
+            self.damage_percent.record(percent);
+        }
+    }
+
+    /// Records the presentation of the last submitted frame.
+    pub fn frame_presented(
+        &mut self,
+        presentation_time: Duration,
+        target_presentation_time: Duration,
+        refresh_interval: Duration,
+    ) {
+        if let Some(submitted_at) = self.submitted_at.take() {
+            let time_to_present = presentation_time.saturating_sub(submitted_at);
+            self.time_to_present_us
+                .record(time_to_present.as_micros() as u64);
+        }
+
+        if !refresh_interval.is_zero() {
+            let missed = missed_vblanks(
+                presentation_time,
+                target_presentation_time,
+                refresh_interval,
+            );
+            self.missed_vblanks.record(missed);
+        }
+    }
+
+    pub fn to_ipc(&self) -> niri_ipc::FrameStats {
+        niri_ipc::FrameStats {
+            frames: self.frames,
+            direct_scanout_frames: self.direct_scanout_frames,
+            render_time_us: self.render_time_us.to_ipc(),
+            time_to_present_us: self.time_to_present_us.to_ipc(),
+            missed_vblanks: self.missed_vblanks.to_ipc(),
+            damage_percent: self.damage_percent.to_ipc(),
+        }
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let idx = self.bounds.partition_point(|&bound| bound < value);
+        self.counts[idx] += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn to_ipc(&self) -> niri_ipc::Histogram {
+        let buckets = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(idx, &count)| niri_ipc::HistogramBucket {
+                le: self.bounds.get(idx).copied(),
+                count,
+            })
+            .collect();
+
+        niri_ipc::Histogram {
+            buckets,
+            count: self.count(),
+            sum: self.sum,
+            max: self.max,
+        }
+    }
+}
+
+/// Returns the damaged part of the output, in percent of its area.
+fn damage_percent(
+    output_size: Size<i32, Physical>,
+    damage: &[Rectangle<i32, Physical>],
+) -> Option<u64> {
+    let output_area = i64::from(output_size.w) * i64::from(output_size.h);
+    if output_area <= 0 {
+        return None;
+    }
+
+    // Damage rectangles can overlap, so only count the parts not covered by earlier ones.
+    let output_rect = Rectangle::from_loc_and_size((0, 0), output_size);
+    let mut union: Vec<Rectangle<i32, Physical>> = Vec::new();
+    for rect in damage {
+        let Some(rect) = rect.intersection(output_rect) else {
+            continue;
+        };
+        let new = rect.subtract_rects_many(union.iter().copied());
+        union.extend(new);
+    }
+
+    let damage_area: i64 = union
+        .iter()
+        .map(|rect| i64::from(rect.size.w) * i64::from(rect.size.h))
+        .sum();
+    let percent = damage_area * 100 / output_area;
+    Some(percent as u64)
+}
+
+/// Returns by how many refresh cycles the frame was presented later than its target.
+fn missed_vblanks(
+    presentation_time: Duration,
+    target_presentation_time: Duration,
+    refresh_interval: Duration,
+) -> u64 {
+    let late = presentation_time.saturating_sub(target_presentation_time);
+    // Round to the nearest cycle since presentation times are not exact.
+    let missed = (late + refresh_interval / 2).as_nanos() / refresh_interval.as_nanos();
+    missed as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = Histogram::new(&[1, 10]);
+        for value in [0, 1, 2, 10, 11, 100] {
+            histogram.record(value);
+        }
+
+        assert_eq!(histogram.counts, [2, 2, 2]);
+        assert_eq!(histogram.count(), 6);
+        assert_eq!(histogram.sum, 124);
+        assert_eq!(histogram.max, 100);
+
+        let ipc = histogram.to_ipc();
+        assert_eq!(ipc.buckets[1].le, Some(10));
+        assert_eq!(ipc.buckets[2].le, None);
+    }
+
+    #[test]
+    fn compute_damage_percent() {
+        let size = Size::from((100, 100));
+        let rect = |x, y, w, h| Rectangle::from_loc_and_size((x, y), (w, h));
+
+        assert_eq!(damage_percent(size, &[]), Some(0));
+        assert_eq!(damage_percent(size, &[rect(0, 0, 50, 50)]), Some(25));
+        assert_eq!(
+            damage_percent(size, &[rect(0, 0, 100, 100), rect(10, 10, 20, 20)]),
+            Some(100)
+        );
+        assert_eq!(
+            damage_percent(size, &[rect(0, 0, 50, 50), rect(25, 0, 50, 50)]),
+            Some(37)
+        );
+        assert_eq!(
+            damage_percent(size, &[rect(0, 0, 50, 50), rect(0, 0, 50, 50)]),
+            Some(25)
+        );
+        assert_eq!(damage_percent(size, &[rect(50, 50, 100, 100)]), Some(25));
+        assert_eq!(damage_percent(Size::from((0, 0)), &[]), None);
+    }
+
+    #[test]
+    fn count_missed_vblanks() {
+        let refresh = Duration::from_micros(16_667);
+        let target = Duration::from_secs(1);
+
+        assert_eq!(missed_vblanks(target, target, refresh), 0);
+        assert_eq!(missed_vblanks(target - refresh, target, refresh), 0);
+        assert_eq!(
+            missed_vblanks(target + Duration::from_micros(100), target, refresh),
+            0
+        );
+        assert_eq!(missed_vblanks(target + refresh, target, refresh), 1);
+        assert_eq!(missed_vblanks(target + refresh * 3, target, refresh), 3);
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::hash_map::Entry;
 
+use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::renderer::utils::{on_commit_buffer_handler, with_renderer_surface_state};
 use smithay::input::pointer::{CursorImageStatus, CursorImageSurfaceData};
 use smithay::reexports::calloop::Interest;
@@ -13,12 +14,14 @@ use smithay::wayland::compositor::{
     SurfaceAttributes,
 };
 use smithay::wayland::dmabuf::get_dmabuf;
+use smithay::wayland::drm_syncobj::{DrmSyncPoint, DrmSyncobjCachedState};
 use smithay::wayland::shell::xdg::XdgToplevelSurfaceData;
 use smithay::wayland::shm::{ShmHandler, ShmState};
 use smithay::{delegate_compositor, delegate_shm};
 
 use super::xdg_shell::add_mapped_toplevel_pre_commit_hook;
 use crate::niri::{ClientState, State};
+use crate::protocols::image_copy_capture::ImageCaptureSource;
 use crate::utils::send_scale_transform;
 use crate::utils::transaction::Transaction;
 use crate::window::{InitialConfigureState, Mapped, ResolvedWindowRules, Unmapped};
@@ -219,6 +222,9 @@ impl CompositorHandler for State {
                             id: id.get(),
                         });
 
+                    let source = ImageCaptureSource::Toplevel(surface.clone());
+                    self.niri.image_copy_capture_state.stop_source(&source);
+
                     self.niri.layout.remove_window(&window, transaction.clone());
                     self.add_default_dmabuf_pre_commit_hook(surface);
 
@@ -404,8 +410,8 @@ delegate_shm!(State);
 impl State {
     pub fn add_default_dmabuf_pre_commit_hook(&mut self, surface: &WlSurface) {
         let hook = add_pre_commit_hook::<Self, _>(surface, move |state, _dh, surface| {
-            let maybe_dmabuf = with_states(surface, |surface_data| {
-                surface_data
+            let (maybe_dmabuf, acquire_point) = with_states(surface, |surface_data| {
+                let dmabuf = surface_data
                     .cached_state
                     .get::<SurfaceAttributes>()
                     .pending()
@@ -414,28 +420,17 @@ impl State {
                     .and_then(|assignment| match assignment {
                         BufferAssignment::NewBuffer(buffer) => get_dmabuf(buffer).cloned().ok(),
                         _ => None,
-                    })
+                    });
+                let acquire_point = surface_data
+                    .cached_state
+                    .get::<DrmSyncobjCachedState>()
+                    .pending()
+                    .acquire_point
+                    .clone();
+                (dmabuf, acquire_point)
             });
             if let Some(dmabuf) = maybe_dmabuf {
-                if let Ok((blocker, source)) = dmabuf.generate_blocker(Interest::READ) {
-                    if let Some(client) = surface.client() {
-                        let res =
-                            state
-                                .niri
-                                .event_loop
-                                .insert_source(source, move |_, _, state| {
-                                    let display_handle = state.niri.display_handle.clone();
-                                    state
-                                        .client_compositor_state(&client)
-                                        .blocker_cleared(state, &display_handle);
-                                    Ok(())
-                                });
-                        if res.is_ok() {
-                            add_blocker(surface, blocker);
-                            trace!("added default dmabuf blocker");
-                        }
-                    }
-                }
+                state.add_dmabuf_blocker(surface, &dmabuf, acquire_point, |_| ());
             }
         });
 
@@ -446,6 +441,62 @@ impl State {
         }
     }
 
+    /// Adds a blocker that clears once the pending dmabuf is ready to be read.
+    ///
+    /// Waits for the explicit sync acquire point if the client set one, and for the dmabuf
+    /// implicit fences otherwise. `on_ready` runs right before the blocker is cleared.
+    pub fn add_dmabuf_blocker(
+        &mut self,
+        surface: &WlSurface,
+        dmabuf: &Dmabuf,
+        acquire_point: Option<DrmSyncPoint>,
+        mut on_ready: impl FnMut(&mut State) + 'static,
+    ) {
+        let Some(client) = surface.client() else {
+            return;
+        };
+
+        let mut on_ready = move |state: &mut State| {
+            on_ready(state);
+
+            let display_handle = state.niri.display_handle.clone();
+            state
+                .client_compositor_state(&client)
+                .blocker_cleared(state, &display_handle);
+        };
+
+        if let Some(acquire_point) = acquire_point {
+            if let Ok((blocker, source)) = acquire_point.generate_blocker() {
+                let res = self
+                    .niri
+                    .event_loop
+                    .insert_source(source, move |_, _, state| {
+                        on_ready(state);
+                        Ok(())
+                    });
+                if res.is_ok() {
+                    add_blocker(surface, blocker);
+                    trace!("added acquire point blocker");
+                }
+            }
+            return;
+        }
+
+        if let Ok((blocker, source)) = dmabuf.generate_blocker(Interest::READ) {
+            let res = self
+                .niri
+                .event_loop
+                .insert_source(source, move |_, _, state| {
+                    on_ready(state);
+                    Ok(())
+                });
+            if res.is_ok() {
+                add_blocker(surface, blocker);
+                trace!("added dmabuf blocker");
+            }
+        }
+    }
+
     pub fn remove_default_dmabuf_pre_commit_hook(&mut self, surface: &WlSurface) {
         if let Some(hook) = self.niri.dmabuf_pre_commit_hook.remove(surface) {
             remove_pre_commit_hook(surface, hook);
@@ -19,6 +19,7 @@ use smithay::input::pointer::{
 use smithay::input::{keyboard, Seat, SeatHandler, SeatState};
 use smithay::output::Output;
 use smithay::reexports::rustix::fs::{fcntl_setfl, OFlags};
+use smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1;
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
 use smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1;
 use smithay::reexports::wayland_server::protocol::wl_data_source::WlDataSource;
@@ -31,6 +32,10 @@ use smithay::wayland::dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportN
 use smithay::wayland::drm_lease::{
     DrmLease, DrmLeaseBuilder, DrmLeaseHandler, DrmLeaseRequest, DrmLeaseState, LeaseRejected,
 };
+use smithay::wayland::drm_syncobj::{DrmSyncobjHandler, DrmSyncobjState};
+use smithay::wayland::foreign_toplevel_list::{
+    ForeignToplevelHandle, ForeignToplevelListHandler, ForeignToplevelListState,
+};
 use smithay::wayland::fractional_scale::FractionalScaleHandler;
 use smithay::wayland::idle_inhibit::IdleInhibitHandler;
 use smithay::wayland::idle_notify::{IdleNotifierHandler, IdleNotifierState};
@@ -58,7 +63,8 @@ use smithay::wayland::xdg_activation::{
 };
 use smithay::{
     delegate_cursor_shape, delegate_data_control, delegate_data_device, delegate_dmabuf,
-    delegate_drm_lease, delegate_fractional_scale, delegate_idle_inhibit, delegate_idle_notify,
+    delegate_drm_lease, delegate_drm_syncobj, delegate_foreign_toplevel_list,
+    delegate_fractional_scale, delegate_idle_inhibit, delegate_idle_notify,
     delegate_input_method_manager, delegate_output, delegate_pointer_constraints,
     delegate_pointer_gestures, delegate_presentation, delegate_primary_selection,
     delegate_relative_pointer, delegate_seat, delegate_security_context, delegate_session_lock,
@@ -72,13 +78,21 @@ use crate::protocols::foreign_toplevel::{
     self, ForeignToplevelHandler, ForeignToplevelManagerState,
 };
 use crate::protocols::gamma_control::{GammaControlHandler, GammaControlManagerState};
+use crate::protocols::image_copy_capture::{
+    ImageCaptureSource, ImageCopyCaptureHandler, ImageCopyCaptureState,
+};
 use crate::protocols::mutter_x11_interop::MutterX11InteropHandler;
 use crate::protocols::output_management::{OutputManagementHandler, OutputManagementManagerState};
+use crate::protocols::output_power_management::{
+    OutputPowerManagementHandler, OutputPowerManagementState,
+};
 use crate::protocols::screencopy::{Screencopy, ScreencopyHandler, ScreencopyManagerState};
+use crate::protocols::tearing_control::TearingControlHandler;
 use crate::utils::{output_size, send_scale_transform, with_toplevel_role};
 use crate::{
-    delegate_foreign_toplevel, delegate_gamma_control, delegate_mutter_x11_interop,
-    delegate_output_management, delegate_screencopy,
+    delegate_foreign_toplevel, delegate_gamma_control, delegate_image_copy_capture,
+    delegate_mutter_x11_interop, delegate_output_management, delegate_output_power_management,
+    delegate_screencopy, delegate_tearing_control,
 };
 
 pub const XDG_ACTIVATION_TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
@@ -361,6 +375,14 @@ impl DmabufHandler for State {
 }
 delegate_dmabuf!(State);
 
+impl DrmSyncobjHandler for State {
+    fn drm_syncobj_state(&mut self) -> &mut DrmSyncobjState {
+        // The global is only created together with the state.
+        self.niri.drm_syncobj_state.as_mut().unwrap()
+    }
+}
+delegate_drm_syncobj!(State);
+
 impl SessionLockHandler for State {
     fn lock_state(&mut self) -> &mut SessionLockManagerState {
         &mut self.niri.session_lock_state
@@ -524,6 +546,47 @@ impl ScreencopyHandler for State {
 }
 delegate_screencopy!(State);
 
+impl ForeignToplevelListHandler for State {
+    fn foreign_toplevel_list_state(&mut self) -> &mut ForeignToplevelListState {
+        &mut self.niri.foreign_toplevel_list_state
+    }
+}
+delegate_foreign_toplevel_list!(State);
+
+impl ImageCopyCaptureHandler for State {
+    fn image_copy_capture_state(&mut self) -> &mut ImageCopyCaptureState {
+        &mut self.niri.image_copy_capture_state
+    }
+
+    fn toplevel_for_handle(&mut self, handle: &ExtForeignToplevelHandleV1) -> Option<WlSurface> {
+        let handle = ForeignToplevelHandle::from_resource(handle)?;
+        handle.user_data().get::<WlSurface>().cloned()
+    }
+
+    fn new_session(&mut self, source: &ImageCaptureSource) {
+        // Buffer constraints are sent during the redraw.
+        self.frame_requested(source);
+    }
+
+    fn frame_requested(&mut self, source: &ImageCaptureSource) {
+        let output = match source {
+            ImageCaptureSource::Output(output) => Some(output.clone()),
+            ImageCaptureSource::Toplevel(surface) => self
+                .niri
+                .layout
+                .find_window_and_output(surface)
+                .map(|(_, output)| output.clone()),
+        };
+
+        if let Some(output) = output {
+            if self.niri.output_state.contains_key(&output) {
+                self.niri.queue_redraw(&output);
+            }
+        }
+    }
+}
+delegate_image_copy_capture!(State);
+
 impl DrmLeaseHandler for State {
     fn drm_lease_state(&mut self, node: DrmNode) -> &mut DrmLeaseState {
         self.backend
@@ -604,6 +667,25 @@ impl GammaControlHandler for State {
 }
 delegate_gamma_control!(State);
 
+impl OutputPowerManagementHandler for State {
+    fn output_power_management_state(&mut self) -> &mut OutputPowerManagementState {
+        &mut self.niri.output_power_management_state
+    }
+
+    fn output_power(&mut self, output: &Output) -> Option<bool> {
+        self.niri
+            .output_state
+            .contains_key(output)
+            .then(|| self.niri.is_output_powered(output))
+    }
+
+    fn set_output_powered(&mut self, output: &Output, powered: bool) {
+        self.niri
+            .set_output_powered(&mut self.backend, output, powered);
+    }
+}
+delegate_output_power_management!(State);
+
 impl XdgActivationHandler for State {
     fn activation_state(&mut self) -> &mut XdgActivationState {
         &mut self.niri.activation_state
@@ -662,3 +744,6 @@ delegate_output_management!(State);
 
 impl MutterX11InteropHandler for State {}
 delegate_mutter_x11_interop!(State);
+
+impl TearingControlHandler for State {}
+delegate_tearing_control!(State);
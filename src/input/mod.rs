@@ -1,5 +1,4 @@
 use std::any::Any;
-use std::cmp::min;
 use std::collections::hash_map::Entry;
 use std::collections::HashSet;
 use std::time::Duration;
@@ -343,14 +342,16 @@ impl State {
             self.hide_cursor_if_needed();
         }
 
-        let Some(Some(bind)) = self.niri.seat.get_keyboard().unwrap().input(
+        let hotkey_overlay_was_open = self.niri.hotkey_overlay.is_open();
+        let mut hotkey_overlay_used_key = false;
+
+        let res = self.niri.seat.get_keyboard().unwrap().input(
             self,
             event.key_code(),
             event.state(),
             serial,
             time,
             |this, mods, keysym| {
-                let bindings = &this.niri.config.borrow().binds;
                 let key_code = event.key_code();
                 let modified = keysym.modified_sym();
                 let raw = keysym.raw_latin_sym_or_raw_current_sym();
@@ -362,6 +363,15 @@ impl State {
                     }
                 }
 
+                // Let the hotkey overlay use plain keys for filtering and scrolling.
+                if pressed && this.niri.hotkey_overlay.on_key(modified, *mods) {
+                    this.niri.suppressed_keys.insert(key_code);
+                    hotkey_overlay_used_key = true;
+                    this.niri.queue_redraw_all();
+                    return FilterResult::Intercept(None);
+                }
+
+                let bindings = &this.niri.config.borrow().binds;
                 should_intercept_key(
                     &mut this.niri.suppressed_keys,
                     bindings,
@@ -375,17 +385,23 @@ impl State {
                     this.niri.config.borrow().input.disable_power_key_handling,
                 )
             },
-        ) else {
-            return;
-        };
+        );
 
-        if !pressed {
-            return;
+        if let Some(Some(bind)) = res {
+            if pressed {
+                self.handle_bind(bind.clone());
+                self.start_key_repeat(bind);
+            }
         }
 
-        self.handle_bind(bind.clone());
-
-        self.start_key_repeat(bind);
+        // Do this last so that screenshot still gets it.
+        if pressed
+            && hotkey_overlay_was_open
+            && !hotkey_overlay_used_key
+            && self.niri.hotkey_overlay.hide()
+        {
+            self.niri.queue_redraw_all();
+        }
     }
 
     fn start_key_repeat(&mut self, bind: Bind) {
@@ -508,8 +524,46 @@ impl State {
                 self.niri.deactivate_monitors(&mut self.backend);
             }
             Action::PowerOnMonitors => {
+                // Also power on the monitors that were powered off individually.
+                let outputs: Vec<_> = self
+                    .niri
+                    .output_state
+                    .keys()
+                    .filter(|output| self.niri.is_output_powered_off(output))
+                    .cloned()
+                    .collect();
+                for output in outputs {
+                    self.niri
+                        .set_output_powered(&mut self.backend, &output, true);
+                }
+                // Forget the disconnected ones too, so they power on when they reconnect.
+                self.niri.powered_off_outputs.clear();
+
                 self.niri.activate_monitors(&mut self.backend);
             }
+            Action::PowerOffMonitor(name) => {
+                if let Some(output) = self.niri.output_by_name_match_all(&name).cloned() {
+                    self.niri
+                        .set_output_powered(&mut self.backend, &output, false);
+                }
+            }
+            Action::PowerOnMonitor(name) => {
+                if let Some(output) = self.niri.output_by_name_match_all(&name).cloned() {
+                    self.niri
+                        .set_output_powered(&mut self.backend, &output, true);
+                }
+            }
+            Action::ToggleRotationLock => {
+                let rotation = &mut self.niri.auto_rotation;
+                let locked = !rotation.is_locked();
+                debug!(
+                    "{} automatic rotation",
+                    if locked { "locking" } else { "unlocking" }
+                );
+                if rotation.set_locked(locked) {
+                    self.reload_output_config();
+                }
+            }
             Action::ToggleDebugTint => {
                 self.backend.toggle_debug_tint();
                 self.niri.queue_redraw_all();
@@ -529,29 +583,43 @@ impl State {
                     self.niri.do_screen_transition(renderer, delay_ms);
                 });
             }
-            Action::ScreenshotScreen => {
-                let active = self.niri.layout.active_output().cloned();
-                if let Some(active) = active {
+            action @ (Action::Screenshot(..)
+            | Action::ScreenshotScreen(..)
+            | Action::ScreenshotWindow(..)
+            | Action::ScreenshotWindowById { .. }) => {
+                self.do_screenshot_action(action, None);
+            }
+            Action::ConfirmScreenshot => {
+                if self.niri.screenshot_ui.is_picking() {
+                    // Nothing to save if there's no window under the pointer.
+                    let Some(picked) = self.niri.screenshot_ui.picked().cloned() else {
+                        return;
+                    };
+
+                    let options = self.niri.screenshot_ui.take_options();
                     self.backend.with_primary_renderer(|renderer| {
-                        if let Err(err) = self.niri.screenshot(renderer, &active) {
+                        let res = self
+                            .niri
+                            .screenshot_picked_surface(renderer, &picked, options);
+                        if let Err(err) = res {
                             warn!("error taking screenshot: {err:?}");
                         }
                     });
-                }
-            }
-            Action::ConfirmScreenshot => {
-                self.backend.with_primary_renderer(|renderer| {
-                    match self.niri.screenshot_ui.capture(renderer) {
-                        Ok((size, pixels)) => {
-                            if let Err(err) = self.niri.save_screenshot(size, pixels) {
-                                warn!("error saving screenshot: {err:?}");
+                } else {
+                    let options = self.niri.screenshot_ui.take_options();
+                    self.backend.with_primary_renderer(|renderer| {
+                        match self.niri.screenshot_ui.capture(renderer) {
+                            Ok((size, pixels)) => {
+                                if let Err(err) = self.niri.save_screenshot(size, pixels, options) {
+                                    warn!("error saving screenshot: {err:?}");
+                                }
+                            }
+                            Err(err) => {
+                                warn!("error capturing screenshot: {err:?}");
                             }
                         }
-                        Err(err) => {
-                            warn!("error capturing screenshot: {err:?}");
-                        }
-                    }
-                });
+                    });
+                }
 
                 self.niri.screenshot_ui.close();
                 self.niri
@@ -570,30 +638,14 @@ impl State {
                 self.niri.screenshot_ui.toggle_pointer();
                 self.niri.queue_redraw_all();
             }
-            Action::Screenshot => {
-                self.open_screenshot_ui();
-            }
-            Action::ScreenshotWindow => {
-                let active = self.niri.layout.active_window();
-                if let Some((mapped, output)) = active {
-                    self.backend.with_primary_renderer(|renderer| {
-                        if let Err(err) = self.niri.screenshot_window(renderer, output, mapped) {
-                            warn!("error taking screenshot: {err:?}");
-                        }
-                    });
-                }
-            }
-            Action::ScreenshotWindowById(id) => {
-                let mut windows = self.niri.layout.windows();
-                let window = windows.find(|(_, m)| m.id().get() == id);
-                if let Some((Some(monitor), mapped)) = window {
-                    let output = monitor.output();
-                    self.backend.with_primary_renderer(|renderer| {
-                        if let Err(err) = self.niri.screenshot_window(renderer, output, mapped) {
-                            warn!("error taking screenshot: {err:?}");
-                        }
-                    });
-                }
+            Action::ScreenshotToggleWindowPicking => {
+                self.niri.screenshot_ui.toggle_picking();
+
+                // Highlight the window under the pointer right away.
+                let pos = self.niri.seat.get_pointer().unwrap().current_location();
+                self.niri.screenshot_ui_pointer_motion(pos);
+
+                self.niri.queue_redraw_all();
             }
             Action::CloseWindow => {
                 if let Some(mapped) = self.niri.layout.focus() {
@@ -1297,6 +1349,29 @@ impl State {
                     }
                 }
             }
+            Action::SetDynamicCastWindow => {
+                let id = self.niri.layout.focus().map(|mapped| mapped.id().get());
+                if let Some(id) = id {
+                    self.set_dynamic_cast_window(id);
+                }
+            }
+            Action::SetDynamicCastWindowById(id) => {
+                if self.niri.layout.windows().any(|(_, m)| m.id().get() == id) {
+                    self.set_dynamic_cast_window(id);
+                }
+            }
+            Action::SetDynamicCastMonitor(output) => {
+                let output = match output {
+                    Some(name) => self.niri.output_by_name_match(&name).cloned(),
+                    None => self.niri.layout.active_output().cloned(),
+                };
+                if let Some(output) = output {
+                    self.set_dynamic_cast_monitor(output);
+                }
+            }
+            Action::ClearDynamicCastTarget => {
+                self.clear_dynamic_cast_target();
+            }
         }
     }
 
@@ -1400,19 +1475,8 @@ impl State {
             }
         }
 
-        if let Some(output) = self.niri.screenshot_ui.selection_output() {
-            let geom = self.niri.global_space.output_geometry(output).unwrap();
-            let mut point = (new_pos - geom.loc.to_f64())
-                .to_physical(output.current_scale().fractional_scale())
-                .to_i32_round::<i32>();
-
-            let size = output.current_mode().unwrap().size;
-            let transform = output.current_transform();
-            let size = transform.transform_size(size);
-            point.x = point.x.clamp(0, size.w - 1);
-            point.y = point.y.clamp(0, size.h - 1);
-
-            self.niri.screenshot_ui.pointer_motion(point);
+        if self.niri.screenshot_ui.is_open() {
+            self.niri.screenshot_ui_pointer_motion(new_pos);
         }
 
         let under = self.niri.contents_under(new_pos);
@@ -1499,19 +1563,8 @@ impl State {
 
         let pointer = self.niri.seat.get_pointer().unwrap();
 
-        if let Some(output) = self.niri.screenshot_ui.selection_output() {
-            let geom = self.niri.global_space.output_geometry(output).unwrap();
-            let mut point = (pos - geom.loc.to_f64())
-                .to_physical(output.current_scale().fractional_scale())
-                .to_i32_round::<i32>();
-
-            let size = output.current_mode().unwrap().size;
-            let transform = output.current_transform();
-            let size = transform.transform_size(size);
-            point.x = point.x.clamp(0, size.w - 1);
-            point.y = point.y.clamp(0, size.h - 1);
-
-            self.niri.screenshot_ui.pointer_motion(point);
+        if self.niri.screenshot_ui.is_open() {
+            self.niri.screenshot_ui_pointer_motion(pos);
         }
 
         let under = self.niri.contents_under(pos);
@@ -1705,26 +1758,17 @@ impl State {
 
         if let Some(button) = event.button() {
             let pos = pointer.current_location();
-            if let Some((output, _)) = self.niri.output_under(pos) {
-                let output = output.clone();
-                let geom = self.niri.global_space.output_geometry(&output).unwrap();
-                let mut point = (pos - geom.loc.to_f64())
-                    .to_physical(output.current_scale().fractional_scale())
-                    .to_i32_round();
-
-                let size = output.current_mode().unwrap().size;
-                let transform = output.current_transform();
-                let size = transform.transform_size(size);
-                point.x = min(size.w - 1, point.x);
-                point.y = min(size.h - 1, point.y);
-
-                if self
-                    .niri
-                    .screenshot_ui
-                    .pointer_button(output, point, button, button_state)
-                {
-                    self.niri.queue_redraw_all();
-                }
+            if self.niri.screenshot_ui.is_picking() {
+                // In the window picking mode, a click saves the window under the pointer.
+                if button == MouseButton::Left && button_state == ButtonState::Pressed {
+                    self.do_action(Action::ConfirmScreenshot, false);
+                }
+            } else if self
+                .niri
+                .screenshot_ui
+                .pointer_button(pos, button, button_state)
+            {
+                self.niri.queue_redraw_all();
             }
         }
 
@@ -2440,7 +2484,28 @@ impl State {
         };
 
         if let Some(action) = action {
-            self.do_action(action, true);
+            // Switch events always spawn, even when the session is locked.
+            let allow_when_locked = matches!(action, Action::Spawn(_));
+            self.do_action(action, allow_when_locked);
+        }
+
+        if matches!(switch, Switch::Lid) {
+            let is_closed = evt.state() == SwitchState::On;
+            if self.niri.lid_closed == is_closed {
+                return;
+            }
+
+            debug!("lid {}", if is_closed { "closed" } else { "opened" });
+            self.niri.lid_closed = is_closed;
+
+            // Turn the laptop panel off or on if there's an external monitor.
+            self.reload_output_config();
+
+            let suspend = self.niri.config.borrow().clamshell.suspend_when_undocked;
+            if is_closed && suspend && !self.backend.has_external_monitor() {
+                info!("suspending because the lid closed without external monitors");
+                self.backend.suspend();
+            }
         }
     }
 }
@@ -2500,6 +2565,7 @@ fn should_intercept_key(
                     repeat: true,
                     cooldown: None,
                     allow_when_locked: false,
+                    hotkey_overlay_title: None,
                 });
             }
         }
@@ -2550,6 +2616,7 @@ fn find_bind(
             repeat: true,
             cooldown: None,
             allow_when_locked: false,
+            hotkey_overlay_title: None,
         });
     }
 
@@ -2608,7 +2675,7 @@ fn find_configured_switch_action(
     };
     switch_action
         .as_ref()
-        .map(|switch_action| Action::Spawn(switch_action.spawn.clone()))
+        .map(|switch_action| switch_action.action.clone())
 }
 
 fn modifiers_from_state(mods: ModifiersState) -> Modifiers {
@@ -2656,8 +2723,8 @@ fn should_activate_monitors<I: InputBackend>(event: &InputEvent<I>) -> bool {
 }
 
 fn should_hide_hotkey_overlay<I: InputBackend>(event: &InputEvent<I>) -> bool {
+    // Key presses are handled in on_keyboard() since the overlay can use them for filtering.
     match event {
-        InputEvent::Keyboard { event } if event.state() == KeyState::Pressed => true,
         InputEvent::PointerButton { event } if event.state() == ButtonState::Pressed => true,
         InputEvent::PointerAxis { .. }
         | InputEvent::GestureSwipeBegin { .. }
@@ -2714,6 +2781,9 @@ fn allowed_when_locked(action: &Action) -> bool {
             | Action::Suspend
             | Action::PowerOffMonitors
             | Action::PowerOnMonitors
+            | Action::PowerOffMonitor(_)
+            | Action::PowerOnMonitor(_)
+            | Action::ToggleRotationLock
             | Action::SwitchLayout(_)
     )
 }
@@ -2726,6 +2796,8 @@ fn allowed_during_screenshot(action: &Action) -> bool {
             | Action::Suspend
             | Action::PowerOffMonitors
             | Action::PowerOnMonitors
+            | Action::PowerOffMonitor(_)
+            | Action::PowerOnMonitor(_)
     )
 }
 
@@ -2997,6 +3069,7 @@ mod tests {
             repeat: true,
             cooldown: None,
             allow_when_locked: false,
+            hotkey_overlay_title: None,
         }]);
 
         let comp_mod = CompositorMod::Super;
@@ -3131,6 +3204,7 @@ mod tests {
                 repeat: true,
                 cooldown: None,
                 allow_when_locked: false,
+                hotkey_overlay_title: None,
             },
             Bind {
                 key: Key {
@@ -3141,6 +3215,7 @@ mod tests {
                 repeat: true,
                 cooldown: None,
                 allow_when_locked: false,
+                hotkey_overlay_title: None,
             },
             Bind {
                 key: Key {
@@ -3151,6 +3226,7 @@ mod tests {
                 repeat: true,
                 cooldown: None,
                 allow_when_locked: false,
+                hotkey_overlay_title: None,
             },
             Bind {
                 key: Key {
@@ -3161,6 +3237,7 @@ mod tests {
                 repeat: true,
                 cooldown: None,
                 allow_when_locked: false,
+                hotkey_overlay_title: None,
             },
             Bind {
                 key: Key {
@@ -3171,6 +3248,7 @@ mod tests {
                 repeat: true,
                 cooldown: None,
                 allow_when_locked: false,
+                hotkey_overlay_title: None,
             },
         ]);
 
@@ -2,8 +2,9 @@ use anyhow::{anyhow, bail, Context};
 use niri_config::OutputName;
 use niri_ipc::socket::Socket;
 use niri_ipc::{
-    Event, KeyboardLayouts, LogicalOutput, Mode, Output, OutputConfigChanged, Request, Response,
-    Transform, Window,
+    BroadcastRgb, Colorimetry, Eotf, Event, FrameStats, FramebufferFormat, HdrMetadata, Histogram,
+    KeyboardLayouts, LogicalOutput, Mode, Output, OutputColor, OutputConfigChanged,
+    RenderTimeStats, Request, Response, Transform, Window,
 };
 use serde_json::json;
 
@@ -21,6 +22,14 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
             output: output.clone(),
             action: action.clone(),
         },
+        Msg::CreateVirtualOutput { mode } => Request::CreateVirtualOutput { mode: *mode },
+        Msg::DestroyVirtualOutput { output } => Request::DestroyVirtualOutput {
+            output: output.clone(),
+        },
+        Msg::RenderTimes => Request::RenderTimes,
+        Msg::FrameStats { output } => Request::FrameStats {
+            output: output.clone(),
+        },
         Msg::Workspaces => Request::Workspaces,
         Msg::Windows => Request::Windows,
         Msg::KeyboardLayouts => Request::KeyboardLayouts,
@@ -185,11 +194,21 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
                 println!("No output is focused.");
             }
         }
-        Msg::Action { .. } => {
-            let Response::Handled = response else {
-                bail!("unexpected response: expected Handled, got {response:?}");
-            };
-        }
+        Msg::Action { .. } => match response {
+            Response::Handled => (),
+            Response::ScreenshotSaved(path) => {
+                if json {
+                    let path = serde_json::to_string(&path).context("error formatting response")?;
+                    println!("{path}");
+                    return Ok(());
+                }
+
+                if let Some(path) = path {
+                    println!("Screenshot saved to {path}");
+                }
+            }
+            response => bail!("unexpected response: expected Handled, got {response:?}"),
+        },
         Msg::Output { output, .. } => {
             let Response::OutputConfigChanged(response) = response else {
                 bail!("unexpected response: expected OutputConfigChanged, got {response:?}");
@@ -207,6 +226,62 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
                 println!("The change will apply when it is connected.");
             }
         }
+        Msg::CreateVirtualOutput { .. } => {
+            let Response::VirtualOutputCreated(name) = response else {
+                bail!("unexpected response: expected VirtualOutputCreated, got {response:?}");
+            };
+
+            if json {
+                let name = serde_json::to_string(&name).context("error formatting response")?;
+                println!("{name}");
+                return Ok(());
+            }
+
+            println!("Created virtual output \"{name}\".");
+        }
+        Msg::DestroyVirtualOutput { .. } => {
+            let Response::Handled = response else {
+                bail!("unexpected response: expected Handled, got {response:?}");
+            };
+        }
+        Msg::RenderTimes => {
+            let Response::RenderTimes(stats) = response else {
+                bail!("unexpected response: expected RenderTimes, got {response:?}");
+            };
+
+            if json {
+                let stats = serde_json::to_string(&stats).context("error formatting response")?;
+                println!("{stats}");
+                return Ok(());
+            }
+
+            let mut stats = stats.into_iter().collect::<Vec<_>>();
+            stats.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+            for (name, stats) in stats {
+                print_render_time_stats(&name, stats);
+                println!();
+            }
+        }
+        Msg::FrameStats { .. } => {
+            let Response::FrameStats(stats) = response else {
+                bail!("unexpected response: expected FrameStats, got {response:?}");
+            };
+
+            if json {
+                let stats = serde_json::to_string(&stats).context("error formatting response")?;
+                println!("{stats}");
+                return Ok(());
+            }
+
+            let mut stats = stats.into_iter().collect::<Vec<_>>();
+            stats.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+            for (name, stats) in stats {
+                print_frame_stats(&name, stats);
+                println!();
+            }
+        }
         Msg::Workspaces => {
             let Response::Workspaces(mut response) = response else {
                 bail!("unexpected response: expected Workspaces, got {response:?}");
@@ -329,6 +404,15 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
                     Event::KeyboardLayoutSwitched { idx } => {
                         println!("Keyboard layout switched: {idx}");
                     }
+                    Event::CastStarted { cast } => {
+                        println!("Cast started or changed: {cast:?}");
+                    }
+                    Event::CastStopped { id } => {
+                        println!("Cast stopped: {id}");
+                    }
+                    Event::OutputProfileChanged { name } => {
+                        println!("Output profile changed: {name:?}");
+                    }
                 }
             }
         }
@@ -337,6 +421,153 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn print_output_color(color: OutputColor) {
+    let OutputColor {
+        max_bpc,
+        framebuffer_format,
+        broadcast_rgb,
+        colorimetry,
+        hdr_supported,
+        hdr_metadata,
+    } = color;
+
+    if let Some(max_bpc) = max_bpc {
+        println!("  Max bpc: {max_bpc}");
+    }
+
+    let framebuffer_format = match framebuffer_format {
+        FramebufferFormat::Xrgb8888 => "8-bit",
+        FramebufferFormat::Xrgb2101010 => "10-bit",
+    };
+    println!("  Framebuffer format: {framebuffer_format}");
+
+    if let Some(broadcast_rgb) = broadcast_rgb {
+        let broadcast_rgb = match broadcast_rgb {
+            BroadcastRgb::Automatic => "automatic",
+            BroadcastRgb::Full => "full",
+            BroadcastRgb::Limited => "limited",
+        };
+        println!("  Broadcast RGB: {broadcast_rgb}");
+    }
+
+    if let Some(colorimetry) = colorimetry {
+        let colorimetry = match colorimetry {
+            Colorimetry::Default => "default",
+            Colorimetry::Bt709Ycc => "BT.709 YCbCr",
+            Colorimetry::Bt2020Rgb => "BT.2020 RGB",
+            Colorimetry::Bt2020Ycc => "BT.2020 YCbCr",
+            Colorimetry::DciP3RgbD65 => "DCI-P3 RGB D65",
+            Colorimetry::OpRgb => "opRGB",
+        };
+        println!("  Colorimetry: {colorimetry}");
+    }
+
+    if let Some(metadata) = hdr_metadata {
+        let HdrMetadata {
+            eotf,
+            max_luminance,
+            min_luminance,
+            max_cll,
+            max_fall,
+        } = metadata;
+        let eotf = match eotf {
+            Eotf::TraditionalSdr => "traditional SDR",
+            Eotf::TraditionalHdr => "traditional HDR",
+            Eotf::Pq => "PQ",
+            Eotf::Hlg => "HLG",
+        };
+        println!(
+            "  HDR metadata: {eotf}, luminance {min_luminance}-{max_luminance} cd/m², \
+             max CLL {max_cll} cd/m², max FALL {max_fall} cd/m²"
+        );
+    } else if hdr_supported {
+        println!("  HDR metadata: supported, not set");
+    } else {
+        println!("  HDR metadata: not supported");
+    }
+}
+
+fn print_render_time_stats(name: &str, stats: RenderTimeStats) {
+    let RenderTimeStats {
+        safety_margin_ms,
+        predicted_render_time_us,
+        delayed_frames,
+        missed_deadlines,
+        falling_back,
+    } = stats;
+
+    println!(r#"Output "{name}""#);
+
+    if let Some(margin) = safety_margin_ms {
+        println!("  Render time prediction: enabled, {margin} ms safety margin");
+    } else {
+        println!("  Render time prediction: disabled");
+    }
+
+    if let Some(us) = predicted_render_time_us {
+        println!("  Predicted render time: {:.3} ms", us as f64 / 1000.);
+    } else {
+        println!("  Predicted render time: unknown");
+    }
+
+    println!("  Delayed frames: {delayed_frames}");
+
+    let falling_back = if falling_back {
+        " (rendering right away)"
+    } else {
+        ""
+    };
+    println!("  Missed deadlines: {missed_deadlines}{falling_back}");
+}
+
+fn print_frame_stats(name: &str, stats: FrameStats) {
+    let FrameStats {
+        frames,
+        direct_scanout_frames,
+        render_time_us,
+        time_to_present_us,
+        missed_vblanks,
+        damage_percent,
+    } = stats;
+
+    println!(r#"Output "{name}""#);
+    println!("  Frames: {frames}, directly scanned out: {direct_scanout_frames}");
+
+    let ms = |us: u64| format!("{:.1} ms", us as f64 / 1000.);
+    print_histogram("Render time", render_time_us, ms);
+    print_histogram("Time to present", time_to_present_us, ms);
+    print_histogram("Missed vblanks", missed_vblanks, |x| x.to_string());
+    print_histogram("Damage", damage_percent, |x| format!("{x}%"));
+}
+
+fn print_histogram(title: &str, histogram: Histogram, fmt_value: impl Fn(u64) -> String) {
+    let Histogram {
+        buckets,
+        count,
+        sum,
+        max,
+    } = histogram;
+
+    if count == 0 {
+        println!("  {title}: no data");
+        return;
+    }
+
+    let mean = fmt_value(sum / count);
+    let max = fmt_value(max);
+    println!("  {title}: mean {mean}, max {max}");
+
+    let mut last_bound = None;
+    for bucket in buckets {
+        let range = match bucket.le {
+            Some(le) => format!("<= {}", fmt_value(le)),
+            None => format!("> {}", fmt_value(last_bound.unwrap_or(0))),
+        };
+        println!("    {range}: {}", bucket.count);
+        last_bound = bucket.le;
+    }
+}
+
 fn print_output(output: Output) -> anyhow::Result<()> {
     let Output {
         name,
@@ -348,6 +579,8 @@ fn print_output(output: Output) -> anyhow::Result<()> {
         current_mode,
         vrr_supported,
         vrr_enabled,
+        powered,
+        color,
         logical,
     } = output;
 
@@ -371,6 +604,10 @@ fn print_output(output: Output) -> anyhow::Result<()> {
         println!("  Disabled");
     }
 
+    if current_mode.is_some() && !powered {
+        println!("  Powered off");
+    }
+
     if vrr_supported {
         let enabled = if vrr_enabled { "enabled" } else { "disabled" };
         println!("  Variable refresh rate: supported, {enabled}");
@@ -378,6 +615,10 @@ fn print_output(output: Output) -> anyhow::Result<()> {
         println!("  Variable refresh rate: not supported");
     }
 
+    if let Some(color) = color {
+        print_output_color(color);
+    }
+
     if let Some((width, height)) = physical_size {
         println!("  Physical size: {width}x{height} mm");
     } else {
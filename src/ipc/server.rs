@@ -15,7 +15,9 @@ use futures_util::io::{AsyncReadExt, BufReader};
 use futures_util::{select_biased, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, FutureExt as _};
 use niri_config::OutputName;
 use niri_ipc::state::{EventStreamState, EventStreamStatePart as _};
-use niri_ipc::{Event, KeyboardLayouts, OutputConfigChanged, Reply, Request, Response, Workspace};
+use niri_ipc::{
+    Action, Event, KeyboardLayouts, OutputConfigChanged, Reply, Request, Response, Workspace,
+};
 use smithay::reexports::calloop::generic::Generic;
 use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
 use smithay::reexports::rustix::fs::unlink;
@@ -267,6 +269,16 @@ async fn process(ctx: &ClientCtx, request: Request) -> Reply {
             Response::FocusedWindow(window)
         }
         Request::Action(action) => {
+            let is_screenshot = matches!(
+                action,
+                Action::Screenshot { .. }
+                    | Action::ScreenshotScreen { .. }
+                    | Action::ScreenshotWindow { .. }
+            );
+            if is_screenshot {
+                return screenshot(ctx, action).await;
+            }
+
             let (tx, rx) = async_channel::bounded(1);
 
             let action = niri_config::Action::from(action);
@@ -325,12 +337,88 @@ async fn process(ctx: &ClientCtx, request: Request) -> Reply {
             let output = result.map_err(|_| String::from("error getting active output info"))?;
             Response::FocusedOutput(output)
         }
+        Request::CreateVirtualOutput { mode } => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let res = state.backend.create_virtual_output(&mut state.niri, mode);
+                let _ = tx.send_blocking(res);
+            });
+
+            let result = rx.recv().await;
+            let result = result.map_err(|_| String::from("error creating virtual output"))?;
+            let name = result.map_err(|err| err.to_string())?;
+            Response::VirtualOutputCreated(name)
+        }
+        Request::DestroyVirtualOutput { output } => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let res = state
+                    .backend
+                    .destroy_virtual_output(&mut state.niri, &output);
+                let _ = tx.send_blocking(res);
+            });
+
+            let result = rx.recv().await;
+            let result = result.map_err(|_| String::from("error destroying virtual output"))?;
+            result.map_err(|err| err.to_string())?;
+            Response::Handled
+        }
+        Request::RenderTimes => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let stats = state.niri.render_time_stats();
+                let _ = tx.send_blocking(stats);
+            });
+            let result = rx.recv().await;
+            let stats = result.map_err(|_| String::from("error getting render time stats"))?;
+            Response::RenderTimes(stats)
+        }
+        Request::FrameStats { output } => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let stats = state.niri.frame_stats();
+                let _ = tx.send_blocking(stats);
+            });
+            let result = rx.recv().await;
+            let mut stats = result.map_err(|_| String::from("error getting frame stats"))?;
+
+            if let Some(output) = output {
+                stats.retain(|name, _| name.eq_ignore_ascii_case(&output));
+                if stats.is_empty() {
+                    return Err(format!("output \"{output}\" is not connected"));
+                }
+            }
+
+            Response::FrameStats(stats)
+        }
         Request::EventStream => Response::Handled,
     };
 
     Ok(response)
 }
 
+async fn screenshot(ctx: &ClientCtx, action: Action) -> Reply {
+    let (tx, rx) = async_channel::bounded(1);
+
+    let action = niri_config::Action::from(action);
+    ctx.event_loop.insert_idle(move |state| {
+        let on_saved = Box::new(move |res: anyhow::Result<Option<PathBuf>>| {
+            let _ = tx.send_blocking(res);
+        });
+        state.do_screenshot_action(action, Some(on_saved));
+    });
+
+    // Wait until the screenshot is saved. This takes a while with a delay or with the screenshot
+    // UI, which waits for the user to confirm the selection.
+    let res = rx
+        .recv()
+        .await
+        .map_err(|_| String::from("the screenshot was not saved"))?;
+    let path = res.map_err(|err| format!("{err:#}"))?;
+    let path = path.map(|path| path.to_string_lossy().into_owned());
+    Ok(Response::ScreenshotSaved(path))
+}
+
 async fn handle_event_stream_client(client: EventStreamClient) -> anyhow::Result<()> {
     let EventStreamClient {
         events,
@@ -585,4 +673,55 @@ impl State {
             server.send_event(event);
         }
     }
+
+    pub fn ipc_refresh_casts(&mut self) {
+        let Some(server) = &self.niri.ipc_server else {
+            return;
+        };
+
+        let _span = tracy_client::span!("State::ipc_refresh_casts");
+
+        let mut state = server.event_stream_state.borrow_mut();
+        let state = &mut state.casts;
+
+        let mut events = Vec::new();
+
+        // Check for new and changed casts.
+        for cast in &self.niri.active_casts {
+            if state.casts.get(&cast.id) != Some(cast) {
+                events.push(Event::CastStarted { cast: cast.clone() });
+            }
+        }
+
+        // Check for stopped casts.
+        for id in state.casts.keys() {
+            if !self.niri.active_casts.iter().any(|cast| cast.id == *id) {
+                events.push(Event::CastStopped { id: *id });
+            }
+        }
+
+        for event in events {
+            state.apply(event.clone());
+            server.send_event(event);
+        }
+    }
+
+    pub fn ipc_refresh_output_profile(&mut self) {
+        let Some(server) = &self.niri.ipc_server else {
+            return;
+        };
+
+        let mut state = server.event_stream_state.borrow_mut();
+        let state = &mut state.output_profile;
+
+        if state.name == self.niri.output_profile {
+            return;
+        }
+
+        let event = Event::OutputProfileChanged {
+            name: self.niri.output_profile.clone(),
+        };
+        state.apply(event.clone());
+        server.send_event(event);
+    }
 }
@@ -247,6 +247,9 @@ pub trait LayoutElement {
     fn cancel_interactive_resize(&mut self);
     fn update_interactive_resize(&mut self, serial: Serial);
     fn interactive_resize_data(&self) -> Option<InteractiveResizeData>;
+
+    /// Whether the element is being screencast or captured by a client.
+    fn is_being_captured(&self) -> bool;
 }
 
 #[derive(Debug)]
@@ -291,6 +294,7 @@ pub struct Options {
     pub focus_ring: niri_config::FocusRing,
     pub border: niri_config::Border,
     pub insert_hint: niri_config::InsertHint,
+    pub capture_indicator: niri_config::CaptureIndicator,
     pub center_focused_column: CenterFocusedColumn,
     pub always_center_single_column: bool,
     /// Column widths that `toggle_width()` switches between.
@@ -313,6 +317,7 @@ impl Default for Options {
             focus_ring: Default::default(),
             border: Default::default(),
             insert_hint: Default::default(),
+            capture_indicator: Default::default(),
             center_focused_column: Default::default(),
             always_center_single_column: false,
             preset_column_widths: vec![
@@ -401,6 +406,7 @@ impl Options {
             focus_ring: layout.focus_ring,
             border: layout.border,
             insert_hint: layout.insert_hint,
+            capture_indicator: config.capture_indicator,
             center_focused_column: layout.center_focused_column,
             always_center_single_column: layout.always_center_single_column,
             preset_column_widths,
@@ -418,6 +424,8 @@ impl Options {
         self.gaps = round(self.gaps);
         self.focus_ring.width = FloatOrInt(round(self.focus_ring.width.0));
         self.border.width = FloatOrInt(round(self.border.width.0));
+        self.capture_indicator.border_width =
+            FloatOrInt(round(self.capture_indicator.border_width.0));
 
         self
     }
@@ -3757,6 +3765,10 @@ mod tests {
         fn interactive_resize_data(&self) -> Option<InteractiveResizeData> {
             None
         }
+
+        fn is_being_captured(&self) -> bool {
+            false
+        }
     }
 
     fn arbitrary_bbox() -> impl Strategy<Value = Rectangle<i32, Logical>> {
@@ -4127,6 +4139,7 @@ mod tests {
                     layout.ensure_named_workspace(&WorkspaceConfig {
                         name: WorkspaceName(format!("ws{ws_name}")),
                         open_on_output: output_name.map(|name| format!("output{name}")),
+                        background_image: None,
                     });
                 }
                 Op::UnnameWorkspace { ws_name } => {
@@ -40,6 +40,9 @@ pub struct Tile<W: LayoutElement> {
     /// currently here.
     focus_ring: FocusRing,
 
+    /// The capture indicator around the window, shown while it's being captured.
+    capture_ring: FocusRing,
+
     /// Whether this tile is fullscreen.
     ///
     /// This will update only when the `window` actually goes fullscreen, rather than right away,
@@ -119,6 +122,7 @@ impl<W: LayoutElement> Tile<W> {
             window,
             border: FocusRing::new(border_config.into()),
             focus_ring: FocusRing::new(focus_ring_config.into()),
+            capture_ring: FocusRing::new(capture_ring_config(&options)),
             is_fullscreen: false, // FIXME: up-to-date fullscreen right away, but we need size.
             fullscreen_backdrop: SolidColorBuffer::new((0., 0.), [0., 0., 0., 1.]),
             fullscreen_size: Default::default(),
@@ -147,11 +151,15 @@ impl<W: LayoutElement> Tile<W> {
             .focus_ring
             .resolve_against(self.options.focus_ring.into());
         self.focus_ring.update_config(focus_ring_config.into());
+
+        self.capture_ring
+            .update_config(capture_ring_config(&self.options));
     }
 
     pub fn update_shaders(&mut self) {
         self.border.update_shaders();
         self.focus_ring.update_shaders();
+        self.capture_ring.update_shaders();
     }
 
     pub fn update_window(&mut self) {
@@ -277,20 +285,28 @@ impl<W: LayoutElement> Tile<W> {
         } else {
             draw_border_with_background
         };
-        let radius = if self.is_fullscreen {
+        let tile_radius = if self.is_fullscreen {
             CornerRadius::default()
         } else if self.effective_border_width().is_some() {
             radius
         } else {
             rules.geometry_corner_radius.unwrap_or_default()
-        }
-        .expanded_by(self.focus_ring.width() as f32);
+        };
         self.focus_ring.update_render_elements(
             self.animated_tile_size(),
             is_active,
             !draw_focus_ring_with_background,
             view_rect,
-            radius,
+            tile_radius.expanded_by(self.focus_ring.width() as f32),
+            self.scale,
+        );
+
+        self.capture_ring.update_render_elements(
+            self.animated_tile_size(),
+            true,
+            true,
+            view_rect,
+            tile_radius.expanded_by(self.capture_ring.width() as f32),
             self.scale,
         );
     }
@@ -824,7 +840,16 @@ impl<W: LayoutElement> Tile<W> {
         let rv = rv.chain(elem.into_iter().flatten());
 
         let elem = focus_ring.then(|| self.focus_ring.render(renderer, location).map(Into::into));
-        rv.chain(elem.into_iter().flatten())
+        let rv = rv.chain(elem.into_iter().flatten());
+
+        // The capture indicator is for the user, so it's excluded from the capture itself.
+        let show_capture_ring = target == RenderTarget::Output
+            && self.window.is_being_captured()
+            && !self.capture_ring.is_off();
+        let elem =
+            show_capture_ring.then(|| self.capture_ring.render(renderer, location).map(Into::into));
+        // Put it in front of the focus ring.
+        elem.into_iter().flatten().chain(rv)
     }
 
     pub fn render<R: NiriRenderer>(
@@ -917,3 +942,15 @@ impl<W: LayoutElement> Tile<W> {
         self.unmap_snapshot.take()
     }
 }
+
+fn capture_ring_config(options: &Options) -> niri_config::FocusRing {
+    let config = options.capture_indicator;
+    niri_config::FocusRing {
+        off: config.off,
+        width: config.border_width,
+        active_color: config.color,
+        inactive_color: config.color,
+        active_gradient: None,
+        inactive_gradient: None,
+    }
+}
@@ -8,6 +8,7 @@ pub mod cursor;
 #[cfg(feature = "dbus")]
 pub mod dbus;
 pub mod frame_clock;
+pub mod frame_stats;
 pub mod handlers;
 pub mod input;
 pub mod ipc;
@@ -12,6 +12,7 @@ use std::{env, mem};
 use clap::Parser;
 use directories::ProjectDirs;
 use niri::animation;
+use niri::backend::headless::HeadlessOptions;
 use niri::cli::{Cli, Sub};
 #[cfg(feature = "dbus")]
 use niri::dbus;
@@ -176,6 +177,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     store_and_increase_nofile_rlimit();
 
+    let headless = cli.headless.map(|outputs| HeadlessOptions {
+        outputs,
+        mode: cli.headless_mode,
+    });
+
     // Create the compositor.
     let mut event_loop = EventLoop::try_new().unwrap();
     let display = Display::new().unwrap();
@@ -184,6 +190,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         event_loop.handle(),
         event_loop.get_signal(),
         display,
+        headless,
     )
     .unwrap();
 
@@ -13,8 +13,8 @@ use _server_decoration::server::org_kde_kwin_server_decoration_manager::Mode as
 use anyhow::{bail, ensure, Context};
 use calloop::futures::Scheduler;
 use niri_config::{
-    Config, FloatOrInt, Key, Modifiers, OutputName, PreviewRender, TrackLayout, WorkspaceReference,
-    DEFAULT_BACKGROUND_COLOR,
+    Action, Config, FloatOrInt, Key, Modifiers, OutputName, OutputProfile, PreviewRender,
+    TrackLayout, WorkspaceReference, DEFAULT_BACKGROUND_COLOR,
 };
 use smithay::backend::allocator::Fourcc;
 use smithay::backend::input::Keycode;
@@ -25,11 +25,11 @@ use smithay::backend::renderer::element::surface::{
     render_elements_from_surface_tree, WaylandSurfaceRenderElement,
 };
 use smithay::backend::renderer::element::utils::{
-    select_dmabuf_feedback, Relocate, RelocateRenderElement,
+    select_dmabuf_feedback, Relocate, RelocateRenderElement, RescaleRenderElement,
 };
 use smithay::backend::renderer::element::{
     default_primary_scanout_output_compare, AsRenderElements, Element as _, Id, Kind,
-    PrimaryScanoutOutput, RenderElementStates,
+    PrimaryScanoutOutput, RenderElement, RenderElementStates,
 };
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::backend::renderer::sync::SyncPoint;
@@ -73,6 +73,8 @@ use smithay::wayland::compositor::{
 };
 use smithay::wayland::cursor_shape::CursorShapeManagerState;
 use smithay::wayland::dmabuf::DmabufState;
+use smithay::wayland::drm_syncobj::DrmSyncobjState;
+use smithay::wayland::foreign_toplevel_list::ForeignToplevelListState;
 use smithay::wayland::fractional_scale::FractionalScaleManagerState;
 use smithay::wayland::idle_inhibit::IdleInhibitManagerState;
 use smithay::wayland::idle_notify::IdleNotifierState;
@@ -100,16 +102,18 @@ use smithay::wayland::virtual_keyboard::VirtualKeyboardManagerState;
 use smithay::wayland::xdg_activation::XdgActivationState;
 use smithay::wayland::xdg_foreign::XdgForeignState;
 
+use crate::backend::headless::HeadlessOptions;
 use crate::backend::tty::SurfaceDmabufFeedback;
-use crate::backend::{Backend, RenderResult, Tty, Winit};
+use crate::backend::{Backend, Headless, RenderResult, Tty, Winit};
 use crate::cursor::{CursorManager, CursorTextureCache, RenderCursor, XCursor};
 #[cfg(feature = "dbus")]
 use crate::dbus::gnome_shell_introspect::{self, IntrospectToNiri, NiriToIntrospect};
 #[cfg(feature = "dbus")]
 use crate::dbus::gnome_shell_screenshot::{NiriToScreenshot, ScreenshotToNiri};
 #[cfg(feature = "xdp-gnome-screencast")]
-use crate::dbus::mutter_screen_cast::{self, ScreenCastToNiri};
+use crate::dbus::mutter_screen_cast::{self, CursorMode, ScreenCastToNiri};
 use crate::frame_clock::FrameClock;
+use crate::frame_stats::FrameStats;
 use crate::handlers::{configure_lock_surface, XDG_ACTIVATION_TOKEN_TIMEOUT};
 use crate::input::scroll_tracker::ScrollTracker;
 use crate::input::{
@@ -121,31 +125,47 @@ use crate::layout::workspace::WorkspaceId;
 use crate::layout::{Layout, LayoutElement as _, MonitorRenderElement};
 use crate::protocols::foreign_toplevel::{self, ForeignToplevelManagerState};
 use crate::protocols::gamma_control::GammaControlManagerState;
+use crate::protocols::image_copy_capture::{
+    ImageCaptureSource, ImageCopyCaptureState, Session as ImageCopyCaptureSession, SessionKind,
+};
 use crate::protocols::mutter_x11_interop::MutterX11InteropManagerState;
 use crate::protocols::output_management::OutputManagementManagerState;
+use crate::protocols::output_power_management::OutputPowerManagementState;
 use crate::protocols::screencopy::{Screencopy, ScreencopyBuffer, ScreencopyManagerState};
+use crate::protocols::tearing_control::{surface_wants_tearing, TearingControlManagerState};
 use crate::pw_utils::{Cast, PipeWire};
 #[cfg(feature = "xdp-gnome-screencast")]
 use crate::pw_utils::{CastSizeChange, CastTarget, PwToNiri};
+use crate::render_helpers::background_image::{
+    self, BackgroundImages, DecodedImage, OutputBackgroundImage,
+};
 use crate::render_helpers::debug::draw_opaque_regions;
+use crate::render_helpers::mirror::place_mirror;
 use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
 use crate::render_helpers::renderer::NiriRenderer;
-use crate::render_helpers::texture::TextureBuffer;
+use crate::render_helpers::texture::{TextureBuffer, TextureRenderElement};
 use crate::render_helpers::{
     render_to_dmabuf, render_to_encompassing_texture, render_to_shm, render_to_texture,
     render_to_vec, shaders, RenderTarget,
 };
+use crate::ui::capture_indicator::{CaptureIndicator, CaptureIndicatorRenderElement};
 use crate::ui::config_error_notification::ConfigErrorNotification;
 use crate::ui::exit_confirm_dialog::ExitConfirmDialog;
 use crate::ui::hotkey_overlay::HotkeyOverlay;
 use crate::ui::screen_transition::{self, ScreenTransition};
-use crate::ui::screenshot_ui::{OutputScreenshot, ScreenshotUi, ScreenshotUiRenderElement};
+use crate::ui::screenshot_ui::{
+    OutputScreenshot, PickedSurface, ScreenshotUi, ScreenshotUiRenderElement,
+};
+use crate::ui::workspace_switch_osd::{self, WorkspaceSwitchOsd};
+use crate::utils::orientation::AutoRotation;
 use crate::utils::scale::{closest_representable_scale, guess_monitor_scale};
-use crate::utils::spawning::CHILD_ENV;
+use crate::utils::spawning::{spawn, CHILD_ENV};
 use crate::utils::{
-    center, center_f64, get_monotonic_time, ipc_transform_to_smithay, logical_output,
-    make_screenshot_path, output_matches_name, output_size, send_scale_transform, write_png_rgba8,
+    center, center_f64, encode_screenshot, format_screenshot_path, get_monotonic_time,
+    ipc_transform_to_smithay, logical_output, make_screenshot_path, output_matches_name,
+    output_size, send_scale_transform, with_screenshot_extension,
 };
+use crate::window::mapped::WindowCastRenderElements;
 use crate::window::{InitialConfigureState, Mapped, ResolvedWindowRules, Unmapped, WindowRef};
 use crate::{animation, niri_render_elements};
 
@@ -156,6 +176,10 @@ const CLEAR_COLOR_LOCKED: [f32; 4] = [0.3, 0.1, 0.1, 1.];
 // should be ~1.995 seconds.
 const FRAME_CALLBACK_THROTTLE: Option<Duration> = Some(Duration::from_millis(995));
 
+/// Refresh rate of the virtual outputs created for RecordVirtual screencasts.
+#[cfg(feature = "xdp-gnome-screencast")]
+const VIRTUAL_CAST_REFRESH: f64 = 60.;
+
 pub struct Niri {
     pub config: Rc<RefCell<Config>>,
 
@@ -165,6 +189,10 @@ pub struct Niri {
     /// reloading the config from disk to determine if the output configuration should be reloaded
     /// (and transient changes dropped).
     pub config_file_output_config: niri_config::Outputs,
+    /// Name of the active output profile.
+    ///
+    /// The profile outputs are applied on top of the config file output config.
+    pub output_profile: Option<String>,
 
     pub event_loop: LoopHandle<'static, State>,
     pub scheduler: Scheduler<()>,
@@ -206,6 +234,15 @@ pub struct Niri {
 
     // When false, we're idling with monitors powered off.
     pub monitors_active: bool,
+    /// Outputs that were powered off individually.
+    ///
+    /// Kept by name rather than in [`OutputState`] so that the outputs stay off when the backend
+    /// recreates them, e.g. on a color settings change or a replug. Such an output is only
+    /// powered on when it's not in this set and [`Niri::monitors_active`] is `true`.
+    pub powered_off_outputs: HashSet<OutputName>,
+    /// Whether the laptop lid is closed.
+    pub lid_closed: bool,
+    pub auto_rotation: AutoRotation,
 
     pub devices: HashSet<input::Device>,
     pub tablets: HashMap<input::Device, TabletData>,
@@ -219,7 +256,9 @@ pub struct Niri {
     pub layer_shell_state: WlrLayerShellState,
     pub session_lock_state: SessionLockManagerState,
     pub foreign_toplevel_state: ForeignToplevelManagerState,
+    pub foreign_toplevel_list_state: ForeignToplevelListState,
     pub screencopy_state: ScreencopyManagerState,
+    pub image_copy_capture_state: ImageCopyCaptureState,
     pub output_management_state: OutputManagementManagerState,
     pub viewporter_state: ViewporterState,
     pub xdg_foreign_state: XdgForeignState,
@@ -245,8 +284,12 @@ pub struct Niri {
     pub presentation_state: PresentationState,
     pub security_context_state: SecurityContextState,
     pub gamma_control_manager_state: GammaControlManagerState,
+    pub output_power_management_state: OutputPowerManagementState,
     pub activation_state: XdgActivationState,
     pub mutter_x11_interop_state: MutterX11InteropManagerState,
+    pub tearing_control_state: TearingControlManagerState,
+    /// Explicit sync state, created by the TTY backend if the primary render device supports it.
+    pub drm_syncobj_state: Option<DrmSyncobjState>,
 
     pub seat: Seat<State>,
     /// Scancodes of the keys to suppress.
@@ -297,6 +340,12 @@ pub struct Niri {
     pub config_error_notification: ConfigErrorNotification,
     pub hotkey_overlay: HotkeyOverlay,
     pub exit_confirm_dialog: Option<ExitConfirmDialog>,
+    pub workspace_switch_osd: WorkspaceSwitchOsd,
+    /// Active workspace of each output as of the last refresh, to show the OSD on changes.
+    pub osd_active_workspaces: HashMap<Output, WorkspaceId>,
+    pub capture_indicator: CaptureIndicator,
+
+    pub background_images: BackgroundImages,
 
     pub debug_draw_opaque_regions: bool,
     pub debug_draw_damage: bool,
@@ -309,6 +358,9 @@ pub struct Niri {
     pub ipc_server: Option<IpcServer>,
     pub ipc_outputs_changed: bool,
 
+    /// Screencasts and screen captures that are currently active, as reported over IPC.
+    pub active_casts: Vec<niri_ipc::Cast>,
+
     // Casts are dropped before PipeWire to prevent a double-free (yay).
     pub casts: Vec<Cast>,
     pub pipewire: Option<PipeWire>,
@@ -316,6 +368,9 @@ pub struct Niri {
     // Screencast output for each mapped window.
     #[cfg(feature = "xdp-gnome-screencast")]
     pub mapped_cast_output: HashMap<Window, Output>,
+    // Virtual outputs created for RecordVirtual screencasts, along with their session ids.
+    #[cfg(feature = "xdp-gnome-screencast")]
+    pub virtual_cast_outputs: Vec<(usize, String)>,
 }
 
 #[derive(Debug)]
@@ -327,6 +382,7 @@ pub struct DndIcon {
 pub struct OutputState {
     pub global: GlobalId,
     pub frame_clock: FrameClock,
+    pub frame_stats: FrameStats,
     pub redraw_state: RedrawState,
     pub on_demand_vrr_enabled: bool,
     // After the last redraw, some ongoing animations still remain.
@@ -356,12 +412,18 @@ pub struct OutputState {
     /// Solid color buffer for the background that we use instead of clearing to avoid damage
     /// tracking issues and make screenshots easier.
     pub background_buffer: SolidColorBuffer,
+    /// Background image drawn on top of the background color.
+    pub background_image: OutputBackgroundImage,
     pub lock_render_state: LockRenderState,
     pub lock_surface: Option<LockSurface>,
     pub lock_color_buffer: SolidColorBuffer,
     screen_transition: Option<ScreenTransition>,
     /// Damage tracker used for the debug damage visualization.
     pub debug_damage_tracker: OutputDamageTracker,
+    /// Output that this output mirrors, if any.
+    ///
+    /// Mirroring outputs are not part of the layout nor of the global space.
+    pub mirror_source: Option<Output>,
 }
 
 #[derive(Debug, Default)]
@@ -377,6 +439,8 @@ pub enum RedrawState {
     WaitingForEstimatedVBlank(RegistrationToken),
     /// A redraw is queued on top of the above.
     WaitingForEstimatedVBlankAndQueued(RegistrationToken),
+    /// A redraw is queued, delayed by a timer until right before the predicted render deadline.
+    WaitingForRenderStart(RegistrationToken),
 }
 
 pub struct PopupGrabState {
@@ -440,6 +504,21 @@ pub enum CenterCoords {
 #[derive(Default)]
 pub struct WindowOffscreenId(pub RefCell<Option<Id>>);
 
+/// How to save a screenshot.
+pub struct ScreenshotOptions {
+    /// Whether to write the screenshot to disk in addition to copying it to the clipboard.
+    pub write_to_disk: bool,
+    /// Path template overriding the `screenshot-path` config option.
+    pub path: Option<String>,
+    /// Called once the screenshot is saved with the path where it was written, if any.
+    ///
+    /// Called with an error if the screenshot couldn't be encoded or written to disk. Dropped
+    /// without being called if the screenshot isn't taken, e.g. if the screenshot UI is cancelled.
+    pub on_saved: Option<OnScreenshotSaved>,
+}
+
+pub type OnScreenshotSaved = Box<dyn FnOnce(anyhow::Result<Option<PathBuf>>) + Send>;
+
 impl RedrawState {
     fn queue_redraw(self) -> Self {
         match self {
@@ -449,9 +528,9 @@ impl RedrawState {
             }
 
             // A redraw is already queued.
-            value @ (RedrawState::Queued | RedrawState::WaitingForEstimatedVBlankAndQueued(_)) => {
-                value
-            }
+            value @ (RedrawState::Queued
+            | RedrawState::WaitingForEstimatedVBlankAndQueued(_)
+            | RedrawState::WaitingForRenderStart(_)) => value,
 
             // We're waiting for VBlank, request a redraw afterwards.
             RedrawState::WaitingForVBlank { .. } => RedrawState::WaitingForVBlank {
@@ -469,6 +548,16 @@ impl Default for SurfaceFrameThrottlingState {
     }
 }
 
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            write_to_disk: true,
+            path: None,
+            on_saved: None,
+        }
+    }
+}
+
 impl KeyboardFocus {
     pub fn surface(&self) -> Option<&WlSurface> {
         match self {
@@ -504,6 +593,7 @@ impl State {
         event_loop: LoopHandle<'static, State>,
         stop_signal: LoopSignal,
         display: Display<State>,
+        headless: Option<HeadlessOptions>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let _span = tracy_client::span!("State::new");
 
@@ -512,7 +602,11 @@ impl State {
         let has_display =
             env::var_os("WAYLAND_DISPLAY").is_some() || env::var_os("DISPLAY").is_some();
 
-        let mut backend = if has_display {
+        let mut backend = if let Some(options) = headless {
+            let headless = Headless::new(config.clone(), event_loop.clone(), options)
+                .context("error initializing the headless backend")?;
+            Backend::Headless(headless)
+        } else if has_display {
             let winit = Winit::new(config.clone(), event_loop.clone())?;
             Backend::Winit(winit)
         } else {
@@ -526,6 +620,9 @@ impl State {
 
         let mut state = Self { backend, niri };
 
+        // Activate the output profile matching the monitors connected at startup.
+        state.refresh_output_profile();
+
         // Initialize some IPC server state.
         state.ipc_keyboard_layouts_changed();
 
@@ -559,6 +656,7 @@ impl State {
 
         // Needs to be called after updating the keyboard focus.
         self.niri.refresh_layout();
+        self.niri.refresh_workspace_switch_osd();
 
         self.niri.cursor_manager.check_cursor_image_surface_alive();
         self.niri.refresh_pointer_outputs();
@@ -570,9 +668,29 @@ impl State {
         self.refresh_ipc_outputs();
         self.ipc_refresh_layout();
         self.ipc_refresh_keyboard_layout_index();
+        self.niri.refresh_active_casts();
+        self.ipc_refresh_casts();
 
         #[cfg(feature = "xdp-gnome-screencast")]
         self.niri.refresh_mapped_cast_outputs();
+        #[cfg(feature = "xdp-gnome-screencast")]
+        self.refresh_virtual_cast_outputs();
+    }
+
+    /// Destroys virtual outputs whose screencasts have stopped.
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn refresh_virtual_cast_outputs(&mut self) {
+        let casts = &self.niri.casts;
+        let (stopped, active): (Vec<_>, Vec<_>) = mem::take(&mut self.niri.virtual_cast_outputs)
+            .into_iter()
+            .partition(|(session_id, _)| !casts.iter().any(|c| c.session_id == *session_id));
+        self.niri.virtual_cast_outputs = active;
+
+        for (_, name) in stopped {
+            if let Err(err) = self.backend.destroy_virtual_output(&mut self.niri, &name) {
+                warn!("error destroying virtual output: {err:?}");
+            }
+        }
     }
 
     fn notify_blocker_cleared(&mut self) {
@@ -1042,7 +1160,9 @@ impl State {
             libinput_config_changed = true;
         }
 
-        if config.outputs != self.niri.config_file_output_config {
+        if config.outputs != self.niri.config_file_output_config
+            || config.output_profiles != old_config.output_profiles
+        {
             output_config_changed = true;
             self.niri
                 .config_file_output_config
@@ -1130,6 +1250,7 @@ impl State {
         }
 
         if output_config_changed {
+            self.apply_output_profile(self.matching_output_profile());
             self.reload_output_config();
         }
 
@@ -1149,6 +1270,8 @@ impl State {
             self.niri.reset_pointer_inactivity_timer();
         }
 
+        self.niri.update_background_images();
+
         // Can't really update xdg-decoration settings since we have to hide the globals for CSD
         // due to the SDL2 bug... I don't imagine clients are prepared for the xdg-decoration
         // global suddenly appearing? Either way, right now it's live-reloaded in a sense that new
@@ -1157,6 +1280,19 @@ impl State {
         self.niri.queue_redraw_all();
     }
 
+    pub fn reload_background_image(&mut self, path: &str) {
+        self.niri.background_images.reload(path);
+    }
+
+    pub fn upload_background_image(&mut self, decoded: DecodedImage) {
+        let changed = self.backend.with_primary_renderer(|renderer| {
+            self.niri.background_images.upload(renderer, decoded)
+        });
+        if changed == Some(true) {
+            self.niri.queue_redraw_all();
+        }
+    }
+
     pub fn reload_output_config(&mut self) {
         let mut resized_outputs = vec![];
         let mut recolored_outputs = vec![];
@@ -1179,6 +1315,9 @@ impl State {
             let mut transform = config
                 .map(|c| ipc_transform_to_smithay(c.transform))
                 .unwrap_or(Transform::Normal);
+            if config.is_some_and(|c| c.auto_rotate) {
+                transform = self.niri.auto_rotation.transform(transform);
+            }
             // FIXME: fix winit damage on other transforms.
             if name.connector == "winit" {
                 transform = Transform::Flipped180;
@@ -1222,6 +1361,7 @@ impl State {
 
         self.backend.on_output_config_changed(&mut self.niri);
 
+        self.niri.refresh_mirrors();
         self.niri.reposition_outputs(None);
 
         if let Some(touch) = self.niri.seat.get_touch() {
@@ -1229,9 +1369,70 @@ impl State {
         }
 
         let config = self.niri.config.borrow().outputs.clone();
+
+        #[cfg(feature = "dbus")]
+        if config.0.iter().any(|o| o.auto_rotate) {
+            if let Some(dbus) = &mut self.niri.dbus {
+                dbus.start_accelerometer_watcher();
+            }
+        }
+
         self.niri.output_management_state.on_config_changed(config);
     }
 
+    /// Activates the output profile that matches the connected outputs, if it changed.
+    pub fn refresh_output_profile(&mut self) {
+        let profile = self.matching_output_profile();
+        if profile.as_ref().map(|p| &p.name) == self.niri.output_profile.as_ref() {
+            return;
+        }
+
+        self.apply_output_profile(profile);
+        self.reload_output_config();
+    }
+
+    fn matching_output_profile(&self) -> Option<OutputProfile> {
+        let connected = self.backend.profile_output_names();
+
+        let config = self.niri.config.borrow();
+        config
+            .output_profiles
+            .iter()
+            .find(|profile| profile.matches(&connected))
+            .cloned()
+    }
+
+    /// Replaces the output config with the config file one with `profile` applied on top.
+    ///
+    /// This drops the transient output config changes.
+    fn apply_output_profile(&mut self, profile: Option<OutputProfile>) {
+        let base = &self.niri.config_file_output_config;
+        let outputs = match &profile {
+            Some(profile) => profile.apply(base),
+            None => base.clone(),
+        };
+        self.niri.config.borrow_mut().outputs = outputs;
+
+        let name = profile.as_ref().map(|profile| profile.name.clone());
+        if self.niri.output_profile == name {
+            return;
+        }
+
+        match &name {
+            Some(name) => info!("activating output profile {name}"),
+            None => info!("no output profile matches the connected outputs"),
+        }
+        self.niri.output_profile = name;
+
+        if let Some(profile) = profile {
+            for exec in profile.exec {
+                spawn(exec.command);
+            }
+        }
+
+        self.ipc_refresh_output_profile();
+    }
+
     pub fn apply_transient_output_config(&mut self, name: &str, action: niri_ipc::OutputAction) {
         {
             // Try hard to find the output config section corresponding to the output set by the
@@ -1276,8 +1477,43 @@ impl State {
                 niri_ipc::OutputAction::Mode { mode } => {
                     config.mode = match mode {
                         niri_ipc::ModeToSet::Automatic => None,
-                        niri_ipc::ModeToSet::Specific(mode) => Some(mode),
-                    }
+                        niri_ipc::ModeToSet::Specific(mode) => Some(niri_config::Mode {
+                            custom: false,
+                            mode,
+                        }),
+                    };
+                    config.modeline = None;
+                }
+                niri_ipc::OutputAction::CustomMode { mode } => {
+                    config.mode = Some(niri_config::Mode { custom: true, mode });
+                    config.modeline = None;
+                }
+                niri_ipc::OutputAction::Modeline {
+                    clock,
+                    hdisplay,
+                    hsync_start,
+                    hsync_end,
+                    htotal,
+                    vdisplay,
+                    vsync_start,
+                    vsync_end,
+                    vtotal,
+                    hsync_polarity,
+                    vsync_polarity,
+                } => {
+                    config.modeline = Some(niri_config::Modeline {
+                        clock: FloatOrInt(clock),
+                        hdisplay,
+                        hsync_start,
+                        hsync_end,
+                        htotal,
+                        vdisplay,
+                        vsync_start,
+                        vsync_end,
+                        vtotal,
+                        hsync_polarity,
+                        vsync_polarity,
+                    });
                 }
                 niri_ipc::OutputAction::Scale { scale } => {
                     config.scale = match scale {
@@ -1306,6 +1542,10 @@ impl State {
                         None
                     }
                 }
+                niri_ipc::OutputAction::Mirror { output, scaling } => {
+                    config.mirror = Some(niri_config::Mirror { output, scaling });
+                }
+                niri_ipc::OutputAction::Unmirror => config.mirror = None,
             }
         }
 
@@ -1328,6 +1568,13 @@ impl State {
                 .find(|output| output.name() == ipc_output.name)
                 .map(logical_output);
             ipc_output.logical = logical;
+
+            ipc_output.powered = self
+                .niri
+                .output_state
+                .keys()
+                .find(|output| output.name() == ipc_output.name)
+                .is_some_and(|output| self.niri.is_output_powered(output));
         }
 
         #[cfg(feature = "dbus")]
@@ -1337,7 +1584,117 @@ impl State {
         self.niri.output_management_state.notify_changes(new_config);
     }
 
-    pub fn open_screenshot_ui(&mut self) {
+    /// Performs a screenshot action after its delay, if any.
+    ///
+    /// `on_saved` is called once the screenshot is saved, see [`ScreenshotOptions::on_saved`].
+    pub fn do_screenshot_action(&mut self, action: Action, on_saved: Option<OnScreenshotSaved>) {
+        let delay_ms = match &action {
+            Action::Screenshot(_, delay_ms, _)
+            | Action::ScreenshotScreen(_, delay_ms, _)
+            | Action::ScreenshotWindow(_, delay_ms, _)
+            | Action::ScreenshotWindowById { delay_ms, .. } => *delay_ms,
+            _ => {
+                error!("not a screenshot action: {action:?}");
+                return;
+            }
+        };
+
+        let Some(delay_ms) = delay_ms.filter(|delay_ms| *delay_ms > 0) else {
+            self.take_screenshot(action, on_saved);
+            return;
+        };
+
+        let mut args = Some((action, on_saved));
+        let timer = Timer::from_duration(Duration::from_millis(u64::from(delay_ms)));
+        self.niri
+            .event_loop
+            .insert_source(timer, move |_, _, state| {
+                if let Some((action, on_saved)) = args.take() {
+                    state.take_screenshot(action, on_saved);
+                }
+                TimeoutAction::Drop
+            })
+            .unwrap();
+    }
+
+    fn take_screenshot(&mut self, action: Action, on_saved: Option<OnScreenshotSaved>) {
+        // The session could have been locked during the delay.
+        if self.niri.is_locked() {
+            return;
+        }
+
+        match action {
+            Action::Screenshot(write_to_disk, _, path) => {
+                self.open_screenshot_ui(ScreenshotOptions {
+                    write_to_disk,
+                    path,
+                    on_saved,
+                });
+            }
+            Action::ScreenshotScreen(write_to_disk, _, path) => {
+                let Some(active) = self.niri.layout.active_output().cloned() else {
+                    return;
+                };
+                let options = ScreenshotOptions {
+                    write_to_disk,
+                    path,
+                    on_saved,
+                };
+                self.backend.with_primary_renderer(|renderer| {
+                    if let Err(err) = self.niri.screenshot(renderer, &active, options) {
+                        warn!("error taking screenshot: {err:?}");
+                    }
+                });
+            }
+            Action::ScreenshotWindow(write_to_disk, _, path) => {
+                let Some((mapped, output)) = self.niri.layout.active_window() else {
+                    return;
+                };
+                let options = ScreenshotOptions {
+                    write_to_disk,
+                    path,
+                    on_saved,
+                };
+                self.backend.with_primary_renderer(|renderer| {
+                    if let Err(err) = self
+                        .niri
+                        .screenshot_window(renderer, output, mapped, options)
+                    {
+                        warn!("error taking screenshot: {err:?}");
+                    }
+                });
+            }
+            Action::ScreenshotWindowById {
+                id,
+                write_to_disk,
+                path,
+                ..
+            } => {
+                let mut windows = self.niri.layout.windows();
+                let window = windows.find(|(_, m)| m.id().get() == id);
+                let Some((Some(monitor), mapped)) = window else {
+                    return;
+                };
+                let output = monitor.output();
+                let options = ScreenshotOptions {
+                    write_to_disk,
+                    path,
+                    on_saved,
+                };
+                self.backend.with_primary_renderer(|renderer| {
+                    if let Err(err) = self
+                        .niri
+                        .screenshot_window(renderer, output, mapped, options)
+                    {
+                        warn!("error taking screenshot: {err:?}");
+                    }
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn open_screenshot_ui(&mut self, options: ScreenshotOptions) {
         if self.niri.is_locked() || self.niri.screenshot_ui.is_open() {
             return;
         }
@@ -1369,7 +1726,7 @@ impl State {
         self.backend.with_primary_renderer(|renderer| {
             self.niri
                 .screenshot_ui
-                .open(renderer, screenshots, default_output)
+                .open(renderer, screenshots, default_output, options)
         });
 
         self.niri
@@ -1382,20 +1739,155 @@ impl State {
     pub fn on_pw_msg(&mut self, msg: PwToNiri) {
         match msg {
             PwToNiri::StopCast { session_id } => self.niri.stop_cast(session_id),
-            PwToNiri::Redraw(target) => match target {
-                CastTarget::Output(weak) => {
-                    if let Some(output) = weak.upgrade() {
-                        self.niri.queue_redraw(&output);
-                    }
+            PwToNiri::Redraw { session_id } => {
+                let targets: Vec<_> = self
+                    .niri
+                    .casts
+                    .iter()
+                    .filter(|cast| cast.session_id == session_id)
+                    .map(|cast| cast.target.clone())
+                    .collect();
+                for target in targets {
+                    self.redraw_cast(target);
                 }
-                CastTarget::Window { id } => {
-                    self.backend.with_primary_renderer(|renderer| {
-                        // FIXME: target presentation time at the time of window commit?
-                        self.niri
-                            .render_window_for_screen_cast(renderer, id, get_monotonic_time());
-                    });
+            }
+            PwToNiri::Resize { session_id, size } => self.on_virtual_cast_resized(session_id, size),
+        }
+    }
+
+    /// Resizes the virtual output of a RecordVirtual cast to the size its consumer picked.
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn on_virtual_cast_resized(&mut self, session_id: usize, size: Size<i32, Physical>) {
+        let Some((_, name)) = self
+            .niri
+            .virtual_cast_outputs
+            .iter()
+            .find(|(id, _)| *id == session_id)
+        else {
+            return;
+        };
+        let name = name.clone();
+
+        // The stream size is in the output's transformed orientation.
+        let output = self.niri.global_space.outputs().find(|o| o.name() == name);
+        let size = output.map_or(size, |o| o.current_transform().transform_size(size));
+        let (Ok(width), Ok(height)) = (u16::try_from(size.w), u16::try_from(size.h)) else {
+            warn!("virtual output {name}: negotiated size {size:?} is too large");
+            return;
+        };
+
+        let mode = niri_ipc::ConfiguredMode {
+            width,
+            height,
+            refresh: Some(VIRTUAL_CAST_REFRESH),
+        };
+        debug!("virtual output {name}: resizing to {width}x{height}");
+        if let Err(err) = self
+            .backend
+            .set_virtual_output_mode(&mut self.niri, &name, mode)
+        {
+            warn!("error resizing virtual output: {err:?}");
+        }
+    }
+
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn redraw_cast(&mut self, target: CastTarget) {
+        match target {
+            CastTarget::Output(weak) => {
+                if let Some(output) = weak.upgrade() {
+                    self.niri.queue_redraw(&output);
                 }
-            },
+            }
+            CastTarget::Window { id } => {
+                self.backend.with_primary_renderer(|renderer| {
+                    // FIXME: target presentation time at the time of window commit?
+                    self.niri
+                        .render_window_for_screen_cast(renderer, id, get_monotonic_time());
+                });
+            }
+            CastTarget::Area { rect } => {
+                for output in self.niri.outputs_intersecting(rect) {
+                    self.niri.queue_redraw(&output);
+                }
+            }
+            CastTarget::Nothing => {
+                self.backend.with_primary_renderer(|renderer| {
+                    self.niri.render_placeholder_for_screen_cast(renderer);
+                });
+            }
+        }
+    }
+
+    pub fn set_dynamic_cast_window(&mut self, id: u64) {
+        #[cfg(feature = "xdp-gnome-screencast")]
+        self.set_dynamic_cast_target(CastTarget::Window { id });
+        #[cfg(not(feature = "xdp-gnome-screencast"))]
+        let _ = id;
+    }
+
+    pub fn set_dynamic_cast_monitor(&mut self, output: Output) {
+        #[cfg(feature = "xdp-gnome-screencast")]
+        self.set_dynamic_cast_target(CastTarget::Output(output.downgrade()));
+        #[cfg(not(feature = "xdp-gnome-screencast"))]
+        let _ = output;
+    }
+
+    pub fn clear_dynamic_cast_target(&mut self) {
+        #[cfg(feature = "xdp-gnome-screencast")]
+        self.set_dynamic_cast_target(CastTarget::Nothing);
+    }
+
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn set_dynamic_cast_target(&mut self, target: CastTarget) {
+        let _span = tracy_client::span!("State::set_dynamic_cast_target");
+
+        // The window cast FPS follows the output the window is on.
+        let refresh = match &target {
+            CastTarget::Output(weak) => weak
+                .upgrade()
+                .map(|output| output.current_mode().unwrap().refresh as u32),
+            CastTarget::Window { id } => {
+                let mut refresh = None;
+                self.niri.layout.with_windows(|mapped, _, _| {
+                    if mapped.id().get() != *id {
+                        return;
+                    }
+
+                    refresh = self
+                        .niri
+                        .mapped_cast_output
+                        .get(&mapped.window)
+                        .map(|output| output.current_mode().unwrap().refresh as u32);
+                });
+                refresh
+            }
+            CastTarget::Area { .. } | CastTarget::Nothing => None,
+        };
+
+        let mut changed = false;
+        let mut to_stop = vec![];
+        for cast in &mut self.niri.casts {
+            if !cast.switch_dynamic_target(&target) {
+                continue;
+            }
+
+            debug!(session_id = cast.session_id, "changing dynamic cast target");
+            changed = true;
+
+            if let Some(refresh) = refresh {
+                if let Err(err) = cast.set_refresh(refresh) {
+                    warn!("error changing cast FPS: {err:?}");
+                    to_stop.push(cast.session_id);
+                }
+            }
+        }
+
+        for id in to_stop {
+            self.niri.stop_cast(id);
+        }
+
+        if changed {
+            self.redraw_cast(target);
         }
     }
 
@@ -1422,11 +1914,39 @@ impl State {
                     }
                 };
 
-                let Some(pw) = &self.niri.pipewire else {
+                if self.niri.pipewire.is_none() {
                     error!("screencasting must be disabled if PipeWire is missing");
                     return;
+                }
+
+                // Create the virtual output up-front, then cast it like any other output. The
+                // consumer picks the size during the stream negotiation, and the output follows.
+                let resizable = matches!(target, StreamTargetId::Virtual);
+                let target = match target {
+                    StreamTargetId::Virtual => {
+                        let mode = niri_ipc::ConfiguredMode {
+                            width: 1920,
+                            height: 1080,
+                            refresh: Some(VIRTUAL_CAST_REFRESH),
+                        };
+                        match self.backend.create_virtual_output(&mut self.niri, mode) {
+                            Ok(name) => {
+                                self.niri
+                                    .virtual_cast_outputs
+                                    .push((session_id, name.clone()));
+                                StreamTargetId::Output { name }
+                            }
+                            Err(err) => {
+                                warn!("error starting screencast: {err:?}");
+                                self.niri.stop_cast(session_id);
+                                return;
+                            }
+                        }
+                    }
+                    target => target,
                 };
 
+                let pw = self.niri.pipewire.as_ref().unwrap();
                 let (target, size, refresh, alpha) = match target {
                     StreamTargetId::Output { name } => {
                         let global_space = &self.niri.global_space;
@@ -1473,7 +1993,44 @@ impl State {
 
                         (CastTarget::Window { id }, bbox.size, refresh, true)
                     }
+                    StreamTargetId::Area(area) => {
+                        let rect = Rectangle::from_loc_and_size(
+                            (area.x, area.y),
+                            (area.width, area.height),
+                        );
+                        let Some((size, _scale)) = self.niri.area_cast_size(rect) else {
+                            warn!("error starting screencast: requested area is outside outputs");
+                            self.niri.stop_cast(session_id);
+                            return;
+                        };
+
+                        let refresh = self
+                            .niri
+                            .outputs_intersecting(rect)
+                            .iter()
+                            .map(|output| output.current_mode().unwrap().refresh as u32)
+                            .max()
+                            .unwrap();
+
+                        (CastTarget::Area { rect }, size, refresh, false)
+                    }
+                    StreamTargetId::Dynamic => {
+                        // Start with the size of the active output so that setting it as the
+                        // target doesn't need a resize.
+                        let output = self.niri.layout.active_output();
+                        let (size, refresh) = match output {
+                            Some(output) => {
+                                let mode = output.current_mode().unwrap();
+                                let transform = output.current_transform();
+                                (transform.transform_size(mode.size), mode.refresh as u32)
+                            }
+                            None => (Size::from((1, 1)), 60000),
+                        };
+                        (CastTarget::Nothing, size, refresh, true)
+                    }
+                    StreamTargetId::Virtual => unreachable!(),
                 };
+                let dynamic_target = target == CastTarget::Nothing;
 
                 let render_formats = self
                     .backend
@@ -1487,6 +2044,8 @@ impl State {
                     render_formats,
                     session_id,
                     target,
+                    dynamic_target,
+                    resizable,
                     size,
                     refresh,
                     alpha,
@@ -1507,6 +2066,14 @@ impl State {
         }
     }
 
+    #[cfg(feature = "dbus")]
+    pub fn on_orientation_changed(&mut self, orientation: crate::utils::orientation::Orientation) {
+        if self.niri.auto_rotation.on_sensor_changed(orientation) {
+            debug!("rotating auto-rotate outputs to {orientation:?}");
+            self.reload_output_config();
+        }
+    }
+
     #[cfg(feature = "dbus")]
     pub fn on_screen_shot_msg(
         &mut self,
@@ -1574,6 +2141,16 @@ impl State {
             windows.insert(id, props);
         });
 
+        // Offer the dynamic cast target in the portal window picker.
+        #[cfg(feature = "xdp-gnome-screencast")]
+        windows.insert(
+            mutter_screen_cast::DYNAMIC_CAST_WINDOW_ID,
+            gnome_shell_introspect::WindowProperties {
+                title: String::from("niri Dynamic Cast Target"),
+                app_id: String::from("niri"),
+            },
+        );
+
         let msg = NiriToIntrospect::Windows(windows);
         if let Err(err) = to_introspect.send_blocking(msg) {
             warn!("error sending windows to introspect: {err:?}");
@@ -1682,9 +2259,18 @@ impl Niri {
                 !client.get_data::<ClientState>().unwrap().restricted
             });
         output_management_state.on_config_changed(config_.outputs.clone());
+        let foreign_toplevel_list_state =
+            ForeignToplevelListState::new_with_filter::<State>(&display_handle, |client| {
+                !client.get_data::<ClientState>().unwrap().restricted
+            });
         let screencopy_state = ScreencopyManagerState::new::<State, _>(&display_handle, |client| {
             !client.get_data::<ClientState>().unwrap().restricted
         });
+        let dmabuf_device = backend.primary_render_node().map(|node| node.dev_id());
+        let image_copy_capture_state =
+            ImageCopyCaptureState::new::<State, _>(&display_handle, dmabuf_device, |client| {
+                !client.get_data::<ClientState>().unwrap().restricted
+            });
         let viewporter_state = ViewporterState::new::<State>(&display_handle);
         let xdg_foreign_state = XdgForeignState::new::<State>(&display_handle);
 
@@ -1693,6 +2279,10 @@ impl Niri {
             GammaControlManagerState::new::<State, _>(&display_handle, move |client| {
                 is_tty && !client.get_data::<ClientState>().unwrap().restricted
             });
+        let output_power_management_state =
+            OutputPowerManagementState::new::<State, _>(&display_handle, |client| {
+                !client.get_data::<ClientState>().unwrap().restricted
+            });
         let activation_state = XdgActivationState::new::<State>(&display_handle);
         event_loop
             .insert_source(
@@ -1708,6 +2298,7 @@ impl Niri {
 
         let mutter_x11_interop_state =
             MutterX11InteropManagerState::new::<State, _>(&display_handle, move |_| true);
+        let tearing_control_state = TearingControlManagerState::new::<State>(&display_handle);
 
         let mut seat: Seat<State> = seat_state.new_wl_seat(&display_handle, backend.seat_name());
         seat.add_keyboard(
@@ -1728,6 +2319,8 @@ impl Niri {
 
         let screenshot_ui = ScreenshotUi::new(config.clone());
         let config_error_notification = ConfigErrorNotification::new(config.clone());
+        let workspace_switch_osd = WorkspaceSwitchOsd::new(config.clone());
+        let capture_indicator = CaptureIndicator::new(config.clone());
 
         let mut hotkey_overlay = HotkeyOverlay::new(config.clone(), backend.mod_key());
         if !config_.hotkey_overlay.skip_at_startup {
@@ -1808,10 +2401,13 @@ impl Niri {
             )
             .unwrap();
 
+        let background_images = BackgroundImages::new(event_loop.clone());
+
         drop(config_);
         let mut niri = Self {
             config,
             config_file_output_config,
+            output_profile: None,
 
             event_loop,
             scheduler,
@@ -1831,6 +2427,9 @@ impl Niri {
             blocker_cleared_tx,
             blocker_cleared_rx,
             monitors_active: true,
+            powered_off_outputs: HashSet::new(),
+            lid_closed: false,
+            auto_rotation: AutoRotation::default(),
 
             devices: HashSet::new(),
             tablets: HashMap::new(),
@@ -1843,8 +2442,10 @@ impl Niri {
             layer_shell_state,
             session_lock_state,
             foreign_toplevel_state,
+            foreign_toplevel_list_state,
             output_management_state,
             screencopy_state,
+            image_copy_capture_state,
             viewporter_state,
             xdg_foreign_state,
             text_input_state,
@@ -1872,8 +2473,11 @@ impl Niri {
             presentation_state,
             security_context_state,
             gamma_control_manager_state,
+            output_power_management_state,
             activation_state,
             mutter_x11_interop_state,
+            tearing_control_state,
+            drm_syncobj_state: None,
 
             seat,
             keyboard_focus: KeyboardFocus::Layout { surface: None },
@@ -1904,6 +2508,11 @@ impl Niri {
             config_error_notification,
             hotkey_overlay,
             exit_confirm_dialog,
+            workspace_switch_osd,
+            osd_active_workspaces: HashMap::new(),
+            capture_indicator,
+
+            background_images,
 
             debug_draw_opaque_regions: false,
             debug_draw_damage: false,
@@ -1916,11 +2525,15 @@ impl Niri {
             ipc_server,
             ipc_outputs_changed: false,
 
+            active_casts: Vec::new(),
+
             pipewire,
             casts: vec![],
 
             #[cfg(feature = "xdp-gnome-screencast")]
             mapped_cast_output: HashMap::new(),
+            #[cfg(feature = "xdp-gnome-screencast")]
+            virtual_cast_outputs: Vec::new(),
         };
 
         niri.reset_pointer_inactivity_timer();
@@ -2093,6 +2706,9 @@ impl Niri {
         let mut transform = c
             .map(|c| ipc_transform_to_smithay(c.transform))
             .unwrap_or(Transform::Normal);
+        if c.is_some_and(|c| c.auto_rotate) {
+            transform = self.auto_rotation.transform(transform);
+        }
 
         let mut background_color = c
             .map(|c| c.background_color)
@@ -2114,7 +2730,10 @@ impl Niri {
             None,
         );
 
-        self.layout.add_output(output.clone());
+        let mirror_source = self.configured_mirror_source(&output);
+        if mirror_source.is_none() {
+            self.layout.add_output(output.clone());
+        }
 
         let lock_render_state = if self.is_locked() {
             // We haven't rendered anything yet so it's as good as locked.
@@ -2130,20 +2749,76 @@ impl Niri {
             on_demand_vrr_enabled: false,
             unfinished_animations_remain: false,
             frame_clock: FrameClock::new(refresh_interval, vrr),
+            frame_stats: FrameStats::new(),
             last_drm_sequence: None,
             frame_callback_sequence: 0,
             background_buffer: SolidColorBuffer::new(size, background_color),
+            background_image: OutputBackgroundImage::default(),
             lock_render_state,
             lock_surface: None,
             lock_color_buffer: SolidColorBuffer::new(size, CLEAR_COLOR_LOCKED),
             screen_transition: None,
             debug_damage_tracker: OutputDamageTracker::from_output(&output),
+            mirror_source: mirror_source.clone(),
         };
         let rv = self.output_state.insert(output.clone(), state);
         assert!(rv.is_none(), "output was already tracked");
 
         // Must be last since it will call queue_redraw(output) which needs things to be filled-in.
-        self.reposition_outputs(Some(&output));
+        if mirror_source.is_some() {
+            self.queue_redraw(&output);
+        } else {
+            self.reposition_outputs(Some(&output));
+
+            // Outputs waiting for this one to connect can start mirroring it now.
+            self.refresh_mirrors();
+        }
+    }
+
+    /// Returns the output that this output should mirror according to the config, if any.
+    fn configured_mirror_source(&self, output: &Output) -> Option<Output> {
+        let config = self.config.borrow();
+        let name = output.user_data().get::<OutputName>().unwrap();
+        let mirror = config.outputs.find(name)?.mirror.as_ref()?;
+
+        // Only outputs in the global space can be mirrored, which rules out chains of mirrors.
+        let source = self.output_by_name_match(&mirror.output)?;
+        (source != output).then(|| source.clone())
+    }
+
+    /// Moves outputs in and out of the layout according to their mirror config.
+    pub fn refresh_mirrors(&mut self) {
+        let _span = tracy_client::span!("Niri::refresh_mirrors");
+
+        let outputs: Vec<_> = self.output_state.keys().cloned().collect();
+        for output in outputs {
+            let mirror_source = self.configured_mirror_source(&output);
+
+            let state = self.output_state.get_mut(&output).unwrap();
+            if state.mirror_source == mirror_source {
+                continue;
+            }
+            let was_mirror = state.mirror_source.is_some();
+            state.mirror_source = mirror_source.clone();
+
+            match (was_mirror, mirror_source.is_some()) {
+                (false, true) => {
+                    debug!("output {} started mirroring", output.name());
+                    self.layout.remove_output(&output);
+                    self.global_space.unmap_output(&output);
+                    self.reposition_outputs(None);
+                }
+                (true, false) => {
+                    debug!("output {} stopped mirroring", output.name());
+                    self.layout.add_output(output.clone());
+                    self.reposition_outputs(Some(&output));
+                }
+                _ => (),
+            }
+
+            self.ipc_outputs_changed = true;
+            self.queue_redraw(&output);
+        }
     }
 
     pub fn remove_output(&mut self, output: &Output) {
@@ -2151,25 +2826,35 @@ impl Niri {
             layer.layer_surface().send_close();
         }
 
-        self.layout.remove_output(output);
+        let is_mirror = self.output_state[output].mirror_source.is_some();
+        if !is_mirror {
+            self.layout.remove_output(output);
+        }
         self.global_space.unmap_output(output);
         self.reposition_outputs(None);
         self.gamma_control_manager_state.output_removed(output);
+        self.output_power_management_state.output_removed(output);
 
         let state = self.output_state.remove(output).unwrap();
 
+        // Outputs mirroring this one go back to the layout.
+        self.refresh_mirrors();
+
         match state.redraw_state {
             RedrawState::Idle => (),
             RedrawState::Queued => (),
             RedrawState::WaitingForVBlank { .. } => (),
             RedrawState::WaitingForEstimatedVBlank(token) => self.event_loop.remove(token),
             RedrawState::WaitingForEstimatedVBlankAndQueued(token) => self.event_loop.remove(token),
+            RedrawState::WaitingForRenderStart(token) => self.event_loop.remove(token),
         }
 
         #[cfg(feature = "xdp-gnome-screencast")]
         self.stop_casts_for_target(CastTarget::Output(output.downgrade()));
 
         self.remove_screencopy_output(output);
+        self.image_copy_capture_state
+            .stop_source(&ImageCaptureSource::Output(output.clone()));
 
         // Disable the output global and remove some time later to give the clients some time to
         // process it.
@@ -2271,6 +2956,8 @@ impl Niri {
 
         self.monitors_active = false;
         backend.set_monitors_active(false);
+
+        self.notify_output_power_changed(false);
     }
 
     pub fn activate_monitors(&mut self, backend: &mut Backend) {
@@ -2281,9 +2968,68 @@ impl Niri {
         self.monitors_active = true;
         backend.set_monitors_active(true);
 
+        self.notify_output_power_changed(true);
         self.queue_redraw_all();
     }
 
+    /// Notifies about monitors getting globally activated or deactivated.
+    ///
+    /// Outputs that were powered off individually are not affected.
+    fn notify_output_power_changed(&mut self, powered: bool) {
+        for output in self.output_state.keys() {
+            if !self.is_output_powered_off(output) {
+                self.output_power_management_state
+                    .output_power_changed(output, powered);
+            }
+        }
+        self.ipc_outputs_changed = true;
+    }
+
+    pub fn is_output_powered(&self, output: &Output) -> bool {
+        self.monitors_active
+            && self.output_state.contains_key(output)
+            && !self.is_output_powered_off(output)
+    }
+
+    /// Returns whether the output was powered off individually.
+    pub fn is_output_powered_off(&self, output: &Output) -> bool {
+        let name = output.user_data().get::<OutputName>().unwrap();
+        self.powered_off_outputs.contains(name)
+    }
+
+    /// Powers a single output on or off.
+    ///
+    /// While monitors are deactivated, this only changes whether the output will power on
+    /// together with the rest of them.
+    pub fn set_output_powered(&mut self, backend: &mut Backend, output: &Output, powered: bool) {
+        if !self.output_state.contains_key(output) {
+            error!("missing output state for {}", output.name());
+            return;
+        }
+        let name = output.user_data().get::<OutputName>().unwrap();
+        let changed = if powered {
+            self.powered_off_outputs.remove(name)
+        } else {
+            self.powered_off_outputs.insert(name.clone())
+        };
+        if !changed {
+            return;
+        }
+
+        if !self.monitors_active {
+            return;
+        }
+
+        backend.set_output_powered(output, powered);
+        self.output_power_management_state
+            .output_power_changed(output, powered);
+        self.ipc_outputs_changed = true;
+
+        if powered {
+            self.queue_redraw(output);
+        }
+    }
+
     pub fn output_under(&self, pos: Point<f64, Logical>) -> Option<(&Output, Point<f64, Logical>)> {
         let output = self.global_space.output_under(pos).next()?;
         let pos_within_output = pos
@@ -2447,6 +3193,78 @@ impl Niri {
         rv
     }
 
+    /// Returns the window or layer surface under the given point for the screenshot UI window
+    /// picking mode, along with its geometry in the global space.
+    ///
+    /// Unlike [`Niri::contents_under()`], this looks through the open screenshot UI, and it uses
+    /// the same stacking order as rendering.
+    pub fn screenshot_target_under(
+        &self,
+        pos: Point<f64, Logical>,
+    ) -> Option<(PickedSurface, Rectangle<i32, Logical>)> {
+        let (output, pos_within_output) = self.output_under(pos)?;
+        let output_pos_in_global_space = self.global_space.output_geometry(output).unwrap().loc;
+
+        let layers = layer_map_for_output(output);
+        let layer_surface_under = |layer| {
+            let surface = layers.layer_under(layer, pos_within_output)?;
+            let geo = layers.layer_geometry(surface)?;
+            let picked = PickedSurface::Layer {
+                surface: surface.clone(),
+                output: output.clone(),
+            };
+            Some((picked, geo))
+        };
+
+        let window_under = || {
+            let (mapped, win_pos_within_output) =
+                self.layout.window_under(output, pos_within_output)?;
+            // The position is missing on the server-side border, where we can't tell the window
+            // geometry.
+            let win_pos_within_output = win_pos_within_output?;
+            let window_geo = mapped.window.geometry();
+            let loc = (win_pos_within_output + window_geo.loc.to_f64()).to_i32_round::<i32>();
+            let picked = PickedSurface::Window(mapped.toplevel().wl_surface().clone());
+            Some((picked, Rectangle::from_loc_and_size(loc, window_geo.size)))
+        };
+
+        let mon = self.layout.monitor_for_output(output).unwrap();
+
+        let mut under = layer_surface_under(Layer::Overlay);
+
+        if mon.render_above_top_layer() {
+            under = under
+                .or_else(window_under)
+                .or_else(|| layer_surface_under(Layer::Top));
+        } else {
+            under = under
+                .or_else(|| layer_surface_under(Layer::Top))
+                .or_else(window_under);
+        }
+
+        let (picked, geo) = under
+            .or_else(|| layer_surface_under(Layer::Bottom))
+            .or_else(|| layer_surface_under(Layer::Background))?;
+
+        let loc = geo.loc + output_pos_in_global_space;
+        Some((picked, Rectangle::from_loc_and_size(loc, geo.size)))
+    }
+
+    /// Updates the open screenshot UI after the pointer has moved to `pos`.
+    pub fn screenshot_ui_pointer_motion(&mut self, pos: Point<f64, Logical>) {
+        let changed = if self.screenshot_ui.is_picking() {
+            let target = self.screenshot_target_under(pos);
+            self.screenshot_ui.set_picked(target)
+        } else {
+            self.screenshot_ui.pointer_motion(pos)
+        };
+
+        // The selection may span several outputs.
+        if changed {
+            self.queue_redraw_all();
+        }
+    }
+
     pub fn output_under_cursor(&self) -> Option<Output> {
         let pos = self.seat.get_pointer().unwrap().current_location();
         self.global_space.output_under(pos).next().cloned()
@@ -2559,6 +3377,14 @@ impl Niri {
             .find(|output| output_matches_name(output, target))
     }
 
+    /// Like [`Self::output_by_name_match()`], but also finds outputs that are not in the global
+    /// space, such as mirrors.
+    pub fn output_by_name_match_all(&self, target: &str) -> Option<&Output> {
+        self.output_state
+            .keys()
+            .find(|output| output_matches_name(output, target))
+    }
+
     pub fn output_for_root(&self, root: &WlSurface) -> Option<&Output> {
         // Check the main layout.
         let win_out = self.layout.find_window_and_output(root);
@@ -2597,6 +3423,13 @@ impl Niri {
     pub fn queue_redraw(&mut self, output: &Output) {
         let state = self.output_state.get_mut(output).unwrap();
         state.redraw_state = mem::take(&mut state.redraw_state).queue_redraw();
+
+        // Outputs mirroring this one show the same contents, so they need a redraw too.
+        for state in self.output_state.values_mut() {
+            if state.mirror_source.as_ref() == Some(output) {
+                state.redraw_state = mem::take(&mut state.redraw_state).queue_redraw();
+            }
+        }
     }
 
     pub fn redraw_queued_outputs(&mut self, backend: &mut Backend) {
@@ -2958,9 +3791,79 @@ impl Niri {
         }
     }
 
+    /// Shows the workspace switch OSD on outputs whose active workspace changed.
+    ///
+    /// Checking here rather than in the actions covers every way to switch workspaces, and skips
+    /// cancelled gestures.
+    pub fn refresh_workspace_switch_osd(&mut self) {
+        let _span = tracy_client::span!("Niri::refresh_workspace_switch_osd");
+
+        let mut changed = Vec::new();
+        let mut active_workspaces = HashMap::new();
+        for output in self.layout.outputs() {
+            let Some(mon) = self.layout.monitor_for_output(output) else {
+                continue;
+            };
+
+            let id = mon.active_workspace_ref().id();
+            // Newly connected outputs don't count as a switch.
+            if self
+                .osd_active_workspaces
+                .get(output)
+                .map_or(false, |prev| *prev != id)
+            {
+                changed.push(output.clone());
+            }
+            active_workspaces.insert(output.clone(), id);
+        }
+        self.osd_active_workspaces = active_workspaces;
+
+        for output in changed {
+            self.show_workspace_switch_osd(&output);
+        }
+    }
+
+    /// Shows the workspace switch OSD on the output, if enabled in the config.
+    pub fn show_workspace_switch_osd(&mut self, output: &Output) {
+        if !self.config.borrow().workspace_switch_osd.on {
+            return;
+        }
+
+        let Some(mon) = self.layout.monitor_for_output(output) else {
+            return;
+        };
+
+        let has_windows = self
+            .layout
+            .workspaces()
+            .filter(|(m, _, _)| m.map_or(false, |m| m.output() == output))
+            .map(|(_, _, ws)| ws.has_windows())
+            .collect();
+        let contents = workspace_switch_osd::Contents {
+            active_idx: mon.active_workspace_idx(),
+            name: mon.active_workspace_ref().name().cloned(),
+            has_windows,
+        };
+
+        self.workspace_switch_osd.show(output.clone(), contents);
+        self.queue_redraw(output);
+    }
+
+    pub fn update_background_images(&mut self) {
+        let config = self.config.borrow();
+        let outputs = config.outputs.0.iter().map(|o| &o.background_image);
+        let workspaces = config.workspaces.iter().map(|ws| &ws.background_image);
+        let paths = outputs
+            .chain(workspaces)
+            .flatten()
+            .map(|image| image.path.as_str());
+        self.background_images.update(paths);
+    }
+
     pub fn update_render_elements(&mut self, output: Option<&Output>) {
         self.layout.update_render_elements(output);
 
+        let config = self.config.borrow();
         for (out, state) in self.output_state.iter_mut() {
             if output.map_or(true, |output| out == output) {
                 let scale = Scale::from(out.current_scale().fractional_scale());
@@ -2969,8 +3872,109 @@ impl Niri {
                 if let Some(transition) = &mut state.screen_transition {
                     transition.update_render_elements(scale, transform);
                 }
+
+                let workspace_name = self
+                    .layout
+                    .monitor_for_output(out)
+                    .and_then(|mon| mon.active_workspace_ref().name());
+                let name = out.user_data().get::<OutputName>().unwrap();
+                let image =
+                    background_image::resolve(&config, name, workspace_name.map(String::as_str));
+                let image = image.and_then(|image| {
+                    let buffer = self.background_images.get(&image.path)?;
+                    Some((buffer, image.fit))
+                });
+                state.background_image.update(
+                    image,
+                    output_size(out),
+                    out.current_scale().fractional_scale(),
+                );
+            }
+        }
+    }
+
+    /// Renders the contents of the source output, scaled to fit the mirroring output.
+    fn render_mirror<R: NiriRenderer>(
+        &self,
+        renderer: &mut R,
+        output: &Output,
+        source: &Output,
+        include_pointer: bool,
+        target: RenderTarget,
+    ) -> Vec<OutputRenderElements<R>> {
+        let _span = tracy_client::span!("Niri::render_mirror");
+
+        let scaling = {
+            let config = self.config.borrow();
+            let name = output.user_data().get::<OutputName>().unwrap();
+            let mirror = config.outputs.find(name).and_then(|c| c.mirror.as_ref());
+            mirror.map(|m| m.scaling).unwrap_or_default()
+        };
+
+        let output_scale = Scale::from(output.current_scale().fractional_scale());
+        let output_size = output_size(output);
+
+        let mut elements = vec![];
+
+        // Render the source offscreen, the same way as for a screencast.
+        let mode = source.current_mode().unwrap();
+        let size = source.current_transform().transform_size(mode.size);
+        let gles_renderer = renderer.as_gles_renderer();
+        let source_elements =
+            self.render::<GlesRenderer>(gles_renderer, source, include_pointer, target);
+        let res = render_to_texture(
+            gles_renderer,
+            size,
+            Scale::from(1.),
+            Transform::Normal,
+            Fourcc::Abgr8888,
+            source_elements.iter().rev(),
+        );
+
+        match res {
+            Ok((texture, _)) => {
+                // A new buffer every frame means full damage, which is fine for a mirror.
+                let mut buffer = TextureBuffer::from_texture(
+                    gles_renderer,
+                    texture,
+                    1.,
+                    Transform::Normal,
+                    vec![],
+                );
+                let source_size = size.to_f64().to_logical(1.);
+
+                let placement = place_mirror(scaling, source_size, output_size, output_scale);
+                buffer.set_texture_scale(placement.texture_scale);
+
+                let elem = TextureRenderElement::from_texture_buffer(
+                    buffer,
+                    placement.location,
+                    1.,
+                    placement.src,
+                    placement.size,
+                    Kind::Unspecified,
+                );
+                elements.push(PrimaryGpuTextureRenderElement(elem).into());
+            }
+            Err(err) => {
+                warn!("error rendering mirrored output: {err:?}");
             }
         }
+
+        // Fill the bars around the mirrored contents with the background color.
+        let state = self.output_state.get(output).unwrap();
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &state.background_buffer,
+                (0, 0),
+                output_scale,
+                1.,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+
+        elements
     }
 
     pub fn render<R: NiriRenderer>(
@@ -2991,6 +3995,11 @@ impl Niri {
             }
         }
 
+        let state = self.output_state.get(output).unwrap();
+        if let Some(source) = &state.mirror_source {
+            return self.render_mirror(renderer, output, source, include_pointer, target);
+        }
+
         let output_scale = Scale::from(output.current_scale().fractional_scale());
 
         // The pointer goes on the top.
@@ -2999,6 +4008,12 @@ impl Niri {
             elements = self.pointer_element(renderer, output);
         }
 
+        // Next, the capture indicator, which must never end up in the capture itself.
+        if target == RenderTarget::Output {
+            let indicator = self.capture_indicator.render(renderer, output);
+            elements.extend(indicator.into_iter().map(OutputRenderElements::from));
+        }
+
         // Next, the screen transition texture.
         {
             let state = self.output_state.get(output).unwrap();
@@ -3019,6 +4034,11 @@ impl Niri {
             elements.push(element.into());
         }
 
+        // Next, the workspace switch OSD.
+        if let Some(element) = self.workspace_switch_osd.render(renderer, output) {
+            elements.push(element.into());
+        }
+
         // If the session is locked, draw the lock surface.
         if self.is_locked() {
             let state = self.output_state.get(output).unwrap();
@@ -3135,7 +4155,13 @@ impl Niri {
         extend_from_layer(&mut elements, Layer::Bottom);
         extend_from_layer(&mut elements, Layer::Background);
 
-        // Then the background.
+        // Then the background image and color.
+        elements.extend(
+            state
+                .background_image
+                .render()
+                .map(OutputRenderElements::from),
+        );
         elements.push(background);
 
         if self.debug_draw_opaque_regions {
@@ -3148,6 +4174,8 @@ impl Niri {
     fn redraw(&mut self, backend: &mut Backend, output: &Output) {
         let _span = tracy_client::span!("Niri::redraw");
 
+        let is_powered = self.monitors_active && !self.is_output_powered_off(output);
+
         // Verify our invariant.
         let state = self.output_state.get_mut(output).unwrap();
         assert!(matches!(
@@ -3157,8 +4185,55 @@ impl Niri {
 
         let target_presentation_time = state.frame_clock.next_presentation_time();
 
+        // With render time prediction, delay the redraw until right before the deadline, so that
+        // the frame picks up the latest input and client buffers.
+        let mut predicted_target = None;
+        if is_powered && matches!(state.redraw_state, RedrawState::Queued) {
+            let name = output.user_data().get::<OutputName>().unwrap();
+            let prediction = self
+                .config
+                .borrow()
+                .outputs
+                .find(name)
+                .and_then(|c| c.render_time_prediction);
+            if let Some(prediction) = prediction {
+                let safety_margin = Duration::from_millis(u64::from(prediction.safety_margin_ms));
+                if let Some(start) = state
+                    .frame_clock
+                    .render_start_time(target_presentation_time, safety_margin)
+                {
+                    let now = get_monotonic_time();
+                    if now < start {
+                        let output = output.clone();
+                        let timer = Timer::from_duration(start - now);
+                        let token = self
+                            .event_loop
+                            .insert_source(timer, move |_, _, state| {
+                                if let Some(output_state) = state.niri.output_state.get_mut(&output)
+                                {
+                                    if let RedrawState::WaitingForRenderStart(_) =
+                                        output_state.redraw_state
+                                    {
+                                        output_state.redraw_state = RedrawState::Queued;
+                                    }
+                                }
+
+                                TimeoutAction::Drop
+                            })
+                            .unwrap();
+                        state.redraw_state = RedrawState::WaitingForRenderStart(token);
+                        state.frame_clock.frame_delayed();
+                        return;
+                    }
+
+                    predicted_target = Some(target_presentation_time);
+                }
+            }
+        }
+
+        let render_start = get_monotonic_time();
         let mut res = RenderResult::Skipped;
-        if self.monitors_active {
+        if is_powered {
             // Update from the config and advance the animations.
             self.layout.advance_animations(target_presentation_time);
 
@@ -3176,6 +4251,11 @@ impl Niri {
             state.unfinished_animations_remain |=
                 self.config_error_notification.are_animations_ongoing();
 
+            self.workspace_switch_osd
+                .advance_animations(target_presentation_time);
+            state.unfinished_animations_remain |=
+                self.workspace_switch_osd.are_animations_ongoing();
+
             self.screenshot_ui
                 .advance_animations(target_presentation_time);
             state.unfinished_animations_remain |= self.screenshot_ui.are_animations_ongoing();
@@ -3197,6 +4277,15 @@ impl Niri {
         let is_locked = self.is_locked();
         let state = self.output_state.get_mut(output).unwrap();
 
+        if res == RenderResult::Submitted {
+            let now = get_monotonic_time();
+            let render_time = now.saturating_sub(render_start);
+            state
+                .frame_clock
+                .frame_rendered(render_time, predicted_target);
+            state.frame_stats.frame_rendered(render_time, now);
+        }
+
         if res == RenderResult::Skipped {
             // Update the redraw state on failed render.
             state.redraw_state = if let RedrawState::WaitingForEstimatedVBlank(token)
@@ -3209,10 +4298,10 @@ impl Niri {
             };
         }
 
-        // Update the lock render state on successful render, or if monitors are inactive. When
+        // Update the lock render state on successful render, or if the monitor is inactive. When
         // monitors are inactive on a TTY, they have no framebuffer attached, so no sensitive data
         // from a last render will be visible.
-        if res != RenderResult::Skipped || !self.monitors_active {
+        if res != RenderResult::Skipped || !is_powered {
             state.lock_render_state = if is_locked {
                 LockRenderState::Locked
             } else {
@@ -3272,12 +4361,48 @@ impl Niri {
                 // unimplemented, but happens to work by chance, since output
                 // redrawing is more eager than it should be.
                 self.render_windows_for_screen_cast(renderer, output, target_presentation_time);
+                self.render_areas_for_screen_cast(renderer, output, target_presentation_time);
             }
 
             self.render_for_screencopy_with_damage(renderer, output);
+            self.render_for_image_copy_capture(renderer, output);
         });
     }
 
+    pub fn render_time_stats(&self) -> HashMap<String, niri_ipc::RenderTimeStats> {
+        let config = self.config.borrow();
+
+        self.output_state
+            .iter()
+            .map(|(output, state)| {
+                let name = output.user_data().get::<OutputName>().unwrap();
+                let prediction = config
+                    .outputs
+                    .find(name)
+                    .and_then(|c| c.render_time_prediction);
+
+                let clock = &state.frame_clock;
+                let stats = niri_ipc::RenderTimeStats {
+                    safety_margin_ms: prediction.map(|p| p.safety_margin_ms),
+                    predicted_render_time_us: clock
+                        .predicted_render_time()
+                        .map(|d| d.as_micros() as u64),
+                    delayed_frames: clock.delayed_frames(),
+                    missed_deadlines: clock.missed_deadlines(),
+                    falling_back: clock.is_falling_back(),
+                };
+                (output.name(), stats)
+            })
+            .collect()
+    }
+
+    pub fn frame_stats(&self) -> HashMap<String, niri_ipc::FrameStats> {
+        self.output_state
+            .iter()
+            .map(|(output, state)| (output.name(), state.frame_stats.to_ipc()))
+            .collect()
+    }
+
     pub fn refresh_on_demand_vrr(&mut self, backend: &mut Backend, output: &Output) {
         let _span = tracy_client::span!("Niri::refresh_on_demand_vrr");
 
@@ -3309,6 +4434,20 @@ impl Niri {
         backend.set_output_on_demand_vrr(self, output, current);
     }
 
+    /// Returns the surface of a fullscreen window on this output that may be presented with
+    /// tearing.
+    ///
+    /// The backend must additionally check that the surface is directly scanned out.
+    pub fn tearing_surface(&self, output: &Output) -> Option<WlSurface> {
+        self.layout.windows_for_output(output).find_map(|mapped| {
+            let surface = mapped.toplevel().wl_surface();
+            let allowed = mapped.rules().allow_tearing == Some(true)
+                && mapped.is_fullscreen()
+                && surface_wants_tearing(surface);
+            allowed.then(|| surface.clone())
+        })
+    }
+
     pub fn update_primary_scanout_output(
         &self,
         output: &Output,
@@ -3748,6 +4887,8 @@ impl Niri {
         let scale = Scale::from(output.current_scale().fractional_scale());
 
         let mut elements = None;
+        let mut elements_with_pointer = None;
+        let mut pointer = None;
         let mut casts_to_stop = vec![];
 
         let mut casts = mem::take(&mut self.casts);
@@ -3760,7 +4901,7 @@ impl Niri {
                 continue;
             }
 
-            match cast.ensure_size(size) {
+            match cast.ensure_size(size, scale) {
                 Ok(CastSizeChange::Ready) => (),
                 Ok(CastSizeChange::Pending) => continue,
                 Err(err) => {
@@ -3773,10 +4914,21 @@ impl Niri {
                 continue;
             }
 
-            // FIXME: Hidden / embedded / metadata cursor
-            let elements = elements.get_or_insert_with(|| {
-                self.render(renderer, output, true, RenderTarget::Screencast)
-            });
+            if matches!(cast.cursor_mode, CursorMode::Metadata) {
+                let (pointer_elements, position) = pointer
+                    .get_or_insert_with(|| self.pointer_for_screen_cast(renderer, output, scale));
+                cast.set_cursor(renderer, pointer_elements, *position, scale);
+            }
+
+            let elements = if matches!(cast.cursor_mode, CursorMode::Embedded) {
+                elements_with_pointer.get_or_insert_with(|| {
+                    self.render(renderer, output, true, RenderTarget::Screencast)
+                })
+            } else {
+                elements.get_or_insert_with(|| {
+                    self.render(renderer, output, false, RenderTarget::Screencast)
+                })
+            };
 
             if cast.dequeue_buffer_and_render(renderer, elements, size, scale) {
                 cast.last_frame_time = target_presentation_time;
@@ -3789,40 +4941,24 @@ impl Niri {
         }
     }
 
+    /// Renders a black frame into dynamic casts without a target.
     #[cfg(feature = "xdp-gnome-screencast")]
-    fn render_windows_for_screen_cast(
-        &mut self,
-        renderer: &mut GlesRenderer,
-        output: &Output,
-        target_presentation_time: Duration,
-    ) {
-        let _span = tracy_client::span!("Niri::render_windows_for_screen_cast");
-
-        let scale = Scale::from(output.current_scale().fractional_scale());
+    fn render_placeholder_for_screen_cast(&mut self, renderer: &mut GlesRenderer) {
+        let _span = tracy_client::span!("Niri::render_placeholder_for_screen_cast");
 
         let mut casts_to_stop = vec![];
 
         let mut casts = mem::take(&mut self.casts);
         for cast in &mut casts {
-            if !cast.is_active.get() {
+            if !cast.is_active.get() || cast.target != CastTarget::Nothing {
                 continue;
             }
 
-            let CastTarget::Window { id } = cast.target else {
-                continue;
-            };
-
-            let mut windows = self.layout.windows_for_output(output);
-            let Some(mapped) = windows.find(|win| win.id().get() == id) else {
-                continue;
-            };
-
-            let bbox = mapped
-                .window
-                .bbox_with_popups()
-                .to_physical_precise_up(scale);
+            // Keep the current size to avoid a renegotiation.
+            let size = cast.size();
+            let scale = Scale::from(1.);
 
-            match cast.ensure_size(bbox.size) {
+            match cast.ensure_size(size, scale) {
                 Ok(CastSizeChange::Ready) => (),
                 Ok(CastSizeChange::Pending) => continue,
                 Err(err) => {
@@ -3831,15 +4967,19 @@ impl Niri {
                 }
             }
 
-            if cast.check_time_and_schedule(&self.event_loop, output, target_presentation_time) {
-                continue;
-            }
+            cast.hide_cursor();
 
-            // FIXME: pointer.
-            let elements: Vec<_> = mapped.render_for_screen_cast(renderer, scale).collect();
+            let buffer = SolidColorBuffer::new(size.to_logical(1), [0., 0., 0., 1.]);
+            let elements = [SolidColorRenderElement::from_buffer(
+                &buffer,
+                (0, 0),
+                scale,
+                1.,
+                Kind::Unspecified,
+            )];
 
-            if cast.dequeue_buffer_and_render(renderer, &elements, bbox.size, scale) {
-                cast.last_frame_time = target_presentation_time;
+            if cast.dequeue_buffer_and_render(renderer, &elements, size, scale) {
+                cast.last_frame_time = get_monotonic_time();
             }
         }
         self.casts = casts;
@@ -3849,25 +4989,427 @@ impl Niri {
         }
     }
 
+    /// Returns the pointer elements for the output and the pointer position within it.
+    ///
+    /// The position is `None` when the pointer is not over the output.
     #[cfg(feature = "xdp-gnome-screencast")]
-    fn render_window_for_screen_cast(
-        &mut self,
+    fn pointer_for_screen_cast(
+        &self,
         renderer: &mut GlesRenderer,
-        window_id: u64,
-        target_presentation_time: Duration,
+        output: &Output,
+        scale: Scale<f64>,
+    ) -> (
+        Vec<OutputRenderElements<GlesRenderer>>,
+        Option<Point<i32, Physical>>,
     ) {
-        let _span = tracy_client::span!("Niri::render_window_for_screen_cast");
+        let elements = self.pointer_element(renderer, output);
 
-        let mut window = None;
-        self.layout.with_windows(|mapped, _, _| {
-            if mapped.id().get() != window_id {
-                return;
-            }
+        let output_geo = self.global_space.output_geometry(output).unwrap();
+        let pointer_pos = self
+            .tablet_cursor_location
+            .unwrap_or_else(|| self.seat.get_pointer().unwrap().current_location());
+        let position = (!elements.is_empty() && output_geo.to_f64().contains(pointer_pos))
+            .then(|| (pointer_pos - output_geo.loc.to_f64()).to_physical_precise_round(scale));
 
-            window = Some(mapped.window.clone());
-        });
+        (elements, position)
+    }
 
-        let Some(window) = window else {
+    /// Returns the pointer elements and position for a window screencast.
+    ///
+    /// Both are relative to the window's screencast frame. The position is `None` unless the
+    /// pointer is over this window.
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn pointer_for_window_screen_cast(
+        &self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        mapped: &Mapped,
+        scale: Scale<f64>,
+    ) -> (
+        Vec<RelocateRenderElement<OutputRenderElements<GlesRenderer>>>,
+        Option<Point<i32, Physical>>,
+    ) {
+        let output_geo = self.global_space.output_geometry(output).unwrap();
+        let pointer_pos = self
+            .tablet_cursor_location
+            .unwrap_or_else(|| self.seat.get_pointer().unwrap().current_location());
+        if !output_geo.to_f64().contains(pointer_pos) {
+            return (vec![], None);
+        }
+
+        let pointer_pos_within_output = pointer_pos - output_geo.loc.to_f64();
+        let Some((under, Some(win_pos))) =
+            self.layout.window_under(output, pointer_pos_within_output)
+        else {
+            return (vec![], None);
+        };
+        if under.id() != mapped.id() {
+            return (vec![], None);
+        }
+
+        let elements = self.pointer_element(renderer, output);
+        if elements.is_empty() {
+            return (vec![], None);
+        }
+
+        // The screencast frame starts at the top-left corner of the window bounding box.
+        let origin = win_pos + mapped.window.bbox_with_popups().loc.to_f64();
+        let origin: Point<i32, Physical> = origin.to_physical_precise_round(scale);
+        let position = pointer_pos_within_output.to_physical_precise_round(scale) - origin;
+        let elements = elements
+            .into_iter()
+            .map(|elem| {
+                RelocateRenderElement::from_element(elem, origin.upscale(-1), Relocate::Relative)
+            })
+            .collect();
+
+        (elements, Some(position))
+    }
+
+    /// Collects the active screencasts and screen captures, and updates the capture indicator.
+    pub fn refresh_active_casts(&mut self) {
+        let _span = tracy_client::span!("Niri::refresh_active_casts");
+
+        let mut casts = Vec::new();
+        let mut captured_outputs = Vec::new();
+        let mut captured_windows = HashSet::new();
+
+        #[cfg(feature = "xdp-gnome-screencast")]
+        for cast in &self.casts {
+            use crate::pw_utils::CastTarget;
+
+            if !cast.is_active.get() {
+                continue;
+            }
+
+            let target = match &cast.target {
+                CastTarget::Output(weak) => {
+                    let Some(output) = weak.upgrade() else {
+                        continue;
+                    };
+                    let name = output.name();
+                    captured_outputs.push(output);
+                    niri_ipc::CastTarget::Output { name }
+                }
+                CastTarget::Window { id } => {
+                    captured_windows.insert(*id);
+                    niri_ipc::CastTarget::Window { id: *id }
+                }
+                CastTarget::Area { rect } => {
+                    captured_outputs.extend(self.outputs_intersecting(*rect));
+                    niri_ipc::CastTarget::Area {
+                        x: rect.loc.x,
+                        y: rect.loc.y,
+                        width: rect.size.w,
+                        height: rect.size.h,
+                    }
+                }
+                CastTarget::Nothing => niri_ipc::CastTarget::Nothing,
+            };
+
+            casts.push(niri_ipc::Cast {
+                id: cast.id,
+                kind: niri_ipc::CastKind::PipeWire,
+                target,
+                // PipeWire casts go through the portal, so there's no client to speak of.
+                pid: None,
+            });
+        }
+
+        let pid = |client: Option<Client>| {
+            let credentials = client?.get_credentials(&self.display_handle).ok()?;
+            Some(credentials.pid)
+        };
+
+        for session in self.image_copy_capture_state.sessions() {
+            if session.is_stopped() || !matches!(session.kind(), SessionKind::Image { .. }) {
+                continue;
+            }
+
+            let target = match session.source() {
+                ImageCaptureSource::Output(output) => {
+                    captured_outputs.push(output.clone());
+                    niri_ipc::CastTarget::Output {
+                        name: output.name(),
+                    }
+                }
+                ImageCaptureSource::Toplevel(surface) => {
+                    let Some((mapped, _)) = self.layout.find_window_and_output(surface) else {
+                        continue;
+                    };
+                    let id = mapped.id().get();
+                    captured_windows.insert(id);
+                    niri_ipc::CastTarget::Window { id }
+                }
+            };
+
+            casts.push(niri_ipc::Cast {
+                id: session.id(),
+                kind: niri_ipc::CastKind::ImageCopyCapture,
+                target,
+                pid: pid(session.resource().client()),
+            });
+        }
+
+        for (manager, queue, output) in self.screencopy_state.active_queues() {
+            captured_outputs.push(output.clone());
+
+            casts.push(niri_ipc::Cast {
+                id: queue.id(),
+                kind: niri_ipc::CastKind::WlrScreencopy,
+                target: niri_ipc::CastTarget::Output {
+                    name: output.name(),
+                },
+                pid: pid(manager.client()),
+            });
+        }
+
+        casts.sort_unstable_by_key(|cast| cast.id);
+
+        let mut outputs_to_redraw = Vec::new();
+        self.layout.with_windows_mut(|mapped, output| {
+            let is_being_captured = captured_windows.contains(&mapped.id().get());
+            if mapped.set_is_being_captured(is_being_captured) {
+                outputs_to_redraw.extend(output.cloned());
+            }
+        });
+        for output in outputs_to_redraw {
+            self.queue_redraw(&output);
+        }
+
+        // Keep the outputs in a stable order so that comparing them is meaningful.
+        let captured_outputs = self
+            .global_space
+            .outputs()
+            .filter(|output| captured_outputs.contains(output))
+            .cloned()
+            .collect();
+        if self
+            .capture_indicator
+            .update(captured_outputs, !casts.is_empty())
+        {
+            self.queue_redraw_all();
+        }
+
+        self.active_casts = casts;
+    }
+
+    /// Returns outputs intersecting a rectangle in the global logical coordinate space.
+    pub fn outputs_intersecting(&self, rect: Rectangle<i32, Logical>) -> Vec<Output> {
+        self.global_space
+            .outputs()
+            .filter(|output| {
+                let geo = self.global_space.output_geometry(output).unwrap();
+                geo.overlaps(rect)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Computes the buffer size and scale for an area screencast.
+    ///
+    /// The area is cast at the highest scale among the outputs it intersects, so that no output
+    /// loses detail. Returns `None` if the area doesn't intersect any output.
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn area_cast_size(
+        &self,
+        rect: Rectangle<i32, Logical>,
+    ) -> Option<(Size<i32, Physical>, Scale<f64>)> {
+        let scale = self
+            .outputs_intersecting(rect)
+            .iter()
+            .map(|output| output.current_scale().fractional_scale())
+            .reduce(f64::max)?;
+        let scale = Scale::from(scale);
+        let size = rect.size.to_f64().to_physical_precise_ceil(scale);
+        Some((size, scale))
+    }
+
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn render_areas_for_screen_cast(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        target_presentation_time: Duration,
+    ) {
+        let _span = tracy_client::span!("Niri::render_areas_for_screen_cast");
+
+        let output_geo = self.global_space.output_geometry(output).unwrap();
+
+        let mut casts_to_stop = vec![];
+
+        let mut casts = mem::take(&mut self.casts);
+        for cast in &mut casts {
+            if !cast.is_active.get() {
+                continue;
+            }
+
+            let CastTarget::Area { rect } = cast.target else {
+                continue;
+            };
+
+            // Areas spanning several outputs are rendered on the redraw of any of them.
+            if !output_geo.overlaps(rect) {
+                continue;
+            }
+
+            let Some((size, scale)) = self.area_cast_size(rect) else {
+                continue;
+            };
+
+            match cast.ensure_size(size, scale) {
+                Ok(CastSizeChange::Ready) => (),
+                Ok(CastSizeChange::Pending) => continue,
+                Err(err) => {
+                    warn!("error updating stream size, stopping screencast: {err:?}");
+                    casts_to_stop.push(cast.session_id);
+                }
+            }
+
+            if cast.check_time_and_schedule(&self.event_loop, output, target_presentation_time) {
+                continue;
+            }
+
+            // Render every output intersecting the area and place it at its offset, rescaled to
+            // the cast scale.
+            let include_pointer = matches!(cast.cursor_mode, CursorMode::Embedded);
+            let mut pointer_over_area = false;
+            let mut elements = vec![];
+            for out in self.outputs_intersecting(rect) {
+                let out_geo = self.global_space.output_geometry(&out).unwrap();
+                let out_scale = out.current_scale().fractional_scale();
+                let offset = (out_geo.loc - rect.loc)
+                    .to_f64()
+                    .to_physical_precise_round(scale);
+                let to_area = |elem| {
+                    let elem = RescaleRenderElement::from_element(
+                        elem,
+                        Point::from((0, 0)),
+                        scale.x / out_scale,
+                    );
+                    RelocateRenderElement::from_element(elem, offset, Relocate::Relative)
+                };
+
+                if matches!(cast.cursor_mode, CursorMode::Metadata) {
+                    let out_scale = Scale::from(out_scale);
+                    let (pointer_elements, position) =
+                        self.pointer_for_screen_cast(renderer, &out, out_scale);
+                    // Only the output under the pointer decides where the cursor is.
+                    if let Some(position) = position {
+                        let position = position.to_f64().to_logical(out_scale);
+                        let position = position.to_physical_precise_round(scale) + offset;
+                        pointer_over_area = true;
+                        let pointer_elements: Vec<_> =
+                            pointer_elements.into_iter().map(to_area).collect();
+                        cast.set_cursor(renderer, &pointer_elements, Some(position), scale);
+                    }
+                }
+
+                let out_elements =
+                    self.render(renderer, &out, include_pointer, RenderTarget::Screencast);
+                elements.extend(out_elements.into_iter().map(to_area));
+            }
+
+            if matches!(cast.cursor_mode, CursorMode::Metadata) && !pointer_over_area {
+                cast.hide_cursor();
+            }
+
+            if cast.dequeue_buffer_and_render(renderer, &elements, size, scale) {
+                cast.last_frame_time = target_presentation_time;
+            }
+        }
+        self.casts = casts;
+
+        for id in casts_to_stop {
+            self.stop_cast(id);
+        }
+    }
+
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn render_windows_for_screen_cast(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        target_presentation_time: Duration,
+    ) {
+        let _span = tracy_client::span!("Niri::render_windows_for_screen_cast");
+
+        let scale = Scale::from(output.current_scale().fractional_scale());
+
+        let mut casts_to_stop = vec![];
+
+        let mut casts = mem::take(&mut self.casts);
+        for cast in &mut casts {
+            if !cast.is_active.get() {
+                continue;
+            }
+
+            let CastTarget::Window { id } = cast.target else {
+                continue;
+            };
+
+            let mut windows = self.layout.windows_for_output(output);
+            let Some(mapped) = windows.find(|win| win.id().get() == id) else {
+                continue;
+            };
+
+            let bbox = mapped
+                .window
+                .bbox_with_popups()
+                .to_physical_precise_up(scale);
+
+            match cast.ensure_size(bbox.size, scale) {
+                Ok(CastSizeChange::Ready) => (),
+                Ok(CastSizeChange::Pending) => continue,
+                Err(err) => {
+                    warn!("error updating stream size, stopping screencast: {err:?}");
+                    casts_to_stop.push(cast.session_id);
+                }
+            }
+
+            if cast.check_time_and_schedule(&self.event_loop, output, target_presentation_time) {
+                continue;
+            }
+
+            if matches!(cast.cursor_mode, CursorMode::Metadata) {
+                let (pointer_elements, position) =
+                    self.pointer_for_window_screen_cast(renderer, output, mapped, scale);
+                cast.set_cursor(renderer, &pointer_elements, position, scale);
+            }
+
+            // FIXME: pointer.
+            let elements: Vec<_> = mapped.render_for_screen_cast(renderer, scale).collect();
+
+            if cast.dequeue_buffer_and_render(renderer, &elements, bbox.size, scale) {
+                cast.last_frame_time = target_presentation_time;
+            }
+        }
+        self.casts = casts;
+
+        for id in casts_to_stop {
+            self.stop_cast(id);
+        }
+    }
+
+    #[cfg(feature = "xdp-gnome-screencast")]
+    fn render_window_for_screen_cast(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        window_id: u64,
+        target_presentation_time: Duration,
+    ) {
+        let _span = tracy_client::span!("Niri::render_window_for_screen_cast");
+
+        let mut window = None;
+        self.layout.with_windows(|mapped, _, _| {
+            if mapped.id().get() != window_id {
+                return;
+            }
+
+            window = Some(mapped.window.clone());
+        });
+
+        let Some(window) = window else {
             return;
         };
 
@@ -3889,6 +5431,7 @@ impl Niri {
             .to_physical_precise_up(scale);
 
         let mut elements = None;
+        let mut pointer = None;
         let mut casts_to_stop = vec![];
 
         let mut casts = mem::take(&mut self.casts);
@@ -3901,7 +5444,7 @@ impl Niri {
                 continue;
             }
 
-            match cast.ensure_size(bbox.size) {
+            match cast.ensure_size(bbox.size, scale) {
                 Ok(CastSizeChange::Ready) => (),
                 Ok(CastSizeChange::Pending) => continue,
                 Err(err) => {
@@ -3914,6 +5457,13 @@ impl Niri {
                 continue;
             }
 
+            if matches!(cast.cursor_mode, CursorMode::Metadata) {
+                let (pointer_elements, position) = pointer.get_or_insert_with(|| {
+                    self.pointer_for_window_screen_cast(renderer, output, mapped, scale)
+                });
+                cast.set_cursor(renderer, pointer_elements, *position, scale);
+            }
+
             let elements = elements.get_or_insert_with(|| {
                 // FIXME: pointer.
                 mapped
@@ -4111,6 +5661,280 @@ impl Niri {
         Ok((sync, damages))
     }
 
+    pub fn render_for_image_copy_capture(&mut self, renderer: &mut GlesRenderer, output: &Output) {
+        let _span = tracy_client::span!("Niri::render_for_image_copy_capture");
+
+        if self.image_copy_capture_state.is_empty() {
+            return;
+        }
+
+        let mut protocol_state = mem::take(&mut self.image_copy_capture_state);
+
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let transform = output.current_transform();
+        let output_size = transform.transform_size(output.current_mode().unwrap().size);
+
+        // Cursor elements and their geometry in the output's physical coordinates.
+        let pointer_elements = self.pointer_element(renderer, output);
+        let cursor_geo = pointer_elements
+            .iter()
+            .map(|elem| elem.geometry(scale))
+            .reduce(|a, b| a.merge(b));
+        let cursor_elements = cursor_geo.map(|geo| {
+            pointer_elements
+                .iter()
+                .map(|elem| {
+                    RelocateRenderElement::from_element(
+                        elem,
+                        geo.loc.upscale(-1),
+                        Relocate::Relative,
+                    )
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Position of the pointer within the output and the window under it.
+        let output_geo = self.global_space.output_geometry(output).unwrap();
+        let pointer_pos = self
+            .tablet_cursor_location
+            .unwrap_or_else(|| self.seat.get_pointer().unwrap().current_location());
+        let pointer_pos_within_output = pointer_pos - output_geo.loc.to_f64();
+        let pointer_on_output = cursor_geo.is_some() && output_geo.to_f64().contains(pointer_pos);
+        let pointer_physical: Point<i32, Physical> =
+            pointer_pos_within_output.to_physical_precise_round(scale);
+        let window_under_pointer = pointer_on_output
+            .then(|| self.layout.window_under(output, pointer_pos_within_output))
+            .flatten();
+
+        // Returns the cursor position and hotspot relative to the source, if it's over it.
+        let cursor_for_source = |source: &ImageCaptureSource| {
+            let hotspot = pointer_physical - cursor_geo?.loc;
+            match source {
+                ImageCaptureSource::Output(_) => {
+                    pointer_on_output.then_some((pointer_physical, hotspot))
+                }
+                ImageCaptureSource::Toplevel(surface) => {
+                    let (mapped, win_pos) = window_under_pointer?;
+                    if mapped.toplevel().wl_surface() != surface {
+                        return None;
+                    }
+                    let bbox = mapped.window.bbox_with_popups();
+                    let origin = win_pos? + bbox.loc.to_f64();
+                    let position = pointer_physical - origin.to_physical_precise_round(scale);
+                    Some((position, hotspot))
+                }
+            }
+        };
+
+        // Returns whether the source is rendered on this output.
+        let is_on_output = |source: &ImageCaptureSource| match source {
+            ImageCaptureSource::Output(source_output) => source_output == output,
+            ImageCaptureSource::Toplevel(surface) => self
+                .layout
+                .find_window_and_output(surface)
+                .is_some_and(|(_, win_output)| win_output == output),
+        };
+
+        for cursor_session in protocol_state.cursor_sessions_mut() {
+            let source = cursor_session.source().clone();
+            if is_on_output(&source) {
+                cursor_session.set_cursor(cursor_for_source(&source));
+            }
+        }
+
+        let output_elements = OnceCell::new();
+        let output_elements_with_pointer = OnceCell::new();
+
+        for session in protocol_state.sessions_mut() {
+            if session.is_stopped() || !is_on_output(session.source()) {
+                continue;
+            }
+
+            match (session.source().clone(), session.kind().clone()) {
+                (ImageCaptureSource::Output(_), SessionKind::Image { paint_cursors }) => {
+                    session.set_buffer_size(output_size);
+                    if !session.has_pending_frame() {
+                        continue;
+                    }
+
+                    let elements = if paint_cursors {
+                        &output_elements_with_pointer
+                    } else {
+                        &output_elements
+                    };
+                    let elements = elements.get_or_init(|| {
+                        self.render(renderer, output, paint_cursors, RenderTarget::ScreenCapture)
+                    });
+                    Self::render_image_copy_capture_frame(
+                        renderer,
+                        &self.event_loop,
+                        session,
+                        output_size,
+                        scale,
+                        elements,
+                    );
+                }
+                (ImageCaptureSource::Toplevel(surface), SessionKind::Image { paint_cursors }) => {
+                    let Some((mapped, _)) = self.layout.find_window_and_output(&surface) else {
+                        continue;
+                    };
+
+                    let bbox = mapped
+                        .window
+                        .bbox_with_popups()
+                        .to_physical_precise_up(scale);
+                    session.set_buffer_size(bbox.size);
+                    if !session.has_pending_frame() {
+                        continue;
+                    }
+
+                    let source = session.source().clone();
+                    let cursor = paint_cursors.then(|| cursor_for_source(&source)).flatten();
+
+                    let mut elements = Vec::new();
+                    if let Some((position, _)) = cursor {
+                        // Move the pointer from the output's coordinates into the window's.
+                        let offset = position - pointer_physical;
+                        elements.extend(
+                            self.pointer_element(renderer, output)
+                                .into_iter()
+                                .map(|elem| {
+                                    RelocateRenderElement::from_element(
+                                        elem,
+                                        offset,
+                                        Relocate::Relative,
+                                    )
+                                })
+                                .map(WindowCaptureRenderElements::from),
+                        );
+                    }
+                    elements.extend(
+                        mapped
+                            .render_for_screen_cast(renderer, scale)
+                            .map(WindowCaptureRenderElements::from),
+                    );
+                    Self::render_image_copy_capture_frame(
+                        renderer,
+                        &self.event_loop,
+                        session,
+                        bbox.size,
+                        scale,
+                        &elements,
+                    );
+                }
+                (source, SessionKind::Cursor(_)) => {
+                    // Wait for the cursor to appear over the source to know its size.
+                    let (Some(geo), Some(elements)) = (cursor_geo, &cursor_elements) else {
+                        continue;
+                    };
+                    if cursor_for_source(&source).is_none() {
+                        continue;
+                    }
+
+                    session.set_buffer_size(geo.size);
+                    if !session.has_pending_frame() {
+                        continue;
+                    }
+
+                    Self::render_image_copy_capture_frame(
+                        renderer,
+                        &self.event_loop,
+                        session,
+                        geo.size,
+                        scale,
+                        elements,
+                    );
+                }
+            }
+        }
+
+        self.image_copy_capture_state = protocol_state;
+    }
+
+    fn render_image_copy_capture_frame(
+        renderer: &mut GlesRenderer,
+        event_loop: &LoopHandle<'static, State>,
+        session: &mut ImageCopyCaptureSession,
+        size: Size<i32, Physical>,
+        scale: Scale<f64>,
+        elements: &[impl RenderElement<GlesRenderer>],
+    ) {
+        let (damage_tracker, frame) = session.split();
+        let Some(frame) = frame else {
+            return;
+        };
+
+        let OutputModeSource::Static {
+            size: last_size,
+            scale: last_scale,
+            ..
+        } = damage_tracker.mode().clone()
+        else {
+            unreachable!("damage tracker must have static mode");
+        };
+        if size != last_size || scale != last_scale {
+            *damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
+        }
+
+        // Just checked damage tracker has static mode.
+        let damage = damage_tracker
+            .damage_output(1, elements)
+            .unwrap()
+            .0
+            .cloned();
+
+        // Regions that the client changed in its buffer need to be copied again regardless of
+        // whether the source changed there.
+        let buffer_damage = frame.buffer_damage();
+        let mut damage = match damage {
+            Some(damage) => damage,
+            None if buffer_damage.is_empty() => {
+                trace!("no damage found, waiting till next redraw");
+                return;
+            }
+            None => Vec::new(),
+        };
+        damage.extend_from_slice(buffer_damage);
+
+        let elements = elements.iter().rev();
+        let res = match frame.buffer() {
+            ScreencopyBuffer::Dmabuf(dmabuf) => render_to_dmabuf(
+                renderer,
+                dmabuf.clone(),
+                size,
+                scale,
+                Transform::Normal,
+                elements,
+            )
+            .context("error rendering to image copy capture dmabuf")
+            .map(Some),
+            ScreencopyBuffer::Shm(wl_buffer) => render_to_shm(
+                renderer,
+                wl_buffer,
+                size,
+                scale,
+                Transform::Normal,
+                elements,
+            )
+            .context("error rendering to image copy capture shm buffer")
+            .map(|()| None),
+        };
+
+        if let Err(err) = renderer.unbind() {
+            warn!("error unbinding after rendering for image copy capture: {err:?}");
+        }
+
+        let frame = session.take_pending_frame().unwrap();
+        match res {
+            Ok(sync) => frame.submit_after_sync(damage, sync, event_loop),
+            Err(err) => {
+                // Report full damage next time.
+                session.reset_damage();
+                warn!("error rendering for image copy capture: {err:?}");
+            }
+        }
+    }
+
     #[cfg(feature = "xdp-gnome-screencast")]
     fn stop_cast(&mut self, session_id: usize) {
         let _span = tracy_client::span!("Niri::stop_cast");
@@ -4148,6 +5972,17 @@ impl Niri {
     pub fn stop_casts_for_target(&mut self, target: CastTarget) {
         let _span = tracy_client::span!("Niri::stop_casts_for_target");
 
+        // Dynamic casts outlive their target and show a placeholder instead.
+        let mut cleared = false;
+        for cast in &mut self.casts {
+            cleared |= cast.clear_dynamic_target(&target);
+        }
+        if cleared {
+            self.event_loop.insert_idle(|state| {
+                state.redraw_cast(CastTarget::Nothing);
+            });
+        }
+
         // This is O(N^2) but it shouldn't be a problem I think.
         let ids: Vec<_> = self
             .casts
@@ -4252,6 +6087,7 @@ impl Niri {
         &mut self,
         renderer: &mut GlesRenderer,
         output: &Output,
+        options: ScreenshotOptions,
     ) -> anyhow::Result<()> {
         let _span = tracy_client::span!("Niri::screenshot");
 
@@ -4274,7 +6110,7 @@ impl Niri {
             elements,
         )?;
 
-        self.save_screenshot(size, pixels)
+        self.save_screenshot(size, pixels, options)
             .context("error saving screenshot")
     }
 
@@ -4283,6 +6119,7 @@ impl Niri {
         renderer: &mut GlesRenderer,
         output: &Output,
         mapped: &Mapped,
+        options: ScreenshotOptions,
     ) -> anyhow::Result<()> {
         let _span = tracy_client::span!("Niri::screenshot_window");
 
@@ -4300,6 +6137,53 @@ impl Niri {
             alpha,
             RenderTarget::ScreenCapture,
         );
+        self.screenshot_elements(renderer, scale, &elements, options)
+    }
+
+    pub fn screenshot_layer_surface(
+        &self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        surface: &LayerSurface,
+        options: ScreenshotOptions,
+    ) -> anyhow::Result<()> {
+        let _span = tracy_client::span!("Niri::screenshot_layer_surface");
+
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+            surface.render_elements(renderer, Point::from((0, 0)), scale, 1.);
+        self.screenshot_elements(renderer, scale, &elements, options)
+    }
+
+    /// Saves a surface picked in the screenshot UI.
+    pub fn screenshot_picked_surface(
+        &self,
+        renderer: &mut GlesRenderer,
+        picked: &PickedSurface,
+        options: ScreenshotOptions,
+    ) -> anyhow::Result<()> {
+        match picked {
+            PickedSurface::Window(wl_surface) => {
+                let (mapped, output) = self
+                    .layout
+                    .find_window_and_output(wl_surface)
+                    .context("the window is gone")?;
+                self.screenshot_window(renderer, output, mapped, options)
+            }
+            PickedSurface::Layer { surface, output } => {
+                self.screenshot_layer_surface(renderer, output, surface, options)
+            }
+        }
+    }
+
+    /// Saves a screenshot of `elements` cropped to their bounding box.
+    fn screenshot_elements(
+        &self,
+        renderer: &mut GlesRenderer,
+        scale: Scale<f64>,
+        elements: &[impl RenderElement<GlesRenderer>],
+        options: ScreenshotOptions,
+    ) -> anyhow::Result<()> {
         let geo = elements
             .iter()
             .map(|ele| ele.geometry(scale))
@@ -4318,7 +6202,7 @@ impl Niri {
             elements,
         )?;
 
-        self.save_screenshot(geo.size, pixels)
+        self.save_screenshot(geo.size, pixels, options)
             .context("error saving screenshot")
     }
 
@@ -4326,14 +6210,28 @@ impl Niri {
         &self,
         size: Size<i32, Physical>,
         pixels: Vec<u8>,
+        options: ScreenshotOptions,
     ) -> anyhow::Result<()> {
-        let path = match make_screenshot_path(&self.config.borrow()) {
-            Ok(path) => path,
-            Err(err) => {
-                warn!("error making screenshot path: {err:?}");
-                None
-            }
+        let ScreenshotOptions {
+            write_to_disk,
+            path,
+            on_saved,
+        } = options;
+
+        let config = self.config.borrow();
+        let format = config.screenshot_format;
+
+        let path = if write_to_disk {
+            let res = match &path {
+                Some(path) => format_screenshot_path(path).map(Some),
+                None => make_screenshot_path(&config),
+            };
+            res.map(|path| path.map(|path| with_screenshot_extension(path, format.format)))
+                .context("error making screenshot path")
+        } else {
+            Ok(None)
         };
+        drop(config);
 
         // Prepare to set the encoded image as our clipboard selection. This must be done from the
         // main thread.
@@ -4344,7 +6242,7 @@ impl Niri {
                     set_data_device_selection(
                         &state.niri.display_handle,
                         &state.niri.seat,
-                        vec![String::from("image/png")],
+                        vec![String::from(format.format.mime_type())],
                         buf.clone(),
                     );
                 }
@@ -4354,20 +6252,27 @@ impl Niri {
 
         // Encode and save the image in a thread as it's slow.
         thread::spawn(move || {
-            let mut buf = vec![];
-
-            let w = std::io::Cursor::new(&mut buf);
-            if let Err(err) = write_png_rgba8(w, size.w as u32, size.h as u32, &pixels) {
-                warn!("error encoding screenshot image: {err:?}");
-                return;
-            }
+            let buf = match encode_screenshot(&format, size.w as u32, size.h as u32, &pixels) {
+                Ok(buf) => buf,
+                Err(err) => {
+                    let err = err.context("error encoding screenshot image");
+                    warn!("{err:?}");
+                    if let Some(on_saved) = on_saved {
+                        on_saved(Err(err));
+                    }
+                    return;
+                }
+            };
 
             let buf: Arc<[u8]> = Arc::from(buf.into_boxed_slice());
             let _ = tx.send(buf.clone());
 
-            let mut image_path = None;
+            let res = path.and_then(|path| {
+                let Some(path) = path else {
+                    debug!("not saving screenshot to disk");
+                    return Ok(None);
+                };
 
-            if let Some(path) = path {
                 debug!("saving screenshot to {path:?}");
 
                 if let Some(parent) = path.parent() {
@@ -4378,14 +6283,18 @@ impl Niri {
                     }
                 }
 
-                match std::fs::write(&path, buf) {
-                    Ok(()) => image_path = Some(path),
-                    Err(err) => {
-                        warn!("error saving screenshot image: {err:?}");
-                    }
-                }
-            } else {
-                debug!("not saving screenshot to disk");
+                std::fs::write(&path, buf)
+                    .with_context(|| format!("error saving screenshot image to {path:?}"))?;
+                Ok(Some(path))
+            });
+
+            if let Err(err) = &res {
+                warn!("{err:?}");
+            }
+            let image_path = res.as_ref().ok().cloned().flatten();
+
+            if let Some(on_saved) = on_saved {
+                on_saved(res);
             }
 
             #[cfg(feature = "dbus")]
@@ -4439,9 +6348,11 @@ impl Niri {
             elements,
         )?;
 
+        // This screenshot is always a PNG, regardless of screenshot-format.
         let path = make_screenshot_path(&self.config.borrow())
             .ok()
             .flatten()
+            .map(|path| with_screenshot_extension(path, niri_config::ImageFormat::Png))
             .unwrap_or_else(|| {
                 let mut path = env::temp_dir();
                 path.push("screenshot.png");
@@ -4459,7 +6370,14 @@ impl Niri {
             };
 
             let w = std::io::BufWriter::new(file);
-            if let Err(err) = write_png_rgba8(w, size.w as u32, size.h as u32, &pixels) {
+            let res = crate::utils::write_png_rgba8(
+                w,
+                size.w as u32,
+                size.h as u32,
+                &pixels,
+                png::Compression::Default,
+            );
+            if let Err(err) = res {
                 warn!("error encoding screenshot image: {err:?}");
                 return;
             }
@@ -4811,6 +6729,13 @@ impl ClientData for ClientState {
     fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
 }
 
+niri_render_elements! {
+    WindowCaptureRenderElements<R> => {
+        Window = WindowCastRenderElements<R>,
+        Pointer = RelocateRenderElement<OutputRenderElements<R>>,
+    }
+}
+
 niri_render_elements! {
     OutputRenderElements<R> => {
         Monitor = MonitorRenderElement<R>,
@@ -4820,6 +6745,7 @@ niri_render_elements! {
         SolidColor = SolidColorRenderElement,
         ScreenshotUi = ScreenshotUiRenderElement,
         Texture = PrimaryGpuTextureRenderElement,
+        CaptureIndicator = CaptureIndicatorRenderElement,
         // Used for the CPU-rendered panels.
         RelocatedMemoryBuffer = RelocateRenderElement<MemoryRenderBufferRenderElement<R>>,
     }
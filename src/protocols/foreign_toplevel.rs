@@ -11,6 +11,7 @@ use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{
     Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
 };
+use smithay::wayland::foreign_toplevel_list::{ForeignToplevelHandle, ForeignToplevelListState};
 use smithay::wayland::shell::xdg::{ToplevelStateSet, XdgToplevelSurfaceRoleAttributes};
 use wayland_protocols_wlr::foreign_toplevel::v1::server::{
     zwlr_foreign_toplevel_handle_v1, zwlr_foreign_toplevel_manager_v1,
@@ -43,6 +44,8 @@ struct ToplevelData {
     states: ArrayVec<u32, 3>,
     output: Option<Output>,
     instances: HashMap<ZwlrForeignToplevelHandleV1, Vec<WlOutput>>,
+    /// Handle for ext-foreign-toplevel-list.
+    ext_handle: ForeignToplevelHandle,
     // FIXME: parent.
 }
 
@@ -74,6 +77,7 @@ pub fn refresh(state: &mut State) {
     let _span = tracy_client::span!("foreign_toplevel::refresh");
 
     let protocol_state = &mut state.niri.foreign_toplevel_state;
+    let list_state = &mut state.niri.foreign_toplevel_list_state;
 
     // Handle closed windows.
     protocol_state.toplevels.retain(|surface, data| {
@@ -84,6 +88,7 @@ pub fn refresh(state: &mut State) {
         for instance in data.instances.keys() {
             instance.closed();
         }
+        list_state.remove_toplevel(&data.ext_handle);
 
         false
     });
@@ -100,7 +105,7 @@ pub fn refresh(state: &mut State) {
             if state.niri.keyboard_focus.surface() == Some(wl_surface) {
                 focused = Some((mapped.window.clone(), output.cloned()));
             } else {
-                refresh_toplevel(protocol_state, wl_surface, role, output, false);
+                refresh_toplevel(protocol_state, list_state, wl_surface, role, output, false);
             }
         });
     });
@@ -110,7 +115,14 @@ pub fn refresh(state: &mut State) {
         let toplevel = window.toplevel().expect("no X11 support");
         let wl_surface = toplevel.wl_surface();
         with_toplevel_role(toplevel, |role| {
-            refresh_toplevel(protocol_state, wl_surface, role, output.as_ref(), true);
+            refresh_toplevel(
+                protocol_state,
+                list_state,
+                wl_surface,
+                role,
+                output.as_ref(),
+                true,
+            );
         });
     }
 }
@@ -142,6 +154,7 @@ pub fn on_output_bound(state: &mut State, output: &Output, wl_output: &WlOutput)
 
 fn refresh_toplevel(
     protocol_state: &mut ForeignToplevelManagerState,
+    list_state: &mut ForeignToplevelListState,
     wl_surface: &WlSurface,
     role: &XdgToplevelSurfaceRoleAttributes,
     output: Option<&Output>,
@@ -189,6 +202,16 @@ fn refresh_toplevel(
             let something_changed =
                 new_title.is_some() || new_app_id.is_some() || states_changed || output_changed;
 
+            if new_title.is_some() || new_app_id.is_some() {
+                if let Some(new_title) = new_title {
+                    data.ext_handle.send_title(new_title);
+                }
+                if let Some(new_app_id) = new_app_id {
+                    data.ext_handle.send_app_id(new_app_id);
+                }
+                data.ext_handle.send_done();
+            }
+
             if something_changed {
                 for (instance, outputs) in &mut data.instances {
                     if let Some(new_title) = new_title {
@@ -224,12 +247,22 @@ fn refresh_toplevel(
         }
         Entry::Vacant(entry) => {
             // New window, start tracking it.
+            let ext_handle = list_state.new_toplevel::<State>(
+                role.title.clone().unwrap_or_default(),
+                role.app_id.clone().unwrap_or_default(),
+            );
+            // Used to find the window for ext-image-capture-source.
+            ext_handle
+                .user_data()
+                .insert_if_missing_threadsafe(|| wl_surface.clone());
+
             let mut data = ToplevelData {
                 title: role.title.clone(),
                 app_id: role.app_id.clone(),
                 states,
                 output: output.cloned(),
                 instances: HashMap::new(),
+                ext_handle,
             };
 
             for manager in &protocol_state.instances {
@@ -0,0 +1,1097 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use calloop::generic::Generic;
+use calloop::{Interest, LoopHandle, Mode, PostAction};
+use smithay::backend::allocator::{Buffer, Fourcc, Modifier};
+use smithay::backend::renderer::damage::OutputDamageTracker;
+use smithay::backend::renderer::sync::SyncPoint;
+use smithay::output::Output;
+use smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1;
+use smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_foreign_toplevel_image_capture_source_manager_v1::{
+    self, ExtForeignToplevelImageCaptureSourceManagerV1,
+};
+use smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_image_capture_source_v1::{
+    self, ExtImageCaptureSourceV1,
+};
+use smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_output_image_capture_source_manager_v1::{
+    self, ExtOutputImageCaptureSourceManagerV1,
+};
+use smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_cursor_session_v1::{
+    self, ExtImageCopyCaptureCursorSessionV1,
+};
+use smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_frame_v1::{
+    self, ExtImageCopyCaptureFrameV1, FailureReason,
+};
+use smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_manager_v1::{
+    self, ExtImageCopyCaptureManagerV1, Options,
+};
+use smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_session_v1::{
+    self, ExtImageCopyCaptureSessionV1,
+};
+use smithay::reexports::wayland_server::backend::ClientId;
+use smithay::reexports::wayland_server::protocol::wl_buffer::WlBuffer;
+use smithay::reexports::wayland_server::protocol::wl_output::Transform as WlTransform;
+use smithay::reexports::wayland_server::protocol::wl_shm::Format;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+use smithay::utils::{Physical, Point, Rectangle, Size, Transform};
+use smithay::wayland::{dmabuf, shm};
+
+use crate::protocols::screencopy::ScreencopyBuffer;
+use crate::utils::get_monotonic_time;
+use crate::utils::id::CAST_ID_COUNTER;
+
+const VERSION: u32 = 1;
+
+/// Formats advertised for and accepted in shm buffers.
+const SHM_FORMATS: [Format; 4] = [
+    Format::Xrgb8888,
+    Format::Argb8888,
+    Format::Xbgr8888,
+    Format::Abgr8888,
+];
+
+/// Formats advertised for and accepted in dmabufs, with the linear modifier.
+const DMABUF_FORMATS: [Fourcc; 4] = [
+    Fourcc::Xrgb8888,
+    Fourcc::Argb8888,
+    Fourcc::Xbgr8888,
+    Fourcc::Abgr8888,
+];
+
+/// Content that can be captured.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageCaptureSource {
+    Output(Output),
+    Toplevel(WlSurface),
+}
+
+pub struct ImageCaptureSourceData {
+    /// The source, or `None` if it was created for an inert object.
+    source: Option<ImageCaptureSource>,
+}
+
+#[derive(Default)]
+pub struct ImageCopyCaptureState {
+    sessions: Vec<Session>,
+    cursor_sessions: Vec<CursorSession>,
+    /// Render node to advertise for dmabuf capture, if any.
+    dmabuf_device: Option<libc::dev_t>,
+}
+
+pub struct ImageCopyCaptureGlobalData {
+    filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+/// Handler trait for ext-image-copy-capture.
+pub trait ImageCopyCaptureHandler {
+    fn image_copy_capture_state(&mut self) -> &mut ImageCopyCaptureState;
+
+    /// Returns the toplevel surface corresponding to a foreign toplevel handle.
+    fn toplevel_for_handle(&mut self, handle: &ExtForeignToplevelHandleV1) -> Option<WlSurface>;
+
+    /// A new session or cursor session was created for the source.
+    ///
+    /// The session will send its buffer constraints after the next render of the source.
+    fn new_session(&mut self, source: &ImageCaptureSource);
+
+    /// A session requested a new frame from the source.
+    fn frame_requested(&mut self, source: &ImageCaptureSource);
+}
+
+impl ImageCopyCaptureState {
+    pub fn new<D, F>(display: &DisplayHandle, dmabuf_device: Option<libc::dev_t>, filter: F) -> Self
+    where
+        D: GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, ImageCopyCaptureGlobalData>,
+        D: GlobalDispatch<
+            ExtForeignToplevelImageCaptureSourceManagerV1,
+            ImageCopyCaptureGlobalData,
+        >,
+        D: GlobalDispatch<ExtImageCopyCaptureManagerV1, ImageCopyCaptureGlobalData>,
+        D: Dispatch<ExtOutputImageCaptureSourceManagerV1, ()>,
+        D: Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>,
+        D: Dispatch<ExtImageCaptureSourceV1, ImageCaptureSourceData>,
+        D: Dispatch<ExtImageCopyCaptureManagerV1, ()>,
+        D: Dispatch<ExtImageCopyCaptureSessionV1, ()>,
+        D: Dispatch<ExtImageCopyCaptureCursorSessionV1, ()>,
+        D: Dispatch<ExtImageCopyCaptureFrameV1, Mutex<FrameState>>,
+        D: ImageCopyCaptureHandler,
+        D: 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Clone + Send + Sync + 'static,
+    {
+        let global_data = || ImageCopyCaptureGlobalData {
+            filter: Box::new(filter.clone()),
+        };
+        display.create_global::<D, ExtOutputImageCaptureSourceManagerV1, _>(VERSION, global_data());
+        display.create_global::<D, ExtForeignToplevelImageCaptureSourceManagerV1, _>(
+            VERSION,
+            global_data(),
+        );
+        display.create_global::<D, ExtImageCopyCaptureManagerV1, _>(VERSION, global_data());
+
+        Self {
+            sessions: Vec::new(),
+            cursor_sessions: Vec::new(),
+            dmabuf_device,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty() && self.cursor_sessions.is_empty()
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter()
+    }
+
+    pub fn sessions_mut(&mut self) -> impl Iterator<Item = &mut Session> {
+        self.sessions.iter_mut()
+    }
+
+    pub fn cursor_sessions_mut(&mut self) -> impl Iterator<Item = &mut CursorSession> {
+        self.cursor_sessions.iter_mut()
+    }
+
+    /// Stops all sessions capturing this source.
+    pub fn stop_source(&mut self, source: &ImageCaptureSource) {
+        for session in &mut self.sessions {
+            if session.source == *source {
+                session.stop();
+            }
+        }
+    }
+
+    fn session_mut(&mut self, resource: &ExtImageCopyCaptureSessionV1) -> Option<&mut Session> {
+        self.sessions.iter_mut().find(|s| s.session == *resource)
+    }
+}
+
+/// What a session captures.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionKind {
+    /// Contents of the source, optionally with the cursor painted on top.
+    Image { paint_cursors: bool },
+    /// The cursor image of a cursor session.
+    Cursor(ExtImageCopyCaptureCursorSessionV1),
+}
+
+/// Capture session.
+pub struct Session {
+    /// Unique ID of this session, as reported over IPC.
+    id: u64,
+    session: ExtImageCopyCaptureSessionV1,
+    source: ImageCaptureSource,
+    kind: SessionKind,
+    dmabuf_device: Option<libc::dev_t>,
+    buffer_size: Option<Size<i32, Physical>>,
+    damage_tracker: OutputDamageTracker,
+    /// Currently existing frame object; there can be at most one at a time.
+    frame: Option<ExtImageCopyCaptureFrameV1>,
+    /// Frame waiting to be rendered.
+    pending: Option<CaptureFrame>,
+    stopped: bool,
+}
+
+impl Session {
+    fn new(
+        session: ExtImageCopyCaptureSessionV1,
+        source: ImageCaptureSource,
+        kind: SessionKind,
+        dmabuf_device: Option<libc::dev_t>,
+    ) -> Self {
+        Self {
+            id: CAST_ID_COUNTER.next(),
+            session,
+            source,
+            kind,
+            dmabuf_device,
+            buffer_size: None,
+            damage_tracker: OutputDamageTracker::new((0, 0), 1.0, Transform::Normal),
+            frame: None,
+            pending: None,
+            stopped: false,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn resource(&self) -> &ExtImageCopyCaptureSessionV1 {
+        &self.session
+    }
+
+    pub fn source(&self) -> &ImageCaptureSource {
+        &self.source
+    }
+
+    pub fn kind(&self) -> &SessionKind {
+        &self.kind
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn has_pending_frame(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Updates the buffer size of the source, sending new constraints if it changed.
+    ///
+    /// A pending frame fails since its buffer no longer matches the constraints.
+    pub fn set_buffer_size(&mut self, size: Size<i32, Physical>) {
+        if self.stopped || self.buffer_size == Some(size) {
+            return;
+        }
+
+        self.buffer_size = Some(size);
+        self.damage_tracker = OutputDamageTracker::new(size, 1.0, Transform::Normal);
+
+        if let Some(mut frame) = self.pending.take() {
+            frame.fail(FailureReason::BufferConstraints);
+        }
+
+        self.session.buffer_size(size.w as u32, size.h as u32);
+        for format in SHM_FORMATS {
+            self.session.shm_format(format);
+        }
+        if let Some(device) = self.dmabuf_device {
+            self.session.dmabuf_device(device.to_ne_bytes().to_vec());
+            let modifier = u64::from(Modifier::Linear);
+            for format in DMABUF_FORMATS {
+                self.session
+                    .dmabuf_format(format as u32, modifier.to_ne_bytes().to_vec());
+            }
+        }
+        self.session.done();
+    }
+
+    /// Returns the damage tracker and the pending frame to render.
+    pub fn split(&mut self) -> (&mut OutputDamageTracker, Option<&CaptureFrame>) {
+        (&mut self.damage_tracker, self.pending.as_ref())
+    }
+
+    pub fn take_pending_frame(&mut self) -> Option<CaptureFrame> {
+        self.pending.take()
+    }
+
+    /// Recreates the damage tracker to report full damage for the next frame.
+    pub fn reset_damage(&mut self) {
+        let size = self.buffer_size.unwrap_or_default();
+        self.damage_tracker = OutputDamageTracker::new(size, 1.0, Transform::Normal);
+    }
+
+    /// Stops the session, for example when its source is gone.
+    pub fn stop(&mut self) {
+        if self.stopped {
+            return;
+        }
+
+        self.stopped = true;
+        if let Some(mut frame) = self.pending.take() {
+            frame.fail(FailureReason::Stopped);
+        }
+        self.session.stopped();
+    }
+}
+
+/// Cursor capture session.
+pub struct CursorSession {
+    cursor_session: ExtImageCopyCaptureCursorSessionV1,
+    source: ImageCaptureSource,
+    has_capture_session: bool,
+    /// Position and hotspot of the cursor in buffer coordinates of the source, if the cursor is
+    /// over the source.
+    cursor: Option<(Point<i32, Physical>, Point<i32, Physical>)>,
+}
+
+impl CursorSession {
+    pub fn resource(&self) -> &ExtImageCopyCaptureCursorSessionV1 {
+        &self.cursor_session
+    }
+
+    pub fn source(&self) -> &ImageCaptureSource {
+        &self.source
+    }
+
+    /// Updates the cursor position and hotspot, sending events if they changed.
+    pub fn set_cursor(&mut self, cursor: Option<(Point<i32, Physical>, Point<i32, Physical>)>) {
+        if self.cursor == cursor {
+            return;
+        }
+
+        match (self.cursor, cursor) {
+            (_, None) => self.cursor_session.leave(),
+            (last, Some((position, hotspot))) => {
+                if last.is_none() {
+                    self.cursor_session.enter();
+                }
+                if last.map(|(p, _)| p) != Some(position) {
+                    self.cursor_session.position(position.x, position.y);
+                }
+                if last.map(|(_, h)| h) != Some(hotspot) {
+                    self.cursor_session.hotspot(hotspot.x, hotspot.y);
+                }
+            }
+        }
+
+        self.cursor = cursor;
+    }
+}
+
+pub struct FrameState {
+    session: ExtImageCopyCaptureSessionV1,
+    buffer: Option<WlBuffer>,
+    /// Regions of the buffer that the client changed since it was last captured into.
+    buffer_damage: Vec<Rectangle<i32, Physical>>,
+    captured: bool,
+}
+
+/// Frame waiting to be rendered.
+pub struct CaptureFrame {
+    frame: ExtImageCopyCaptureFrameV1,
+    buffer: ScreencopyBuffer,
+    buffer_damage: Vec<Rectangle<i32, Physical>>,
+    finished: bool,
+}
+
+impl Drop for CaptureFrame {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.frame.failed(FailureReason::Unknown);
+        }
+    }
+}
+
+impl CaptureFrame {
+    /// Get the target buffer to copy to.
+    pub fn buffer(&self) -> &ScreencopyBuffer {
+        &self.buffer
+    }
+
+    /// Regions of the buffer that the client changed, and which need to be copied again even if
+    /// the source didn't change there.
+    pub fn buffer_damage(&self) -> &[Rectangle<i32, Physical>] {
+        &self.buffer_damage
+    }
+
+    fn fail(&mut self, reason: FailureReason) {
+        self.frame.failed(reason);
+        self.finished = true;
+    }
+
+    fn submit(
+        mut self,
+        damage: impl Iterator<Item = Rectangle<i32, Physical>>,
+        timestamp: Duration,
+    ) {
+        self.frame.transform(WlTransform::Normal);
+
+        for Rectangle { loc, size } in damage {
+            self.frame.damage(loc.x, loc.y, size.w, size.h);
+        }
+
+        let tv_sec_hi = (timestamp.as_secs() >> 32) as u32;
+        let tv_sec_lo = (timestamp.as_secs() & 0xFFFFFFFF) as u32;
+        let tv_nsec = timestamp.subsec_nanos();
+        self.frame.presentation_time(tv_sec_hi, tv_sec_lo, tv_nsec);
+
+        self.frame.ready();
+        self.finished = true;
+    }
+
+    pub fn submit_after_sync<T>(
+        self,
+        damage: Vec<Rectangle<i32, Physical>>,
+        sync_point: Option<SyncPoint>,
+        event_loop: &LoopHandle<'_, T>,
+    ) {
+        let timestamp = get_monotonic_time();
+        match sync_point.and_then(|s| s.export()) {
+            None => self.submit(damage.into_iter(), timestamp),
+            Some(sync_fd) => {
+                let source = Generic::new(sync_fd, Interest::READ, Mode::OneShot);
+                let mut frame = Some((self, damage));
+                event_loop
+                    .insert_source(source, move |_, _, _| {
+                        let (frame, damage) = frame.take().unwrap();
+                        frame.submit(damage.into_iter(), timestamp);
+                        Ok(PostAction::Remove)
+                    })
+                    .unwrap();
+            }
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, ImageCopyCaptureGlobalData, D>
+    for ImageCopyCaptureState
+where
+    D: GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, ImageCopyCaptureGlobalData>,
+    D: Dispatch<ExtOutputImageCaptureSourceManagerV1, ()>,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ExtOutputImageCaptureSourceManagerV1>,
+        _global_data: &ImageCopyCaptureGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+
+    fn can_view(client: Client, global_data: &ImageCopyCaptureGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ExtOutputImageCaptureSourceManagerV1, (), D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtOutputImageCaptureSourceManagerV1, ()>,
+    D: Dispatch<ExtImageCaptureSourceV1, ImageCaptureSourceData>,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _manager: &ExtOutputImageCaptureSourceManagerV1,
+        request: ext_output_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_output_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                output,
+            } => {
+                let source_data = ImageCaptureSourceData {
+                    source: Output::from_resource(&output).map(ImageCaptureSource::Output),
+                };
+                data_init.init(source, source_data);
+            }
+            ext_output_image_capture_source_manager_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ImageCopyCaptureGlobalData, D>
+    for ImageCopyCaptureState
+where
+    D: GlobalDispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ImageCopyCaptureGlobalData>,
+    D: Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ExtForeignToplevelImageCaptureSourceManagerV1>,
+        _global_data: &ImageCopyCaptureGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+
+    fn can_view(client: Client, global_data: &ImageCopyCaptureGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, (), D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>,
+    D: Dispatch<ExtImageCaptureSourceV1, ImageCaptureSourceData>,
+    D: ImageCopyCaptureHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &ExtForeignToplevelImageCaptureSourceManagerV1,
+        request: ext_foreign_toplevel_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                toplevel_handle,
+            } => {
+                let source_data = ImageCaptureSourceData {
+                    source: state
+                        .toplevel_for_handle(&toplevel_handle)
+                        .map(ImageCaptureSource::Toplevel),
+                };
+                data_init.init(source, source_data);
+            }
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ExtImageCaptureSourceV1, ImageCaptureSourceData, D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtImageCaptureSourceV1, ImageCaptureSourceData>,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _source: &ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &ImageCaptureSourceData,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_capture_source_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ExtImageCopyCaptureManagerV1, ImageCopyCaptureGlobalData, D>
+    for ImageCopyCaptureState
+where
+    D: GlobalDispatch<ExtImageCopyCaptureManagerV1, ImageCopyCaptureGlobalData>,
+    D: Dispatch<ExtImageCopyCaptureManagerV1, ()>,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ExtImageCopyCaptureManagerV1>,
+        _global_data: &ImageCopyCaptureGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+
+    fn can_view(client: Client, global_data: &ImageCopyCaptureGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ExtImageCopyCaptureManagerV1, (), D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtImageCopyCaptureManagerV1, ()>,
+    D: Dispatch<ExtImageCopyCaptureSessionV1, ()>,
+    D: Dispatch<ExtImageCopyCaptureCursorSessionV1, ()>,
+    D: ImageCopyCaptureHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ExtImageCopyCaptureManagerV1,
+        request: ext_image_copy_capture_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_manager_v1::Request::CreateSession {
+                session,
+                source,
+                options,
+            } => {
+                let Ok(options) = options.into_result() else {
+                    manager.post_error(
+                        ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                        "invalid options",
+                    );
+                    return;
+                };
+
+                let session = data_init.init(session, ());
+                let Some(source) = source_from_resource(&source) else {
+                    trace!("image copy capture client requested an inert source");
+                    session.stopped();
+                    return;
+                };
+
+                let kind = SessionKind::Image {
+                    paint_cursors: options.contains(Options::PaintCursors),
+                };
+                let protocol_state = state.image_copy_capture_state();
+                let session =
+                    Session::new(session, source.clone(), kind, protocol_state.dmabuf_device);
+                protocol_state.sessions.push(session);
+
+                state.new_session(&source);
+            }
+            ext_image_copy_capture_manager_v1::Request::CreatePointerCursorSession {
+                session,
+                source,
+                pointer: _,
+            } => {
+                let cursor_session = data_init.init(session, ());
+                let Some(source) = source_from_resource(&source) else {
+                    trace!("image copy capture client requested an inert source");
+                    return;
+                };
+
+                let protocol_state = state.image_copy_capture_state();
+                protocol_state.cursor_sessions.push(CursorSession {
+                    cursor_session,
+                    source: source.clone(),
+                    has_capture_session: false,
+                    cursor: None,
+                });
+
+                state.new_session(&source);
+            }
+            ext_image_copy_capture_manager_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn source_from_resource(source: &ExtImageCaptureSourceV1) -> Option<ImageCaptureSource> {
+    let data = source.data::<ImageCaptureSourceData>()?;
+    data.source.clone()
+}
+
+impl<D> Dispatch<ExtImageCopyCaptureCursorSessionV1, (), D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtImageCopyCaptureCursorSessionV1, ()>,
+    D: Dispatch<ExtImageCopyCaptureSessionV1, ()>,
+    D: ImageCopyCaptureHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ExtImageCopyCaptureCursorSessionV1,
+        request: ext_image_copy_capture_cursor_session_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_cursor_session_v1::Request::GetCaptureSession { session } => {
+                let session = data_init.init(session, ());
+
+                let protocol_state = state.image_copy_capture_state();
+                let dmabuf_device = protocol_state.dmabuf_device;
+                let Some(cursor_session) = protocol_state
+                    .cursor_sessions
+                    .iter_mut()
+                    .find(|s| s.cursor_session == *resource)
+                else {
+                    // The cursor session was created for an inert source.
+                    session.stopped();
+                    return;
+                };
+
+                if cursor_session.has_capture_session {
+                    resource.post_error(
+                        ext_image_copy_capture_cursor_session_v1::Error::DuplicateSession,
+                        "get_capture_session was already sent",
+                    );
+                    return;
+                }
+                cursor_session.has_capture_session = true;
+
+                let source = cursor_session.source.clone();
+                let kind = SessionKind::Cursor(resource.clone());
+                let session = Session::new(session, source.clone(), kind, dmabuf_device);
+                protocol_state.sessions.push(session);
+
+                state.new_session(&source);
+            }
+            ext_image_copy_capture_cursor_session_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtImageCopyCaptureCursorSessionV1,
+        _data: &(),
+    ) {
+        let protocol_state = state.image_copy_capture_state();
+        protocol_state
+            .cursor_sessions
+            .retain(|s| s.cursor_session != *resource);
+
+        // The capture session of a destroyed cursor session stops producing frames.
+        for session in &mut protocol_state.sessions {
+            if session.kind == SessionKind::Cursor(resource.clone()) {
+                session.stop();
+            }
+        }
+    }
+}
+
+impl<D> Dispatch<ExtImageCopyCaptureSessionV1, (), D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtImageCopyCaptureSessionV1, ()>,
+    D: Dispatch<ExtImageCopyCaptureFrameV1, Mutex<FrameState>>,
+    D: ImageCopyCaptureHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ExtImageCopyCaptureSessionV1,
+        request: ext_image_copy_capture_session_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_session_v1::Request::CreateFrame { frame } => {
+                let frame = data_init.init(
+                    frame,
+                    Mutex::new(FrameState {
+                        session: resource.clone(),
+                        buffer: None,
+                        buffer_damage: Vec::new(),
+                        captured: false,
+                    }),
+                );
+
+                let protocol_state = state.image_copy_capture_state();
+                let Some(session) = protocol_state.session_mut(resource) else {
+                    // The session was created for an inert source.
+                    return;
+                };
+
+                if session.frame.as_ref().is_some_and(|f| f.is_alive()) {
+                    resource.post_error(
+                        ext_image_copy_capture_session_v1::Error::DuplicateFrame,
+                        "a frame already exists",
+                    );
+                    return;
+                }
+                session.frame = Some(frame);
+            }
+            ext_image_copy_capture_session_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtImageCopyCaptureSessionV1,
+        _data: &(),
+    ) {
+        let protocol_state = state.image_copy_capture_state();
+        protocol_state.sessions.retain(|s| s.session != *resource);
+    }
+}
+
+impl<D> Dispatch<ExtImageCopyCaptureFrameV1, Mutex<FrameState>, D> for ImageCopyCaptureState
+where
+    D: Dispatch<ExtImageCopyCaptureFrameV1, Mutex<FrameState>>,
+    D: ImageCopyCaptureHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        frame: &ExtImageCopyCaptureFrameV1,
+        request: ext_image_copy_capture_frame_v1::Request,
+        data: &Mutex<FrameState>,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut frame_state = data.lock().unwrap();
+
+        if matches!(request, ext_image_copy_capture_frame_v1::Request::Destroy) {
+            return;
+        }
+
+        if frame_state.captured {
+            frame.post_error(
+                ext_image_copy_capture_frame_v1::Error::AlreadyCaptured,
+                "capture was already requested",
+            );
+            return;
+        }
+
+        match request {
+            ext_image_copy_capture_frame_v1::Request::AttachBuffer { buffer } => {
+                frame_state.buffer = Some(buffer);
+            }
+            ext_image_copy_capture_frame_v1::Request::DamageBuffer {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if x < 0 || y < 0 || width <= 0 || height <= 0 {
+                    frame.post_error(
+                        ext_image_copy_capture_frame_v1::Error::InvalidBufferDamage,
+                        "invalid buffer damage",
+                    );
+                    return;
+                }
+
+                let damage = Rectangle::from_loc_and_size((x, y), (width, height));
+                frame_state.buffer_damage.push(damage);
+            }
+            ext_image_copy_capture_frame_v1::Request::Capture => {
+                let Some(buffer) = frame_state.buffer.clone() else {
+                    frame.post_error(
+                        ext_image_copy_capture_frame_v1::Error::NoBuffer,
+                        "no buffer attached",
+                    );
+                    return;
+                };
+                frame_state.captured = true;
+
+                let protocol_state = state.image_copy_capture_state();
+                let Some(session) = protocol_state.session_mut(&frame_state.session) else {
+                    frame.failed(FailureReason::Stopped);
+                    return;
+                };
+
+                if session.stopped {
+                    frame.failed(FailureReason::Stopped);
+                    return;
+                }
+
+                let Some(size) = session.buffer_size else {
+                    frame.failed(FailureReason::BufferConstraints);
+                    return;
+                };
+
+                let Some(buffer) = validate_buffer(buffer, size) else {
+                    frame.failed(FailureReason::BufferConstraints);
+                    return;
+                };
+
+                let bounds = Rectangle::from_loc_and_size((0, 0), size);
+                let buffer_damage = frame_state
+                    .buffer_damage
+                    .drain(..)
+                    .filter_map(|damage| damage.intersection(bounds))
+                    .collect();
+
+                session.pending = Some(CaptureFrame {
+                    frame: frame.clone(),
+                    buffer,
+                    buffer_damage,
+                    finished: false,
+                });
+
+                let source = session.source.clone();
+                drop(frame_state);
+                state.frame_requested(&source);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtImageCopyCaptureFrameV1,
+        data: &Mutex<FrameState>,
+    ) {
+        let frame_state = data.lock().unwrap();
+        let protocol_state = state.image_copy_capture_state();
+        let Some(session) = protocol_state.session_mut(&frame_state.session) else {
+            return;
+        };
+
+        if session.frame.as_ref() == Some(resource) {
+            session.frame = None;
+        }
+
+        if session
+            .pending
+            .as_ref()
+            .is_some_and(|p| p.frame == *resource)
+        {
+            // The frame object is gone, there's nobody to notify.
+            let mut pending = session.pending.take().unwrap();
+            pending.finished = true;
+        }
+    }
+}
+
+fn validate_buffer(buffer: WlBuffer, size: Size<i32, Physical>) -> Option<ScreencopyBuffer> {
+    if let Ok(dmabuf) = dmabuf::get_dmabuf(&buffer) {
+        let valid = is_valid_dmabuf(dmabuf.format().code, dmabuf.width(), dmabuf.height(), size);
+        return valid.then(|| ScreencopyBuffer::Dmabuf(dmabuf.clone()));
+    }
+
+    let valid = shm::with_buffer_contents(&buffer, |_, shm_len, buffer_data| {
+        is_valid_shm_buffer(
+            buffer_data.format,
+            buffer_data.width,
+            buffer_data.height,
+            buffer_data.stride,
+            shm_len,
+            size,
+        )
+    })
+    .unwrap_or(false);
+    valid.then_some(ScreencopyBuffer::Shm(buffer))
+}
+
+fn is_valid_dmabuf(format: Fourcc, width: u32, height: u32, size: Size<i32, Physical>) -> bool {
+    DMABUF_FORMATS.contains(&format) && width == size.w as u32 && height == size.h as u32
+}
+
+fn is_valid_shm_buffer(
+    format: Format,
+    width: i32,
+    height: i32,
+    stride: i32,
+    len: usize,
+    size: Size<i32, Physical>,
+) -> bool {
+    SHM_FORMATS.contains(&format)
+        && width == size.w
+        && height == size.h
+        && stride == size.w * 4
+        && len == stride as usize * height as usize
+}
+
+#[macro_export]
+macro_rules! delegate_image_copy_capture {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1: $crate::protocols::image_copy_capture::ImageCopyCaptureGlobalData
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1: $crate::protocols::image_copy_capture::ImageCopyCaptureGlobalData
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1: $crate::protocols::image_copy_capture::ImageCopyCaptureGlobalData
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1: ()
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1: ()
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_image_capture_source_v1::ExtImageCaptureSourceV1: $crate::protocols::image_copy_capture::ImageCaptureSourceData
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1: ()
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1: ()
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_cursor_session_v1::ExtImageCopyCaptureCursorSessionV1: ()
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1: std::sync::Mutex<$crate::protocols::image_copy_capture::FrameState>
+        ] => $crate::protocols::image_copy_capture::ImageCopyCaptureState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shm_and_dmabuf_formats_match() {
+        let shm: Vec<_> = SHM_FORMATS
+            .iter()
+            .map(|format| shm::shm_format_to_fourcc(*format))
+            .collect();
+        let dmabuf: Vec<_> = DMABUF_FORMATS.iter().copied().map(Some).collect();
+        assert_eq!(shm, dmabuf);
+    }
+
+    #[test]
+    fn validate_shm_buffer() {
+        let size = Size::from((100, 50));
+        let len = 100 * 4 * 50;
+
+        for format in SHM_FORMATS {
+            assert!(is_valid_shm_buffer(format, 100, 50, 400, len, size));
+        }
+        assert!(!is_valid_shm_buffer(
+            Format::Rgb565,
+            100,
+            50,
+            400,
+            len,
+            size
+        ));
+
+        // Size must match the constraints exactly.
+        assert!(!is_valid_shm_buffer(
+            Format::Argb8888,
+            50,
+            100,
+            200,
+            len,
+            size
+        ));
+        assert!(!is_valid_shm_buffer(
+            Format::Argb8888,
+            100,
+            49,
+            400,
+            len,
+            size
+        ));
+
+        // Padded rows aren't supported.
+        let padded_len = 128 * 4 * 50;
+        assert!(!is_valid_shm_buffer(
+            Format::Argb8888,
+            100,
+            50,
+            512,
+            padded_len,
+            size
+        ));
+
+        // The pool must fit the buffer exactly.
+        assert!(!is_valid_shm_buffer(
+            Format::Argb8888,
+            100,
+            50,
+            400,
+            len - 1,
+            size
+        ));
+        assert!(!is_valid_shm_buffer(
+            Format::Argb8888,
+            100,
+            50,
+            400,
+            len + 400,
+            size
+        ));
+    }
+
+    #[test]
+    fn validate_dmabuf() {
+        let size = Size::from((100, 50));
+
+        for format in DMABUF_FORMATS {
+            assert!(is_valid_dmabuf(format, 100, 50, size));
+        }
+        assert!(!is_valid_dmabuf(Fourcc::Argb2101010, 100, 50, size));
+        assert!(!is_valid_dmabuf(Fourcc::Nv12, 100, 50, size));
+
+        assert!(!is_valid_dmabuf(Fourcc::Argb8888, 50, 100, size));
+        assert!(!is_valid_dmabuf(Fourcc::Argb8888, 100, 51, size));
+    }
+}
@@ -1,7 +1,10 @@
 pub mod foreign_toplevel;
 pub mod gamma_control;
+pub mod image_copy_capture;
 pub mod mutter_x11_interop;
 pub mod output_management;
+pub mod output_power_management;
 pub mod screencopy;
+pub mod tearing_control;
 
 pub mod raw;
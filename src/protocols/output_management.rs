@@ -619,18 +619,21 @@ where
                     return;
                 };
 
-                new_config.mode = Some(niri_ipc::ConfiguredMode {
-                    width: mode.width,
-                    height: mode.height,
-                    refresh: Some(mode.refresh_rate as f64 / 1000.),
+                new_config.mode = Some(niri_config::Mode {
+                    custom: false,
+                    mode: niri_ipc::ConfiguredMode {
+                        width: mode.width,
+                        height: mode.height,
+                        refresh: Some(mode.refresh_rate as f64 / 1000.),
+                    },
                 });
+                new_config.modeline = None;
             }
             zwlr_output_configuration_head_v1::Request::SetCustomMode {
                 width,
                 height,
                 refresh,
             } => {
-                // FIXME: Support custom mode
                 let (width, height, refresh): (u16, u16, u32) =
                     match (width.try_into(), height.try_into(), refresh.try_into()) {
                         (Ok(width), Ok(height), Ok(refresh)) => (width, height, refresh),
@@ -645,20 +648,31 @@ where
                     return;
                 };
 
-                let Some(mode) = current_config.modes.iter().find(|m| {
+                // Prefer a matching advertised mode, and only compute a custom one otherwise.
+                let matching = current_config.modes.iter().find(|m| {
                     m.width == width
                         && m.height == height
                         && (refresh == 0 || m.refresh_rate == refresh)
-                }) else {
-                    warn!("SetCustomMode: no matching mode");
-                    return;
-                };
-
-                new_config.mode = Some(niri_ipc::ConfiguredMode {
-                    width: mode.width,
-                    height: mode.height,
-                    refresh: Some(mode.refresh_rate as f64 / 1000.),
                 });
+                new_config.mode = Some(match matching {
+                    Some(mode) => niri_config::Mode {
+                        custom: false,
+                        mode: niri_ipc::ConfiguredMode {
+                            width: mode.width,
+                            height: mode.height,
+                            refresh: Some(mode.refresh_rate as f64 / 1000.),
+                        },
+                    },
+                    None => niri_config::Mode {
+                        custom: true,
+                        mode: niri_ipc::ConfiguredMode {
+                            width,
+                            height,
+                            refresh: (refresh != 0).then(|| refresh as f64 / 1000.),
+                        },
+                    },
+                });
+                new_config.modeline = None;
             }
             zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
                 new_config.position = Some(niri_config::Position { x, y });
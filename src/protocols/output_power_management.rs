@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use smithay::output::Output;
+use smithay::reexports::wayland_protocols_wlr;
+use smithay::reexports::wayland_server::backend::ClientId;
+use smithay::reexports::wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+use wayland_protocols_wlr::output_power_management::v1::server::{
+    zwlr_output_power_manager_v1, zwlr_output_power_v1,
+};
+use zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1;
+use zwlr_output_power_v1::ZwlrOutputPowerV1;
+
+const VERSION: u32 = 1;
+
+pub struct OutputPowerManagementState {
+    // Power objects of existing outputs. Failed ones are removed.
+    output_powers: HashMap<Output, Vec<ZwlrOutputPowerV1>>,
+}
+
+pub struct OutputPowerManagementGlobalData {
+    filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+pub trait OutputPowerManagementHandler {
+    fn output_power_management_state(&mut self) -> &mut OutputPowerManagementState;
+    /// Returns whether the output is currently powered on, or `None` if it doesn't exist.
+    fn output_power(&mut self, output: &Output) -> Option<bool>;
+    fn set_output_powered(&mut self, output: &Output, powered: bool);
+}
+
+impl OutputPowerManagementState {
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerManagementGlobalData>,
+        D: Dispatch<ZwlrOutputPowerManagerV1, ()>,
+        D: Dispatch<ZwlrOutputPowerV1, ()>,
+        D: OutputPowerManagementHandler,
+        D: 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let global_data = OutputPowerManagementGlobalData {
+            filter: Box::new(filter),
+        };
+        display.create_global::<D, ZwlrOutputPowerManagerV1, _>(VERSION, global_data);
+
+        Self {
+            output_powers: HashMap::new(),
+        }
+    }
+
+    /// Notifies clients that the output was powered on or off.
+    pub fn output_power_changed(&mut self, output: &Output, powered: bool) {
+        let Some(output_powers) = self.output_powers.get(output) else {
+            return;
+        };
+
+        let mode = power_mode(powered);
+        for output_power in output_powers {
+            output_power.mode(mode);
+        }
+    }
+
+    pub fn output_removed(&mut self, output: &Output) {
+        if let Some(output_powers) = self.output_powers.remove(output) {
+            for output_power in output_powers {
+                output_power.failed();
+            }
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerManagementGlobalData, D>
+    for OutputPowerManagementState
+where
+    D: GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerManagementGlobalData>,
+    D: Dispatch<ZwlrOutputPowerManagerV1, ()>,
+    D: Dispatch<ZwlrOutputPowerV1, ()>,
+    D: OutputPowerManagementHandler,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwlrOutputPowerManagerV1>,
+        _manager_state: &OutputPowerManagementGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+
+    fn can_view(client: Client, global_data: &OutputPowerManagementGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerManagerV1, (), D> for OutputPowerManagementState
+where
+    D: Dispatch<ZwlrOutputPowerManagerV1, ()>,
+    D: Dispatch<ZwlrOutputPowerV1, ()>,
+    D: OutputPowerManagementHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputPowerManagerV1,
+        request: <ZwlrOutputPowerManagerV1 as Resource>::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_manager_v1::Request::GetOutputPower { id, output } => {
+                let output_power = data_init.init(id, ());
+
+                let Some(output) = Output::from_resource(&output) else {
+                    output_power.failed();
+                    return;
+                };
+                // The output may have been removed already.
+                let Some(powered) = state.output_power(&output) else {
+                    output_power.failed();
+                    return;
+                };
+
+                output_power.mode(power_mode(powered));
+                state
+                    .output_power_management_state()
+                    .output_powers
+                    .entry(output)
+                    .or_default()
+                    .push(output_power);
+            }
+            zwlr_output_power_manager_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerV1, (), D> for OutputPowerManagementState
+where
+    D: Dispatch<ZwlrOutputPowerV1, ()>,
+    D: OutputPowerManagementHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputPowerV1,
+        request: <ZwlrOutputPowerV1 as Resource>::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_v1::Request::SetMode { mode } => {
+                let powered = match mode {
+                    WEnum::Value(zwlr_output_power_v1::Mode::On) => true,
+                    WEnum::Value(zwlr_output_power_v1::Mode::Off) => false,
+                    _ => {
+                        resource.post_error(
+                            zwlr_output_power_v1::Error::InvalidMode,
+                            "invalid power mode",
+                        );
+                        return;
+                    }
+                };
+
+                let output_powers = &state.output_power_management_state().output_powers;
+                let Some((output, _)) = output_powers
+                    .iter()
+                    .find(|(_, powers)| powers.contains(resource))
+                else {
+                    // The power object has failed.
+                    return;
+                };
+                let output = output.clone();
+
+                trace!(
+                    "setting power mode for output {} to {}",
+                    output.name(),
+                    if powered { "on" } else { "off" }
+                );
+                state.set_output_powered(&output, powered);
+            }
+            zwlr_output_power_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputPowerV1, _data: &()) {
+        let output_powers = &mut state.output_power_management_state().output_powers;
+        for powers in output_powers.values_mut() {
+            powers.retain(|x| x != resource);
+        }
+    }
+}
+
+fn power_mode(powered: bool) -> zwlr_output_power_v1::Mode {
+    if powered {
+        zwlr_output_power_v1::Mode::On
+    } else {
+        zwlr_output_power_v1::Mode::Off
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_output_power_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: $crate::protocols::output_power_management::OutputPowerManagementGlobalData
+        ] => $crate::protocols::output_power_management::OutputPowerManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: ()
+        ] => $crate::protocols::output_power_management::OutputPowerManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_v1::ZwlrOutputPowerV1: ()
+        ] => $crate::protocols::output_power_management::OutputPowerManagementState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read};
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+
+    use smithay::output::{PhysicalProperties, Subpixel};
+    use smithay::reexports::wayland_server::backend::ClientData;
+    use smithay::reexports::wayland_server::Display;
+    use smithay::utils::Size;
+
+    use super::*;
+    use crate::delegate_output_power_management;
+
+    struct TestState {
+        state: OutputPowerManagementState,
+    }
+
+    impl OutputPowerManagementHandler for TestState {
+        fn output_power_management_state(&mut self) -> &mut OutputPowerManagementState {
+            &mut self.state
+        }
+
+        fn output_power(&mut self, _output: &Output) -> Option<bool> {
+            Some(true)
+        }
+
+        fn set_output_powered(&mut self, _output: &Output, _powered: bool) {}
+    }
+    delegate_output_power_management!(TestState);
+
+    struct TestClientData;
+    impl ClientData for TestClientData {}
+
+    struct Fixture {
+        display: Display<TestState>,
+        state: TestState,
+        client: Client,
+        client_stream: UnixStream,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let display = Display::<TestState>::new().unwrap();
+            let state = TestState {
+                state: OutputPowerManagementState::new::<TestState, _>(&display.handle(), |_| true),
+            };
+
+            let (client_stream, server_stream) = UnixStream::pair().unwrap();
+            client_stream.set_nonblocking(true).unwrap();
+            let client = display
+                .handle()
+                .insert_client(server_stream, Arc::new(TestClientData))
+                .unwrap();
+
+            Self {
+                display,
+                state,
+                client,
+                client_stream,
+            }
+        }
+
+        fn add_output_power(&mut self, output: &Output) -> ZwlrOutputPowerV1 {
+            let output_power = self
+                .display
+                .handle()
+                .create_resource::<ZwlrOutputPowerV1, _, TestState>(&self.client, VERSION, ())
+                .unwrap();
+            self.state
+                .state
+                .output_powers
+                .entry(output.clone())
+                .or_default()
+                .push(output_power.clone());
+            output_power
+        }
+
+        /// Returns the events sent to the client as (object id, opcode, arguments).
+        fn events(&mut self) -> Vec<(u32, u16, Vec<u32>)> {
+            self.display.flush_clients().unwrap();
+
+            let mut bytes = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                match self.client_stream.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => bytes.extend_from_slice(&buf[..n]),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => panic!("error reading events: {err:?}"),
+                }
+            }
+
+            let words: Vec<u32> = bytes
+                .chunks_exact(4)
+                .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+                .collect();
+
+            let mut events = Vec::new();
+            let mut rest = &words[..];
+            while let [id, size_opcode, ..] = rest {
+                let size = (size_opcode >> 16) as usize / 4;
+                let opcode = (size_opcode & 0xffff) as u16;
+                events.push((*id, opcode, rest[2..size].to_vec()));
+                rest = &rest[size..];
+            }
+            events
+        }
+    }
+
+    fn make_output() -> Output {
+        Output::new(
+            String::from("output"),
+            PhysicalProperties {
+                size: Size::from((1280, 720)),
+                subpixel: Subpixel::Unknown,
+                make: String::new(),
+                model: String::new(),
+            },
+        )
+    }
+
+    const EVENT_MODE: u16 = 0;
+    const EVENT_FAILED: u16 = 1;
+
+    #[test]
+    fn mode_events() {
+        let mut f = Fixture::new();
+        let a = make_output();
+        let b = make_output();
+
+        let a1 = f.add_output_power(&a).id().protocol_id();
+        let a2 = f.add_output_power(&a).id().protocol_id();
+        f.add_output_power(&b);
+
+        f.state.state.output_power_changed(&a, false);
+        let off = zwlr_output_power_v1::Mode::Off as u32;
+        assert_eq!(
+            f.events(),
+            [(a1, EVENT_MODE, vec![off]), (a2, EVENT_MODE, vec![off])]
+        );
+
+        f.state.state.output_power_changed(&a, true);
+        let on = zwlr_output_power_v1::Mode::On as u32;
+        assert_eq!(
+            f.events(),
+            [(a1, EVENT_MODE, vec![on]), (a2, EVENT_MODE, vec![on])]
+        );
+
+        // Outputs without power objects get no events.
+        f.state.state.output_power_changed(&make_output(), false);
+        assert!(f.events().is_empty());
+    }
+
+    #[test]
+    fn removed_output_fails() {
+        let mut f = Fixture::new();
+        let a = make_output();
+        let b = make_output();
+
+        let a1 = f.add_output_power(&a).id().protocol_id();
+        f.add_output_power(&b);
+
+        f.state.state.output_removed(&a);
+        assert_eq!(f.events(), [(a1, EVENT_FAILED, vec![])]);
+        assert!(!f.state.state.output_powers.contains_key(&a));
+        assert!(f.state.state.output_powers.contains_key(&b));
+
+        // Failed power objects get no more events.
+        f.state.state.output_power_changed(&a, false);
+        f.state.state.output_removed(&a);
+        assert!(f.events().is_empty());
+    }
+}
@@ -26,12 +26,23 @@ use smithay::utils::{Physical, Point, Rectangle, Size, Transform};
 use smithay::wayland::{dmabuf, shm};
 
 use crate::utils::get_monotonic_time;
+use crate::utils::id::CAST_ID_COUNTER;
 
 const VERSION: u32 = 3;
 
+/// How long a queue counts as capturing after its last copy finished.
+///
+/// Recording clients request the next copy only after the previous one is ready, so this keeps
+/// them active between frames.
+const RECURRING_COPY_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub struct ScreencopyQueue {
+    /// Unique ID of this queue, as reported over IPC.
+    id: u64,
     damage_tracker: OutputDamageTracker,
     screencopies: Vec<Screencopy>,
+    /// Output of the last finished screencopy, and when it finished.
+    last_copy: Option<(Output, Duration)>,
 }
 
 impl Default for ScreencopyQueue {
@@ -43,15 +54,35 @@ impl Default for ScreencopyQueue {
 impl ScreencopyQueue {
     pub fn new() -> Self {
         Self {
+            id: CAST_ID_COUNTER.next(),
             damage_tracker: OutputDamageTracker::new((0, 0), 1.0, Transform::Normal),
             screencopies: Vec::new(),
+            last_copy: None,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the output that this queue is capturing, if any.
+    ///
+    /// This is the output of a pending screencopy, or of a recently finished one to account for
+    /// clients that copy every frame.
+    pub fn output(&self, now: Duration) -> Option<&Output> {
+        if let Some(screencopy) = self.screencopies.first() {
+            return Some(screencopy.output());
         }
+
+        let (output, finished_at) = self.last_copy.as_ref()?;
+        (now.saturating_sub(*finished_at) < RECURRING_COPY_TIMEOUT).then_some(output)
     }
 
     pub fn split(&mut self) -> (&mut OutputDamageTracker, Option<&Screencopy>) {
         let ScreencopyQueue {
             damage_tracker,
             screencopies,
+            ..
         } = self;
         (damage_tracker, screencopies.first())
     }
@@ -61,12 +92,17 @@ impl ScreencopyQueue {
     }
 
     pub fn pop(&mut self) -> Screencopy {
-        self.screencopies.pop().unwrap()
+        let screencopy = self.screencopies.pop().unwrap();
+        self.last_copy = Some((screencopy.output().clone(), get_monotonic_time()));
+        screencopy
     }
 
     pub fn remove_output(&mut self, output: &Output) {
         self.screencopies
             .retain(|screencopy| screencopy.output() != output);
+        if self.last_copy.as_ref().is_some_and(|(o, _)| o == output) {
+            self.last_copy = None;
+        }
     }
 }
 
@@ -117,6 +153,17 @@ impl ScreencopyManagerState {
     pub fn queues_mut(&mut self) -> impl Iterator<Item = &mut ScreencopyQueue> {
         self.queues.values_mut()
     }
+
+    /// Returns the queues of live managers that are capturing an output, along with the output.
+    pub fn active_queues(
+        &self,
+    ) -> impl Iterator<Item = (&ZwlrScreencopyManagerV1, &ScreencopyQueue, &Output)> {
+        let now = get_monotonic_time();
+        self.queues.iter().filter_map(move |(manager, queue)| {
+            let output = queue.output(now)?;
+            manager.is_alive().then_some((manager, queue, output))
+        })
+    }
 }
 
 impl<D> GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData, D>
@@ -506,3 +553,60 @@ impl Screencopy {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::{PhysicalProperties, Subpixel};
+
+    use super::*;
+
+    fn make_output() -> Output {
+        Output::new(
+            String::from("output"),
+            PhysicalProperties {
+                size: Size::from((1280, 720)),
+                subpixel: Subpixel::Unknown,
+                make: String::new(),
+                model: String::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn queue_output_after_copy() {
+        let output = make_output();
+        let finished_at = Duration::from_secs(10);
+
+        let mut queue = ScreencopyQueue::new();
+        assert_eq!(queue.output(finished_at), None);
+
+        queue.last_copy = Some((output.clone(), finished_at));
+        assert_eq!(queue.output(finished_at), Some(&output));
+
+        // Clients recording every frame keep the queue capturing between copies.
+        let now = finished_at + RECURRING_COPY_TIMEOUT - Duration::from_millis(1);
+        assert_eq!(queue.output(now), Some(&output));
+
+        // The queue stops capturing once the client stops copying.
+        assert_eq!(queue.output(finished_at + RECURRING_COPY_TIMEOUT), None);
+
+        // A clock going backwards doesn't end the capture early.
+        assert_eq!(queue.output(Duration::ZERO), Some(&output));
+    }
+
+    #[test]
+    fn queue_remove_output() {
+        let output = make_output();
+        let other = make_output();
+        let finished_at = Duration::from_secs(10);
+
+        let mut queue = ScreencopyQueue::new();
+        queue.last_copy = Some((output.clone(), finished_at));
+
+        queue.remove_output(&other);
+        assert_eq!(queue.output(finished_at), Some(&output));
+
+        queue.remove_output(&output);
+        assert_eq!(queue.output(finished_at), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::Mutex;
+
+use smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::{
+    wp_tearing_control_manager_v1, wp_tearing_control_v1,
+};
+use smithay::reexports::wayland_server::backend::ClientId;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+use smithay::wayland::compositor::{self, Cacheable};
+use wp_tearing_control_manager_v1::WpTearingControlManagerV1;
+use wp_tearing_control_v1::WpTearingControlV1;
+
+const VERSION: u32 = 1;
+
+pub struct TearingControlManagerState {}
+
+pub trait TearingControlHandler {}
+
+/// Double-buffered presentation hint of a surface.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TearingControlCachedState {
+    /// Whether the client prefers tearing over vsync for this surface.
+    pub is_async: bool,
+}
+
+impl Cacheable for TearingControlCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        *self
+    }
+
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+// Whether the surface has a tearing control object, which is a protocol error to create twice.
+#[derive(Default)]
+struct TearingControlSurfaceData(Mutex<bool>);
+
+impl TearingControlManagerState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<WpTearingControlManagerV1, ()>,
+        D: Dispatch<WpTearingControlManagerV1, ()>,
+        D: Dispatch<WpTearingControlV1, WlSurface>,
+        D: TearingControlHandler,
+        D: 'static,
+    {
+        display.create_global::<D, WpTearingControlManagerV1, _>(VERSION, ());
+
+        Self {}
+    }
+}
+
+/// Returns whether the client hinted that this surface should be presented with tearing.
+pub fn surface_wants_tearing(surface: &WlSurface) -> bool {
+    compositor::with_states(surface, |states| {
+        states
+            .cached_state
+            .get::<TearingControlCachedState>()
+            .current()
+            .is_async
+    })
+}
+
+impl<D> GlobalDispatch<WpTearingControlManagerV1, (), D> for TearingControlManagerState
+where
+    D: GlobalDispatch<WpTearingControlManagerV1, ()>,
+    D: Dispatch<WpTearingControlManagerV1, ()>,
+    D: Dispatch<WpTearingControlV1, WlSurface>,
+    D: TearingControlHandler,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        manager: New<WpTearingControlManagerV1>,
+        _manager_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+}
+
+impl<D> Dispatch<WpTearingControlManagerV1, (), D> for TearingControlManagerState
+where
+    D: Dispatch<WpTearingControlManagerV1, ()>,
+    D: Dispatch<WpTearingControlV1, WlSurface>,
+    D: TearingControlHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        resource: &WpTearingControlManagerV1,
+        request: <WpTearingControlManagerV1 as Resource>::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_tearing_control_manager_v1::Request::GetTearingControl { id, surface } => {
+                let exists = compositor::with_states(&surface, |states| {
+                    states
+                        .data_map
+                        .insert_if_missing_threadsafe(TearingControlSurfaceData::default);
+                    let data = states.data_map.get::<TearingControlSurfaceData>().unwrap();
+                    let mut exists = data.0.lock().unwrap();
+                    std::mem::replace(&mut *exists, true)
+                });
+
+                if exists {
+                    resource.post_error(
+                        wp_tearing_control_manager_v1::Error::TearingControlExists,
+                        "the surface already has a tearing control object",
+                    );
+                    return;
+                }
+
+                data_init.init(id, surface);
+            }
+            wp_tearing_control_manager_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpTearingControlV1, WlSurface, D> for TearingControlManagerState
+where
+    D: Dispatch<WpTearingControlV1, WlSurface>,
+    D: TearingControlHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &WpTearingControlV1,
+        request: <WpTearingControlV1 as Resource>::Request,
+        surface: &WlSurface,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_tearing_control_v1::Request::SetPresentationHint { hint } => {
+                // The object is inert once the surface is destroyed.
+                if !surface.is_alive() {
+                    return;
+                }
+
+                // Unknown hints are treated as vsync.
+                let is_async = matches!(
+                    hint,
+                    WEnum::Value(wp_tearing_control_v1::PresentationHint::Async)
+                );
+
+                set_pending_hint(surface, is_async);
+            }
+            wp_tearing_control_v1::Request::Destroy => (),
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        _resource: &WpTearingControlV1,
+        surface: &WlSurface,
+    ) {
+        if !surface.is_alive() {
+            return;
+        }
+
+        // Destroying the object resets the hint to vsync on the next commit.
+        set_pending_hint(surface, false);
+
+        compositor::with_states(surface, |states| {
+            if let Some(data) = states.data_map.get::<TearingControlSurfaceData>() {
+                *data.0.lock().unwrap() = false;
+            }
+        });
+    }
+}
+
+fn set_pending_hint(surface: &WlSurface, is_async: bool) {
+    compositor::with_states(surface, |states| {
+        states
+            .cached_state
+            .get::<TearingControlCachedState>()
+            .pending()
+            .is_async = is_async;
+    });
+}
+
+#[macro_export]
+macro_rules! delegate_tearing_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::wp_tearing_control_manager_v1::WpTearingControlManagerV1: ()
+        ] => $crate::protocols::tearing_control::TearingControlManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::wp_tearing_control_manager_v1::WpTearingControlManagerV1: ()
+        ] => $crate::protocols::tearing_control::TearingControlManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::wp_tearing_control_v1::WpTearingControlV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::protocols::tearing_control::TearingControlManagerState);
+    };
+}
@@ -4,6 +4,7 @@ use std::io::Cursor;
 use std::iter::zip;
 use std::mem;
 use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::ptr;
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -34,23 +35,35 @@ use smithay::backend::allocator::gbm::{GbmBuffer, GbmBufferFlags, GbmDevice};
 use smithay::backend::allocator::{Format, Fourcc};
 use smithay::backend::drm::DrmDeviceFd;
 use smithay::backend::renderer::damage::OutputDamageTracker;
-use smithay::backend::renderer::element::RenderElement;
+use smithay::backend::renderer::element::utils::{Relocate, RelocateRenderElement};
+use smithay::backend::renderer::element::{Element, RenderElement};
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::output::{Output, OutputModeSource, WeakOutput};
 use smithay::reexports::calloop::generic::Generic;
 use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
 use smithay::reexports::gbm::Modifier;
-use smithay::utils::{Physical, Scale, Size, Transform};
+use smithay::utils::{Logical, Physical, Point, Rectangle, Scale, Size, Transform};
 use zbus::SignalContext;
 
 use crate::dbus::mutter_screen_cast::{self, CursorMode};
 use crate::niri::State;
-use crate::render_helpers::render_to_dmabuf;
+use crate::render_helpers::{render_to_dmabuf, render_to_vec};
 use crate::utils::get_monotonic_time;
+use crate::utils::id::CAST_ID_COUNTER;
 
 // Give a 0.1 ms allowance for presentation time errors.
 const CAST_DELAY_ALLOWANCE: Duration = Duration::from_micros(100);
 
+// Maximum cursor bitmap width and height in the metadata cursor mode. Bigger cursors are sent
+// without a bitmap.
+const CURSOR_BITMAP_SIZE: usize = 384;
+/// Largest stream size that the consumer can pick for resizable casts.
+const MAX_RESIZABLE_SIZE: u32 = 8192;
+const CURSOR_BPP: usize = 4;
+const CURSOR_META_SIZE: usize = mem::size_of::<spa_meta_cursor>()
+    + mem::size_of::<spa_meta_bitmap>()
+    + CURSOR_BITMAP_SIZE * CURSOR_BITMAP_SIZE * CURSOR_BPP;
+
 pub struct PipeWire {
     _context: Context,
     pub core: Core,
@@ -58,16 +71,31 @@ pub struct PipeWire {
 }
 
 pub enum PwToNiri {
-    StopCast { session_id: usize },
-    Redraw(CastTarget),
+    StopCast {
+        session_id: usize,
+    },
+    Redraw {
+        session_id: usize,
+    },
+    /// The consumer of a resizable cast negotiated a new size.
+    Resize {
+        session_id: usize,
+        size: Size<i32, Physical>,
+    },
 }
 
 pub struct Cast {
+    /// Unique ID of this cast, as reported over IPC.
+    pub id: u64,
     pub session_id: usize,
     pub stream: Stream,
     _listener: StreamListener<()>,
     pub is_active: Rc<Cell<bool>>,
     pub target: CastTarget,
+    /// Whether the target can be changed at runtime with the dynamic cast actions.
+    pub dynamic_target: bool,
+    /// Whether the consumer picks the stream size, and the target is resized to match.
+    resizable: bool,
     formats: FormatSet,
     state: Rc<RefCell<CastState>>,
     refresh: Rc<Cell<u32>>,
@@ -76,9 +104,29 @@ pub struct Cast {
     pub last_frame_time: Duration,
     min_time_between_frames: Rc<Cell<Duration>>,
     dmabufs: Rc<RefCell<HashMap<i64, Dmabuf>>>,
+    // Cursor metadata of each buffer, keyed by the fd of the first plane, same as dmabufs.
+    cursor_metas: Rc<RefCell<HashMap<i64, *mut spa_meta>>>,
+    cursor: CastCursor,
     scheduled_redraw: Option<RegistrationToken>,
 }
 
+/// Cursor state for the metadata cursor mode.
+#[derive(Default)]
+struct CastCursor {
+    /// Position of the cursor hotspot within the cast, or `None` if it's not over the cast.
+    position: Option<Point<i32, Physical>>,
+    /// Hotspot within the bitmap.
+    hotspot: Point<i32, Physical>,
+    /// Cursor image in RGBA.
+    bitmap: Option<(Vec<u8>, Size<i32, Physical>)>,
+    /// Damage tracker used to avoid downloading the cursor image when it doesn't change.
+    damage_tracker: Option<OutputDamageTracker>,
+    /// Whether the bitmap changed since it was last sent.
+    bitmap_changed: bool,
+    /// Whether anything changed since the cursor was last sent.
+    changed: bool,
+}
+
 #[derive(Debug)]
 pub enum CastState {
     ResizePending {
@@ -109,21 +157,29 @@ pub enum CastSizeChange {
 #[derive(Clone, PartialEq, Eq)]
 pub enum CastTarget {
     Output(WeakOutput),
-    Window { id: u64 },
+    Window {
+        id: u64,
+    },
+    /// Rectangle in the global logical coordinate space, possibly spanning several outputs.
+    Area {
+        rect: Rectangle<i32, Logical>,
+    },
+    /// Dynamic cast target that was cleared or hasn't been set yet; shows a placeholder frame.
+    Nothing,
 }
 
 macro_rules! make_params {
-    ($params:ident, $formats:expr, $size:expr, $refresh:expr, $alpha:expr) => {
+    ($params:ident, $formats:expr, $size:expr, $refresh:expr, $alpha:expr, $resizable:expr) => {
         let mut b1 = Vec::new();
         let mut b2 = Vec::new();
 
-        let o1 = make_video_params($formats, $size, $refresh, false);
+        let o1 = make_video_params($formats, $size, $refresh, false, $resizable);
         let pod1 = make_pod(&mut b1, o1);
 
         let mut p1;
         let mut p2;
         $params = if $alpha {
-            let o2 = make_video_params($formats, $size, $refresh, true);
+            let o2 = make_video_params($formats, $size, $refresh, true, $resizable);
             p2 = [pod1, make_pod(&mut b2, o2)];
             &mut p2[..]
         } else {
@@ -184,6 +240,8 @@ impl PipeWire {
         formats: FormatSet,
         session_id: usize,
         target: CastTarget,
+        dynamic_target: bool,
+        resizable: bool,
         size: Size<i32, Physical>,
         refresh: u32,
         alpha: bool,
@@ -198,14 +256,20 @@ impl PipeWire {
                 warn!("error sending StopCast to niri: {err:?}");
             }
         };
-        let target_ = target.clone();
         let to_niri_ = self.to_niri.clone();
         let redraw = move || {
-            if let Err(err) = to_niri_.send(PwToNiri::Redraw(target_.clone())) {
+            if let Err(err) = to_niri_.send(PwToNiri::Redraw { session_id }) {
                 warn!("error sending Redraw to niri: {err:?}");
             }
         };
         let redraw_ = redraw.clone();
+        let to_niri_ = self.to_niri.clone();
+        let resize = move |size: Size<u32, Physical>| {
+            let size = Size::from((size.w as i32, size.h as i32));
+            if let Err(err) = to_niri_.send(PwToNiri::Resize { session_id, size }) {
+                warn!("error sending Resize to niri: {err:?}");
+            }
+        };
 
         let stream = Stream::new(&self.core, "niri-screen-cast-src", Properties::new())
             .context("error creating Stream")?;
@@ -215,6 +279,7 @@ impl PipeWire {
         let is_active = Rc::new(Cell::new(false));
         let min_time_between_frames = Rc::new(Cell::new(Duration::ZERO));
         let dmabufs = Rc::new(RefCell::new(HashMap::new()));
+        let cursor_metas = Rc::new(RefCell::new(HashMap::new()));
         let refresh = Rc::new(Cell::new(refresh));
 
         let pending_size = Size::from((size.w as u32, size.h as u32));
@@ -303,7 +368,13 @@ impl PipeWire {
                     let format_size = Size::from((format.size().width, format.size().height));
 
                     let mut state = state.borrow_mut();
-                    if format_size != state.expected_format_size() {
+                    if format_size != state.expected_format_size() && resizable {
+                        debug!("pw stream: consumer picked a new size, resizing the target");
+                        *state = CastState::ResizePending {
+                            pending_size: format_size,
+                        };
+                        resize(format_size);
+                    } else if format_size != state.expected_format_size() {
                         if !matches!(&*state, CastState::ResizePending { .. }) {
                             warn!("pw stream: wrong size, but we're not resizing");
                             stop_cast();
@@ -394,6 +465,7 @@ impl PipeWire {
                             format_size,
                             refresh.get(),
                             format_has_alpha,
+                            resizable,
                         );
                         let pod1 = make_pod(&mut b1, o1);
 
@@ -402,6 +474,7 @@ impl PipeWire {
                             format_size,
                             refresh.get(),
                             format_has_alpha,
+                            resizable,
                         );
                         let mut params = [pod1, make_pod(&mut b2, o2)];
 
@@ -523,25 +596,33 @@ impl PipeWire {
                         ),
                     );
 
-                    // FIXME: Hidden / embedded / metadata cursor
-
-                    // let o2 = pod::object!(
-                    //     SpaTypes::ObjectParamMeta,
-                    //     ParamType::Meta,
-                    //     Property::new(SPA_PARAM_META_type,
-                    // pod::Value::Id(Id(SPA_META_Header))),
-                    //     Property::new(
-                    //         SPA_PARAM_META_size,
-                    //         pod::Value::Int(size_of::<spa_meta_header>() as i32)
-                    //     ),
-                    // );
                     let mut b1 = vec![];
-                    // let mut b2 = vec![];
-                    let mut params = [
-                        make_pod(&mut b1, o1), // make_pod(&mut b2, o2)
-                    ];
+                    let mut b2 = vec![];
+                    let pod1 = make_pod(&mut b1, o1);
+
+                    let mut p1;
+                    let mut p2;
+                    let params = if matches!(cursor_mode, CursorMode::Metadata) {
+                        let o2 = pod::object!(
+                            SpaTypes::ObjectParamMeta,
+                            ParamType::Meta,
+                            Property::new(
+                                SPA_PARAM_META_type,
+                                pod::Value::Id(spa::utils::Id(SPA_META_Cursor))
+                            ),
+                            Property::new(
+                                SPA_PARAM_META_size,
+                                pod::Value::Int(CURSOR_META_SIZE as i32)
+                            ),
+                        );
+                        p2 = [pod1, make_pod(&mut b2, o2)];
+                        &mut p2[..]
+                    } else {
+                        p1 = [pod1];
+                        &mut p1[..]
+                    };
 
-                    if let Err(err) = stream.update_params(&mut params) {
+                    if let Err(err) = stream.update_params(params) {
                         warn!("error updating stream params: {err:?}");
                         stop_cast();
                     }
@@ -549,6 +630,7 @@ impl PipeWire {
             })
             .add_buffer({
                 let dmabufs = dmabufs.clone();
+                let cursor_metas = cursor_metas.clone();
                 let stop_cast = stop_cast.clone();
                 let state = state.clone();
                 move |stream, (), buffer| {
@@ -603,6 +685,10 @@ impl PipeWire {
 
                         let fd = (*(*spa_buffer).datas).fd;
                         assert!(dmabufs.borrow_mut().insert(fd, dmabuf).is_none());
+
+                        if let Some(meta) = find_meta(spa_buffer, SPA_META_Cursor) {
+                            cursor_metas.borrow_mut().insert(fd, meta);
+                        }
                     }
 
                     // During size re-negotiation, the stream sometimes just keeps running, in
@@ -614,6 +700,7 @@ impl PipeWire {
             })
             .remove_buffer({
                 let dmabufs = dmabufs.clone();
+                let cursor_metas = cursor_metas.clone();
                 move |_stream, (), buffer| {
                     trace!("pw stream: remove_buffer");
 
@@ -624,6 +711,7 @@ impl PipeWire {
 
                         let fd = (*spa_data).fd;
                         dmabufs.borrow_mut().remove(&fd);
+                        cursor_metas.borrow_mut().remove(&fd);
                     }
                 }
             })
@@ -633,7 +721,14 @@ impl PipeWire {
         trace!("starting pw stream with size={pending_size:?}, refresh={refresh:?}");
 
         let params;
-        make_params!(params, &formats, pending_size, refresh.get(), alpha);
+        make_params!(
+            params,
+            &formats,
+            pending_size,
+            refresh.get(),
+            alpha,
+            resizable
+        );
         stream
             .connect(
                 Direction::Output,
@@ -644,11 +739,14 @@ impl PipeWire {
             .context("error connecting stream")?;
 
         let cast = Cast {
+            id: CAST_ID_COUNTER.next(),
             session_id,
             stream,
             _listener: listener,
             is_active,
             target,
+            dynamic_target,
+            resizable,
             formats,
             state,
             refresh,
@@ -657,6 +755,8 @@ impl PipeWire {
             last_frame_time: Duration::ZERO,
             min_time_between_frames,
             dmabufs,
+            cursor_metas,
+            cursor: CastCursor::default(),
             scheduled_redraw: None,
         };
         Ok(cast)
@@ -664,12 +764,35 @@ impl PipeWire {
 }
 
 impl Cast {
-    pub fn ensure_size(&self, size: Size<i32, Physical>) -> anyhow::Result<CastSizeChange> {
+    pub fn ensure_size(
+        &self,
+        size: Size<i32, Physical>,
+        scale: Scale<f64>,
+    ) -> anyhow::Result<CastSizeChange> {
         let new_size = Size::from((size.w as u32, size.h as u32));
 
         let mut state = self.state.borrow_mut();
-        if matches!(&*state, CastState::Ready { size, .. } if *size == new_size) {
-            return Ok(CastSizeChange::Ready);
+        if let CastState::Ready {
+            size,
+            damage_tracker,
+            ..
+        } = &mut *state
+        {
+            if *size == new_size {
+                // Size change will drop the damage tracker, but scale change won't, so check it
+                // here. The damage tracker must be recreated for the new scale, otherwise the
+                // damage will be computed for the old element geometry.
+                if let Some(tracker) = damage_tracker {
+                    let OutputModeSource::Static { scale: t_scale, .. } = tracker.mode() else {
+                        unreachable!();
+                    };
+                    if *t_scale != scale {
+                        *damage_tracker = None;
+                    }
+                }
+
+                return Ok(CastSizeChange::Ready);
+            }
         }
 
         if state.pending_size() == Some(new_size) {
@@ -677,6 +800,12 @@ impl Cast {
             return Ok(CastSizeChange::Pending);
         }
 
+        if self.resizable {
+            // The consumer picks the size, so wait for the target to be resized to match.
+            debug!("target size doesn't match the negotiated stream size, skipping frame");
+            return Ok(CastSizeChange::Pending);
+        }
+
         let _span = tracy_client::span!("Cast::ensure_size");
         debug!("cast size changed, updating stream size");
 
@@ -690,7 +819,8 @@ impl Cast {
             &self.formats,
             new_size,
             self.refresh.get(),
-            self.offer_alpha
+            self.offer_alpha,
+            self.resizable
         );
         self.stream
             .update_params(params)
@@ -699,6 +829,27 @@ impl Cast {
         Ok(CastSizeChange::Pending)
     }
 
+    /// Switches the target to `target` if this cast has a dynamic target.
+    ///
+    /// Returns `true` if the target changed.
+    pub fn switch_dynamic_target(&mut self, target: &CastTarget) -> bool {
+        switch_dynamic_target(self.dynamic_target, &mut self.target, target)
+    }
+
+    /// Replaces the target with [`CastTarget::Nothing`] if this cast has a dynamic target and it
+    /// is `removed`.
+    ///
+    /// Returns `true` if the target was cleared.
+    pub fn clear_dynamic_target(&mut self, removed: &CastTarget) -> bool {
+        clear_dynamic_target(self.dynamic_target, &mut self.target, removed)
+    }
+
+    /// Returns the current stream size, or the size it's being resized to.
+    pub fn size(&self) -> Size<i32, Physical> {
+        let size = self.state.borrow().expected_format_size();
+        Size::from((size.w as i32, size.h as i32))
+    }
+
     pub fn set_refresh(&mut self, refresh: u32) -> anyhow::Result<()> {
         if self.refresh.get() == refresh {
             return Ok(());
@@ -710,7 +861,14 @@ impl Cast {
 
         let size = self.state.borrow().expected_format_size();
         let params;
-        make_params!(params, &self.formats, size, refresh, self.offer_alpha);
+        make_params!(
+            params,
+            &self.formats,
+            size,
+            refresh,
+            self.offer_alpha,
+            self.resizable
+        );
         self.stream
             .update_params(params)
             .context("error updating stream params")?;
@@ -806,6 +964,105 @@ impl Cast {
         }
     }
 
+    /// Updates the cursor sent along with the frames in the metadata cursor mode.
+    ///
+    /// `elements` are the cursor elements in the cast's physical coordinate space, and `position`
+    /// is the pointer location in the same space, or `None` if the pointer isn't over the cast.
+    ///
+    /// Must be called before [`Cast::dequeue_buffer_and_render()`] so that cursor-only changes
+    /// can be sent without rendering a new frame.
+    pub fn set_cursor(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        elements: &[impl RenderElement<GlesRenderer>],
+        position: Option<Point<i32, Physical>>,
+        scale: Scale<f64>,
+    ) {
+        let _span = tracy_client::span!("Cast::set_cursor");
+
+        let geo = elements
+            .iter()
+            .map(|elem| elem.geometry(scale))
+            .reduce(|a, b| a.merge(b));
+        let (Some(position), Some(geo)) = (position, geo) else {
+            self.hide_cursor();
+            return;
+        };
+
+        let cursor = &mut self.cursor;
+        let hotspot = position - geo.loc;
+        if cursor.position != Some(position) || cursor.hotspot != hotspot {
+            cursor.position = Some(position);
+            cursor.hotspot = hotspot;
+            cursor.changed = true;
+        }
+
+        let size = geo.size;
+        if size.w as usize > CURSOR_BITMAP_SIZE || size.h as usize > CURSOR_BITMAP_SIZE {
+            if cursor.bitmap.take().is_some() {
+                cursor.bitmap_changed = true;
+                cursor.changed = true;
+            }
+            cursor.damage_tracker = None;
+            return;
+        }
+
+        let elements: Vec<_> = elements
+            .iter()
+            .map(|elem| {
+                RelocateRenderElement::from_element(elem, geo.loc.upscale(-1), Relocate::Relative)
+            })
+            .collect();
+
+        // Only download the cursor image when it actually changes.
+        let damage_tracker = cursor
+            .damage_tracker
+            .get_or_insert_with(|| OutputDamageTracker::new(size, scale, Transform::Normal));
+        let OutputModeSource::Static {
+            size: t_size,
+            scale: t_scale,
+            ..
+        } = damage_tracker.mode()
+        else {
+            unreachable!();
+        };
+        if *t_size != size || *t_scale != scale {
+            *damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
+        }
+
+        let (damage, _states) = damage_tracker.damage_output(1, &elements).unwrap();
+        if damage.is_none() && cursor.bitmap.is_some() {
+            return;
+        }
+
+        match render_to_vec(
+            renderer,
+            size,
+            scale,
+            Transform::Normal,
+            Fourcc::Abgr8888,
+            elements.iter().rev(),
+        ) {
+            Ok(pixels) => {
+                cursor.bitmap = Some((pixels, size));
+                cursor.bitmap_changed = true;
+                cursor.changed = true;
+            }
+            Err(err) => {
+                warn!("error rendering cursor bitmap: {err:?}");
+                cursor.damage_tracker = None;
+            }
+        }
+    }
+
+    /// Hides the cursor in the metadata cursor mode.
+    pub fn hide_cursor(&mut self) {
+        if self.cursor.position.is_some() {
+            self.cursor.position = None;
+            self.cursor.changed = true;
+        }
+    }
+
     pub fn dequeue_buffer_and_render(
         &mut self,
         renderer: &mut GlesRenderer,
@@ -820,16 +1077,11 @@ impl Cast {
         let damage_tracker = damage_tracker
             .get_or_insert_with(|| OutputDamageTracker::new(size, scale, Transform::Normal));
 
-        // Size change will drop the damage tracker, but scale change won't, so check it here.
-        let OutputModeSource::Static { scale: t_scale, .. } = damage_tracker.mode() else {
-            unreachable!();
-        };
-        if *t_scale != scale {
-            *damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
-        }
+        let cursor_changed =
+            matches!(self.cursor_mode, CursorMode::Metadata) && self.cursor.changed;
 
         let (damage, _states) = damage_tracker.damage_output(1, elements).unwrap();
-        if damage.is_none() {
+        if damage.is_none() && !cursor_changed {
             trace!("no damage, skipping frame");
             return false;
         }
@@ -843,6 +1095,23 @@ impl Cast {
         };
 
         let fd = buffer.datas_mut()[0].as_raw().fd;
+
+        if matches!(self.cursor_mode, CursorMode::Metadata) {
+            if let Some(meta) = self.cursor_metas.borrow().get(&fd) {
+                unsafe { write_cursor_meta(*meta, &mut self.cursor) };
+            }
+        }
+
+        if damage.is_none() {
+            // Only the cursor changed: send a buffer without frame data so that the consumer
+            // doesn't need to re-encode anything.
+            trace!("only the cursor changed, sending an empty frame");
+            for data in buffer.datas_mut() {
+                *data.chunk_mut().size_mut() = 0;
+            }
+            return true;
+        }
+
         let dmabuf = &self.dmabufs.borrow()[&fd];
 
         if let Err(err) = render_to_dmabuf(
@@ -893,11 +1162,108 @@ impl CastState {
     }
 }
 
+/// Finds the metadata of the given type in the buffer.
+unsafe fn find_meta(buffer: *mut spa_buffer, type_: u32) -> Option<*mut spa_meta> {
+    (0..(*buffer).n_metas as usize)
+        .map(|i| (*buffer).metas.add(i))
+        .find(|meta| (**meta).type_ == type_)
+}
+
+/// Writes the cursor into the buffer's cursor metadata and marks it as sent.
+unsafe fn write_cursor_meta(meta: *mut spa_meta, cursor: &mut CastCursor) {
+    if ((*meta).size as usize) < CURSOR_META_SIZE {
+        return;
+    }
+
+    let cursor_meta = (*meta).data as *mut spa_meta_cursor;
+    let bitmap_offset = mem::size_of::<spa_meta_cursor>();
+    let bitmap_meta = (*meta).data.cast::<u8>().add(bitmap_offset) as *mut spa_meta_bitmap;
+    let pixels_offset = mem::size_of::<spa_meta_bitmap>();
+
+    (*cursor_meta).id = 1;
+    (*cursor_meta).flags = 0;
+
+    // An empty bitmap hides the cursor, like in Mutter.
+    let write_empty_bitmap = || {
+        (*cursor_meta).bitmap_offset = bitmap_offset as u32;
+        (*bitmap_meta).format = VideoFormat::RGBA.as_raw();
+        (*bitmap_meta).size = spa_rectangle {
+            width: 0,
+            height: 0,
+        };
+        (*bitmap_meta).stride = 0;
+        (*bitmap_meta).offset = pixels_offset as u32;
+    };
+
+    let Some(position) = cursor.position else {
+        (*cursor_meta).position = spa_point { x: 0, y: 0 };
+        (*cursor_meta).hotspot = spa_point { x: 0, y: 0 };
+        write_empty_bitmap();
+
+        // The cursor image needs to be sent again once the pointer is back.
+        cursor.bitmap_changed = true;
+        cursor.changed = false;
+        return;
+    };
+
+    (*cursor_meta).position = spa_point {
+        x: position.x,
+        y: position.y,
+    };
+    (*cursor_meta).hotspot = spa_point {
+        x: cursor.hotspot.x,
+        y: cursor.hotspot.y,
+    };
+
+    // A zero bitmap offset means that the bitmap didn't change.
+    (*cursor_meta).bitmap_offset = 0;
+    if cursor.bitmap_changed {
+        if let Some((pixels, size)) = &cursor.bitmap {
+            (*cursor_meta).bitmap_offset = bitmap_offset as u32;
+            (*bitmap_meta).format = VideoFormat::RGBA.as_raw();
+            (*bitmap_meta).size = spa_rectangle {
+                width: size.w as u32,
+                height: size.h as u32,
+            };
+            (*bitmap_meta).stride = size.w * CURSOR_BPP as i32;
+            (*bitmap_meta).offset = pixels_offset as u32;
+
+            let dst = bitmap_meta.cast::<u8>().add(pixels_offset);
+            ptr::copy_nonoverlapping(pixels.as_ptr(), dst, pixels.len());
+        } else {
+            // The cursor is too big for the metadata.
+            write_empty_bitmap();
+        }
+    }
+
+    cursor.bitmap_changed = false;
+    cursor.changed = false;
+}
+
+fn switch_dynamic_target(dynamic: bool, target: &mut CastTarget, new: &CastTarget) -> bool {
+    if !dynamic || target == new {
+        return false;
+    }
+
+    *target = new.clone();
+    true
+}
+
+fn clear_dynamic_target(dynamic: bool, target: &mut CastTarget, removed: &CastTarget) -> bool {
+    if !dynamic || target != removed || *target == CastTarget::Nothing {
+        return false;
+    }
+
+    *target = CastTarget::Nothing;
+    true
+}
+
 fn make_video_params(
     formats: &FormatSet,
     size: Size<u32, Physical>,
     refresh: u32,
     alpha: bool,
+    resizable: bool,
 ) -> pod::Object {
     let format = if alpha {
         VideoFormat::BGRA
@@ -924,6 +1290,30 @@ fn make_video_params(
         PropertyFlags::empty()
     };
 
+    let size = Rectangle {
+        width: size.w,
+        height: size.h,
+    };
+    let size = if resizable {
+        pod::property!(
+            FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            size,
+            Rectangle {
+                width: 1,
+                height: 1,
+            },
+            Rectangle {
+                width: MAX_RESIZABLE_SIZE,
+                height: MAX_RESIZABLE_SIZE,
+            }
+        )
+    } else {
+        pod::property!(FormatProperties::VideoSize, Rectangle, size)
+    };
+
     pod::object!(
         SpaTypes::ObjectParamFormat,
         ParamType::EnumFormat,
@@ -941,14 +1331,7 @@ fn make_video_params(
                 }
             )))
         },
-        pod::property!(
-            FormatProperties::VideoSize,
-            Rectangle,
-            Rectangle {
-                width: size.w,
-                height: size.h,
-            }
-        ),
+        size,
         pod::property!(
             FormatProperties::VideoFramerate,
             Fraction,
@@ -1041,3 +1424,227 @@ fn allocate_dmabuf(
         .context("error exporting GBM buffer object as dmabuf")?;
     Ok(dmabuf)
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::{PhysicalProperties, Subpixel};
+
+    use super::*;
+
+    fn make_output() -> Output {
+        Output::new(
+            String::from("output"),
+            PhysicalProperties {
+                size: Size::from((1280, 720)),
+                subpixel: Subpixel::Unknown,
+                make: String::new(),
+                model: String::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn switch_dynamic_cast_target() {
+        let output = make_output();
+        let window = CastTarget::Window { id: 1 };
+
+        let mut target = CastTarget::Nothing;
+        assert!(switch_dynamic_target(true, &mut target, &window));
+        assert!(target == window);
+
+        // Switching to the same target is not a change.
+        assert!(!switch_dynamic_target(true, &mut target, &window));
+
+        let output_target = CastTarget::Output(output.downgrade());
+        assert!(switch_dynamic_target(true, &mut target, &output_target));
+        assert!(target == output_target);
+
+        assert!(switch_dynamic_target(
+            true,
+            &mut target,
+            &CastTarget::Nothing
+        ));
+        assert!(target == CastTarget::Nothing);
+
+        // Casts with a fixed target never switch.
+        let mut target = window.clone();
+        assert!(!switch_dynamic_target(false, &mut target, &output_target));
+        assert!(target == window);
+    }
+
+    #[test]
+    fn clear_dynamic_cast_target() {
+        let output = make_output();
+        let other = make_output();
+        let output_target = CastTarget::Output(output.downgrade());
+
+        // Another target going away doesn't affect the cast.
+        let mut target = output_target.clone();
+        assert!(!clear_dynamic_target(
+            true,
+            &mut target,
+            &CastTarget::Output(other.downgrade())
+        ));
+        assert!(!clear_dynamic_target(
+            true,
+            &mut target,
+            &CastTarget::Window { id: 1 }
+        ));
+        assert!(target == output_target);
+
+        // The dynamic cast falls back to the placeholder.
+        assert!(clear_dynamic_target(true, &mut target, &output_target));
+        assert!(target == CastTarget::Nothing);
+        assert!(!clear_dynamic_target(
+            true,
+            &mut target,
+            &CastTarget::Nothing
+        ));
+
+        // Casts with a fixed target keep it, so that they get stopped.
+        let window = CastTarget::Window { id: 1 };
+        let mut target = window.clone();
+        assert!(!clear_dynamic_target(false, &mut target, &window));
+        assert!(target == window);
+    }
+
+    struct Meta {
+        // u64 for the alignment of the meta structs.
+        data: Vec<u64>,
+        meta: spa_meta,
+    }
+
+    impl Meta {
+        fn new(size: usize) -> Self {
+            let mut data = vec![0u64; size.div_ceil(8)];
+            let meta = spa_meta {
+                type_: SPA_META_Cursor,
+                size: size as u32,
+                data: data.as_mut_ptr().cast(),
+            };
+            Self { data, meta }
+        }
+
+        fn write(&mut self, cursor: &mut CastCursor) {
+            unsafe { write_cursor_meta(&mut self.meta, cursor) }
+        }
+
+        fn cursor(&self) -> &spa_meta_cursor {
+            unsafe { &*self.data.as_ptr().cast::<spa_meta_cursor>() }
+        }
+
+        fn bitmap(&self) -> &spa_meta_bitmap {
+            let offset = mem::size_of::<spa_meta_cursor>();
+            unsafe {
+                &*self
+                    .data
+                    .as_ptr()
+                    .cast::<u8>()
+                    .add(offset)
+                    .cast::<spa_meta_bitmap>()
+            }
+        }
+
+        fn pixels(&self, len: usize) -> &[u8] {
+            let offset = mem::size_of::<spa_meta_cursor>() + mem::size_of::<spa_meta_bitmap>();
+            unsafe { std::slice::from_raw_parts(self.data.as_ptr().cast::<u8>().add(offset), len) }
+        }
+    }
+
+    fn visible_cursor() -> CastCursor {
+        CastCursor {
+            position: Some(Point::from((100, 50))),
+            hotspot: Point::from((2, 3)),
+            bitmap: Some(((0..32).collect(), Size::from((4, 2)))),
+            damage_tracker: None,
+            bitmap_changed: true,
+            changed: true,
+        }
+    }
+
+    #[test]
+    fn cursor_meta_with_bitmap() {
+        let mut meta = Meta::new(CURSOR_META_SIZE);
+        let mut cursor = visible_cursor();
+        meta.write(&mut cursor);
+
+        let c = meta.cursor();
+        assert_eq!(c.id, 1);
+        assert_eq!((c.position.x, c.position.y), (100, 50));
+        assert_eq!((c.hotspot.x, c.hotspot.y), (2, 3));
+        assert_eq!(c.bitmap_offset as usize, mem::size_of::<spa_meta_cursor>());
+
+        let b = meta.bitmap();
+        assert_eq!(b.format, VideoFormat::RGBA.as_raw());
+        assert_eq!((b.size.width, b.size.height), (4, 2));
+        assert_eq!(b.stride, 16);
+        assert_eq!(b.offset as usize, mem::size_of::<spa_meta_bitmap>());
+        assert_eq!(meta.pixels(32), (0..32).collect::<Vec<u8>>());
+
+        assert!(!cursor.bitmap_changed);
+        assert!(!cursor.changed);
+    }
+
+    #[test]
+    fn cursor_meta_position_only() {
+        let mut meta = Meta::new(CURSOR_META_SIZE);
+        let mut cursor = visible_cursor();
+        meta.write(&mut cursor);
+
+        cursor.position = Some(Point::from((110, 60)));
+        cursor.changed = true;
+        meta.write(&mut cursor);
+
+        // The bitmap isn't sent again when only the position changes.
+        let c = meta.cursor();
+        assert_eq!((c.position.x, c.position.y), (110, 60));
+        assert_eq!(c.bitmap_offset, 0);
+    }
+
+    #[test]
+    fn cursor_meta_hidden() {
+        let mut meta = Meta::new(CURSOR_META_SIZE);
+        let mut cursor = visible_cursor();
+        meta.write(&mut cursor);
+
+        cursor.position = None;
+        cursor.changed = true;
+        meta.write(&mut cursor);
+
+        // An empty bitmap hides the cursor.
+        let c = meta.cursor();
+        assert_ne!(c.bitmap_offset, 0);
+        let b = meta.bitmap();
+        assert_eq!((b.size.width, b.size.height), (0, 0));
+
+        // The bitmap must be sent again once the cursor is back.
+        assert!(cursor.bitmap_changed);
+        assert!(!cursor.changed);
+    }
+
+    #[test]
+    fn cursor_meta_too_big_for_bitmap() {
+        let mut meta = Meta::new(CURSOR_META_SIZE);
+        let mut cursor = visible_cursor();
+        cursor.bitmap = None;
+        meta.write(&mut cursor);
+
+        let c = meta.cursor();
+        assert_eq!((c.position.x, c.position.y), (100, 50));
+        assert_ne!(c.bitmap_offset, 0);
+        let b = meta.bitmap();
+        assert_eq!((b.size.width, b.size.height), (0, 0));
+    }
+
+    #[test]
+    fn cursor_meta_too_small() {
+        let mut meta = Meta::new(CURSOR_META_SIZE - 1);
+        let mut cursor = visible_cursor();
+        meta.write(&mut cursor);
+
+        // Nothing is written, and the cursor stays pending.
+        assert_eq!(meta.cursor().id, 0);
+        assert!(cursor.bitmap_changed);
+        assert!(cursor.changed);
+    }
+}
@@ -0,0 +1,673 @@
+//! Background images drawn behind the workspaces.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use anyhow::{bail, Context};
+use niri_config::{BackgroundImage, BackgroundImageFit, Config, OutputName};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::element::{Id, Kind};
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::backend::renderer::Texture as _;
+use smithay::reexports::calloop::channel::{self, Event};
+use smithay::reexports::calloop::{LoopHandle, RegistrationToken};
+use smithay::utils::{Buffer, Logical, Point, Rectangle, Size, Transform};
+
+use super::primary_gpu_texture::PrimaryGpuTextureRenderElement;
+use super::texture::{TextureBuffer, TextureRenderElement};
+use crate::niri::State;
+use crate::utils::watcher::Watcher;
+use crate::utils::{expand_home, round_logical_in_physical};
+
+/// Tiling small images can result in a huge number of elements, so we cap it.
+const MAX_TILES: usize = 1024;
+
+/// Background images, keyed by their path as written in the config.
+///
+/// Images are decoded on a worker thread and uploaded on the main thread once ready.
+pub struct BackgroundImages {
+    event_loop: LoopHandle<'static, State>,
+    images: HashMap<String, Image>,
+    /// Sender for the images decoded on worker threads.
+    to_upload: channel::Sender<DecodedImage>,
+    next_generation: u64,
+}
+
+struct Image {
+    /// Uploaded image, or `None` if it hasn't loaded (yet).
+    buffer: Option<TextureBuffer<GlesTexture>>,
+    /// Generation of the latest decode, results of earlier ones are discarded.
+    generation: u64,
+    /// Watcher that reloads the image when the file changes.
+    _watcher: Watcher,
+    token: RegistrationToken,
+}
+
+/// Image decoded on a worker thread, waiting to be uploaded.
+pub struct DecodedImage {
+    path: String,
+    generation: u64,
+    pixels: anyhow::Result<Pixels>,
+}
+
+struct Pixels {
+    /// RGBA8 pixels.
+    data: Vec<u8>,
+    size: Size<i32, Buffer>,
+    has_alpha: bool,
+}
+
+/// Where to draw one copy of the image on the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    location: Point<f64, Logical>,
+    texture_scale: f64,
+    src: Option<Rectangle<f64, Logical>>,
+}
+
+/// Background image laid out for a single output.
+#[derive(Debug, Default)]
+pub struct OutputBackgroundImage {
+    /// What the current elements were laid out for.
+    key: Option<LayoutKey>,
+    elements: Vec<PrimaryGpuTextureRenderElement>,
+}
+
+#[derive(Debug, PartialEq)]
+struct LayoutKey {
+    image: Id,
+    fit: BackgroundImageFit,
+    output_size: Size<f64, Logical>,
+    scale: f64,
+}
+
+impl BackgroundImages {
+    pub fn new(event_loop: LoopHandle<'static, State>) -> Self {
+        let (to_upload, rx) = channel::channel();
+        event_loop
+            .insert_source(rx, move |event, _, state| {
+                if let Event::Msg(decoded) = event {
+                    state.upload_background_image(decoded);
+                }
+            })
+            .unwrap();
+
+        Self {
+            event_loop,
+            images: HashMap::new(),
+            to_upload,
+            next_generation: 0,
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&TextureBuffer<GlesTexture>> {
+        self.images.get(path)?.buffer.as_ref()
+    }
+
+    /// Loads newly referenced images and drops images that are no longer referenced.
+    pub fn update<'a>(&mut self, paths: impl IntoIterator<Item = &'a str>) {
+        let _span = tracy_client::span!("BackgroundImages::update");
+
+        let mut images = HashMap::new();
+        for path in paths {
+            if images.contains_key(path) {
+                continue;
+            }
+
+            let image = match self.images.remove(path) {
+                Some(image) => image,
+                None => self.load(path),
+            };
+            images.insert(path.to_owned(), image);
+        }
+
+        // Whatever remains is no longer referenced.
+        for (_, image) in self.images.drain() {
+            self.event_loop.remove(image.token);
+        }
+
+        self.images = images;
+    }
+
+    /// Drops all images, for example when the renderer is recreated.
+    pub fn clear(&mut self) {
+        for (_, image) in self.images.drain() {
+            self.event_loop.remove(image.token);
+        }
+    }
+
+    /// Decodes the image at `path` again, keeping the current one until the new one is ready.
+    pub fn reload(&mut self, path: &str) {
+        let generation = self.next_generation;
+        let Some(image) = self.images.get_mut(path) else {
+            return;
+        };
+
+        debug!("reloading background image {path}");
+        image.generation = generation;
+        self.next_generation += 1;
+        self.decode_in_thread(path, generation);
+    }
+
+    /// Uploads an image decoded on a worker thread.
+    ///
+    /// Returns `true` if the image changed. A failed reload keeps the previous image.
+    pub fn upload(&mut self, renderer: &mut GlesRenderer, decoded: DecodedImage) -> bool {
+        let _span = tracy_client::span!("BackgroundImages::upload");
+
+        let DecodedImage {
+            path,
+            generation,
+            pixels,
+        } = decoded;
+
+        // The image may have been dropped or reloaded while it was decoding.
+        let Some(image) = self.images.get_mut(&path) else {
+            return false;
+        };
+        if image.generation != generation {
+            return false;
+        }
+
+        let pixels = match pixels {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                warn!("error loading background image {path}: {err:?}");
+                return false;
+            }
+        };
+
+        let opaque_regions = if pixels.has_alpha {
+            vec![]
+        } else {
+            vec![Rectangle::from_loc_and_size((0, 0), pixels.size)]
+        };
+
+        match TextureBuffer::from_memory(
+            renderer,
+            &pixels.data,
+            Fourcc::Abgr8888,
+            pixels.size,
+            false,
+            1.,
+            Transform::Normal,
+            opaque_regions,
+        ) {
+            Ok(buffer) => {
+                image.buffer = Some(buffer);
+                true
+            }
+            Err(err) => {
+                warn!("error uploading background image {path}: {err:?}");
+                false
+            }
+        }
+    }
+
+    fn load(&mut self, path: &str) -> Image {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.decode_in_thread(path, generation);
+
+        let (tx, rx) = channel::sync_channel(1);
+        let watcher = Watcher::new(expanded_path(path), tx);
+        let path = path.to_owned();
+        let token = self
+            .event_loop
+            .insert_source(rx, move |event, _, state| {
+                if let Event::Msg(()) = event {
+                    state.reload_background_image(&path);
+                }
+            })
+            .unwrap();
+
+        Image {
+            buffer: None,
+            generation,
+            _watcher: watcher,
+            token,
+        }
+    }
+
+    fn decode_in_thread(&self, path: &str, generation: u64) {
+        let to_upload = self.to_upload.clone();
+        let path = path.to_owned();
+        let res = thread::Builder::new()
+            .name("Background Image Decoder".to_owned())
+            .spawn(move || {
+                let _span = tracy_client::span!("background_image::decode");
+
+                let pixels = decode(&expanded_path(&path));
+                let _ = to_upload.send(DecodedImage {
+                    path,
+                    generation,
+                    pixels,
+                });
+            });
+
+        if let Err(err) = res {
+            warn!("error spawning a thread to decode a background image: {err:?}");
+        }
+    }
+}
+
+impl Drop for BackgroundImages {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl OutputBackgroundImage {
+    /// Lays out the image for the output, unless it's already laid out with the same parameters.
+    pub fn update(
+        &mut self,
+        image: Option<(&TextureBuffer<GlesTexture>, BackgroundImageFit)>,
+        output_size: Size<f64, Logical>,
+        scale: f64,
+    ) {
+        let Some((buffer, fit)) = image else {
+            self.key = None;
+            self.elements.clear();
+            return;
+        };
+
+        let key = LayoutKey {
+            image: buffer.id().clone(),
+            fit,
+            output_size,
+            scale,
+        };
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+
+        self.elements = layout(buffer, fit, output_size, scale);
+        self.key = Some(key);
+    }
+
+    pub fn render(&self) -> impl Iterator<Item = PrimaryGpuTextureRenderElement> + '_ {
+        self.elements.iter().cloned()
+    }
+}
+
+fn expanded_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    match expand_home(&path) {
+        Ok(Some(expanded)) => expanded,
+        Ok(None) => path,
+        Err(err) => {
+            warn!("error expanding ~: {err:?}");
+            path
+        }
+    }
+}
+
+/// Decodes a PNG or JPEG image into RGBA8 pixels.
+fn decode(path: &Path) -> anyhow::Result<Pixels> {
+    let mut file = BufReader::new(File::open(path).context("error opening file")?);
+
+    let mut magic = [0; 8];
+    file.read_exact(&mut magic).context("error reading file")?;
+    file.rewind().context("error reading file")?;
+
+    if magic == [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'] {
+        decode_png(file)
+    } else if magic[..3] == [0xff, 0xd8, 0xff] {
+        decode_jpeg(file)
+    } else {
+        bail!("unsupported image format, only PNG and JPEG are supported")
+    }
+}
+
+fn decode_png(file: BufReader<File>) -> anyhow::Result<Pixels> {
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().context("error reading PNG header")?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).context("error decoding PNG")?;
+    buf.truncate(info.buffer_size());
+
+    let (pixels, has_alpha) = match info.color_type {
+        png::ColorType::Rgba => (buf, true),
+        png::ColorType::Rgb => (rgb_to_rgba(&buf), false),
+        png::ColorType::GrayscaleAlpha => (
+            buf.chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            true,
+        ),
+        png::ColorType::Grayscale => (gray_to_rgba(&buf), false),
+        png::ColorType::Indexed => bail!("unexpected indexed PNG after expansion"),
+    };
+
+    let size = Size::from((
+        i32::try_from(info.width).context("image is too large")?,
+        i32::try_from(info.height).context("image is too large")?,
+    ));
+    Ok(Pixels {
+        data: pixels,
+        size,
+        has_alpha,
+    })
+}
+
+fn decode_jpeg(file: BufReader<File>) -> anyhow::Result<Pixels> {
+    let mut decoder = jpeg_decoder::Decoder::new(file);
+    let buf = decoder.decode().context("error decoding JPEG")?;
+    let info = decoder.info().context("missing JPEG info")?;
+
+    let pixels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => rgb_to_rgba(&buf),
+        jpeg_decoder::PixelFormat::L8 => gray_to_rgba(&buf),
+        jpeg_decoder::PixelFormat::L16 => {
+            let gray: Vec<u8> = buf
+                .chunks_exact(2)
+                .map(|p| (u16::from_ne_bytes([p[0], p[1]]) >> 8) as u8)
+                .collect();
+            gray_to_rgba(&gray)
+        }
+        jpeg_decoder::PixelFormat::CMYK32 => buf
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let k = u16::from(p[3]);
+                let convert = |c: u8| (u16::from(c) * k / 255) as u8;
+                [convert(p[0]), convert(p[1]), convert(p[2]), 255]
+            })
+            .collect(),
+    };
+
+    let size = Size::from((i32::from(info.width), i32::from(info.height)));
+    Ok(Pixels {
+        data: pixels,
+        size,
+        has_alpha: false,
+    })
+}
+
+fn rgb_to_rgba(buf: &[u8]) -> Vec<u8> {
+    buf.chunks_exact(3)
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect()
+}
+
+fn gray_to_rgba(buf: &[u8]) -> Vec<u8> {
+    buf.iter().flat_map(|&p| [p, p, p, 255]).collect()
+}
+
+/// Returns the background image for an output, which a named workspace can override.
+pub fn resolve<'a>(
+    config: &'a Config,
+    output_name: &OutputName,
+    workspace_name: Option<&str>,
+) -> Option<&'a BackgroundImage> {
+    let workspace_image = workspace_name
+        .and_then(|name| {
+            config
+                .workspaces
+                .iter()
+                .find(|ws| ws.name.0.eq_ignore_ascii_case(name))
+        })
+        .and_then(|ws| ws.background_image.as_ref());
+
+    workspace_image.or_else(|| config.outputs.find(output_name)?.background_image.as_ref())
+}
+
+fn layout(
+    buffer: &TextureBuffer<GlesTexture>,
+    fit: BackgroundImageFit,
+    output_size: Size<f64, Logical>,
+    scale: f64,
+) -> Vec<PrimaryGpuTextureRenderElement> {
+    let placements = place(buffer.texture().size(), fit, output_size, scale);
+    let tiled = placements.len() > 1;
+
+    placements
+        .into_iter()
+        .map(|placement| {
+            // Each tile needs its own id for damage tracking.
+            let mut buffer = if tiled {
+                buffer.with_new_id()
+            } else {
+                buffer.clone()
+            };
+            buffer.set_texture_scale(placement.texture_scale);
+
+            PrimaryGpuTextureRenderElement(TextureRenderElement::from_texture_buffer(
+                buffer,
+                placement.location,
+                1.,
+                placement.src,
+                None,
+                Kind::Unspecified,
+            ))
+        })
+        .collect()
+}
+
+fn place(
+    image_size: Size<i32, Buffer>,
+    mut fit: BackgroundImageFit,
+    output_size: Size<f64, Logical>,
+    scale: f64,
+) -> Vec<Placement> {
+    if image_size.w <= 0 || image_size.h <= 0 {
+        return vec![];
+    }
+    let image_size = Size::<f64, Logical>::from((f64::from(image_size.w), f64::from(image_size.h)));
+
+    // Unscaled modes show one image pixel per physical pixel.
+    let unscaled_size = image_size.downscale(scale);
+
+    if fit == BackgroundImageFit::Tile {
+        let cols = (output_size.w / unscaled_size.w).ceil() as usize;
+        let rows = (output_size.h / unscaled_size.h).ceil() as usize;
+        if cols.saturating_mul(rows) > MAX_TILES {
+            warn!("background image is too small to tile, scaling it to fill instead");
+            fit = BackgroundImageFit::Fill;
+        }
+    }
+
+    match fit {
+        BackgroundImageFit::Fill => {
+            let factor = f64::max(output_size.w / image_size.w, output_size.h / image_size.h);
+
+            // Crop the overflowing part evenly from both sides.
+            let scaled_size = image_size.upscale(factor);
+            let offset = Point::from((
+                (scaled_size.w - output_size.w) / 2.,
+                (scaled_size.h - output_size.h) / 2.,
+            ));
+
+            vec![Placement {
+                location: Point::from((0., 0.)),
+                texture_scale: 1. / factor,
+                src: Some(Rectangle::from_loc_and_size(offset, output_size)),
+            }]
+        }
+        BackgroundImageFit::Fit => {
+            let factor = f64::min(output_size.w / image_size.w, output_size.h / image_size.h);
+            let scaled_size = image_size.upscale(factor);
+
+            vec![Placement {
+                location: centered(output_size, scaled_size, scale),
+                texture_scale: 1. / factor,
+                src: None,
+            }]
+        }
+        BackgroundImageFit::Center => vec![Placement {
+            location: centered(output_size, unscaled_size, scale),
+            texture_scale: scale,
+            src: None,
+        }],
+        BackgroundImageFit::Tile => {
+            let cols = (output_size.w / unscaled_size.w).ceil() as i32;
+            let rows = (output_size.h / unscaled_size.h).ceil() as i32;
+
+            let mut placements = Vec::new();
+            for row in 0..rows {
+                for col in 0..cols {
+                    placements.push(Placement {
+                        location: Point::from((
+                            f64::from(col) * unscaled_size.w,
+                            f64::from(row) * unscaled_size.h,
+                        )),
+                        texture_scale: scale,
+                        src: None,
+                    });
+                }
+            }
+            placements
+        }
+    }
+}
+
+fn centered(
+    output_size: Size<f64, Logical>,
+    size: Size<f64, Logical>,
+    scale: f64,
+) -> Point<f64, Logical> {
+    // Keep the image aligned to physical pixels so that it doesn't get blurry.
+    Point::from((
+        round_logical_in_physical(scale, (output_size.w - size.w) / 2.),
+        round_logical_in_physical(scale, (output_size.h - size.h) / 2.),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(
+        image_size: (i32, i32),
+        fit: BackgroundImageFit,
+        output_size: (f64, f64),
+        scale: f64,
+    ) -> Vec<Placement> {
+        super::place(Size::from(image_size), fit, Size::from(output_size), scale)
+    }
+
+    #[test]
+    fn place_fill_crops_evenly() {
+        let placements = place((1000, 1000), BackgroundImageFit::Fill, (1920., 1080.), 1.);
+        assert_eq!(
+            placements,
+            [Placement {
+                location: Point::from((0., 0.)),
+                texture_scale: 1. / (1920. / 1000.),
+                src: Some(Rectangle::from_loc_and_size((0., 420.), (1920., 1080.))),
+            }]
+        );
+    }
+
+    #[test]
+    fn place_fit_letterboxes() {
+        let placements = place((1000, 1000), BackgroundImageFit::Fit, (1920., 1080.), 1.);
+        assert_eq!(
+            placements,
+            [Placement {
+                location: Point::from((420., 0.)),
+                texture_scale: 1. / (1080. / 1000.),
+                src: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn place_tile_covers_output() {
+        // At scale 2, a 200 px image covers 100 logical pixels.
+        let placements = place((200, 200), BackgroundImageFit::Tile, (250., 150.), 2.);
+        let locations: Vec<_> = placements.iter().map(|p| p.location).collect();
+        assert_eq!(
+            locations,
+            [
+                Point::from((0., 0.)),
+                Point::from((100., 0.)),
+                Point::from((200., 0.)),
+                Point::from((0., 100.)),
+                Point::from((100., 100.)),
+                Point::from((200., 100.)),
+            ]
+        );
+        assert!(placements.iter().all(|p| p.texture_scale == 2.));
+    }
+
+    #[test]
+    fn place_tile_falls_back_to_fill_above_max_tiles() {
+        // 32 × 32 = 1024 tiles still tile.
+        let placements = place((10, 10), BackgroundImageFit::Tile, (320., 320.), 1.);
+        assert_eq!(placements.len(), MAX_TILES);
+
+        // 33 × 33 tiles fill instead.
+        let placements = place((10, 10), BackgroundImageFit::Tile, (330., 330.), 1.);
+        assert_eq!(
+            placements,
+            place((10, 10), BackgroundImageFit::Fill, (330., 330.), 1.)
+        );
+    }
+
+    #[test]
+    fn place_empty_image() {
+        assert!(place((0, 10), BackgroundImageFit::Fill, (1920., 1080.), 1.).is_empty());
+    }
+
+    #[test]
+    fn resolve_workspace_override() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            output "eDP-1" {
+                background-image "output.png"
+            }
+
+            workspace "browser" {
+                background-image "browser.png" fit="tile"
+            }
+
+            workspace "chat"
+            "#,
+        )
+        .unwrap();
+
+        let edp = OutputName {
+            connector: "eDP-1".to_owned(),
+            make: None,
+            model: None,
+            serial: None,
+        };
+        let hdmi = OutputName {
+            connector: "HDMI-A-1".to_owned(),
+            ..edp.clone()
+        };
+
+        let path = |image: Option<&BackgroundImage>| image.map(|image| image.path.clone());
+        assert_eq!(
+            path(resolve(&config, &edp, None)).as_deref(),
+            Some("output.png")
+        );
+        assert_eq!(
+            path(resolve(&config, &edp, Some("chat"))).as_deref(),
+            Some("output.png")
+        );
+        assert_eq!(
+            path(resolve(&config, &edp, Some("Browser"))).as_deref(),
+            Some("browser.png")
+        );
+        assert_eq!(
+            path(resolve(&config, &hdmi, Some("browser"))).as_deref(),
+            Some("browser.png")
+        );
+        assert_eq!(path(resolve(&config, &hdmi, None)), None);
+    }
+
+    #[test]
+    fn centered_is_pixel_aligned() {
+        let loc = centered(Size::from((1280., 720.)), Size::from((101., 51.)), 1.5);
+        let physical = loc.to_physical(1.5);
+        assert_eq!(physical.x, physical.x.round());
+        assert_eq!(physical.y, physical.y.round());
+    }
+}
@@ -0,0 +1,143 @@
+use niri_ipc::MirrorScaling;
+use smithay::utils::{Logical, Point, Rectangle, Scale, Size};
+
+/// Where to draw the mirrored output contents on the mirroring output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MirrorPlacement {
+    pub location: Point<f64, Logical>,
+    pub texture_scale: f64,
+    pub src: Option<Rectangle<f64, Logical>>,
+    pub size: Option<Size<f64, Logical>>,
+}
+
+/// Places a source rendered at scale 1 onto an output of `output_size`.
+pub fn place_mirror(
+    scaling: MirrorScaling,
+    source_size: Size<f64, Logical>,
+    output_size: Size<f64, Logical>,
+    output_scale: Scale<f64>,
+) -> MirrorPlacement {
+    match scaling {
+        MirrorScaling::Fit | MirrorScaling::Fill => {
+            let factor_w = output_size.w / source_size.w;
+            let factor_h = output_size.h / source_size.h;
+            let factor = if scaling == MirrorScaling::Fit {
+                f64::min(factor_w, factor_h)
+            } else {
+                f64::max(factor_w, factor_h)
+            };
+
+            let scaled_size = source_size.upscale(factor);
+            let offset = Point::from((
+                (output_size.w - scaled_size.w) / 2.,
+                (output_size.h - scaled_size.h) / 2.,
+            ));
+
+            if scaling == MirrorScaling::Fit {
+                let location = offset
+                    .to_physical_precise_round(output_scale)
+                    .to_logical(output_scale);
+                MirrorPlacement {
+                    location,
+                    texture_scale: 1. / factor,
+                    src: None,
+                    size: None,
+                }
+            } else {
+                // Crop the overflowing part evenly from both sides.
+                let src = Rectangle::from_loc_and_size(offset.upscale(-1.), output_size);
+                MirrorPlacement {
+                    location: Point::from((0., 0.)),
+                    texture_scale: 1. / factor,
+                    src: Some(src),
+                    size: None,
+                }
+            }
+        }
+        MirrorScaling::Stretch => MirrorPlacement {
+            location: Point::from((0., 0.)),
+            texture_scale: 1.,
+            src: None,
+            size: Some(output_size),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(scaling: MirrorScaling, source: (f64, f64), output: (f64, f64)) -> MirrorPlacement {
+        place_mirror(
+            scaling,
+            Size::from(source),
+            Size::from(output),
+            Scale::from(1.),
+        )
+    }
+
+    #[test]
+    fn fit_letterboxes() {
+        // 16:9 source on a 4:3 output gets bars at the top and bottom.
+        let p = place(MirrorScaling::Fit, (1920., 1080.), (1024., 768.));
+        assert_eq!(p.texture_scale, 1920. / 1024.);
+        assert_eq!(p.location, Point::from((0., 96.)));
+        assert_eq!(p.src, None);
+        assert_eq!(p.size, None);
+
+        // 4:3 source on a 16:9 output gets bars on the sides.
+        let p = place(MirrorScaling::Fit, (1024., 768.), (1920., 1080.));
+        assert_eq!(p.texture_scale, 768. / 1080.);
+        assert_eq!(p.location, Point::from((240., 0.)));
+    }
+
+    #[test]
+    fn fit_location_is_pixel_aligned() {
+        let p = place_mirror(
+            MirrorScaling::Fit,
+            Size::from((1920., 1080.)),
+            Size::from((1000., 1000.)),
+            Scale::from(1.5),
+        );
+        let physical = p.location.to_physical(1.5);
+        assert_eq!(physical.x, physical.x.round());
+        assert_eq!(physical.y, physical.y.round());
+    }
+
+    #[test]
+    fn fill_crops_evenly() {
+        // 16:9 source on a 4:3 output gets cropped on the sides.
+        let p = place(MirrorScaling::Fill, (1920., 1080.), (1024., 768.));
+        assert_eq!(p.texture_scale, 1080. / 768.);
+        assert_eq!(p.location, Point::from((0., 0.)));
+        let src = p.src.unwrap();
+        assert!((src.loc.x - 512. / 3.).abs() < 1e-9);
+        assert_eq!(src.loc.y, 0.);
+        assert_eq!(src.size, Size::from((1024., 768.)));
+        assert_eq!(p.size, None);
+    }
+
+    #[test]
+    fn stretch_covers_output() {
+        let p = place(MirrorScaling::Stretch, (1920., 1080.), (1024., 768.));
+        assert_eq!(p.texture_scale, 1.);
+        assert_eq!(p.location, Point::from((0., 0.)));
+        assert_eq!(p.src, None);
+        assert_eq!(p.size, Some(Size::from((1024., 768.))));
+    }
+
+    #[test]
+    fn same_aspect_ratio() {
+        for scaling in [MirrorScaling::Fit, MirrorScaling::Fill] {
+            let p = place(scaling, (3840., 2160.), (1920., 1080.));
+            assert_eq!(p.texture_scale, 2.);
+            assert_eq!(p.location, Point::from((0., 0.)));
+        }
+
+        let p = place(MirrorScaling::Fill, (3840., 2160.), (1920., 1080.));
+        assert_eq!(
+            p.src,
+            Some(Rectangle::from_loc_and_size((0., 0.), (1920., 1080.)))
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::ptr;
 
-use anyhow::{ensure, Context};
+use anyhow::{bail, ensure, Context};
 use niri_config::BlockOutFrom;
 use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::allocator::{Buffer, Fourcc};
@@ -18,11 +18,13 @@ use solid_color::{SolidColorBuffer, SolidColorRenderElement};
 use self::primary_gpu_texture::PrimaryGpuTextureRenderElement;
 use self::texture::{TextureBuffer, TextureRenderElement};
 
+pub mod background_image;
 pub mod border;
 pub mod clipped_surface;
 pub mod damage;
 pub mod debug;
 pub mod memory;
+pub mod mirror;
 pub mod offscreen;
 pub mod primary_gpu_texture;
 pub mod render_elements;
@@ -261,17 +263,22 @@ pub fn render_to_shm(
 ) -> anyhow::Result<()> {
     let _span = tracy_client::span!();
     shm::with_buffer_contents_mut(buffer, |shm_buffer, shm_len, buffer_data| {
+        // The buffer prefers pixels in little endian ...
+        let fourcc = match buffer_data.format {
+            wl_shm::Format::Xrgb8888 => Fourcc::Xrgb8888,
+            wl_shm::Format::Argb8888 => Fourcc::Argb8888,
+            wl_shm::Format::Xbgr8888 => Fourcc::Xbgr8888,
+            wl_shm::Format::Abgr8888 => Fourcc::Abgr8888,
+            format => bail!("unsupported buffer format: {format:?}"),
+        };
         ensure!(
-            // The buffer prefers pixels in little endian ...
-            buffer_data.format == wl_shm::Format::Xrgb8888
-                && buffer_data.width == size.w
+            buffer_data.width == size.w
                 && buffer_data.height == size.h
                 && buffer_data.stride == size.w * 4
                 && shm_len == buffer_data.stride as usize * buffer_data.height as usize,
             "invalid buffer format or size"
         );
-        let mapping =
-            render_and_download(renderer, size, scale, transform, Fourcc::Xrgb8888, elements)?;
+        let mapping = render_and_download(renderer, size, scale, transform, fourcc, elements)?;
 
         let bytes = renderer
             .map_texture(&mapping)
@@ -85,6 +85,10 @@ impl<T> TextureBuffer<T> {
         )
     }
 
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
     pub fn texture(&self) -> &T {
         &self.texture
     }
@@ -106,6 +110,18 @@ impl<T> TextureBuffer<T> {
     }
 }
 
+impl<T: Clone> TextureBuffer<T> {
+    /// Returns a copy of this buffer with a new element id.
+    ///
+    /// Needed to draw the same texture several times in one frame.
+    pub fn with_new_id(&self) -> Self {
+        Self {
+            id: Id::new(),
+            ..self.clone()
+        }
+    }
+}
+
 impl<T: Texture> TextureBuffer<T> {
     pub fn logical_size(&self) -> Size<f64, Logical> {
         self.texture
@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+use niri_config::Config;
+use ordered_float::NotNan;
+use pangocairo::cairo::{self, ImageSurface};
+use smithay::backend::renderer::element::Kind;
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::output::Output;
+use smithay::reexports::gbm::Format as Fourcc;
+use smithay::utils::{Point, Transform};
+
+use crate::niri_render_elements;
+use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
+use crate::render_helpers::renderer::NiriRenderer;
+use crate::render_helpers::solid_color::{SolidColorBuffer, SolidColorRenderElement};
+use crate::render_helpers::texture::{TextureBuffer, TextureRenderElement};
+use crate::utils::{output_size, to_physical_precise_round};
+
+const BADGE_SIZE: i32 = 12;
+const BADGE_MARGIN: i32 = 8;
+
+/// Indicator shown to the user while something is being screencast or captured.
+///
+/// The indicator is only drawn on the outputs themselves and never ends up in the capture.
+pub struct CaptureIndicator {
+    /// Outputs that are being captured, fully or partially.
+    outputs: Vec<Output>,
+    /// Whether anything at all is being captured, including individual windows.
+    is_capturing: bool,
+
+    /// Edges of the border frame around each captured output.
+    borders: RefCell<HashMap<Output, [SolidColorBuffer; 4]>>,
+    badge: RefCell<HashMap<NotNan<f64>, Option<TextureBuffer<GlesTexture>>>>,
+    badge_color: RefCell<[f32; 4]>,
+
+    config: Rc<RefCell<Config>>,
+}
+
+niri_render_elements! {
+    CaptureIndicatorRenderElement => {
+        SolidColor = SolidColorRenderElement,
+        Texture = PrimaryGpuTextureRenderElement,
+    }
+}
+
+impl CaptureIndicator {
+    pub fn new(config: Rc<RefCell<Config>>) -> Self {
+        Self {
+            outputs: Vec::new(),
+            is_capturing: false,
+            borders: RefCell::new(HashMap::new()),
+            badge: RefCell::new(HashMap::new()),
+            badge_color: RefCell::new([0.; 4]),
+            config,
+        }
+    }
+
+    /// Updates what is being captured and returns whether anything changed.
+    pub fn update(&mut self, outputs: Vec<Output>, is_capturing: bool) -> bool {
+        if self.outputs == outputs && self.is_capturing == is_capturing {
+            return false;
+        }
+
+        self.borders
+            .borrow_mut()
+            .retain(|output, _| outputs.contains(output));
+        self.outputs = outputs;
+        self.is_capturing = is_capturing;
+        true
+    }
+
+    pub fn render<R: NiriRenderer>(
+        &self,
+        renderer: &mut R,
+        output: &Output,
+    ) -> Vec<CaptureIndicatorRenderElement> {
+        let mut elements = Vec::new();
+
+        let config = self.config.borrow().capture_indicator;
+        if config.off || !self.is_capturing {
+            return elements;
+        }
+
+        let scale = output.current_scale().fractional_scale();
+        let output_size = output_size(output);
+        let color = config.color.to_array_premul();
+
+        if !config.hide_badge {
+            let badge_color = config.color.to_array_unpremul();
+            if *self.badge_color.borrow() != badge_color {
+                *self.badge_color.borrow_mut() = badge_color;
+                self.badge.borrow_mut().clear();
+            }
+
+            let mut badge = self.badge.borrow_mut();
+            let buffer = badge.entry(NotNan::new(scale).unwrap()).or_insert_with(|| {
+                render_badge(renderer.as_gles_renderer(), scale, badge_color).ok()
+            });
+
+            if let Some(buffer) = buffer.clone() {
+                let size = buffer.logical_size();
+                let margin = f64::from(BADGE_MARGIN);
+                let location = Point::from((output_size.w - size.w - margin, margin));
+                let location = location.to_physical_precise_round(scale).to_logical(scale);
+
+                let elem = TextureRenderElement::from_texture_buffer(
+                    buffer,
+                    location,
+                    1.,
+                    None,
+                    None,
+                    Kind::Unspecified,
+                );
+                elements.push(PrimaryGpuTextureRenderElement(elem).into());
+            }
+        }
+
+        let width = config.border_width.0;
+        if self.outputs.contains(output) && width > 0. {
+            let mut borders = self.borders.borrow_mut();
+            let edges = borders.entry(output.clone()).or_default();
+
+            let (w, h) = (output_size.w, output_size.h);
+            let rects = [
+                // Top.
+                (Point::from((0., 0.)), (w, width)),
+                // Bottom.
+                (Point::from((0., h - width)), (w, width)),
+                // Left.
+                (Point::from((0., width)), (width, h - width * 2.)),
+                // Right.
+                (Point::from((w - width, width)), (width, h - width * 2.)),
+            ];
+
+            for (buffer, (location, size)) in edges.iter_mut().zip(rects) {
+                buffer.update(size, color);
+                let elem =
+                    SolidColorRenderElement::from_buffer(buffer, location, 1., Kind::Unspecified);
+                elements.push(elem.into());
+            }
+        }
+
+        elements
+    }
+}
+
+fn render_badge(
+    renderer: &mut GlesRenderer,
+    scale: f64,
+    color: [f32; 4],
+) -> anyhow::Result<TextureBuffer<GlesTexture>> {
+    let _span = tracy_client::span!("capture_indicator::render_badge");
+
+    let size: i32 = to_physical_precise_round(scale, BADGE_SIZE);
+
+    let surface = ImageSurface::create(cairo::Format::ARgb32, size, size)?;
+    let cr = cairo::Context::new(&surface)?;
+
+    let [r, g, b, a] = color.map(f64::from);
+    cr.set_source_rgba(r, g, b, a);
+
+    let radius = f64::from(size) / 2.;
+    cr.arc(radius, radius, radius, 0., 2. * PI);
+    cr.fill()?;
+    drop(cr);
+
+    let data = surface.take_data().unwrap();
+    let buffer = TextureBuffer::from_memory(
+        renderer,
+        &data,
+        Fourcc::Argb8888,
+        (size, size),
+        false,
+        scale,
+        Transform::Normal,
+        Vec::new(),
+    )?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::{PhysicalProperties, Subpixel};
+    use smithay::utils::Size;
+
+    use super::*;
+
+    fn make_output() -> Output {
+        Output::new(
+            String::from("output"),
+            PhysicalProperties {
+                size: Size::from((1280, 720)),
+                subpixel: Subpixel::Unknown,
+                make: String::new(),
+                model: String::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn update_reports_changes() {
+        let mut indicator = CaptureIndicator::new(Rc::new(RefCell::new(Config::default())));
+        let output = make_output();
+
+        assert!(!indicator.update(vec![], false));
+
+        // A window capture shows the badge without any output border.
+        assert!(indicator.update(vec![], true));
+        assert!(!indicator.update(vec![], true));
+
+        assert!(indicator.update(vec![output.clone()], true));
+        assert!(!indicator.update(vec![output.clone()], true));
+
+        assert!(indicator.update(vec![], false));
+    }
+
+    #[test]
+    fn update_drops_borders_of_uncaptured_outputs() {
+        let mut indicator = CaptureIndicator::new(Rc::new(RefCell::new(Config::default())));
+        let a = make_output();
+        let b = make_output();
+
+        indicator.update(vec![a.clone(), b.clone()], true);
+        indicator.borders.borrow_mut().extend([
+            (a.clone(), Default::default()),
+            (b.clone(), Default::default()),
+        ]);
+
+        indicator.update(vec![b.clone()], true);
+        let borders = indicator.borders.borrow();
+        assert!(!borders.contains_key(&a));
+        assert!(borders.contains_key(&b));
+    }
+}
@@ -1,18 +1,22 @@
 use std::cell::RefCell;
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::collections::HashMap;
-use std::iter::zip;
 use std::rc::Rc;
 
-use niri_config::{Action, Config, Key, Modifiers, Trigger};
+use niri_config::{Action, Config, Key, Modifiers, Trigger, WorkspaceReference};
+use niri_ipc::{LayoutSwitchTarget, SizeChange};
 use pangocairo::cairo::{self, ImageSurface};
-use pangocairo::pango::{AttrColor, AttrInt, AttrList, AttrString, FontDescription, Weight};
+use pangocairo::glib::markup_escape_text;
+use pangocairo::pango::{
+    self, AttrColor, AttrInt, AttrList, AttrString, FontDescription, Layout, Weight,
+};
 use smithay::backend::renderer::element::Kind;
 use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
 use smithay::input::keyboard::xkb::keysym_get_name;
+use smithay::input::keyboard::{Keysym, ModifiersState};
 use smithay::output::{Output, WeakOutput};
 use smithay::reexports::gbm::Format as Fourcc;
-use smithay::utils::{Scale, Transform};
+use smithay::utils::{Logical, Scale, Size, Transform};
 
 use crate::input::CompositorMod;
 use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
@@ -21,21 +25,58 @@ use crate::render_helpers::texture::{TextureBuffer, TextureRenderElement};
 use crate::utils::{output_size, to_physical_precise_round};
 
 const PADDING: i32 = 8;
-// const MARGIN: i32 = PADDING * 2;
+const MARGIN: i32 = PADDING * 2;
 const FONT: &str = "sans 14px";
 const BORDER: i32 = 4;
 const LINE_INTERVAL: i32 = 2;
 const TITLE: &str = "Important Hotkeys";
+const FILTER_PLACEHOLDER: &str = "Type to filter";
+const NO_MATCHES: &str = "No matching hotkeys";
+const SCROLL_HINT: &str = "↑ ↓ Page Up Page Down to scroll";
+/// Number of lines to scroll with Page Up and Page Down.
+const PAGE_LINES: usize = 10;
 
 pub struct HotkeyOverlay {
     is_open: bool,
     config: Rc<RefCell<Config>>,
     comp_mod: CompositorMod,
+    /// Text typed to filter the hotkeys.
+    filter: String,
+    /// Index of the first visible line.
+    scroll: usize,
     buffers: RefCell<HashMap<WeakOutput, RenderedOverlay>>,
 }
 
 pub struct RenderedOverlay {
     buffer: Option<TextureBuffer<GlesTexture>>,
+    /// Largest useful value of the scroll for this output.
+    max_scroll: usize,
+}
+
+/// Category that a hotkey is listed under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Category {
+    General,
+    Applications,
+    Windows,
+    Columns,
+    Workspaces,
+    Monitors,
+    Screenshots,
+}
+
+/// A hotkey entry; binds with the same action and title share an entry.
+struct Entry<'a> {
+    category: Category,
+    action: &'a Action,
+    /// Pango markup.
+    title: String,
+    keys: Vec<String>,
+}
+
+enum Line {
+    Heading(&'static str),
+    Entry { keys: String, title: String },
 }
 
 impl HotkeyOverlay {
@@ -44,6 +85,8 @@ impl HotkeyOverlay {
             is_open: false,
             config,
             comp_mod,
+            filter: String::new(),
+            scroll: 0,
             buffers: RefCell::new(HashMap::new()),
         }
     }
@@ -51,6 +94,7 @@ impl HotkeyOverlay {
     pub fn show(&mut self) -> bool {
         if !self.is_open {
             self.is_open = true;
+            self.reset();
             true
         } else {
             false
@@ -60,6 +104,7 @@ impl HotkeyOverlay {
     pub fn hide(&mut self) -> bool {
         if self.is_open {
             self.is_open = false;
+            self.reset();
             true
         } else {
             false
@@ -74,6 +119,59 @@ impl HotkeyOverlay {
         self.buffers.borrow_mut().clear();
     }
 
+    fn reset(&mut self) {
+        if !self.filter.is_empty() || self.scroll != 0 {
+            self.filter.clear();
+            self.scroll = 0;
+            self.buffers.borrow_mut().clear();
+        }
+    }
+
+    /// Handles a key press while the overlay is open.
+    ///
+    /// Returns `true` if the overlay used the key for filtering or scrolling.
+    pub fn on_key(&mut self, keysym: Keysym, mods: ModifiersState) -> bool {
+        if !self.is_open || mods.ctrl || mods.alt || mods.logo {
+            return false;
+        }
+
+        let max_scroll = self
+            .buffers
+            .borrow()
+            .values()
+            .map(|rendered| rendered.max_scroll)
+            .max()
+            .unwrap_or(0);
+
+        match keysym {
+            Keysym::Escape | Keysym::BackSpace if self.filter.is_empty() => return false,
+            Keysym::Escape => {
+                self.filter.clear();
+                self.scroll = 0;
+            }
+            Keysym::BackSpace => {
+                self.filter.pop();
+                self.scroll = 0;
+            }
+            Keysym::Up => self.scroll = self.scroll.saturating_sub(1),
+            Keysym::Down => self.scroll = min(self.scroll + 1, max_scroll),
+            Keysym::Page_Up => self.scroll = self.scroll.saturating_sub(PAGE_LINES),
+            Keysym::Page_Down => self.scroll = min(self.scroll + PAGE_LINES, max_scroll),
+            Keysym::Home => self.scroll = 0,
+            Keysym::End => self.scroll = max_scroll,
+            _ => match keysym.key_char() {
+                Some(c) if !c.is_control() => {
+                    self.filter.push(c);
+                    self.scroll = 0;
+                }
+                _ => return false,
+            },
+        }
+
+        self.buffers.borrow_mut().clear();
+        true
+    }
+
     pub fn render<R: NiriRenderer>(
         &self,
         renderer: &mut R,
@@ -101,8 +199,19 @@ impl HotkeyOverlay {
 
         let rendered = buffers.entry(weak).or_insert_with(|| {
             let renderer = renderer.as_gles_renderer();
-            render(renderer, &self.config.borrow(), self.comp_mod, scale)
-                .unwrap_or_else(|_| RenderedOverlay { buffer: None })
+            render(
+                renderer,
+                &self.config.borrow(),
+                self.comp_mod,
+                scale,
+                output_size,
+                &self.filter,
+                self.scroll,
+            )
+            .unwrap_or_else(|_| RenderedOverlay {
+                buffer: None,
+                max_scroll: 0,
+            })
         });
         let buffer = rendered.buffer.as_ref()?;
 
@@ -125,124 +234,96 @@ impl HotkeyOverlay {
     }
 }
 
+/// Collects the entries to show, in display order.
+fn entries(config: &Config, comp_mod: CompositorMod) -> Vec<Entry<'_>> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for bind in &config.binds.0 {
+        let title = match &bind.hotkey_overlay_title {
+            Some(None) => continue,
+            Some(Some(title)) => title.clone(),
+            None => action_name(&bind.action),
+        };
+        let key = key_name(comp_mod, &bind.key);
+
+        if let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| *entry.action == bind.action && entry.title == title)
+        {
+            entry.keys.push(key);
+            continue;
+        }
+
+        entries.push(Entry {
+            category: category(&bind.action),
+            action: &bind.action,
+            title,
+            keys: vec![key],
+        });
+    }
+
+    // Stable sort keeps the config order within each category.
+    entries.sort_by_key(|entry| entry.category);
+    entries
+}
+
+fn matches_filter(entry: &Entry<'_>, filter: &str) -> bool {
+    if filter.is_empty() {
+        return true;
+    }
+
+    let filter = filter.to_lowercase();
+    let title = pango::parse_markup(&entry.title, '\0')
+        .map(|(_, text, _)| text.to_string())
+        .unwrap_or_else(|_| entry.title.clone());
+
+    title.to_lowercase().contains(&filter)
+        || category_name(entry.category)
+            .to_lowercase()
+            .contains(&filter)
+        || entry
+            .keys
+            .iter()
+            .any(|key| key.to_lowercase().contains(&filter))
+}
+
 fn render(
     renderer: &mut GlesRenderer,
     config: &Config,
     comp_mod: CompositorMod,
     scale: f64,
+    output_size: Size<f64, Logical>,
+    filter: &str,
+    scroll: usize,
 ) -> anyhow::Result<RenderedOverlay> {
     let _span = tracy_client::span!("hotkey_overlay::render");
 
-    // let margin = MARGIN * scale;
+    let margin: i32 = to_physical_precise_round(scale, MARGIN);
     let padding: i32 = to_physical_precise_round(scale, PADDING);
     let line_interval: i32 = to_physical_precise_round(scale, LINE_INTERVAL);
 
-    // FIXME: if it doesn't fit, try splitting in two columns or something.
-    // let mut target_size = output_size;
-    // target_size.w -= margin * 2;
-    // target_size.h -= margin * 2;
-    // anyhow::ensure!(target_size.w > 0 && target_size.h > 0);
-
-    let binds = &config.binds.0;
-
-    // Collect actions that we want to show.
-    let mut actions = vec![&Action::ShowHotkeyOverlay];
-
-    // Prefer Quit(false) if found, otherwise try Quit(true), and if there's neither, fall back to
-    // Quit(false).
-    if binds.iter().any(|bind| bind.action == Action::Quit(false)) {
-        actions.push(&Action::Quit(false));
-    } else if binds.iter().any(|bind| bind.action == Action::Quit(true)) {
-        actions.push(&Action::Quit(true));
-    } else {
-        actions.push(&Action::Quit(false));
-    }
-
-    actions.extend(&[
-        &Action::CloseWindow,
-        &Action::FocusColumnLeft,
-        &Action::FocusColumnRight,
-        &Action::MoveColumnLeft,
-        &Action::MoveColumnRight,
-        &Action::FocusWorkspaceDown,
-        &Action::FocusWorkspaceUp,
-    ]);
-
-    // Prefer move-column-to-workspace-down, but fall back to move-window-to-workspace-down.
-    if binds
-        .iter()
-        .any(|bind| bind.action == Action::MoveColumnToWorkspaceDown)
-    {
-        actions.push(&Action::MoveColumnToWorkspaceDown);
-    } else if binds
-        .iter()
-        .any(|bind| bind.action == Action::MoveWindowToWorkspaceDown)
-    {
-        actions.push(&Action::MoveWindowToWorkspaceDown);
-    } else {
-        actions.push(&Action::MoveColumnToWorkspaceDown);
-    }
+    let mut lines = Vec::new();
+    let mut last_category = None;
+    for entry in entries(config, comp_mod) {
+        if !matches_filter(&entry, filter) {
+            continue;
+        }
 
-    // Same for -up.
-    if binds
-        .iter()
-        .any(|bind| bind.action == Action::MoveColumnToWorkspaceUp)
-    {
-        actions.push(&Action::MoveColumnToWorkspaceUp);
-    } else if binds
-        .iter()
-        .any(|bind| bind.action == Action::MoveWindowToWorkspaceUp)
-    {
-        actions.push(&Action::MoveWindowToWorkspaceUp);
-    } else {
-        actions.push(&Action::MoveColumnToWorkspaceUp);
-    }
-
-    actions.extend(&[
-        &Action::SwitchPresetColumnWidth,
-        &Action::MaximizeColumn,
-        &Action::ConsumeWindowIntoColumn,
-        &Action::ExpelWindowFromColumn,
-    ]);
-
-    // Screenshot is not as important, can omit if not bound.
-    if binds.iter().any(|bind| bind.action == Action::Screenshot) {
-        actions.push(&Action::Screenshot);
-    }
-
-    // Add the spawn actions.
-    let mut spawn_actions = Vec::new();
-    for bind in binds.iter().filter(|bind| {
-        matches!(bind.action, Action::Spawn(_))
-            // Only show binds with Mod or Super to filter out stuff like volume up/down.
-            && (bind.key.modifiers.contains(Modifiers::COMPOSITOR)
-                || bind.key.modifiers.contains(Modifiers::SUPER))
-            // Also filter out wheel and touchpad scroll binds.
-            && matches!(bind.key.trigger, Trigger::Keysym(_))
-    }) {
-        let action = &bind.action;
-
-        // We only show one bind for each action, so we need to deduplicate the Spawn actions.
-        if !spawn_actions.contains(&action) {
-            spawn_actions.push(action);
+        if last_category != Some(entry.category) {
+            last_category = Some(entry.category);
+            lines.push(Line::Heading(category_name(entry.category)));
         }
-    }
-    actions.extend(spawn_actions);
 
-    let strings = actions
-        .into_iter()
-        .map(|action| {
-            let key = config
-                .binds
-                .0
+        lines.push(Line::Entry {
+            keys: entry
+                .keys
                 .iter()
-                .find(|bind| bind.action == *action)
-                .map(|bind| key_name(comp_mod, &bind.key))
-                .unwrap_or_else(|| String::from("(not bound)"));
-
-            (format!(" {key} "), action_name(action))
-        })
-        .collect::<Vec<_>>();
+                .map(|key| format!(" {key} "))
+                .collect::<Vec<_>>()
+                .join(" "),
+            title: entry.title,
+        });
+    }
 
     let mut font = FontDescription::from_string(FONT);
     font.set_absolute_size(to_physical_precise_round(scale, font.size()));
@@ -255,42 +336,107 @@ fn render(
 
     let bold = AttrList::new();
     bold.insert(AttrInt::new_weight(Weight::Bold));
-    layout.set_attributes(Some(&bold));
-    layout.set_text(TITLE);
-    let title_size = layout.pixel_size();
 
-    let attrs = AttrList::new();
-    attrs.insert(AttrString::new_family("Monospace"));
-    attrs.insert(AttrColor::new_background(12000, 12000, 12000));
+    let key_attrs = AttrList::new();
+    key_attrs.insert(AttrString::new_family("Monospace"));
+    key_attrs.insert(AttrColor::new_background(12000, 12000, 12000));
+
+    let dim = AttrList::new();
+    dim.insert(AttrColor::new_foreground(40000, 40000, 40000));
+
+    let filter_text = if filter.is_empty() {
+        FILTER_PLACEHOLDER.to_owned()
+    } else {
+        format!("{filter}_")
+    };
 
-    layout.set_attributes(Some(&attrs));
-    let key_sizes = strings
+    let size_of = |attrs: Option<&AttrList>, text: &str, markup: bool| -> (i32, i32) {
+        set_text(&layout, attrs, text, markup);
+        layout.pixel_size()
+    };
+
+    let title_size = size_of(Some(&bold), TITLE, false);
+    let filter_size = size_of(Some(&dim), &filter_text, false);
+    let hint_size = size_of(Some(&dim), SCROLL_HINT, false);
+
+    // Measure all lines, so that the width doesn't change while scrolling.
+    let line_sizes = lines
         .iter()
-        .map(|(key, _)| {
-            layout.set_text(key);
-            layout.pixel_size()
+        .map(|line| match line {
+            Line::Heading(name) => (size_of(Some(&bold), name, false), (0, 0)),
+            Line::Entry { keys, title } => (
+                size_of(Some(&key_attrs), keys, false),
+                size_of(None, title, true),
+            ),
         })
         .collect::<Vec<_>>();
 
-    layout.set_attributes(None);
-    let action_sizes = strings
+    let key_width = lines
         .iter()
-        .map(|(_, action)| {
-            layout.set_markup(action);
-            layout.pixel_size()
+        .zip(&line_sizes)
+        .filter(|(line, _)| matches!(line, Line::Entry { .. }))
+        .map(|(_, ((w, _), _))| *w)
+        .max()
+        .unwrap_or(0);
+    let mut width = line_sizes
+        .iter()
+        .zip(&lines)
+        .map(|(((key_w, _), (title_w, _)), line)| match line {
+            Line::Heading(_) => *key_w,
+            Line::Entry { .. } => key_width + padding + title_w,
         })
-        .collect::<Vec<_>>();
+        .chain([title_size.0, filter_size.0, hint_size.0])
+        .max()
+        .unwrap();
+    let line_height = |((_, key_h), (_, title_h)): &((i32, i32), (i32, i32))| max(*key_h, *title_h);
+
+    // Fit as many lines as we can into the output, starting from the scroll position.
+    let header_height = title_size.1 + padding + filter_size.1 + padding;
+    let max_height = (output_size.h * scale).round() as i32 - margin * 2 - padding * 2;
+    let total_height = line_sizes
+        .iter()
+        .map(|size| line_height(size) + line_interval)
+        .sum::<i32>();
+    let overflows = header_height + total_height > max_height;
+
+    let mut available = max_height - header_height;
+    if overflows {
+        available -= padding + hint_size.1;
+    }
+
+    // Largest scroll that still fills the available space.
+    let mut max_scroll = lines.len();
+    let mut used = 0;
+    while max_scroll > 0 {
+        let h = line_height(&line_sizes[max_scroll - 1]) + line_interval;
+        if used + h > available {
+            break;
+        }
+        used += h;
+        max_scroll -= 1;
+    }
+    let scroll = min(scroll, max_scroll);
 
-    let key_width = key_sizes.iter().map(|(w, _)| w).max().unwrap();
-    let action_width = action_sizes.iter().map(|(w, _)| w).max().unwrap();
-    let mut width = key_width + padding + action_width;
+    let mut visible = 0;
+    let mut lines_height = 0;
+    for size in &line_sizes[scroll..] {
+        let h = line_height(size) + line_interval;
+        if lines_height + h > available && visible > 0 {
+            break;
+        }
+        lines_height += h;
+        visible += 1;
+    }
 
-    let mut height = zip(&key_sizes, &action_sizes)
-        .map(|((_, key_h), (_, act_h))| max(key_h, act_h))
-        .sum::<i32>()
-        + (key_sizes.len() - 1) as i32 * line_interval
-        + title_size.1
-        + padding;
+    let mut height = header_height + lines_height;
+    if lines.is_empty() {
+        let size = size_of(Some(&dim), NO_MATCHES, false);
+        width = max(width, size.0);
+        height += size.1;
+    }
+    if overflows {
+        height += padding + hint_size.1;
+    }
 
     width += padding * 2;
     height += padding * 2;
@@ -300,7 +446,6 @@ fn render(
     cr.set_source_rgb(0.1, 0.1, 0.1);
     cr.paint()?;
 
-    cr.move_to(padding.into(), padding.into());
     let layout = pangocairo::functions::create_layout(&cr);
     layout.context().set_round_glyph_positions(false);
     layout.set_font_description(Some(&font));
@@ -308,27 +453,54 @@ fn render(
     cr.set_source_rgb(1., 1., 1.);
 
     cr.move_to(((width - title_size.0) / 2).into(), padding.into());
-    layout.set_attributes(Some(&bold));
-    layout.set_text(TITLE);
+    set_text(&layout, Some(&bold), TITLE, false);
     pangocairo::functions::show_layout(&cr, &layout);
 
-    cr.move_to(padding.into(), (padding + title_size.1 + padding).into());
+    let mut y = padding + title_size.1 + padding;
+    cr.move_to(((width - filter_size.0) / 2).into(), y.into());
+    set_text(&layout, Some(&dim), &filter_text, false);
+    pangocairo::functions::show_layout(&cr, &layout);
+    y += filter_size.1 + padding;
 
-    for ((key, action), ((_, key_h), (_, act_h))) in zip(&strings, zip(&key_sizes, &action_sizes)) {
-        layout.set_attributes(Some(&attrs));
-        layout.set_text(key);
+    if lines.is_empty() {
+        let size = size_of(Some(&dim), NO_MATCHES, false);
+        cr.move_to(((width - size.0) / 2).into(), y.into());
+        set_text(&layout, Some(&dim), NO_MATCHES, false);
         pangocairo::functions::show_layout(&cr, &layout);
+    }
+
+    for (line, size) in lines[scroll..]
+        .iter()
+        .zip(&line_sizes[scroll..])
+        .take(visible)
+    {
+        cr.move_to(padding.into(), y.into());
 
-        cr.rel_move_to((key_width + padding).into(), 0.);
+        match line {
+            Line::Heading(name) => {
+                set_text(&layout, Some(&bold), name, false);
+                pangocairo::functions::show_layout(&cr, &layout);
+            }
+            Line::Entry { keys, title } => {
+                set_text(&layout, Some(&key_attrs), keys, false);
+                pangocairo::functions::show_layout(&cr, &layout);
 
-        layout.set_attributes(None);
-        layout.set_markup(action);
-        pangocairo::functions::show_layout(&cr, &layout);
+                cr.move_to((padding + key_width + padding).into(), y.into());
+                set_text(&layout, None, title, true);
+                pangocairo::functions::show_layout(&cr, &layout);
+            }
+        }
 
-        cr.rel_move_to(
-            (-(key_width + padding)).into(),
-            (max(key_h, act_h) + line_interval).into(),
+        y += line_height(size) + line_interval;
+    }
+
+    if overflows {
+        cr.move_to(
+            ((width - hint_size.0) / 2).into(),
+            (height - padding - hint_size.1).into(),
         );
+        set_text(&layout, Some(&dim), SCROLL_HINT, false);
+        pangocairo::functions::show_layout(&cr, &layout);
     }
 
     cr.move_to(0., 0.);
@@ -356,34 +528,320 @@ fn render(
 
     Ok(RenderedOverlay {
         buffer: Some(buffer),
+        max_scroll,
     })
 }
 
-fn action_name(action: &Action) -> String {
+fn set_text(layout: &Layout, attrs: Option<&AttrList>, text: &str, markup: bool) {
+    layout.set_attributes(attrs);
+    if markup {
+        layout.set_markup(text);
+    } else {
+        layout.set_text(text);
+    }
+}
+
+fn category(action: &Action) -> Category {
     match action {
-        Action::Quit(_) => String::from("Exit niri"),
-        Action::ShowHotkeyOverlay => String::from("Show Important Hotkeys"),
-        Action::CloseWindow => String::from("Close Focused Window"),
-        Action::FocusColumnLeft => String::from("Focus Column to the Left"),
-        Action::FocusColumnRight => String::from("Focus Column to the Right"),
-        Action::MoveColumnLeft => String::from("Move Column Left"),
-        Action::MoveColumnRight => String::from("Move Column Right"),
-        Action::FocusWorkspaceDown => String::from("Switch Workspace Down"),
-        Action::FocusWorkspaceUp => String::from("Switch Workspace Up"),
-        Action::MoveColumnToWorkspaceDown => String::from("Move Column to Workspace Down"),
-        Action::MoveColumnToWorkspaceUp => String::from("Move Column to Workspace Up"),
-        Action::MoveWindowToWorkspaceDown => String::from("Move Window to Workspace Down"),
-        Action::MoveWindowToWorkspaceUp => String::from("Move Window to Workspace Up"),
-        Action::SwitchPresetColumnWidth => String::from("Switch Preset Column Widths"),
-        Action::MaximizeColumn => String::from("Maximize Column"),
-        Action::ConsumeWindowIntoColumn => String::from("Consume Window Into Column"),
-        Action::ExpelWindowFromColumn => String::from("Expel Window From Column"),
-        Action::Screenshot => String::from("Take a Screenshot"),
-        Action::Spawn(args) => format!(
-            "Spawn <span face='monospace' bgcolor='#000000'>{}</span>",
-            args.first().unwrap_or(&String::new())
-        ),
-        _ => String::from("FIXME: Unknown"),
+        Action::Spawn(_) => Category::Applications,
+        Action::ConfirmScreenshot
+        | Action::CancelScreenshot
+        | Action::ScreenshotTogglePointer
+        | Action::ScreenshotToggleWindowPicking
+        | Action::Screenshot(..)
+        | Action::ScreenshotScreen(..)
+        | Action::ScreenshotWindow(..)
+        | Action::ScreenshotWindowById { .. } => Category::Screenshots,
+        Action::FocusWindowOrWorkspaceDown
+        | Action::FocusWindowOrWorkspaceUp
+        | Action::MoveWindowDownOrToWorkspaceDown
+        | Action::MoveWindowUpOrToWorkspaceUp
+        | Action::FocusWorkspaceDown
+        | Action::FocusWorkspaceUp
+        | Action::FocusWorkspace(_)
+        | Action::FocusWorkspacePrevious
+        | Action::MoveWindowToWorkspaceDown
+        | Action::MoveWindowToWorkspaceUp
+        | Action::MoveWindowToWorkspace(_)
+        | Action::MoveWindowToWorkspaceById { .. }
+        | Action::MoveColumnToWorkspaceDown
+        | Action::MoveColumnToWorkspaceUp
+        | Action::MoveColumnToWorkspace(_)
+        | Action::MoveWorkspaceDown
+        | Action::MoveWorkspaceUp
+        | Action::MoveWorkspaceToMonitorLeft
+        | Action::MoveWorkspaceToMonitorRight
+        | Action::MoveWorkspaceToMonitorDown
+        | Action::MoveWorkspaceToMonitorUp => Category::Workspaces,
+        Action::PowerOffMonitors
+        | Action::PowerOnMonitors
+        | Action::PowerOffMonitor(_)
+        | Action::PowerOnMonitor(_)
+        | Action::FocusWindowOrMonitorUp
+        | Action::FocusWindowOrMonitorDown
+        | Action::FocusColumnOrMonitorLeft
+        | Action::FocusColumnOrMonitorRight
+        | Action::MoveColumnLeftOrToMonitorLeft
+        | Action::MoveColumnRightOrToMonitorRight
+        | Action::FocusMonitorLeft
+        | Action::FocusMonitorRight
+        | Action::FocusMonitorDown
+        | Action::FocusMonitorUp
+        | Action::MoveWindowToMonitorLeft
+        | Action::MoveWindowToMonitorRight
+        | Action::MoveWindowToMonitorDown
+        | Action::MoveWindowToMonitorUp
+        | Action::MoveColumnToMonitorLeft
+        | Action::MoveColumnToMonitorRight
+        | Action::MoveColumnToMonitorDown
+        | Action::MoveColumnToMonitorUp
+        | Action::SetDynamicCastMonitor(_) => Category::Monitors,
+        Action::FocusColumnLeft
+        | Action::FocusColumnRight
+        | Action::FocusColumnFirst
+        | Action::FocusColumnLast
+        | Action::FocusColumnRightOrFirst
+        | Action::FocusColumnLeftOrLast
+        | Action::FocusWindowDownOrColumnLeft
+        | Action::FocusWindowDownOrColumnRight
+        | Action::FocusWindowUpOrColumnLeft
+        | Action::FocusWindowUpOrColumnRight
+        | Action::MoveColumnLeft
+        | Action::MoveColumnRight
+        | Action::MoveColumnToFirst
+        | Action::MoveColumnToLast
+        | Action::ConsumeWindowIntoColumn
+        | Action::ExpelWindowFromColumn
+        | Action::CenterColumn
+        | Action::SwitchPresetColumnWidth
+        | Action::MaximizeColumn
+        | Action::SetColumnWidth(_) => Category::Columns,
+        Action::CloseWindow
+        | Action::CloseWindowById(_)
+        | Action::FullscreenWindow
+        | Action::FullscreenWindowById(_)
+        | Action::FocusWindow(_)
+        | Action::FocusWindowDown
+        | Action::FocusWindowUp
+        | Action::MoveWindowDown
+        | Action::MoveWindowUp
+        | Action::ConsumeOrExpelWindowLeft
+        | Action::ConsumeOrExpelWindowLeftById(_)
+        | Action::ConsumeOrExpelWindowRight
+        | Action::ConsumeOrExpelWindowRightById(_)
+        | Action::SetWindowHeight(_)
+        | Action::SetWindowHeightById { .. }
+        | Action::ResetWindowHeight
+        | Action::ResetWindowHeightById(_)
+        | Action::SwitchPresetWindowHeight
+        | Action::SwitchPresetWindowHeightById(_)
+        | Action::SetDynamicCastWindow
+        | Action::SetDynamicCastWindowById(_) => Category::Windows,
+        Action::Quit(_)
+        | Action::ChangeVt(_)
+        | Action::Suspend
+        | Action::ToggleRotationLock
+        | Action::ToggleDebugTint
+        | Action::DebugToggleOpaqueRegions
+        | Action::DebugToggleDamage
+        | Action::DoScreenTransition(_)
+        | Action::SwitchLayout(_)
+        | Action::ShowHotkeyOverlay
+        | Action::ClearDynamicCastTarget => Category::General,
+    }
+}
+
+fn category_name(category: Category) -> &'static str {
+    match category {
+        Category::General => "General",
+        Category::Applications => "Applications",
+        Category::Windows => "Windows",
+        Category::Columns => "Columns",
+        Category::Workspaces => "Workspaces",
+        Category::Monitors => "Monitors",
+        Category::Screenshots => "Screenshots",
+    }
+}
+
+fn action_name(action: &Action) -> String {
+    let name = match action {
+        Action::Quit(_) => "Exit niri",
+        Action::ChangeVt(vt) => return format!("Switch to VT {vt}"),
+        Action::Suspend => "Suspend",
+        Action::PowerOffMonitors => "Power Off Monitors",
+        Action::PowerOnMonitors => "Power On Monitors",
+        Action::PowerOffMonitor(name) => {
+            return format!("Power Off Monitor {}", markup_escape_text(name))
+        }
+        Action::PowerOnMonitor(name) => {
+            return format!("Power On Monitor {}", markup_escape_text(name))
+        }
+        Action::ToggleRotationLock => "Toggle Rotation Lock",
+        Action::ToggleDebugTint => "Toggle Debug Tint",
+        Action::DebugToggleOpaqueRegions => "Toggle Opaque Regions Debug View",
+        Action::DebugToggleDamage => "Toggle Damage Debug View",
+        Action::Spawn(args) => {
+            return format!(
+                "Spawn <span face='monospace' bgcolor='#000000'>{}</span>",
+                markup_escape_text(args.first().map_or("", |arg| arg.as_str()))
+            )
+        }
+        Action::DoScreenTransition(_) => "Do Screen Transition",
+        Action::ConfirmScreenshot => "Confirm Screenshot",
+        Action::CancelScreenshot => "Cancel Screenshot",
+        Action::ScreenshotTogglePointer => "Toggle Pointer in Screenshot",
+        Action::ScreenshotToggleWindowPicking => "Toggle Window Picking in Screenshot",
+        Action::Screenshot(write_to_disk, _, _) => {
+            return screenshot_name("Take a Screenshot", *write_to_disk)
+        }
+        Action::ScreenshotScreen(write_to_disk, _, _) => {
+            return screenshot_name("Screenshot the Focused Monitor", *write_to_disk)
+        }
+        Action::ScreenshotWindow(write_to_disk, _, _) => {
+            return screenshot_name("Screenshot the Focused Window", *write_to_disk)
+        }
+        Action::ScreenshotWindowById {
+            id, write_to_disk, ..
+        } => return screenshot_name(&format!("Screenshot Window with id {id}"), *write_to_disk),
+        Action::CloseWindow => "Close Focused Window",
+        Action::CloseWindowById(id) => return format!("Close Window with id {id}"),
+        Action::FullscreenWindow => "Toggle Fullscreen",
+        Action::FullscreenWindowById(id) => {
+            return format!("Toggle Fullscreen for Window with id {id}")
+        }
+        Action::FocusWindow(id) => return format!("Focus Window with id {id}"),
+        Action::FocusColumnLeft => "Focus Column to the Left",
+        Action::FocusColumnRight => "Focus Column to the Right",
+        Action::FocusColumnFirst => "Focus First Column",
+        Action::FocusColumnLast => "Focus Last Column",
+        Action::FocusColumnRightOrFirst => "Focus Column to the Right or the First",
+        Action::FocusColumnLeftOrLast => "Focus Column to the Left or the Last",
+        Action::FocusWindowOrMonitorUp => "Focus Window or Monitor Up",
+        Action::FocusWindowOrMonitorDown => "Focus Window or Monitor Down",
+        Action::FocusColumnOrMonitorLeft => "Focus Column or Monitor to the Left",
+        Action::FocusColumnOrMonitorRight => "Focus Column or Monitor to the Right",
+        Action::FocusWindowDown => "Focus Window Down",
+        Action::FocusWindowUp => "Focus Window Up",
+        Action::FocusWindowDownOrColumnLeft => "Focus Window Down or Column to the Left",
+        Action::FocusWindowDownOrColumnRight => "Focus Window Down or Column to the Right",
+        Action::FocusWindowUpOrColumnLeft => "Focus Window Up or Column to the Left",
+        Action::FocusWindowUpOrColumnRight => "Focus Window Up or Column to the Right",
+        Action::FocusWindowOrWorkspaceDown => "Focus Window or Workspace Down",
+        Action::FocusWindowOrWorkspaceUp => "Focus Window or Workspace Up",
+        Action::MoveColumnLeft => "Move Column Left",
+        Action::MoveColumnRight => "Move Column Right",
+        Action::MoveColumnToFirst => "Move Column to the Start",
+        Action::MoveColumnToLast => "Move Column to the End",
+        Action::MoveColumnLeftOrToMonitorLeft => "Move Column Left or to Monitor Left",
+        Action::MoveColumnRightOrToMonitorRight => "Move Column Right or to Monitor Right",
+        Action::MoveWindowDown => "Move Window Down",
+        Action::MoveWindowUp => "Move Window Up",
+        Action::MoveWindowDownOrToWorkspaceDown => "Move Window Down or to Workspace Down",
+        Action::MoveWindowUpOrToWorkspaceUp => "Move Window Up or to Workspace Up",
+        Action::ConsumeOrExpelWindowLeft => "Consume or Expel Window Left",
+        Action::ConsumeOrExpelWindowLeftById(id) => {
+            return format!("Consume or Expel Window with id {id} Left")
+        }
+        Action::ConsumeOrExpelWindowRight => "Consume or Expel Window Right",
+        Action::ConsumeOrExpelWindowRightById(id) => {
+            return format!("Consume or Expel Window with id {id} Right")
+        }
+        Action::ConsumeWindowIntoColumn => "Consume Window Into Column",
+        Action::ExpelWindowFromColumn => "Expel Window From Column",
+        Action::CenterColumn => "Center Column",
+        Action::FocusWorkspaceDown => "Switch Workspace Down",
+        Action::FocusWorkspaceUp => "Switch Workspace Up",
+        Action::FocusWorkspace(reference) => {
+            return format!("Switch to Workspace {}", workspace_name(reference))
+        }
+        Action::FocusWorkspacePrevious => "Switch to the Previous Workspace",
+        Action::MoveWindowToWorkspaceDown => "Move Window to Workspace Down",
+        Action::MoveWindowToWorkspaceUp => "Move Window to Workspace Up",
+        Action::MoveWindowToWorkspace(reference) => {
+            return format!("Move Window to Workspace {}", workspace_name(reference))
+        }
+        Action::MoveWindowToWorkspaceById {
+            window_id,
+            reference,
+        } => {
+            return format!(
+                "Move Window with id {window_id} to Workspace {}",
+                workspace_name(reference)
+            )
+        }
+        Action::MoveColumnToWorkspaceDown => "Move Column to Workspace Down",
+        Action::MoveColumnToWorkspaceUp => "Move Column to Workspace Up",
+        Action::MoveColumnToWorkspace(reference) => {
+            return format!("Move Column to Workspace {}", workspace_name(reference))
+        }
+        Action::MoveWorkspaceDown => "Move Workspace Down",
+        Action::MoveWorkspaceUp => "Move Workspace Up",
+        Action::FocusMonitorLeft => "Focus Monitor to the Left",
+        Action::FocusMonitorRight => "Focus Monitor to the Right",
+        Action::FocusMonitorDown => "Focus Monitor Down",
+        Action::FocusMonitorUp => "Focus Monitor Up",
+        Action::MoveWindowToMonitorLeft => "Move Window to Monitor Left",
+        Action::MoveWindowToMonitorRight => "Move Window to Monitor Right",
+        Action::MoveWindowToMonitorDown => "Move Window to Monitor Down",
+        Action::MoveWindowToMonitorUp => "Move Window to Monitor Up",
+        Action::MoveColumnToMonitorLeft => "Move Column to Monitor Left",
+        Action::MoveColumnToMonitorRight => "Move Column to Monitor Right",
+        Action::MoveColumnToMonitorDown => "Move Column to Monitor Down",
+        Action::MoveColumnToMonitorUp => "Move Column to Monitor Up",
+        Action::SetWindowHeight(change) => return size_change_name("Window Height", *change),
+        Action::SetWindowHeightById { id, change } => {
+            return size_change_name(&format!("Height of Window with id {id}"), *change)
+        }
+        Action::ResetWindowHeight => "Reset Window Height",
+        Action::ResetWindowHeightById(id) => return format!("Reset Height of Window with id {id}"),
+        Action::SwitchPresetColumnWidth => "Switch Preset Column Widths",
+        Action::SwitchPresetWindowHeight => "Switch Preset Window Heights",
+        Action::SwitchPresetWindowHeightById(id) => {
+            return format!("Switch Preset Heights of Window with id {id}")
+        }
+        Action::MaximizeColumn => "Maximize Column",
+        Action::SetColumnWidth(change) => return size_change_name("Column Width", *change),
+        Action::SwitchLayout(LayoutSwitchTarget::Next) => "Switch to the Next Keyboard Layout",
+        Action::SwitchLayout(LayoutSwitchTarget::Prev) => "Switch to the Previous Keyboard Layout",
+        Action::ShowHotkeyOverlay => "Show Important Hotkeys",
+        Action::MoveWorkspaceToMonitorLeft => "Move Workspace to Monitor Left",
+        Action::MoveWorkspaceToMonitorRight => "Move Workspace to Monitor Right",
+        Action::MoveWorkspaceToMonitorDown => "Move Workspace to Monitor Down",
+        Action::MoveWorkspaceToMonitorUp => "Move Workspace to Monitor Up",
+        Action::SetDynamicCastWindow => "Cast the Focused Window",
+        Action::SetDynamicCastWindowById(id) => return format!("Cast Window with id {id}"),
+        Action::SetDynamicCastMonitor(None) => "Cast the Focused Monitor",
+        Action::SetDynamicCastMonitor(Some(name)) => {
+            return format!("Cast Monitor {}", markup_escape_text(name))
+        }
+        Action::ClearDynamicCastTarget => "Stop Casting the Dynamic Target",
+    };
+
+    String::from(name)
+}
+
+fn screenshot_name(name: &str, write_to_disk: bool) -> String {
+    if write_to_disk {
+        String::from(name)
+    } else {
+        format!("{name} to the Clipboard")
+    }
+}
+
+fn size_change_name(what: &str, change: SizeChange) -> String {
+    match change {
+        SizeChange::SetFixed(value) => format!("Set {what} to {value}"),
+        SizeChange::SetProportion(value) => format!("Set {what} to {value}%"),
+        SizeChange::AdjustFixed(value) => format!("Change {what} by {value:+}"),
+        SizeChange::AdjustProportion(value) => format!("Change {what} by {value:+}%"),
+    }
+}
+
+fn workspace_name(reference: &WorkspaceReference) -> String {
+    match reference {
+        WorkspaceReference::Id(id) => format!("with id {id}"),
+        WorkspaceReference::Index(idx) => idx.to_string(),
+        WorkspaceReference::Name(name) => format!("\"{}\"", markup_escape_text(name)),
     }
 }
 
@@ -450,3 +908,194 @@ fn prettify_keysym_name(name: &str) -> String {
         name.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config::parse(
+            "test.kdl",
+            r#"
+            binds {
+                Mod+Shift+E { quit; }
+                Mod+T hotkey-overlay-title="Open a Terminal" { spawn "alacritty"; }
+                Mod+D { spawn "a<b&c"; }
+                Mod+Q hotkey-overlay-title=null { close-window; }
+                Mod+Left { focus-column-left; }
+                Mod+H { focus-column-left; }
+                Mod+1 { focus-workspace 1; }
+                Mod+Ctrl+Right { move-workspace-to-monitor-right; }
+                Print { screenshot write-to-disk=false; }
+            }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn entries_from_binds() {
+        let config = config();
+        let entries = entries(&config, CompositorMod::Super);
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.category, entry.title.as_str(), entry.keys.join(", ")))
+            .collect();
+
+        assert_eq!(
+            entries,
+            [
+                (
+                    Category::General,
+                    "Exit niri",
+                    String::from("Super + Shift + E")
+                ),
+                (
+                    Category::Applications,
+                    "Open a Terminal",
+                    String::from("Super + T")
+                ),
+                (
+                    Category::Applications,
+                    "Spawn <span face='monospace' bgcolor='#000000'>a&lt;b&amp;c</span>",
+                    String::from("Super + D")
+                ),
+                (
+                    Category::Columns,
+                    "Focus Column to the Left",
+                    String::from("Super + Left, Super + H")
+                ),
+                (
+                    Category::Workspaces,
+                    "Switch to Workspace 1",
+                    String::from("Super + 1")
+                ),
+                (
+                    Category::Workspaces,
+                    "Move Workspace to Monitor Right",
+                    String::from("Super + Ctrl + Right")
+                ),
+                (
+                    Category::Screenshots,
+                    "Take a Screenshot to the Clipboard",
+                    String::from("PrtSc")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_entries() {
+        let config = config();
+        let entries = entries(&config, CompositorMod::Super);
+        let matching = |filter: &str| {
+            entries
+                .iter()
+                .filter(|entry| matches_filter(entry, filter))
+                .map(|entry| entry.title.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching("").len(), entries.len());
+        assert_eq!(matching("EXIT"), ["Exit niri"]);
+        // Matches the text rather than the markup.
+        assert_eq!(matching("span"), Vec::<&str>::new());
+        assert_eq!(matching("a<b"), [entries[2].title.as_str()]);
+        // Matches the category name.
+        assert_eq!(matching("applications").len(), 2);
+        // Matches the keys.
+        assert_eq!(matching("prtsc"), ["Take a Screenshot to the Clipboard"]);
+        assert_eq!(matching("super + h"), ["Focus Column to the Left"]);
+    }
+
+    #[test]
+    fn action_categories() {
+        assert_eq!(category(&Action::Spawn(vec![])), Category::Applications);
+        assert_eq!(category(&Action::CloseWindowById(1)), Category::Windows);
+        assert_eq!(
+            category(&Action::FocusWindowDownOrColumnLeft),
+            Category::Columns
+        );
+        assert_eq!(
+            category(&Action::MoveWindowDownOrToWorkspaceDown),
+            Category::Workspaces
+        );
+        assert_eq!(
+            category(&Action::MoveWorkspaceToMonitorLeft),
+            Category::Workspaces
+        );
+        assert_eq!(
+            category(&Action::SetDynamicCastMonitor(None)),
+            Category::Monitors
+        );
+        assert_eq!(
+            category(&Action::ScreenshotWindow(true, None, None)),
+            Category::Screenshots
+        );
+        assert_eq!(
+            category(&Action::SwitchPresetColumnWidth),
+            Category::Columns
+        );
+        assert_eq!(category(&Action::Suspend), Category::General);
+    }
+
+    #[test]
+    fn action_titles() {
+        assert_eq!(
+            action_name(&Action::Screenshot(true, Some(500), None)),
+            "Take a Screenshot"
+        );
+        assert_eq!(
+            action_name(&Action::SetDynamicCastMonitor(Some(String::from("DP-<1>")))),
+            "Cast Monitor DP-&lt;1&gt;"
+        );
+        assert_eq!(
+            action_name(&Action::SetColumnWidth(SizeChange::AdjustProportion(-10.))),
+            "Change Column Width by -10%"
+        );
+        assert_eq!(
+            action_name(&Action::SetWindowHeight(SizeChange::SetFixed(400))),
+            "Set Window Height to 400"
+        );
+    }
+
+    #[test]
+    fn filter_and_scroll_keys() {
+        let config = Rc::new(RefCell::new(config()));
+        let mut overlay = HotkeyOverlay::new(config, CompositorMod::Super);
+        let mods = ModifiersState::default();
+
+        // Keys are ignored while the overlay is closed.
+        assert!(!overlay.on_key(Keysym::a, mods));
+
+        overlay.show();
+        assert!(overlay.on_key(Keysym::a, mods));
+        assert!(overlay.on_key(Keysym::b, mods));
+        assert_eq!(overlay.filter, "ab");
+
+        // Shortcuts with modifiers go to the bindings.
+        let ctrl = ModifiersState { ctrl: true, ..mods };
+        assert!(!overlay.on_key(Keysym::c, ctrl));
+
+        assert!(overlay.on_key(Keysym::BackSpace, mods));
+        assert_eq!(overlay.filter, "a");
+        assert!(overlay.on_key(Keysym::Escape, mods));
+        assert_eq!(overlay.filter, "");
+        // With an empty filter, Escape closes the overlay instead.
+        assert!(!overlay.on_key(Keysym::Escape, mods));
+
+        // Nothing has been rendered, so there's nothing to scroll.
+        assert!(overlay.on_key(Keysym::Page_Down, mods));
+        assert_eq!(overlay.scroll, 0);
+
+        overlay.scroll = 5;
+        assert!(overlay.on_key(Keysym::Up, mods));
+        assert_eq!(overlay.scroll, 4);
+        assert!(overlay.on_key(Keysym::x, mods));
+        assert_eq!(overlay.scroll, 0);
+
+        // Closing resets the filter.
+        overlay.hide();
+        assert_eq!(overlay.filter, "");
+    }
+}
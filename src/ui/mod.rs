@@ -1,5 +1,7 @@
+pub mod capture_indicator;
 pub mod config_error_notification;
 pub mod exit_confirm_dialog;
 pub mod hotkey_overlay;
 pub mod screen_transition;
 pub mod screenshot_ui;
+pub mod workspace_switch_osd;
@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::iter::zip;
+use std::mem;
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -16,16 +17,19 @@ use smithay::backend::renderer::element::utils::{Relocate, RelocateRenderElement
 use smithay::backend::renderer::element::Kind;
 use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
 use smithay::backend::renderer::{ExportMem, Texture as _};
+use smithay::desktop::LayerSurface;
 use smithay::input::keyboard::{Keysym, ModifiersState};
-use smithay::output::{Output, WeakOutput};
-use smithay::utils::{Physical, Point, Rectangle, Scale, Size, Transform};
+use smithay::output::Output;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{Logical, Physical, Point, Rectangle, Scale, Size, Transform};
 
 use crate::animation::Animation;
+use crate::niri::ScreenshotOptions;
 use crate::niri_render_elements;
 use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
 use crate::render_helpers::solid_color::{SolidColorBuffer, SolidColorRenderElement};
 use crate::render_helpers::texture::{TextureBuffer, TextureRenderElement};
-use crate::render_helpers::{render_to_texture, RenderTarget};
+use crate::render_helpers::{render_to_texture, render_to_vec, RenderTarget};
 use crate::utils::to_physical_precise_round;
 
 const SELECTION_BORDER: i32 = 2;
@@ -35,41 +39,72 @@ const FONT: &str = "sans 14px";
 const BORDER: i32 = 4;
 const TEXT_HIDE_P: &str =
     "Press <span face='mono' bgcolor='#2C2C2C'> Space </span> to save the screenshot.\n\
-     Press <span face='mono' bgcolor='#2C2C2C'> P </span> to hide the pointer.";
+     Press <span face='mono' bgcolor='#2C2C2C'> P </span> to hide the pointer.\n\
+     Press <span face='mono' bgcolor='#2C2C2C'> W </span> to pick a window.";
 const TEXT_SHOW_P: &str =
     "Press <span face='mono' bgcolor='#2C2C2C'> Space </span> to save the screenshot.\n\
-     Press <span face='mono' bgcolor='#2C2C2C'> P </span> to show the pointer.";
-
-// Ideally the screenshot UI should support cross-output selections. However, that poses some
-// technical challenges when the outputs have different scales and such. So, this implementation
-// allows only single-output selections for now.
-//
-// As a consequence of this, selection coordinates are in output-local coordinate space.
+     Press <span face='mono' bgcolor='#2C2C2C'> P </span> to show the pointer.\n\
+     Press <span face='mono' bgcolor='#2C2C2C'> W </span> to pick a window.";
+const TEXT_PICK: &str = "Click a window or a layer surface to save it.\n\
+     Press <span face='mono' bgcolor='#2C2C2C'> W </span> to select an area.";
+
+// Selection coordinates are in the global logical coordinate space, which lets the selection span
+// multiple outputs. They are converted to output-local physical coordinates for every output, so
+// that on a single output the selection remains pixel-exact regardless of the output scale.
 #[allow(clippy::large_enum_variant)]
 pub enum ScreenshotUi {
     Closed {
-        last_selection: Option<(WeakOutput, Rectangle<i32, Physical>)>,
+        last_selection: Option<(Point<f64, Logical>, Point<f64, Logical>)>,
         config: Rc<RefCell<Config>>,
     },
     Open {
-        selection: (Output, Point<i32, Physical>, Point<i32, Physical>),
+        selection: (Point<f64, Logical>, Point<f64, Logical>),
         output_data: HashMap<Output, OutputData>,
         mouse_down: bool,
         show_pointer: bool,
+        /// Whether the window picking mode is active.
+        ///
+        /// In this mode, the window or layer surface under the pointer is highlighted, and a click
+        /// saves it instead of the selected area.
+        picking: bool,
+        /// Surface under the pointer in the window picking mode, and its global geometry.
+        picked: Option<(PickedSurface, Rectangle<i32, Logical>)>,
+        /// How to save the screenshot once confirmed.
+        options: ScreenshotOptions,
         open_anim: Animation,
         config: Rc<RefCell<Config>>,
     },
 }
 
+/// Surface that can be saved from the window picking mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickedSurface {
+    /// Toplevel window, identified by its root surface.
+    Window(WlSurface),
+    Layer {
+        surface: LayerSurface,
+        output: Output,
+    },
+}
+
 pub struct OutputData {
-    size: Size<i32, Physical>,
-    scale: f64,
+    geo: OutputGeometry,
     transform: Transform,
     // Output, screencast, screen capture.
     screenshot: [OutputScreenshot; 3],
     buffers: [SolidColorBuffer; 8],
     locations: [Point<i32, Physical>; 8],
-    panel: Option<(TextureBuffer<GlesTexture>, TextureBuffer<GlesTexture>)>,
+    // Show pointer, hide pointer, window picking.
+    panel: Option<[TextureBuffer<GlesTexture>; 3]>,
+}
+
+/// Where an output is and how to convert selection coordinates for it.
+#[derive(Debug, Clone, Copy)]
+struct OutputGeometry {
+    /// Location of the output in the global space.
+    location: Point<i32, Logical>,
+    size: Size<i32, Physical>,
+    scale: f64,
 }
 
 pub struct OutputScreenshot {
@@ -99,6 +134,7 @@ impl ScreenshotUi {
         // Output, screencast, screen capture.
         screenshots: HashMap<Output, [OutputScreenshot; 3]>,
         default_output: Output,
+        options: ScreenshotOptions,
     ) -> bool {
         if screenshots.is_empty() {
             return false;
@@ -112,33 +148,7 @@ impl ScreenshotUi {
             return false;
         };
 
-        let last_selection = last_selection
-            .take()
-            .and_then(|(weak, sel)| weak.upgrade().map(|output| (output, sel)));
-        let selection = match last_selection {
-            Some(selection) if screenshots.contains_key(&selection.0) => selection,
-            _ => {
-                let output = default_output;
-                let output_transform = output.current_transform();
-                let output_mode = output.current_mode().unwrap();
-                let size = output_transform.transform_size(output_mode.size);
-                (
-                    output,
-                    Rectangle::from_loc_and_size(
-                        (size.w / 4, size.h / 4),
-                        (size.w / 2, size.h / 2),
-                    ),
-                )
-            }
-        };
-
-        let selection = (
-            selection.0,
-            selection.1.loc,
-            selection.1.loc + selection.1.size - Size::from((1, 1)),
-        );
-
-        let output_data = screenshots
+        let output_data: HashMap<_, _> = screenshots
             .into_iter()
             .map(|(output, screenshot)| {
                 let transform = output.current_transform();
@@ -164,11 +174,17 @@ impl ScreenshotUi {
                 };
                 let panel_show = render_panel_(TEXT_SHOW_P);
                 let panel_hide = render_panel_(TEXT_HIDE_P);
-                let panel = Option::zip(panel_show, panel_hide);
+                let panel_pick = render_panel_(TEXT_PICK);
+                let panel = Option::zip(panel_show, panel_hide)
+                    .zip(panel_pick)
+                    .map(|((show, hide), pick)| [show, hide, pick]);
 
                 let data = OutputData {
-                    size,
-                    scale,
+                    geo: OutputGeometry {
+                        location: output.current_location(),
+                        size,
+                        scale,
+                    },
                     transform,
                     screenshot,
                     buffers,
@@ -179,6 +195,22 @@ impl ScreenshotUi {
             })
             .collect();
 
+        // Restore the last selection if it is still visible on some output.
+        let selection = last_selection
+            .take()
+            .filter(|(a, b)| {
+                output_data
+                    .values()
+                    .any(|data| data.geo.selection_rect(*a, *b).is_some())
+            })
+            .unwrap_or_else(|| {
+                let data = output_data
+                    .get(&default_output)
+                    .or_else(|| output_data.values().next())
+                    .unwrap();
+                data.geo.default_selection()
+            });
+
         let open_anim = {
             let c = config.borrow();
             Animation::new(0., 1., 0., c.animations.screenshot_ui_open.0)
@@ -189,6 +221,9 @@ impl ScreenshotUi {
             output_data,
             mouse_down: false,
             show_pointer: true,
+            picking: false,
+            picked: None,
+            options,
             open_anim,
             config: config.clone(),
         };
@@ -206,13 +241,8 @@ impl ScreenshotUi {
             return false;
         };
 
-        let last_selection = Some((
-            selection.0.downgrade(),
-            rect_from_corner_points(selection.1, selection.2),
-        ));
-
         *self = Self::Closed {
-            last_selection,
+            last_selection: Some(*selection),
             config: config.clone(),
         };
 
@@ -225,6 +255,34 @@ impl ScreenshotUi {
         }
     }
 
+    pub fn toggle_picking(&mut self) {
+        let Self::Open {
+            mouse_down,
+            picking,
+            picked,
+            ..
+        } = self
+        else {
+            return;
+        };
+
+        *picking = !*picking;
+        *picked = None;
+        // Don't leave a half-finished area selection behind.
+        *mouse_down = false;
+
+        self.update_buffers();
+    }
+
+    /// Takes the options for saving the screenshot, leaving the defaults in their place.
+    pub fn take_options(&mut self) -> ScreenshotOptions {
+        if let Self::Open { options, .. } = self {
+            mem::take(options)
+        } else {
+            ScreenshotOptions::default()
+        }
+    }
+
     pub fn is_open(&self) -> bool {
         matches!(self, ScreenshotUi::Open { .. })
     }
@@ -249,72 +307,24 @@ impl ScreenshotUi {
         let Self::Open {
             selection,
             output_data,
+            picking,
+            picked,
             ..
         } = self
         else {
             panic!("screenshot UI must be open to update buffers");
         };
 
-        let (selection_output, a, b) = selection;
-        let mut rect = rect_from_corner_points(*a, *b);
-
-        for (output, data) in output_data {
-            let buffers = &mut data.buffers;
-            let locations = &mut data.locations;
-            let size = data.size;
-            let scale = data.scale;
-
-            if output == selection_output {
-                // Check if the selection is still valid. If not, reset it back to default.
-                if !Rectangle::from_loc_and_size((0, 0), size).contains_rect(rect) {
-                    rect = Rectangle::from_loc_and_size(
-                        (size.w / 4, size.h / 4),
-                        (size.w / 2, size.h / 2),
-                    );
-                    *a = rect.loc;
-                    *b = rect.loc + rect.size - Size::from((1, 1));
-                }
-
-                let border = to_physical_precise_round(scale, SELECTION_BORDER);
-
-                let resize = move |buffer: &mut SolidColorBuffer, w: i32, h: i32| {
-                    let size = Size::<_, Physical>::from((w, h));
-                    buffer.resize(size.to_f64().to_logical(scale));
-                };
-
-                resize(&mut buffers[0], rect.size.w + border * 2, border);
-                resize(&mut buffers[1], rect.size.w + border * 2, border);
-                resize(&mut buffers[2], border, rect.size.h);
-                resize(&mut buffers[3], border, rect.size.h);
-
-                resize(&mut buffers[4], size.w, rect.loc.y);
-                resize(&mut buffers[5], size.w, size.h - rect.loc.y - rect.size.h);
-                resize(&mut buffers[6], rect.loc.x, rect.size.h);
-                resize(
-                    &mut buffers[7],
-                    size.w - rect.loc.x - rect.size.w,
-                    rect.size.h,
-                );
-
-                locations[0] = Point::from((rect.loc.x - border, rect.loc.y - border));
-                locations[1] = Point::from((rect.loc.x - border, rect.loc.y + rect.size.h));
-                locations[2] = Point::from((rect.loc.x - border, rect.loc.y));
-                locations[3] = Point::from((rect.loc.x + rect.size.w, rect.loc.y));
-
-                locations[5] = Point::from((0, rect.loc.y + rect.size.h));
-                locations[6] = Point::from((0, rect.loc.y));
-                locations[7] = Point::from((rect.loc.x + rect.size.w, rect.loc.y));
+        for data in output_data.values_mut() {
+            let rect = if *picking {
+                picked
+                    .as_ref()
+                    .and_then(|(_, geo)| data.geo.geometry_rect(*geo))
             } else {
-                buffers[0].resize((0., 0.));
-                buffers[1].resize((0., 0.));
-                buffers[2].resize((0., 0.));
-                buffers[3].resize((0., 0.));
-
-                buffers[4].resize(size.to_f64().to_logical(data.scale));
-                buffers[5].resize((0., 0.));
-                buffers[6].resize((0., 0.));
-                buffers[7].resize((0., 0.));
-            }
+                data.geo.selection_rect(selection.0, selection.1)
+            };
+
+            data.update_buffers(rect);
         }
     }
 
@@ -329,6 +339,7 @@ impl ScreenshotUi {
             output_data,
             show_pointer,
             mouse_down,
+            picking,
             open_anim,
             ..
         } = self
@@ -342,17 +353,23 @@ impl ScreenshotUi {
             return elements;
         };
 
-        let scale = output_data.scale;
+        let scale = output_data.geo.scale;
         let progress = open_anim.clamped_value().clamp(0., 1.) as f32;
 
         // The help panel goes on top.
-        if let Some((show, hide)) = &output_data.panel {
-            let buffer = if *show_pointer { hide } else { show };
+        if let Some([show, hide, pick]) = &output_data.panel {
+            let buffer = if *picking {
+                pick
+            } else if *show_pointer {
+                hide
+            } else {
+                show
+            };
 
             let size = buffer.texture().size();
             let padding: i32 = to_physical_precise_round(scale, PADDING);
-            let x = max(0, (output_data.size.w - size.w) / 2);
-            let y = max(0, output_data.size.h - size.h - padding * 2);
+            let x = max(0, (output_data.geo.size.w - size.w) / 2);
+            let y = max(0, output_data.geo.size.h - size.h - padding * 2);
             let location = Point::<_, Physical>::from((x, y))
                 .to_f64()
                 .to_logical(scale);
@@ -415,58 +432,16 @@ impl ScreenshotUi {
             panic!("screenshot UI must be open to capture");
         };
 
-        let data = &output_data[&selection.0];
-        let rect = rect_from_corner_points(selection.1, selection.2);
-
-        let screenshot = &data.screenshot[0];
-
-        // Composite the pointer on top if needed.
-        let mut tex_rect = None;
-        if *show_pointer {
-            if let Some(pointer) = screenshot.pointer.clone() {
-                let scale = pointer.0.buffer().texture_scale();
-                let offset = rect.loc.upscale(-1);
-
-                let mut elements = ArrayVec::<_, 2>::new();
-                elements.push(pointer);
-                elements.push(screenshot.buffer.clone());
-                let elements = elements.iter().rev().map(|elem| {
-                    RelocateRenderElement::from_element(elem, offset, Relocate::Relative)
-                });
+        let parts: Vec<_> = output_data
+            .values()
+            .filter_map(|data| Some((data, data.geo.selection_rect(selection.0, selection.1)?)))
+            .collect();
 
-                let res = render_to_texture(
-                    renderer,
-                    rect.size,
-                    scale,
-                    Transform::Normal,
-                    Fourcc::Abgr8888,
-                    elements,
-                );
-                match res {
-                    Ok((texture, _)) => {
-                        tex_rect = Some((texture, Rectangle::from_loc_and_size((0, 0), rect.size)));
-                    }
-                    Err(err) => {
-                        warn!("error compositing pointer onto screenshot: {err:?}");
-                    }
-                }
-            }
+        match &parts[..] {
+            [] => anyhow::bail!("selection is outside all outputs"),
+            [(data, rect)] => data.capture(renderer, *rect, *show_pointer),
+            _ => capture_multiple(renderer, &parts, *show_pointer),
         }
-
-        let (texture, rect) = tex_rect.unwrap_or_else(|| (screenshot.texture.clone(), rect));
-        // The size doesn't actually matter because we're not transforming anything.
-        let buf_rect = rect
-            .to_logical(1)
-            .to_buffer(1, Transform::Normal, &Size::from((1, 1)));
-
-        let mapping = renderer
-            .copy_texture(&texture, buf_rect, Fourcc::Abgr8888)
-            .context("error copying texture")?;
-        let copy = renderer
-            .map_texture(&mapping)
-            .context("error mapping texture")?;
-
-        Ok((rect.size, copy.to_vec()))
     }
 
     pub fn action(&self, raw: Keysym, mods: ModifiersState) -> Option<Action> {
@@ -477,13 +452,19 @@ impl ScreenshotUi {
         action(raw, mods)
     }
 
-    pub fn selection_output(&self) -> Option<&Output> {
+    pub fn is_picking(&self) -> bool {
+        matches!(self, Self::Open { picking: true, .. })
+    }
+
+    /// Returns the surface to save in the window picking mode.
+    pub fn picked(&self) -> Option<&PickedSurface> {
         if let Self::Open {
-            selection: (output, _, _),
+            picking: true,
+            picked: Some((surface, _)),
             ..
         } = self
         {
-            Some(output)
+            Some(surface)
         } else {
             None
         }
@@ -492,31 +473,59 @@ impl ScreenshotUi {
     pub fn output_size(&self, output: &Output) -> Option<(Size<i32, Physical>, f64, Transform)> {
         if let Self::Open { output_data, .. } = self {
             let data = output_data.get(output)?;
-            Some((data.size, data.scale, data.transform))
+            Some((data.geo.size, data.geo.scale, data.transform))
         } else {
             None
         }
     }
 
-    /// The pointer has moved to `point` relative to the current selection output.
-    pub fn pointer_motion(&mut self, point: Point<i32, Physical>) {
+    /// The pointer has moved to `point` in the global space.
+    ///
+    /// Returns `true` if the selection has changed.
+    pub fn pointer_motion(&mut self, point: Point<f64, Logical>) -> bool {
         let Self::Open {
             selection,
             mouse_down: true,
+            picking: false,
             ..
         } = self
         else {
-            return;
+            return false;
+        };
+
+        selection.1 = point;
+        self.update_buffers();
+
+        true
+    }
+
+    /// Sets the surface under the pointer in the window picking mode.
+    ///
+    /// Returns `true` if the highlighted surface has changed.
+    pub fn set_picked(&mut self, new: Option<(PickedSurface, Rectangle<i32, Logical>)>) -> bool {
+        let Self::Open {
+            picking: true,
+            picked,
+            ..
+        } = self
+        else {
+            return false;
         };
 
-        selection.2 = point;
+        if *picked == new {
+            return false;
+        }
+
+        *picked = new;
         self.update_buffers();
+
+        true
     }
 
+    /// A pointer button was pressed or released with the pointer at `point` in the global space.
     pub fn pointer_button(
         &mut self,
-        output: Output,
-        point: Point<i32, Physical>,
+        point: Point<f64, Logical>,
         button: MouseButton,
         state: ButtonState,
     ) -> bool {
@@ -524,6 +533,7 @@ impl ScreenshotUi {
             selection,
             output_data,
             mouse_down,
+            picking: false,
             ..
         } = self
         else {
@@ -539,26 +549,27 @@ impl ScreenshotUi {
             return false;
         }
 
-        if down && !output_data.contains_key(&output) {
+        if down
+            && !output_data
+                .values()
+                .any(|data| data.geo.selection_rect(point, point).is_some())
+        {
             return false;
         }
 
         *mouse_down = down;
 
         if down {
-            *selection = (output, point, point);
+            *selection = (point, point);
         } else {
             // Check if the resulting selection is zero-sized, and try to come up with a small
-            // default rectangle.
-            let (output, a, b) = selection;
-            let mut rect = rect_from_corner_points(*a, *b);
-            if rect.size.is_empty() || rect.size == Size::from((1, 1)) {
-                let data = &output_data[output];
-                rect = Rectangle::from_loc_and_size((rect.loc.x - 16, rect.loc.y - 16), (32, 32))
-                    .intersection(Rectangle::from_loc_and_size((0, 0), data.size))
-                    .unwrap_or_default();
-                *a = rect.loc;
-                *b = rect.loc + rect.size - Size::from((1, 1));
+            // default rectangle on the output where the selection started.
+            let (a, b) = *selection;
+            let data = output_data
+                .values()
+                .find(|data| data.geo.selection_rect(a, a).is_some());
+            if let Some(data) = data {
+                *selection = data.geo.expand_tiny_selection(a, b);
             }
         }
 
@@ -568,6 +579,182 @@ impl ScreenshotUi {
     }
 }
 
+impl OutputGeometry {
+    /// Converts a point in the global space to output-local physical coordinates.
+    fn to_local(&self, point: Point<f64, Logical>) -> Point<i32, Physical> {
+        (point - self.location.to_f64())
+            .to_physical(self.scale)
+            .to_i32_round()
+    }
+
+    /// Converts a point in output-local physical coordinates to the global space.
+    fn to_global(&self, point: Point<i32, Physical>) -> Point<f64, Logical> {
+        point.to_f64().to_logical(self.scale) + self.location.to_f64()
+    }
+
+    fn bounds(&self) -> Rectangle<i32, Physical> {
+        Rectangle::from_loc_and_size((0, 0), self.size)
+    }
+
+    /// Returns the part of the selection with corners `a` and `b` that is on this output, in
+    /// output-local physical coordinates.
+    fn selection_rect(
+        &self,
+        a: Point<f64, Logical>,
+        b: Point<f64, Logical>,
+    ) -> Option<Rectangle<i32, Physical>> {
+        rect_from_corner_points(self.to_local(a), self.to_local(b)).intersection(self.bounds())
+    }
+
+    /// Returns the part of the global `geo` that is on this output, in output-local physical
+    /// coordinates.
+    fn geometry_rect(&self, geo: Rectangle<i32, Logical>) -> Option<Rectangle<i32, Physical>> {
+        let geo = geo.to_f64();
+        let rect =
+            Rectangle::from_extemities(self.to_local(geo.loc), self.to_local(geo.loc + geo.size));
+        rect.intersection(self.bounds())
+    }
+
+    fn default_selection(&self) -> (Point<f64, Logical>, Point<f64, Logical>) {
+        let size = self.size;
+        let a = Point::from((size.w / 4, size.h / 4));
+        let b = a + Point::from((size.w / 2 - 1, size.h / 2 - 1));
+        (self.to_global(a), self.to_global(b))
+    }
+
+    /// Returns the selection with a small default rectangle around `a` if the selection from `a`
+    /// to `b` is zero-sized, e.g. after a click without a drag.
+    fn expand_tiny_selection(
+        &self,
+        a: Point<f64, Logical>,
+        b: Point<f64, Logical>,
+    ) -> (Point<f64, Logical>, Point<f64, Logical>) {
+        let rect = rect_from_corner_points(self.to_local(a), self.to_local(b));
+        if !rect.size.is_empty() && rect.size != Size::from((1, 1)) {
+            return (a, b);
+        }
+
+        let rect = Rectangle::from_loc_and_size((rect.loc.x - 16, rect.loc.y - 16), (32, 32))
+            .intersection(self.bounds())
+            .unwrap_or_default();
+        (
+            self.to_global(rect.loc),
+            self.to_global(rect.loc + rect.size - Size::from((1, 1))),
+        )
+    }
+}
+
+impl OutputData {
+    /// Updates the buffers to highlight `rect` in output-local physical coordinates, or to dim the
+    /// whole output if it is `None`.
+    fn update_buffers(&mut self, rect: Option<Rectangle<i32, Physical>>) {
+        let buffers = &mut self.buffers;
+        let locations = &mut self.locations;
+        let size = self.geo.size;
+        let scale = self.geo.scale;
+
+        if let Some(rect) = rect {
+            let border = to_physical_precise_round(scale, SELECTION_BORDER);
+
+            let resize = move |buffer: &mut SolidColorBuffer, w: i32, h: i32| {
+                let size = Size::<_, Physical>::from((w, h));
+                buffer.resize(size.to_f64().to_logical(scale));
+            };
+
+            resize(&mut buffers[0], rect.size.w + border * 2, border);
+            resize(&mut buffers[1], rect.size.w + border * 2, border);
+            resize(&mut buffers[2], border, rect.size.h);
+            resize(&mut buffers[3], border, rect.size.h);
+
+            resize(&mut buffers[4], size.w, rect.loc.y);
+            resize(&mut buffers[5], size.w, size.h - rect.loc.y - rect.size.h);
+            resize(&mut buffers[6], rect.loc.x, rect.size.h);
+            resize(
+                &mut buffers[7],
+                size.w - rect.loc.x - rect.size.w,
+                rect.size.h,
+            );
+
+            locations[0] = Point::from((rect.loc.x - border, rect.loc.y - border));
+            locations[1] = Point::from((rect.loc.x - border, rect.loc.y + rect.size.h));
+            locations[2] = Point::from((rect.loc.x - border, rect.loc.y));
+            locations[3] = Point::from((rect.loc.x + rect.size.w, rect.loc.y));
+
+            locations[5] = Point::from((0, rect.loc.y + rect.size.h));
+            locations[6] = Point::from((0, rect.loc.y));
+            locations[7] = Point::from((rect.loc.x + rect.size.w, rect.loc.y));
+        } else {
+            buffers[0].resize((0., 0.));
+            buffers[1].resize((0., 0.));
+            buffers[2].resize((0., 0.));
+            buffers[3].resize((0., 0.));
+
+            buffers[4].resize(size.to_f64().to_logical(scale));
+            buffers[5].resize((0., 0.));
+            buffers[6].resize((0., 0.));
+            buffers[7].resize((0., 0.));
+        }
+    }
+
+    /// Captures `rect` in output-local physical coordinates.
+    fn capture(
+        &self,
+        renderer: &mut GlesRenderer,
+        rect: Rectangle<i32, Physical>,
+        show_pointer: bool,
+    ) -> anyhow::Result<(Size<i32, Physical>, Vec<u8>)> {
+        let screenshot = &self.screenshot[0];
+
+        // Composite the pointer on top if needed.
+        let mut tex_rect = None;
+        if show_pointer {
+            if let Some(pointer) = screenshot.pointer.clone() {
+                let scale = pointer.0.buffer().texture_scale();
+                let offset = rect.loc.upscale(-1);
+
+                let mut elements = ArrayVec::<_, 2>::new();
+                elements.push(pointer);
+                elements.push(screenshot.buffer.clone());
+                let elements = elements.iter().rev().map(|elem| {
+                    RelocateRenderElement::from_element(elem, offset, Relocate::Relative)
+                });
+
+                let res = render_to_texture(
+                    renderer,
+                    rect.size,
+                    scale,
+                    Transform::Normal,
+                    Fourcc::Abgr8888,
+                    elements,
+                );
+                match res {
+                    Ok((texture, _)) => {
+                        tex_rect = Some((texture, Rectangle::from_loc_and_size((0, 0), rect.size)));
+                    }
+                    Err(err) => {
+                        warn!("error compositing pointer onto screenshot: {err:?}");
+                    }
+                }
+            }
+        }
+
+        let (texture, rect) = tex_rect.unwrap_or_else(|| (screenshot.texture.clone(), rect));
+        // The size doesn't actually matter because we're not transforming anything.
+        let buf_rect = rect
+            .to_logical(1)
+            .to_buffer(1, Transform::Normal, &Size::from((1, 1)));
+
+        let mapping = renderer
+            .copy_texture(&texture, buf_rect, Fourcc::Abgr8888)
+            .context("error copying texture")?;
+        let copy = renderer
+            .map_texture(&mapping)
+            .context("error mapping texture")?;
+
+        Ok((rect.size, copy.to_vec()))
+    }
+}
+
 impl OutputScreenshot {
     pub fn from_textures(
         renderer: &mut GlesRenderer,
@@ -634,9 +821,77 @@ fn action(raw: Keysym, mods: ModifiersState) -> Option<Action> {
         return Some(Action::ScreenshotTogglePointer);
     }
 
+    if !mods.ctrl && raw == Keysym::w {
+        return Some(Action::ScreenshotToggleWindowPicking);
+    }
+
     None
 }
 
+/// Captures a selection spanning multiple outputs.
+///
+/// The parts of the selection are composited together at the highest scale among the outputs.
+/// Areas of the selection not covered by any output stay transparent.
+fn capture_multiple(
+    renderer: &mut GlesRenderer,
+    parts: &[(&OutputData, Rectangle<i32, Physical>)],
+    show_pointer: bool,
+) -> anyhow::Result<(Size<i32, Physical>, Vec<u8>)> {
+    let scale = parts
+        .iter()
+        .map(|(data, _)| data.geo.scale)
+        .fold(0., f64::max);
+
+    let bbox = parts
+        .iter()
+        .map(|(data, rect)| {
+            let loc = data.geo.to_global(rect.loc);
+            let size = rect.size.to_f64().to_logical(data.geo.scale);
+            Rectangle::from_loc_and_size(loc, size)
+        })
+        .reduce(|a, b| a.merge(b))
+        .unwrap();
+    let size = bbox.size.to_physical(scale).to_i32_round();
+
+    let mut buffers = Vec::new();
+    let mut pointers = Vec::new();
+    for (data, _) in parts {
+        let offset = (data.geo.location.to_f64() - bbox.loc)
+            .to_physical(scale)
+            .to_i32_round::<i32>();
+
+        let screenshot = &data.screenshot[0];
+        buffers.push(RelocateRenderElement::from_element(
+            screenshot.buffer.clone(),
+            offset,
+            Relocate::Relative,
+        ));
+
+        if show_pointer {
+            if let Some(pointer) = screenshot.pointer.clone() {
+                pointers.push(RelocateRenderElement::from_element(
+                    pointer,
+                    offset,
+                    Relocate::Relative,
+                ));
+            }
+        }
+    }
+
+    // Draw the pointers after all outputs so that they aren't covered by a neighboring output.
+    let elements = buffers.into_iter().chain(pointers);
+    let pixels = render_to_vec(
+        renderer,
+        size,
+        Scale::from(scale),
+        Transform::Normal,
+        Fourcc::Abgr8888,
+        elements,
+    )?;
+
+    Ok((size, pixels))
+}
+
 pub fn rect_from_corner_points(
     a: Point<i32, Physical>,
     b: Point<i32, Physical>,
@@ -719,3 +974,123 @@ fn render_panel(
 
     Ok(buffer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Physical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    // A 1920×1080 output at scale 1, and a 2560×1440 output at scale 2 to the right of it.
+    fn outputs() -> (OutputGeometry, OutputGeometry) {
+        let left = OutputGeometry {
+            location: Point::from((0, 0)),
+            size: Size::from((1920, 1080)),
+            scale: 1.,
+        };
+        let right = OutputGeometry {
+            location: Point::from((1920, 0)),
+            size: Size::from((2560, 1440)),
+            scale: 2.,
+        };
+        (left, right)
+    }
+
+    #[test]
+    fn local_and_global_coordinates() {
+        let (_, right) = outputs();
+
+        assert_eq!(
+            right.to_local(Point::from((2020., 50.))),
+            Point::from((200, 100))
+        );
+        assert_eq!(
+            right.to_global(Point::from((200, 100))),
+            Point::from((2020., 50.))
+        );
+    }
+
+    #[test]
+    fn selection_across_outputs() {
+        let (left, right) = outputs();
+
+        let a = Point::from((1800., 100.));
+        let b = Point::from((2000., 200.));
+        assert_eq!(left.selection_rect(a, b), Some(rect(1800, 100, 120, 101)));
+        assert_eq!(right.selection_rect(a, b), Some(rect(0, 200, 161, 201)));
+
+        // The corners can come in any order.
+        assert_eq!(right.selection_rect(b, a), Some(rect(0, 200, 161, 201)));
+
+        let b = Point::from((1900., 200.));
+        assert_eq!(left.selection_rect(a, b), Some(rect(1800, 100, 101, 101)));
+        assert_eq!(right.selection_rect(a, b), None);
+    }
+
+    #[test]
+    fn picked_geometry_across_outputs() {
+        let (left, right) = outputs();
+
+        let geo = Rectangle::from_loc_and_size((1820, 0), (200, 100));
+        assert_eq!(left.geometry_rect(geo), Some(rect(1820, 0, 100, 100)));
+        assert_eq!(right.geometry_rect(geo), Some(rect(0, 0, 200, 200)));
+
+        let geo = Rectangle::from_loc_and_size((0, 0), (200, 100));
+        assert_eq!(right.geometry_rect(geo), None);
+    }
+
+    #[test]
+    fn default_selection_is_centered() {
+        let (left, right) = outputs();
+
+        let (a, b) = left.default_selection();
+        assert_eq!(left.selection_rect(a, b), Some(rect(480, 270, 960, 540)));
+        assert_eq!(right.selection_rect(a, b), None);
+
+        let (a, b) = right.default_selection();
+        assert_eq!(right.selection_rect(a, b), Some(rect(640, 360, 1280, 720)));
+        assert_eq!(left.selection_rect(a, b), None);
+    }
+
+    #[test]
+    fn expand_tiny_selection() {
+        let (left, _) = outputs();
+
+        // A click selects a small rectangle around the pointer.
+        let p = Point::from((100., 100.));
+        let (a, b) = left.expand_tiny_selection(p, p);
+        assert_eq!(left.selection_rect(a, b), Some(rect(84, 84, 32, 32)));
+
+        // Clamped to the output.
+        let p = Point::from((5., 5.));
+        let (a, b) = left.expand_tiny_selection(p, p);
+        assert_eq!(left.selection_rect(a, b), Some(rect(0, 0, 21, 21)));
+
+        // A real selection stays as is.
+        let a = Point::from((100., 100.));
+        let b = Point::from((200., 150.));
+        assert_eq!(left.expand_tiny_selection(a, b), (a, b));
+    }
+
+    #[test]
+    fn toggle_keys() {
+        let none = ModifiersState::default();
+        let ctrl = ModifiersState {
+            ctrl: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            action(Keysym::w, none),
+            Some(Action::ScreenshotToggleWindowPicking)
+        );
+        assert_eq!(
+            action(Keysym::p, none),
+            Some(Action::ScreenshotTogglePointer)
+        );
+        assert_eq!(action(Keysym::w, ctrl), None);
+        assert_eq!(action(Keysym::c, ctrl), Some(Action::ConfirmScreenshot));
+    }
+}
@@ -0,0 +1,390 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use niri_config::Config;
+use ordered_float::NotNan;
+use pangocairo::cairo::{self, ImageSurface};
+use pangocairo::pango::FontDescription;
+use smithay::backend::renderer::element::Kind;
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::output::Output;
+use smithay::reexports::gbm::Format as Fourcc;
+use smithay::utils::{Point, Transform};
+
+use crate::animation::Animation;
+use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
+use crate::render_helpers::renderer::NiriRenderer;
+use crate::render_helpers::texture::{TextureBuffer, TextureRenderElement};
+use crate::utils::{get_monotonic_time, output_size, to_physical_precise_round};
+
+const PADDING: i32 = 16;
+const FONT: &str = "sans bold 20px";
+const CELL_WIDTH: i32 = 32;
+const CELL_HEIGHT: i32 = 18;
+const CELL_GAP: i32 = 4;
+const BORDER: i32 = 2;
+
+pub struct WorkspaceSwitchOsd {
+    state: State,
+    buffers: RefCell<HashMap<NotNan<f64>, Option<TextureBuffer<GlesTexture>>>>,
+
+    /// Output that the OSD is shown on.
+    output: Option<Output>,
+    contents: Contents,
+
+    config: Rc<RefCell<Config>>,
+}
+
+/// What the OSD shows.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Contents {
+    /// Index of the active workspace on the output.
+    pub active_idx: usize,
+    /// Name of the active workspace, if any.
+    pub name: Option<String>,
+    /// Whether each workspace on the output has windows, for the minimap.
+    pub has_windows: Vec<bool>,
+}
+
+enum State {
+    Hidden,
+    Showing(Animation),
+    Shown(Duration),
+    Hiding(Animation),
+}
+
+impl WorkspaceSwitchOsd {
+    pub fn new(config: Rc<RefCell<Config>>) -> Self {
+        Self {
+            state: State::Hidden,
+            buffers: RefCell::new(HashMap::new()),
+            output: None,
+            contents: Contents::default(),
+            config,
+        }
+    }
+
+    fn animation(&self, from: f64, to: f64) -> Animation {
+        let c = self.config.borrow();
+        Animation::new(from, to, 0., c.animations.workspace_switch_osd_open_close.0)
+    }
+
+    fn duration(&self) -> Duration {
+        let c = self.config.borrow();
+        Duration::from_millis(u64::from(c.workspace_switch_osd.duration_ms))
+    }
+
+    pub fn show(&mut self, output: Output, contents: Contents) {
+        if self.contents != contents {
+            self.contents = contents;
+            self.buffers.borrow_mut().clear();
+        }
+        self.output = Some(output);
+
+        match &self.state {
+            State::Hidden => self.state = State::Showing(self.animation(0., 1.)),
+            State::Showing(_) => (),
+            // Keep the OSD up for the full duration after the last switch.
+            State::Shown(_) => self.state = State::Shown(get_monotonic_time() + self.duration()),
+            State::Hiding(anim) => {
+                let from = anim.clamped_value();
+                self.state = State::Showing(self.animation(from, 1.));
+            }
+        }
+    }
+
+    pub fn hide(&mut self) {
+        if matches!(self.state, State::Hidden) {
+            return;
+        }
+
+        let from = match &self.state {
+            State::Showing(anim) => anim.clamped_value(),
+            _ => 1.,
+        };
+        self.state = State::Hiding(self.animation(from, 0.));
+    }
+
+    pub fn advance_animations(&mut self, target_presentation_time: Duration) {
+        match &mut self.state {
+            State::Hidden => (),
+            State::Showing(anim) => {
+                anim.set_current_time(target_presentation_time);
+                if anim.is_done() {
+                    self.state = State::Shown(target_presentation_time + self.duration());
+                }
+            }
+            State::Shown(deadline) => {
+                if target_presentation_time >= *deadline {
+                    self.hide();
+                }
+            }
+            State::Hiding(anim) => {
+                anim.set_current_time(target_presentation_time);
+                if anim.is_clamped_done() {
+                    self.state = State::Hidden;
+                }
+            }
+        }
+    }
+
+    pub fn are_animations_ongoing(&self) -> bool {
+        !matches!(self.state, State::Hidden)
+    }
+
+    pub fn render<R: NiriRenderer>(
+        &self,
+        renderer: &mut R,
+        output: &Output,
+    ) -> Option<PrimaryGpuTextureRenderElement> {
+        if matches!(self.state, State::Hidden) || self.output.as_ref() != Some(output) {
+            return None;
+        }
+
+        let scale = output.current_scale().fractional_scale();
+        let output_size = output_size(output);
+        let contents = &self.contents;
+
+        let mut buffers = self.buffers.borrow_mut();
+        let buffer = buffers
+            .entry(NotNan::new(scale).unwrap())
+            .or_insert_with(move || render(renderer.as_gles_renderer(), scale, contents).ok());
+        let buffer = buffer.clone()?;
+
+        let alpha = match &self.state {
+            State::Hidden => unreachable!(),
+            State::Showing(anim) | State::Hiding(anim) => anim.clamped_value() as f32,
+            State::Shown(_) => 1.,
+        };
+
+        let size = buffer.logical_size();
+        let location = Point::from((
+            (output_size.w - size.w).max(0.) / 2.,
+            (output_size.h - size.h).max(0.) / 2.,
+        ));
+        let location = location.to_physical_precise_round(scale).to_logical(scale);
+
+        let elem = TextureRenderElement::from_texture_buffer(
+            buffer,
+            location,
+            alpha,
+            None,
+            None,
+            Kind::Unspecified,
+        );
+        Some(PrimaryGpuTextureRenderElement(elem))
+    }
+}
+
+fn render(
+    renderer: &mut GlesRenderer,
+    scale: f64,
+    contents: &Contents,
+) -> anyhow::Result<TextureBuffer<GlesTexture>> {
+    let _span = tracy_client::span!("workspace_switch_osd::render");
+
+    let padding: i32 = to_physical_precise_round(scale, PADDING);
+    let cell_width: i32 = to_physical_precise_round(scale, CELL_WIDTH);
+    let cell_height: i32 = to_physical_precise_round(scale, CELL_HEIGHT);
+    let cell_gap: i32 = to_physical_precise_round(scale, CELL_GAP);
+    let border: i32 = to_physical_precise_round(scale, BORDER);
+
+    // Workspace indices are 1-based for the user, same as in the IPC.
+    let mut text = (contents.active_idx + 1).to_string();
+    if let Some(name) = &contents.name {
+        text = format!("{text}: {name}");
+    }
+
+    let mut font = FontDescription::from_string(FONT);
+    font.set_absolute_size(to_physical_precise_round(scale, font.size()));
+
+    let surface = ImageSurface::create(cairo::Format::ARgb32, 0, 0)?;
+    let cr = cairo::Context::new(&surface)?;
+    let layout = pangocairo::functions::create_layout(&cr);
+    layout.context().set_round_glyph_positions(false);
+    layout.set_font_description(Some(&font));
+    layout.set_text(&text);
+
+    let (text_width, text_height) = layout.pixel_size();
+
+    // The minimap is a column of cells, one per workspace, like the workspaces themselves.
+    let count = contents.has_windows.len() as i32;
+    let minimap_height = count * cell_height + (count - 1).max(0) * cell_gap;
+
+    let width = text_width + padding * 3 + cell_width;
+    let height = text_height.max(minimap_height) + padding * 2;
+
+    let surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+    let cr = cairo::Context::new(&surface)?;
+    cr.set_source_rgb(0.1, 0.1, 0.1);
+    cr.paint()?;
+
+    cr.move_to(padding.into(), f64::from((height - text_height) / 2));
+    let layout = pangocairo::functions::create_layout(&cr);
+    layout.context().set_round_glyph_positions(false);
+    layout.set_font_description(Some(&font));
+    layout.set_text(&text);
+
+    cr.set_source_rgb(1., 1., 1.);
+    pangocairo::functions::show_layout(&cr, &layout);
+
+    let x = text_width + padding * 2;
+    let mut y = (height - minimap_height) / 2;
+    cr.set_line_width(border.into());
+    for (idx, has_windows) in contents.has_windows.iter().enumerate() {
+        if idx == contents.active_idx {
+            cr.set_source_rgb(0.5, 0.78, 1.);
+        } else {
+            cr.set_source_rgb(0.5, 0.5, 0.5);
+        }
+
+        if idx == contents.active_idx || *has_windows {
+            cr.rectangle(x.into(), y.into(), cell_width.into(), cell_height.into());
+            cr.fill()?;
+        } else {
+            // Inset the outline by half the line width so it stays inside the cell.
+            let half = f64::from(border) / 2.;
+            cr.rectangle(
+                f64::from(x) + half,
+                f64::from(y) + half,
+                f64::from(cell_width) - f64::from(border),
+                f64::from(cell_height) - f64::from(border),
+            );
+            cr.stroke()?;
+        }
+
+        y += cell_height + cell_gap;
+    }
+    drop(cr);
+
+    let data = surface.take_data().unwrap();
+    let buffer = TextureBuffer::from_memory(
+        renderer,
+        &data,
+        Fourcc::Argb8888,
+        (width, height),
+        false,
+        scale,
+        Transform::Normal,
+        Vec::new(),
+    )?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::{PhysicalProperties, Subpixel};
+    use smithay::utils::Size;
+
+    use super::*;
+
+    fn make_osd() -> WorkspaceSwitchOsd {
+        WorkspaceSwitchOsd::new(Rc::new(RefCell::new(Config::default())))
+    }
+
+    fn make_output() -> Output {
+        Output::new(
+            String::from("output"),
+            PhysicalProperties {
+                size: Size::from((1280, 720)),
+                subpixel: Subpixel::Unknown,
+                make: String::new(),
+                model: String::new(),
+            },
+        )
+    }
+
+    fn contents(active_idx: usize) -> Contents {
+        Contents {
+            active_idx,
+            name: None,
+            has_windows: vec![true, false],
+        }
+    }
+
+    #[test]
+    fn show_and_hide_after_duration() {
+        let mut osd = make_osd();
+        assert!(!osd.are_animations_ongoing());
+
+        osd.show(make_output(), contents(0));
+        assert!(matches!(osd.state, State::Showing(_)));
+        assert!(osd.are_animations_ongoing());
+
+        // Finish the open animation.
+        let now = get_monotonic_time() + Duration::from_secs(10);
+        osd.advance_animations(now);
+        let State::Shown(deadline) = osd.state else {
+            panic!("OSD must be shown after the open animation");
+        };
+        assert_eq!(deadline, now + osd.duration());
+
+        // Stay up until the deadline.
+        osd.advance_animations(deadline - Duration::from_millis(1));
+        assert!(matches!(osd.state, State::Shown(_)));
+
+        osd.advance_animations(deadline);
+        assert!(matches!(osd.state, State::Hiding(_)));
+
+        // Finish the close animation.
+        osd.advance_animations(deadline + Duration::from_secs(10));
+        assert!(matches!(osd.state, State::Hidden));
+        assert!(!osd.are_animations_ongoing());
+    }
+
+    #[test]
+    fn show_while_shown_restarts_deadline() {
+        let mut osd = make_osd();
+        osd.show(make_output(), contents(0));
+        osd.advance_animations(get_monotonic_time() + Duration::from_secs(10));
+        let State::Shown(deadline) = osd.state else {
+            panic!("OSD must be shown after the open animation");
+        };
+
+        osd.show(make_output(), contents(1));
+        let State::Shown(new_deadline) = osd.state else {
+            panic!("OSD must stay shown");
+        };
+        // The new deadline counts from the real time, which is before the advanced time.
+        assert!(new_deadline >= get_monotonic_time());
+        assert!(new_deadline <= deadline);
+        assert_eq!(osd.contents, contents(1));
+    }
+
+    #[test]
+    fn show_while_hiding_shows_again() {
+        let mut osd = make_osd();
+        osd.show(make_output(), contents(0));
+        osd.hide();
+        assert!(matches!(osd.state, State::Hiding(_)));
+
+        osd.show(make_output(), contents(0));
+        assert!(matches!(osd.state, State::Showing(_)));
+    }
+
+    #[test]
+    fn hide_when_hidden() {
+        let mut osd = make_osd();
+        osd.hide();
+        assert!(matches!(osd.state, State::Hidden));
+    }
+
+    #[test]
+    fn buffers_cleared_on_contents_change() {
+        let mut osd = make_osd();
+        let scale = NotNan::new(1.).unwrap();
+
+        osd.show(make_output(), contents(0));
+        osd.buffers.borrow_mut().insert(scale, None);
+
+        // Same contents keep the rendered buffers.
+        osd.show(make_output(), contents(0));
+        assert!(osd.buffers.borrow().contains_key(&scale));
+
+        osd.show(make_output(), contents(1));
+        assert!(osd.buffers.borrow().is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+//! Display calibration curves from ICC profiles.
+//!
+//! Only the video card gamma table (`vcgt` tag) is used. It holds the per-channel curves that a
+//! calibration tool measured for the monitor, and it maps directly onto the CRTC gamma LUT.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+
+const ICC_HEADER_SIZE: usize = 128;
+const VCGT_SIGNATURE: &[u8; 4] = b"vcgt";
+
+/// Video card gamma table of an ICC profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vcgt {
+    /// Red, green and blue curves.
+    channels: [Curve; 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Curve {
+    /// Values evenly spaced over the input range, normalized to 0–1.
+    Table(Vec<f64>),
+    /// `min + (max - min) * x^gamma`.
+    Formula { gamma: f64, min: f64, max: f64 },
+}
+
+impl Vcgt {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = fs::read(path).context("error reading file")?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        ensure!(data.len() >= ICC_HEADER_SIZE + 4, "profile is too short");
+        ensure!(&data[36..40] == b"acsp", "not an ICC profile");
+
+        let tag_count = read_u32(data, ICC_HEADER_SIZE)? as usize;
+        let mut vcgt = None;
+        for idx in 0..tag_count {
+            let entry = ICC_HEADER_SIZE + 4 + idx * 12;
+            let signature = data
+                .get(entry..entry + 4)
+                .context("tag table is truncated")?;
+            if signature != VCGT_SIGNATURE {
+                continue;
+            }
+
+            let offset = read_u32(data, entry + 4)? as usize;
+            let size = read_u32(data, entry + 8)? as usize;
+            let end = offset
+                .checked_add(size)
+                .context("vcgt tag is out of bounds")?;
+            vcgt = Some(data.get(offset..end).context("vcgt tag is out of bounds")?);
+            break;
+        }
+
+        let tag = vcgt.context("profile has no vcgt tag")?;
+        ensure!(tag.len() >= 12, "vcgt tag is too short");
+        ensure!(&tag[..4] == VCGT_SIGNATURE, "wrong vcgt tag type");
+
+        let channels = match read_u32(tag, 8)? {
+            0 => parse_table(tag)?,
+            1 => parse_formula(tag)?,
+            ty => bail!("unknown vcgt type {ty}"),
+        };
+
+        Ok(Self { channels })
+    }
+
+    /// Evaluates the curve of `channel` at `x` in 0–1.
+    fn eval(&self, channel: usize, x: f64) -> f64 {
+        let x = x.clamp(0., 1.);
+        let y = match &self.channels[channel] {
+            Curve::Table(values) => {
+                let pos = x * (values.len() - 1) as f64;
+                let idx = (pos.floor() as usize).min(values.len() - 2);
+                let t = pos - idx as f64;
+                values[idx] * (1. - t) + values[idx + 1] * t
+            }
+            Curve::Formula { gamma, min, max } => min + (max - min) * x.powf(*gamma),
+        };
+        y.clamp(0., 1.)
+    }
+}
+
+/// Computes the gamma ramp to set on the CRTC.
+///
+/// `client` is the ramp set through the gamma control protocol (e.g. by a night light tool). The
+/// calibration curves apply on top of it, since they describe the monitor rather than the
+/// content. Ramps have `size` red values, then green, then blue.
+///
+/// Returns `None` when neither is set, meaning that the gamma should be reset.
+pub fn gamma_ramp(client: Option<&[u16]>, vcgt: Option<&Vcgt>, size: usize) -> Option<Vec<u16>> {
+    let Some(vcgt) = vcgt else {
+        return client.map(<[u16]>::to_vec);
+    };
+
+    if size < 2 {
+        return client.map(<[u16]>::to_vec);
+    }
+
+    let mut ramp = Vec::with_capacity(size * 3);
+    for channel in 0..3 {
+        for i in 0..size {
+            let x = match client {
+                Some(client) => f64::from(client[channel * size + i]) / 65535.,
+                None => i as f64 / (size - 1) as f64,
+            };
+            let y = vcgt.eval(channel, x);
+            ramp.push((y * 65535.).round() as u16);
+        }
+    }
+    Some(ramp)
+}
+
+fn parse_table(tag: &[u8]) -> anyhow::Result<[Curve; 3]> {
+    let channel_count = read_u16(tag, 12)? as usize;
+    let entry_count = read_u16(tag, 14)? as usize;
+    let entry_size = read_u16(tag, 16)? as usize;
+
+    ensure!(
+        channel_count == 1 || channel_count == 3,
+        "unsupported vcgt channel count {channel_count}"
+    );
+    ensure!(entry_count >= 2, "vcgt table is too short");
+
+    let max = match entry_size {
+        1 => f64::from(u8::MAX),
+        2 => f64::from(u16::MAX),
+        _ => bail!("unsupported vcgt entry size {entry_size}"),
+    };
+
+    let read_curve = |channel: usize| {
+        let start = 18 + channel * entry_count * entry_size;
+        let values = (0..entry_count)
+            .map(|idx| {
+                let offset = start + idx * entry_size;
+                let value = if entry_size == 1 {
+                    tag.get(offset).copied().map(u16::from)
+                } else {
+                    read_u16(tag, offset).ok()
+                };
+                value.map(|v| f64::from(v) / max)
+            })
+            .collect::<Option<Vec<_>>>()
+            .context("vcgt table is truncated")?;
+        anyhow::Ok(Curve::Table(values))
+    };
+
+    if channel_count == 1 {
+        let curve = read_curve(0)?;
+        Ok([curve.clone(), curve.clone(), curve])
+    } else {
+        Ok([read_curve(0)?, read_curve(1)?, read_curve(2)?])
+    }
+}
+
+fn parse_formula(tag: &[u8]) -> anyhow::Result<[Curve; 3]> {
+    let read_curve = |channel: usize| {
+        let start = 12 + channel * 12;
+        let gamma = read_s15fixed16(tag, start)?;
+        let min = read_s15fixed16(tag, start + 4)?;
+        let max = read_s15fixed16(tag, start + 8)?;
+        ensure!(gamma > 0., "vcgt gamma must be positive");
+        anyhow::Ok(Curve::Formula { gamma, min, max })
+    };
+
+    Ok([read_curve(0)?, read_curve(1)?, read_curve(2)?])
+}
+
+fn read_u16(data: &[u8], offset: usize) -> anyhow::Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .context("unexpected end of data")?;
+    Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("unexpected end of data")?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_s15fixed16(data: &[u8], offset: usize) -> anyhow::Result<f64> {
+    let value = read_u32(data, offset)? as i32;
+    Ok(f64::from(value) / 65536.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(vcgt: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; ICC_HEADER_SIZE];
+        data[36..40].copy_from_slice(b"acsp");
+
+        let offset = ICC_HEADER_SIZE + 4 + 12;
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(VCGT_SIGNATURE);
+        data.extend_from_slice(&(offset as u32).to_be_bytes());
+        data.extend_from_slice(&(vcgt.len() as u32).to_be_bytes());
+        data.extend_from_slice(vcgt);
+        data
+    }
+
+    fn table_vcgt(channels: &[&[u16]]) -> Vec<u8> {
+        let mut tag = Vec::new();
+        tag.extend_from_slice(VCGT_SIGNATURE);
+        tag.extend_from_slice(&[0; 4]);
+        tag.extend_from_slice(&0u32.to_be_bytes());
+        tag.extend_from_slice(&(channels.len() as u16).to_be_bytes());
+        tag.extend_from_slice(&(channels[0].len() as u16).to_be_bytes());
+        tag.extend_from_slice(&2u16.to_be_bytes());
+        for channel in channels {
+            for value in *channel {
+                tag.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        tag
+    }
+
+    #[test]
+    fn parse_table() {
+        let tag = table_vcgt(&[&[0, 65535], &[0, 32767], &[65535, 0]]);
+        let vcgt = Vcgt::parse(&profile(&tag)).unwrap();
+        assert_eq!(
+            gamma_ramp(None, Some(&vcgt), 3).unwrap(),
+            [0, 32768, 65535, 0, 16384, 32767, 65535, 32768, 0]
+        );
+
+        let err = Vcgt::parse(&profile(&tag[..tag.len() - 2])).unwrap_err();
+        assert_eq!(err.to_string(), "vcgt table is truncated");
+    }
+
+    #[test]
+    fn parse_formula() {
+        let mut tag = Vec::new();
+        tag.extend_from_slice(VCGT_SIGNATURE);
+        tag.extend_from_slice(&[0; 4]);
+        tag.extend_from_slice(&1u32.to_be_bytes());
+        for (gamma, min, max) in [(1., 0., 1.), (2., 0., 1.), (1., 0., 0.5)] {
+            for value in [gamma, min, max] {
+                let value = (value * 65536.) as u32;
+                tag.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+
+        let vcgt = Vcgt::parse(&profile(&tag)).unwrap();
+        assert_eq!(
+            gamma_ramp(None, Some(&vcgt), 3).unwrap(),
+            [0, 32768, 65535, 0, 16384, 65535, 0, 16384, 32768]
+        );
+    }
+
+    #[test]
+    fn missing_vcgt() {
+        let mut data = profile(&table_vcgt(&[&[0, 65535]]));
+        data[ICC_HEADER_SIZE + 4..ICC_HEADER_SIZE + 8].copy_from_slice(b"desc");
+        let err = Vcgt::parse(&data).unwrap_err();
+        assert_eq!(err.to_string(), "profile has no vcgt tag");
+
+        let err = Vcgt::parse(b"not a profile").unwrap_err();
+        assert_eq!(err.to_string(), "profile is too short");
+    }
+
+    #[test]
+    fn ramp_applies_on_top_of_client() {
+        // Inverts every channel.
+        let vcgt = table_vcgt(&[&[65535, 0]]);
+        let vcgt = Vcgt::parse(&profile(&vcgt)).unwrap();
+
+        let client = [0, 65535, 0, 32768, 65535, 65535];
+        assert_eq!(
+            gamma_ramp(Some(&client), Some(&vcgt), 2).unwrap(),
+            [65535, 0, 65535, 32767, 0, 0]
+        );
+
+        assert_eq!(gamma_ramp(Some(&client), None, 2).unwrap(), client);
+        assert_eq!(gamma_ramp(None, None, 2), None);
+    }
+}
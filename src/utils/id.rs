@@ -19,6 +19,9 @@ impl IdCounter {
     }
 }
 
+/// Counter for the IDs of all screencasts and screen captures, regardless of the protocol.
+pub static CAST_ID_COUNTER: IdCounter = IdCounter::new();
+
 impl Default for IdCounter {
     fn default() -> Self {
         Self::new()
@@ -10,7 +10,7 @@ use anyhow::{ensure, Context};
 use bitflags::bitflags;
 use directories::UserDirs;
 use git_version::git_version;
-use niri_config::{Config, OutputName};
+use niri_config::{Config, ImageFormat, OutputName, PngCompression, ScreenshotFormat};
 use smithay::input::pointer::CursorIcon;
 use smithay::output::{self, Output};
 use smithay::reexports::rustix::time::{clock_gettime, ClockId};
@@ -23,7 +23,9 @@ use smithay::wayland::shell::xdg::{
     ToplevelSurface, XdgToplevelSurfaceData, XdgToplevelSurfaceRoleAttributes,
 };
 
+pub mod icc;
 pub mod id;
+pub mod orientation;
 pub mod scale;
 pub mod spawning;
 pub mod transaction;
@@ -142,6 +144,13 @@ pub fn logical_output(output: &Output) -> niri_ipc::LogicalOutput {
     }
 }
 
+/// Returns whether the connector name belongs to a built-in laptop panel.
+///
+/// Loosely matches the check in Mutter.
+pub fn is_laptop_panel(connector: &str) -> bool {
+    matches!(connector.get(..4), Some("eDP-" | "LVDS" | "DSI-"))
+}
+
 pub fn ipc_transform_to_smithay(transform: niri_ipc::Transform) -> Transform {
     match transform {
         niri_ipc::Transform::Normal => Transform::Normal,
@@ -181,7 +190,12 @@ pub fn make_screenshot_path(config: &Config) -> anyhow::Result<Option<PathBuf>>
         return Ok(None);
     };
 
-    let format = CString::new(path.clone()).context("path must not contain nul bytes")?;
+    format_screenshot_path(path).map(Some)
+}
+
+/// Formats the screenshot path template with the current time and expands `~`.
+pub fn format_screenshot_path(path: &str) -> anyhow::Result<PathBuf> {
+    let format = CString::new(path).context("path must not contain nul bytes")?;
 
     let mut buf = [0u8; 2048];
     let mut path;
@@ -202,7 +216,30 @@ pub fn make_screenshot_path(config: &Config) -> anyhow::Result<Option<PathBuf>>
         path = expanded;
     }
 
-    Ok(Some(path))
+    Ok(path)
+}
+
+/// Makes the extension of a screenshot path match the image format.
+///
+/// Paths without an extension get one, and extensions of other image formats are replaced, since
+/// the default path template ends in `.png`. Other extensions are kept as is.
+pub fn with_screenshot_extension(mut path: PathBuf, format: ImageFormat) -> PathBuf {
+    const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "qoi"];
+
+    let replace = match path.extension().and_then(|ext| ext.to_str()) {
+        None => true,
+        Some(ext) => {
+            let ext = ext.to_ascii_lowercase();
+            let matches =
+                ext == format.extension() || (format == ImageFormat::Jpeg && ext == "jpeg");
+            !matches && IMAGE_EXTENSIONS.contains(&&*ext)
+        }
+    };
+    if replace {
+        path.set_extension(format.extension());
+    }
+
+    path
 }
 
 pub fn write_png_rgba8(
@@ -210,15 +247,54 @@ pub fn write_png_rgba8(
     width: u32,
     height: u32,
     pixels: &[u8],
+    compression: png::Compression,
 ) -> Result<(), png::EncodingError> {
     let mut encoder = png::Encoder::new(w, width, height);
     encoder.set_color(png::ColorType::Rgba);
     encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
 
     let mut writer = encoder.write_header()?;
     writer.write_image_data(pixels)
 }
 
+/// Encodes RGBA8 pixels into an image of the given format.
+pub fn encode_screenshot(
+    format: &ScreenshotFormat,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    match format.format {
+        ImageFormat::Png => {
+            let compression = match format.compression {
+                PngCompression::Fast => png::Compression::Fast,
+                PngCompression::Default => png::Compression::Default,
+                PngCompression::Best => png::Compression::Best,
+            };
+            write_png_rgba8(&mut buf, width, height, pixels, compression)?;
+        }
+        ImageFormat::Jpeg => {
+            let width = u16::try_from(width).context("image is too wide for JPEG")?;
+            let height = u16::try_from(height).context("image is too tall for JPEG")?;
+            let quality = format.quality.clamp(1, 100);
+            let encoder = jpeg_encoder::Encoder::new(&mut buf, quality);
+            encoder.encode(pixels, width, height, jpeg_encoder::ColorType::Rgba)?;
+        }
+        ImageFormat::Webp => {
+            let encoder = image_webp::WebPEncoder::new(&mut buf);
+            encoder.encode(pixels, width, height, image_webp::ColorType::Rgba8)?;
+        }
+        ImageFormat::Qoi => {
+            buf = qoi::encode_to_vec(pixels, width, height)?;
+        }
+    }
+
+    Ok(buf)
+}
+
 pub fn output_matches_name(output: &Output, target: &str) -> bool {
     let name = output.user_data().get::<OutputName>().unwrap();
     name.matches(target)
@@ -277,3 +353,42 @@ pub fn cause_panic() {
     let b = Duration::from_secs(2);
     let _ = a - b;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshot_extension() {
+        let check = |path: &str, format| with_screenshot_extension(PathBuf::from(path), format);
+
+        assert_eq!(
+            check("a/shot.png", ImageFormat::Png),
+            PathBuf::from("a/shot.png")
+        );
+        assert_eq!(
+            check("a/shot.png", ImageFormat::Jpeg),
+            PathBuf::from("a/shot.jpg")
+        );
+        assert_eq!(
+            check("a/shot.PNG", ImageFormat::Webp),
+            PathBuf::from("a/shot.webp")
+        );
+        assert_eq!(
+            check("a/shot.jpeg", ImageFormat::Jpeg),
+            PathBuf::from("a/shot.jpeg")
+        );
+        assert_eq!(
+            check("a/shot", ImageFormat::Qoi),
+            PathBuf::from("a/shot.qoi")
+        );
+        assert_eq!(
+            check("a/shot.img", ImageFormat::Jpeg),
+            PathBuf::from("a/shot.img")
+        );
+        assert_eq!(
+            check("a/12.30.00", ImageFormat::Png),
+            PathBuf::from("a/12.30.00")
+        );
+    }
+}
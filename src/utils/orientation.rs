@@ -0,0 +1,155 @@
+//! Automatic screen rotation from the accelerometer orientation.
+//!
+//! Orientation names and their mapping to transforms follow iio-sensor-proxy and Mutter.
+
+use std::str::FromStr;
+
+use smithay::utils::Transform;
+
+/// Orientation of the device, as reported by the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Normal,
+    BottomUp,
+    LeftUp,
+    RightUp,
+}
+
+/// State of automatic rotation.
+#[derive(Debug, Default)]
+pub struct AutoRotation {
+    /// Last orientation reported by the sensor.
+    sensor: Option<Orientation>,
+    /// Orientation applied to the auto-rotating outputs.
+    applied: Option<Orientation>,
+    /// Whether the applied orientation is locked.
+    locked: bool,
+}
+
+impl Orientation {
+    /// Rotates a configured output transform to this orientation.
+    pub fn apply(self, transform: Transform) -> Transform {
+        let quarter_turns = match self {
+            Orientation::Normal => 0,
+            Orientation::LeftUp => 1,
+            Orientation::BottomUp => 2,
+            Orientation::RightUp => 3,
+        };
+
+        let (flipped, turns) = match transform {
+            Transform::Normal => (false, 0),
+            Transform::_90 => (false, 1),
+            Transform::_180 => (false, 2),
+            Transform::_270 => (false, 3),
+            Transform::Flipped => (true, 0),
+            Transform::Flipped90 => (true, 1),
+            Transform::Flipped180 => (true, 2),
+            Transform::Flipped270 => (true, 3),
+        };
+
+        match (flipped, (turns + quarter_turns) % 4) {
+            (false, 0) => Transform::Normal,
+            (false, 1) => Transform::_90,
+            (false, 2) => Transform::_180,
+            (false, _) => Transform::_270,
+            (true, 0) => Transform::Flipped,
+            (true, 1) => Transform::Flipped90,
+            (true, 2) => Transform::Flipped180,
+            (true, _) => Transform::Flipped270,
+        }
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Self::Normal),
+            "bottom-up" => Ok(Self::BottomUp),
+            "left-up" => Ok(Self::LeftUp),
+            "right-up" => Ok(Self::RightUp),
+            _ => {
+                Err(r#"invalid orientation, can be "normal", "bottom-up", "left-up" or "right-up""#)
+            }
+        }
+    }
+}
+
+impl AutoRotation {
+    /// Records a new orientation from the sensor.
+    ///
+    /// Returns `true` if the applied orientation changed.
+    pub fn on_sensor_changed(&mut self, orientation: Orientation) -> bool {
+        self.sensor = Some(orientation);
+        if self.locked || self.applied == self.sensor {
+            return false;
+        }
+
+        self.applied = self.sensor;
+        true
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Locks or unlocks the applied orientation.
+    ///
+    /// Returns `true` if the applied orientation changed, which happens when unlocking after the
+    /// device was rotated.
+    pub fn set_locked(&mut self, locked: bool) -> bool {
+        self.locked = locked;
+        if locked || self.applied == self.sensor {
+            return false;
+        }
+
+        self.applied = self.sensor;
+        true
+    }
+
+    /// Returns the transform for an auto-rotating output with the `configured` transform.
+    pub fn transform(&self, configured: Transform) -> Transform {
+        match self.applied {
+            Some(orientation) => orientation.apply(configured),
+            None => configured,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_orientation() {
+        assert_eq!(Orientation::Normal.apply(Transform::_90), Transform::_90);
+        assert_eq!(Orientation::LeftUp.apply(Transform::Normal), Transform::_90);
+        assert_eq!(
+            Orientation::RightUp.apply(Transform::_90),
+            Transform::Normal
+        );
+        assert_eq!(Orientation::BottomUp.apply(Transform::_270), Transform::_90);
+        assert_eq!(
+            Orientation::LeftUp.apply(Transform::Flipped270),
+            Transform::Flipped
+        );
+    }
+
+    #[test]
+    fn lock_rotation() {
+        let mut rotation = AutoRotation::default();
+        assert_eq!(rotation.transform(Transform::_90), Transform::_90);
+
+        assert!(rotation.on_sensor_changed(Orientation::LeftUp));
+        assert!(!rotation.on_sensor_changed(Orientation::LeftUp));
+        assert_eq!(rotation.transform(Transform::Normal), Transform::_90);
+
+        assert!(!rotation.set_locked(true));
+        assert!(!rotation.on_sensor_changed(Orientation::BottomUp));
+        assert_eq!(rotation.transform(Transform::Normal), Transform::_90);
+
+        assert!(rotation.set_locked(false));
+        assert_eq!(rotation.transform(Transform::Normal), Transform::_180);
+    }
+}
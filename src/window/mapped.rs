@@ -60,6 +60,9 @@ pub struct Mapped {
     /// Whether this window is the active window in its column.
     is_active_in_column: bool,
 
+    /// Whether this window is being screencast or captured by a client.
+    is_being_captured: bool,
+
     /// Buffer to draw instead of the window when it should be blocked out.
     block_out_buffer: RefCell<SolidColorBuffer>,
 
@@ -144,6 +147,7 @@ impl Mapped {
             need_to_recompute_rules: false,
             is_focused: false,
             is_active_in_column: false,
+            is_being_captured: false,
             block_out_buffer: RefCell::new(SolidColorBuffer::new((0., 0.), [0., 0., 0., 1.])),
             animate_next_configure: false,
             animate_serials: Vec::new(),
@@ -205,6 +209,13 @@ impl Mapped {
         self.need_to_recompute_rules = true;
     }
 
+    /// Sets whether this window is being captured and returns whether it changed.
+    pub fn set_is_being_captured(&mut self, is_being_captured: bool) -> bool {
+        let changed = self.is_being_captured != is_being_captured;
+        self.is_being_captured = is_being_captured;
+        changed
+    }
+
     fn render_snapshot(&self, renderer: &mut GlesRenderer) -> LayoutElementRenderSnapshot {
         let _span = tracy_client::span!("Mapped::render_snapshot");
 
@@ -783,4 +794,8 @@ impl LayoutElement for Mapped {
     fn interactive_resize_data(&self) -> Option<InteractiveResizeData> {
         Some(self.interactive_resize.as_ref()?.data())
     }
+
+    fn is_being_captured(&self) -> bool {
+        self.is_being_captured
+    }
 }
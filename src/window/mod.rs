@@ -73,6 +73,9 @@ pub struct ResolvedWindowRules {
 
     /// Whether to enable VRR on this window's primary output if it is on-demand.
     pub variable_refresh_rate: Option<bool>,
+
+    /// Whether to present this window with tearing when it asks for it and is scanned out.
+    pub allow_tearing: Option<bool>,
 }
 
 impl<'a> WindowRef<'a> {
@@ -134,6 +137,7 @@ impl ResolvedWindowRules {
             clip_to_geometry: None,
             block_out_from: None,
             variable_refresh_rate: None,
+            allow_tearing: None,
         }
     }
 
@@ -228,6 +232,9 @@ impl ResolvedWindowRules {
                 if let Some(x) = rule.variable_refresh_rate {
                     resolved.variable_refresh_rate = Some(x);
                 }
+                if let Some(x) = rule.allow_tearing {
+                    resolved.allow_tearing = Some(x);
+                }
             }
 
             resolved.open_on_output = open_on_output.map(|x| x.to_owned());